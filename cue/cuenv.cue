@@ -1,13 +1,15 @@
 package cuenv
 
-// #Env defines the structure for environment variable configuration
+// #Env defines the structure for environment variable configuration.
+// The package field `env` is loaded by default, other top-level fields of
+// the same shape are selected as named instances with `--instance`
 #Env: {
 	// Environment variables - keys must be valid environment variable names
-	[=~"^[A-Z][A-Z0-9_]*$"]: string | #Secret
+	[=~"^[A-Z][A-Z0-9_]*$"]: #Value
 
 	// Environment-specific overrides
 	environment?: [string]: {
-		[=~"^[A-Z][A-Z0-9_]*$"]: string | #Secret
+		[=~"^[A-Z][A-Z0-9_]*$"]: #Value
 	}
 
 	// Capability definitions with associated commands
@@ -31,12 +33,39 @@ package cuenv
 
 // #Secret represents a secret reference that will be resolved at runtime
 #Secret: {
-	resolver: #Resolver
+	resolver: #Resolver | #VaultResolver | #FileResolver | #PassResolver | #KeyringResolver
+
+	// Cache the resolved value for this long, e.g. "15m", "1h30m" or "7d"
+	ttl?: =~"^([0-9]+[smhd])+$"
 	...
 }
 
-// #Resolver defines how to resolve a secret value
+// #Value is any value an environment variable accepts. Numbers and booleans
+// are rendered as strings, and lists are joined with ":" unless the variable
+// sets another separator, e.g. HOSTS: ["a", "b"] @separator(",")
+#Value: string | number | bool | [...(string | number | bool)] | #Secret | #Expand | #PathList | #JSON
+
+// #JSON passes a structured value as its JSON serialization
+#JSON: {
+	json: _
+}
+
+// #Expand is a value with `$VAR` and `~` references to the environment
+// cuenv was loaded from, e.g. {expand: "$HOME/go"}
+#Expand: {
+	expand: string
+}
+
+// #PathList adds entries around the current value of a path-like variable
+#PathList: {
+	prepend?: [...string]
+	append?: [...string]
+	separator: string | *":"
+}
+
+// #Resolver defines how to resolve a secret value by running a command
 #Resolver: {
+	type?:   "exec"
 	command: string
 	args: [...string]
 }
@@ -82,7 +111,9 @@ package cuenv
 	// Embedded shell script to execute (mutually exclusive with command)
 	script?: string
 
-	// List of task names that must complete successfully before this task runs
+	// List of task names that must complete successfully before this task runs.
+	// Tasks of other projects are named like "//libs/proto:generate", relative
+	// to the workspace root, or "../shared:codegen"
 	dependencies?: [...string]
 
 	// Working directory for task execution (defaults to current directory)
@@ -91,9 +122,62 @@ package cuenv
 	// Shell to use for execution (e.g., "bash", "sh", "zsh")
 	shell?: string
 
-	// Input files/patterns (for future implementation)
+	// Input files/patterns whose contents contribute to the cache key
 	inputs?: [...string]
 
+	// Environment variables whose values contribute to the cache key
+	envInputs?: [...string]
+
+	// Environment variables set for this task only
+	env?: [=~"^[A-Z][A-Z0-9_]*$"]: string | #Secret | #Expand | #PathList
+
+	// Capabilities whose tagged variables this task receives. Without it,
+	// the task only receives the capabilities selected with `cuenv run -c`
+	capabilities?: [...string]
+
 	// Output files/patterns (for future implementation)
 	outputs?: [...string]
+
+	// Seconds the task may run before its process group is killed (default 3600)
+	timeout?: int & >0
+
+	// Number of times a failed or timed out run is retried
+	retries?: int & >=0
+
+	// Seconds to wait before the first retry, doubled for each further one (default 1)
+	retryDelay?: int & >=0
+
+	// Run dependent tasks even if this one fails
+	allowFailure?: bool
+
+	// Parameters accepted as `cuenv run <task> --name value`, substituted for
+	// {{name}} in the command or script and set as CUENV_PARAM_<NAME>
+	params?: [=~"^[a-z][a-z0-9-]*$"]: #Param
+
+	// Resources the task may use, enforced by a cgroup of its own
+	resources?: #Resources
+}
+
+// #Resources limits the resources of a task. Without cgroup v2, memory falls
+// back to rlimits and cpus, pids and ioWeight are not enforced
+#Resources: {
+	// Memory limit in bytes, or a size like "512Mi" or "2G"
+	memory?: int & >0 | =~"^[0-9]+(\\.[0-9]+)?([KMGTP]i?)?$"
+
+	// Number of CPUs the task may use, like 1.5
+	cpus?: number & >0
+
+	// Maximum number of processes and threads
+	pids?: int & >0
+
+	// Weight of the task's IO against other tasks (default 100)
+	ioWeight?: int & >=1 & <=10000
+}
+
+// #Param declares a task parameter, which is required unless it has a default
+#Param: {
+	type: *"string" | "int" | "bool"
+	default?: string | int | bool
+	enum?: [...(string | int)]
+	description?: string
 }
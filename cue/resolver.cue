@@ -3,9 +3,69 @@ package cuenv
 #Resolver: {
 	ref:      string
 	resolver: #ExecResolver
+
+	// Cache the resolved value for this long, e.g. "15m", "1h30m" or "7d"
+	ttl?: =~"^([0-9]+[smhd])+$"
 }
 
 #ExecResolver: {
+	type?:   "exec"
 	command: string
 	args: [...string]
 }
+
+// #VaultResolver reads a field of a HashiCorp Vault KV v2 secret.
+// The token is taken from VAULT_TOKEN or ~/.vault-token.
+#VaultResolver: {
+	type:       "vault"
+	path:       string
+	field:      string
+	mount:      string | *"secret"
+	address?:   string
+	version?:   int
+	namespace?: string
+}
+
+// #FileResolver reads a secret file, or one key of a directory of
+// files such as a mounted Kubernetes secret.
+#FileResolver: {
+	type: "file"
+	path: string
+	key?: string
+}
+
+// #PassResolver decrypts a pass entry or a GPG-encrypted file with gpg.
+// Without a field the first line of the entry is used.
+#PassResolver: {
+	type: "pass"
+	{
+		name:   string
+		store?: string
+	} | {
+		file: string
+	}
+	field?: string
+}
+
+// #KeyringResolver reads a key from the Linux kernel keyring or an item
+// from the Secret Service (GNOME Keyring, KWallet). Secret Service items are
+// looked up with libsecret's secret-tool, which has to be installed.
+#KeyringResolver: {
+	type: "keyring"
+	{
+		backend:     "kernel"
+		description: string
+		keyType:     string | *"user"
+	} | {
+		backend: "secret-service"
+		attributes: [string]: string
+	}
+
+	// Retries of a failed lookup, and how many failed lookups make further
+	// ones fail right away for breakDuration seconds
+	resilience?: {
+		retries?:          int & >=0
+		failureThreshold?: int & >0
+		breakDuration?:    int & >0
+	}
+}
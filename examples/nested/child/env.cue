@@ -12,9 +12,9 @@ env: cuenv.#Env & {
 	CHILD_SERVICE: "enabled"
 	CHILD_PORT:    "4000"
 
-	// Note: In the new structure, composition happens through CUE imports
-	// rather than automatic parent directory loading. To reference parent
-	// values, you would import the parent package explicitly.
+	// With CUENV_HIERARCHICAL=1, the parent package is loaded first and
+	// merged with this one, so APP_NAME and LOG_LEVEL are inherited while
+	// values defined here take precedence.
 
 	// Additional commands for child
 	capabilities: {
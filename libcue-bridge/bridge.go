@@ -8,13 +8,16 @@ import (
 	"encoding/json"
 	"fmt"
 	"os"
+	"path/filepath"
 	"sort"
 	"strings"
 	"unsafe"
 
 	"cuelang.org/go/cue"
 	"cuelang.org/go/cue/cuecontext"
+	cueerrors "cuelang.org/go/cue/errors"
 	"cuelang.org/go/cue/load"
+	"cuelang.org/go/cue/parser"
 	"cuelang.org/go/mod/modconfig"
 )
 
@@ -26,6 +29,37 @@ func extractSecretReference(val cue.Value) string {
 		return ""
 	}
 
+	// Secrets with a ttl are cached by the Rust side, so pass it along
+	var ttl string
+	if ttlField := val.LookupPath(cue.ParsePath("ttl")); ttlField.Exists() {
+		if err := ttlField.Decode(&ttl); err != nil {
+			return ""
+		}
+	}
+
+	// Built-in resolvers are selected by their type and handed to the Rust
+	// side with their whole configuration
+	if typeField := resolverField.LookupPath(cue.ParsePath("type")); typeField.Exists() {
+		var resolverType string
+		if err := typeField.Decode(&resolverType); err != nil {
+			return ""
+		}
+		if resolverType != "exec" {
+			var resolver map[string]interface{}
+			if err := resolverField.Decode(&resolver); err != nil {
+				return ""
+			}
+			if ttl != "" {
+				resolver["ttl"] = ttl
+			}
+			jsonBytes, err := json.Marshal(resolver)
+			if err != nil {
+				return ""
+			}
+			return "cuenv-resolver://" + string(jsonBytes)
+		}
+	}
+
 	// Extract the resolver configuration
 	// Check for both "cmd" and "command" fields for compatibility
 	cmdField := resolverField.LookupPath(cue.ParsePath("cmd"))
@@ -43,6 +77,7 @@ func extractSecretReference(val cue.Value) string {
 	type Resolver struct {
 		Cmd  string   `json:"cmd"`
 		Args []string `json:"args"`
+		TTL  string   `json:"ttl,omitempty"`
 	}
 
 	var cmd string
@@ -63,6 +98,7 @@ func extractSecretReference(val cue.Value) string {
 	resolver := Resolver{
 		Cmd:  cmd,
 		Args: args,
+		TTL:  ttl,
 	}
 
 	// Encode as JSON with a special prefix to identify it as a resolver
@@ -74,21 +110,199 @@ func extractSecretReference(val cue.Value) string {
 	return "cuenv-resolver://" + string(jsonBytes)
 }
 
+// extractJSON serializes the value of a #JSON marker, so structured values
+// reach the environment as a JSON string
+func extractJSON(val cue.Value) (string, bool) {
+	if val.IncompleteKind() != cue.StructKind {
+		return "", false
+	}
+	jsonField := val.LookupPath(cue.ParsePath("json"))
+	if !jsonField.Exists() {
+		return "", false
+	}
+
+	var goVal interface{}
+	if err := jsonField.Decode(&goVal); err != nil {
+		return "", false
+	}
+	jsonBytes, err := json.Marshal(goVal)
+	if err != nil {
+		return "", false
+	}
+	return string(jsonBytes), true
+}
+
+// extractVariableValue converts an environment variable, passing secrets and
+// interpolations on as references. Values that can't be decoded are returned
+// as nil, so the Rust side can warn about the skipped key.
+func extractVariableValue(val cue.Value) interface{} {
+	if ref := extractSecretReference(val); ref != "" {
+		return ref
+	}
+	if ref := extractInterpolation(val); ref != "" {
+		return ref
+	}
+	if jsonVal, ok := extractJSON(val); ok {
+		return jsonVal
+	}
+
+	var goVal interface{}
+	if err := val.Decode(&goVal); err != nil {
+		return nil
+	}
+	return goVal
+}
+
+// extractVariableMetadata reads the @capability and @separator attributes of a variable
+func extractVariableMetadata(val cue.Value) map[string]interface{} {
+	varMeta := make(map[string]interface{})
+	for _, attr := range val.Attributes(cue.ValueAttr) {
+		switch attr.Name() {
+		case "capability":
+			if caps, err := attr.String(0); err == nil {
+				varMeta["capability"] = caps
+			}
+		case "separator":
+			if separator, err := attr.String(0); err == nil {
+				varMeta["separator"] = separator
+			}
+		}
+	}
+	return varMeta
+}
+
+// extractInterpolation encodes #Expand and #PathList values, which the Rust
+// side evaluates against the environment cuenv is loaded from
+func extractInterpolation(val cue.Value) string {
+	if val.IncompleteKind() != cue.StructKind {
+		return ""
+	}
+
+	interpolation := make(map[string]interface{})
+	if expandField := val.LookupPath(cue.ParsePath("expand")); expandField.Exists() {
+		var expand string
+		if err := expandField.Decode(&expand); err != nil {
+			return ""
+		}
+		interpolation["expand"] = expand
+	} else {
+		for _, field := range []string{"prepend", "append"} {
+			if listField := val.LookupPath(cue.ParsePath(field)); listField.Exists() {
+				var entries []string
+				if err := listField.Decode(&entries); err != nil {
+					return ""
+				}
+				interpolation[field] = entries
+			}
+		}
+		if len(interpolation) == 0 {
+			return ""
+		}
+		if sepField := val.LookupPath(cue.ParsePath("separator")); sepField.Exists() {
+			var separator string
+			if err := sepField.Decode(&separator); err == nil {
+				interpolation["separator"] = separator
+			}
+		}
+	}
+
+	jsonBytes, err := json.Marshal(interpolation)
+	if err != nil {
+		return ""
+	}
+	return "cuenv-expand://" + string(jsonBytes)
+}
+
+// cueDiagnostic is a single CUE error with the location it refers to
+type cueDiagnostic struct {
+	Message string `json:"message"`
+	File    string `json:"file,omitempty"`
+	Line    int    `json:"line,omitempty"`
+	Column  int    `json:"column,omitempty"`
+	Path    string `json:"path,omitempty"`
+}
+
+// errorResponse encodes a CUE error for the Rust side, with one diagnostic
+// per error it contains
+func errorResponse(err error) string {
+	var diagnostics []cueDiagnostic
+	for _, e := range cueerrors.Errors(err) {
+		format, args := e.Msg()
+		diagnostic := cueDiagnostic{
+			Message: fmt.Sprintf(format, args...),
+			Path:    strings.Join(e.Path(), "."),
+		}
+		if pos := e.Position(); pos.IsValid() {
+			diagnostic.File = pos.Filename()
+			diagnostic.Line = pos.Line()
+			diagnostic.Column = pos.Column()
+		}
+		diagnostics = append(diagnostics, diagnostic)
+	}
+	return encodeError(err.Error(), diagnostics)
+}
+
+// messageResponse encodes an error that has no source location
+func messageResponse(message string) string {
+	return encodeError(message, nil)
+}
+
+func encodeError(message string, diagnostics []cueDiagnostic) string {
+	errMsg := map[string]interface{}{"error": message}
+	if len(diagnostics) > 0 {
+		errMsg["diagnostics"] = diagnostics
+	}
+	errBytes, _ := json.Marshal(errMsg)
+	return string(errBytes)
+}
+
 //export cue_free_string
 func cue_free_string(s *C.char) {
 	C.free(unsafe.Pointer(s))
 }
 
 //export cue_eval_package
-func cue_eval_package(dirPath *C.char, packageName *C.char) *C.char {
-	goDir := C.GoString(dirPath)
-	goPkg := C.GoString(packageName)
+func cue_eval_package(dirPath *C.char, packageName *C.char, instanceName *C.char) *C.char {
+	return C.CString(evalPackage(C.GoString(dirPath), C.GoString(packageName), C.GoString(instanceName), false))
+}
+
+//export cue_eval_package_local
+func cue_eval_package_local(dirPath *C.char, packageName *C.char, instanceName *C.char) *C.char {
+	return C.CString(evalPackage(C.GoString(dirPath), C.GoString(packageName), C.GoString(instanceName), true))
+}
 
-	// Only allow loading the "env" package
-	if goPkg != "env" {
-		errMsg := map[string]string{"error": fmt.Sprintf("Only 'env' package is supported, got '%s'. Please ensure your .cue files use 'package env'", goPkg)}
-		errBytes, _ := json.Marshal(errMsg)
-		return C.CString(string(errBytes))
+// localPackageFiles returns the .cue files directly inside dir that belong to pkg
+func localPackageFiles(dir string, pkg string) ([]string, error) {
+	entries, err := os.ReadDir(dir)
+	if err != nil {
+		return nil, err
+	}
+
+	var files []string
+	for _, entry := range entries {
+		if entry.IsDir() || !strings.HasSuffix(entry.Name(), ".cue") {
+			continue
+		}
+		path := filepath.Join(dir, entry.Name())
+		f, err := parser.ParseFile(path, nil, parser.PackageClauseOnly)
+		if err != nil {
+			return nil, err
+		}
+		if f.PackageName() == pkg {
+			files = append(files, entry.Name())
+		}
+	}
+	sort.Strings(files)
+	return files, nil
+}
+
+// evalPackage evaluates a CUE package and returns the environment defined by its
+// top-level instance field as JSON. When localOnly is set, only files directly
+// inside goDir are loaded, so files of the same package in parent directories of
+// the module are not unified in.
+func evalPackage(goDir string, goPkg string, instance string, localOnly bool) string {
+	if instance == "" {
+		instance = "env"
 	}
 
 	// Create a registry for module resolution
@@ -96,9 +310,7 @@ func cue_eval_package(dirPath *C.char, packageName *C.char) *C.char {
 		Env: os.Environ(),
 	})
 	if err != nil {
-		errMsg := map[string]string{"error": "Failed to create registry: " + err.Error()}
-		errBytes, _ := json.Marshal(errMsg)
-		return C.CString(string(errBytes))
+		return messageResponse("Failed to create registry: " + err.Error())
 	}
 
 	// Load the CUE package from the directory
@@ -111,19 +323,26 @@ func cue_eval_package(dirPath *C.char, packageName *C.char) *C.char {
 	}
 
 	// Load all .cue files in the directory
-	instances := load.Instances(nil, cfg)
+	var args []string
+	if localOnly {
+		files, err := localPackageFiles(goDir, goPkg)
+		if err != nil {
+			return messageResponse("Failed to list CUE files: " + err.Error())
+		}
+		if len(files) == 0 {
+			return ""
+		}
+		args = files
+	}
+	instances := load.Instances(args, cfg)
 	if len(instances) == 0 {
-		errMsg := map[string]string{"error": "No CUE instances found in directory"}
-		errBytes, _ := json.Marshal(errMsg)
-		return C.CString(string(errBytes))
+		return messageResponse("No CUE instances found in directory")
 	}
 
 	// Check for load errors
 	inst := instances[0]
 	if inst.Err != nil {
-		errMsg := map[string]string{"error": inst.Err.Error()}
-		errBytes, _ := json.Marshal(errMsg)
-		return C.CString(string(errBytes))
+		return errorResponse(inst.Err)
 	}
 
 	// Build the instance
@@ -131,27 +350,28 @@ func cue_eval_package(dirPath *C.char, packageName *C.char) *C.char {
 	v := ctx.BuildInstance(inst)
 
 	if v.Err() != nil {
-		errMsg := map[string]string{"error": v.Err().Error()}
-		errBytes, _ := json.Marshal(errMsg)
-		return C.CString(string(errBytes))
+		return errorResponse(v.Err())
+	}
+
+	// Named instances must exist, a package without the default env field is empty
+	if instance != "env" && !v.LookupPath(cue.ParsePath(instance)).Exists() {
+		return messageResponse(fmt.Sprintf("Instance '%s' not found in package '%s'", instance, goPkg))
 	}
 
 	// Use the same extraction logic as cue_parse_string
-	result := extractCueData(v)
+	result := extractCueData(v, instance)
 
 	// Convert to JSON
 	jsonBytes, err := json.Marshal(result)
 	if err != nil {
-		errMsg := map[string]string{"error": err.Error()}
-		errBytes, _ := json.Marshal(errMsg)
-		return C.CString(string(errBytes))
+		return messageResponse(err.Error())
 	}
 
-	return C.CString(string(jsonBytes))
+	return string(jsonBytes)
 }
 
-// extractCueData extracts the structured data from a CUE value
-func extractCueData(v cue.Value) map[string]interface{} {
+// extractCueData extracts the structured data of the given instance from a CUE value
+func extractCueData(v cue.Value, instance string) map[string]interface{} {
 	result := map[string]interface{}{
 		"variables":    make(map[string]interface{}),
 		"metadata":     make(map[string]interface{}),
@@ -164,8 +384,8 @@ func extractCueData(v cue.Value) map[string]interface{} {
 	// Get metadata map reference for use throughout
 	metadata := result["metadata"].(map[string]interface{})
 
-	// Look for the 'env' field which contains the environment definition
-	envRoot := v.LookupPath(cue.ParsePath("env"))
+	// Look for the instance field which contains the environment definition
+	envRoot := v.LookupPath(cue.ParsePath(instance))
 	if !envRoot.Exists() {
 		// Return empty result if no env field
 		return result
@@ -184,37 +404,17 @@ func extractCueData(v cue.Value) map[string]interface{} {
 				key := envIter.Label()
 				val := envIter.Value()
 
-				// Extract attributes for environment-specific variables
-				attrs := val.Attributes(cue.ValueAttr)
-				varMeta := make(map[string]interface{})
-
-				for _, attr := range attrs {
-					if attr.Name() == "capability" {
-						if caps, err := attr.String(0); err == nil {
-							varMeta["capability"] = caps
-							// Also store in parent metadata if not already there
-							if _, exists := metadata[key]; !exists {
-								metadata[key] = map[string]interface{}{"capability": caps}
-							}
-						}
-					}
-				}
-
+				// Extract attributes for environment-specific variables, also
+				// storing them in the parent metadata if not already there
+				varMeta := extractVariableMetadata(val)
 				if len(varMeta) > 0 {
 					envMeta[key] = varMeta
-				}
-
-				// Check if this is a secret type
-				secretRef := extractSecretReference(val)
-				if secretRef != "" {
-					envVars[key] = secretRef
-				} else {
-					// Regular value
-					var goVal interface{}
-					if err := val.Decode(&goVal); err == nil {
-						envVars[key] = goVal
+					if _, exists := metadata[key]; !exists {
+						metadata[key] = varMeta
 					}
 				}
+
+				envVars[key] = extractVariableValue(val)
 			}
 			envs[envName] = envVars
 		}
@@ -364,6 +564,51 @@ func extractCueData(v cue.Value) map[string]interface{} {
 				}
 			}
 
+			// Extract envInputs
+			if envInputsField := iter.Value().LookupPath(cue.ParsePath("envInputs")); envInputsField.Exists() {
+				var envInputs []string
+				if err := envInputsField.Decode(&envInputs); err == nil {
+					taskConfig["envInputs"] = envInputs
+				}
+			}
+
+			// Extract env, keeping secrets as resolver references
+			if envField := iter.Value().LookupPath(cue.ParsePath("env")); envField.Exists() {
+				taskEnv := make(map[string]interface{})
+				envIter, _ := envField.Fields()
+				for envIter.Next() {
+					if secretRef := extractSecretReference(envIter.Value()); secretRef != "" {
+						taskEnv[envIter.Label()] = secretRef
+						continue
+					}
+					if interpolation := extractInterpolation(envIter.Value()); interpolation != "" {
+						taskEnv[envIter.Label()] = interpolation
+						continue
+					}
+					var envVal string
+					if err := envIter.Value().Decode(&envVal); err == nil {
+						taskEnv[envIter.Label()] = envVal
+					}
+				}
+				taskConfig["env"] = taskEnv
+			}
+
+			// Extract capabilities
+			if capsField := iter.Value().LookupPath(cue.ParsePath("capabilities")); capsField.Exists() {
+				var caps []string
+				if err := capsField.Decode(&caps); err == nil {
+					taskConfig["capabilities"] = caps
+				}
+			}
+
+			// Extract params, declared as a map from name to type, default, enum and description
+			if paramsField := iter.Value().LookupPath(cue.ParsePath("params")); paramsField.Exists() {
+				var params map[string]interface{}
+				if err := paramsField.Decode(&params); err == nil {
+					taskConfig["params"] = params
+				}
+			}
+
 			// Extract outputs
 			if outputsField := iter.Value().LookupPath(cue.ParsePath("outputs")); outputsField.Exists() {
 				var outputs []string
@@ -424,6 +669,14 @@ func extractCueData(v cue.Value) map[string]interface{} {
 					}
 				}
 
+				// Extract requireEnforcement
+				if reField := securityField.LookupPath(cue.ParsePath("requireEnforcement")); reField.Exists() {
+					var requireEnforcement bool
+					if err := reField.Decode(&requireEnforcement); err == nil {
+						security["requireEnforcement"] = requireEnforcement
+					}
+				}
+
 				taskConfig["security"] = security
 			}
 
@@ -443,6 +696,57 @@ func extractCueData(v cue.Value) map[string]interface{} {
 				}
 			}
 
+			// Extract timeout, retries and retryDelay, all whole numbers
+			for _, field := range []string{"timeout", "retries", "retryDelay"} {
+				if numField := iter.Value().LookupPath(cue.ParsePath(field)); numField.Exists() {
+					var num uint32
+					if err := numField.Decode(&num); err == nil {
+						taskConfig[field] = num
+					}
+				}
+			}
+
+			// Extract allowFailure
+			if allowFailureField := iter.Value().LookupPath(cue.ParsePath("allowFailure")); allowFailureField.Exists() {
+				var allowFailure bool
+				if err := allowFailureField.Decode(&allowFailure); err == nil {
+					taskConfig["allowFailure"] = allowFailure
+				}
+			}
+
+			// Extract resources, memory is a number of bytes or a size like "2Gi"
+			if resourcesField := iter.Value().LookupPath(cue.ParsePath("resources")); resourcesField.Exists() {
+				resources := make(map[string]interface{})
+				if memoryField := resourcesField.LookupPath(cue.ParsePath("memory")); memoryField.Exists() {
+					if memoryField.Kind() == cue.StringKind {
+						var memory string
+						if err := memoryField.Decode(&memory); err == nil {
+							resources["memory"] = memory
+						}
+					} else {
+						var memory uint64
+						if err := memoryField.Decode(&memory); err == nil {
+							resources["memory"] = memory
+						}
+					}
+				}
+				if cpusField := resourcesField.LookupPath(cue.ParsePath("cpus")); cpusField.Exists() {
+					var cpus float64
+					if err := cpusField.Decode(&cpus); err == nil {
+						resources["cpus"] = cpus
+					}
+				}
+				for _, field := range []string{"pids", "ioWeight"} {
+					if numField := resourcesField.LookupPath(cue.ParsePath(field)); numField.Exists() {
+						var num uint64
+						if err := numField.Decode(&num); err == nil {
+							resources[field] = num
+						}
+					}
+				}
+				taskConfig["resources"] = resources
+			}
+
 			tasks[taskName] = taskConfig
 		}
 		result["tasks"] = tasks
@@ -538,34 +842,11 @@ func extractCueData(v cue.Value) map[string]interface{} {
 		}
 
 		// Extract attributes (like @capability)
-		attrs := val.Attributes(cue.ValueAttr)
-		varMeta := make(map[string]interface{})
-
-		for _, attr := range attrs {
-			if attr.Name() == "capability" {
-				if caps, err := attr.String(0); err == nil {
-					varMeta["capability"] = caps
-				}
-			}
+		if varMeta := extractVariableMetadata(val); len(varMeta) > 0 {
+			metadata[key] = varMeta
 		}
 
-		// Check if this is a secret type and convert accordingly
-		secretRef := extractSecretReference(val)
-		if secretRef != "" {
-			vars[key] = secretRef
-			if len(varMeta) > 0 {
-				metadata[key] = varMeta
-			}
-		} else {
-			// Convert CUE value to Go value
-			var goVal interface{}
-			if err := val.Decode(&goVal); err == nil {
-				vars[key] = goVal
-				if len(varMeta) > 0 {
-					metadata[key] = varMeta
-				}
-			}
-		}
+		vars[key] = extractVariableValue(val)
 	}
 
 	return result
@@ -80,7 +80,8 @@ extern "C" {
 #endif
 
 extern void cue_free_string(char* s);
-extern char* cue_eval_package(char* dirPath, char* packageName);
+extern char* cue_eval_package(char* dirPath, char* packageName, char* instanceName);
+extern char* cue_eval_package_local(char* dirPath, char* packageName, char* instanceName);
 
 #ifdef __cplusplus
 }
@@ -1,61 +1,165 @@
-use crate::constants::{AUDIT_IGNORED_PATH_PREFIXES, AUDIT_LOG_PATH, LD_SO_CACHE};
+use crate::constants::{AUDIT_IGNORED_PATH_PREFIXES, LD_SO_CACHE};
 use crate::errors::{Error, Result};
+use crate::network_proxy::HostAllowlist;
+#[cfg(target_os = "linux")]
+use crate::network_proxy::{isolate_network, PendingProxy};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::process::Command;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+/// Highest Landlock ABI restrictions are built for: filesystem access rights
+/// up to ioctl on devices, and TCP ports
+const LANDLOCK_MAX_ABI: i32 = 5;
+
+/// How much of the requested restrictions the kernel can enforce
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnforcementLevel {
+    Full,
+    Partial,
+    None,
+}
+
+impl EnforcementLevel {
+    /// Level of restrictions made of parts enforced at the given levels
+    fn combine(levels: &[EnforcementLevel]) -> Self {
+        if levels.iter().all(|level| *level == EnforcementLevel::Full) {
+            EnforcementLevel::Full
+        } else if levels.iter().all(|level| *level == EnforcementLevel::None) {
+            EnforcementLevel::None
+        } else {
+            EnforcementLevel::Partial
+        }
+    }
+}
+
+impl std::fmt::Display for EnforcementLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EnforcementLevel::Full => write!(f, "fully enforced"),
+            EnforcementLevel::Partial => write!(f, "partially enforced"),
+            EnforcementLevel::None => write!(f, "not enforced"),
+        }
+    }
+}
+
+/// How a file was accessed in audit mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AccessKind {
+    Read,
+    Write,
+    Exec,
+}
+
+impl std::fmt::Display for AccessKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccessKind::Read => write!(f, "read"),
+            AccessKind::Write => write!(f, "write"),
+            AccessKind::Exec => write!(f, "exec"),
+        }
+    }
+}
 
-/// Report generated by audit mode showing actual access patterns
-#[derive(Debug, Clone)]
+/// Report generated by audit mode showing the actual access patterns of a task
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct AuditReport {
-    /// Files that were accessed during execution
-    pub accessed_files: Vec<String>,
-    /// Network connections that were attempted
-    pub network_connections: Vec<String>,
+    /// Task the accesses were made by
+    pub task: String,
+    /// Directory the task ran in
+    pub working_dir: PathBuf,
+    /// Files accessed during execution, with how they were accessed
+    pub files: BTreeMap<PathBuf, BTreeSet<AccessKind>>,
+    /// Addresses connections were made to
+    pub connections: BTreeSet<SocketAddr>,
 }
 
 impl AuditReport {
+    /// Create an empty report for `task`
+    pub fn new(task: impl Into<String>) -> Self {
+        Self {
+            task: task.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Files accessed, without system paths no policy needs to allow unless
+    /// they are in the directory the task ran in
+    pub fn relevant_files(&self) -> impl Iterator<Item = (&PathBuf, &BTreeSet<AccessKind>)> {
+        self.files.iter().filter(|(path, _)| {
+            let name = path.to_string_lossy();
+            let in_working_dir =
+                !self.working_dir.as_os_str().is_empty() && path.starts_with(&self.working_dir);
+            let ignored = AUDIT_IGNORED_PATH_PREFIXES
+                .iter()
+                .any(|prefix| name.starts_with(prefix))
+                || name == LD_SO_CACHE;
+            in_working_dir || !ignored
+        })
+    }
+
     /// Print a human-readable summary of the audit report
     pub fn print_summary(&self) {
-        println!("🔍 Audit Report:");
-        println!("================");
+        print!("{}", self.summary());
+    }
 
-        if !self.accessed_files.is_empty() {
-            println!(
-                "\n📁 File Access ({} unique paths):",
-                self.accessed_files.len()
-            );
-            for file in &self.accessed_files {
-                println!("  • {file}");
+    /// Human-readable summary of the audit report, with recommendations
+    pub fn summary(&self) -> String {
+        let mut out = String::new();
+        let files: Vec<_> = self.relevant_files().collect();
+        let _ = writeln!(out, "🔍 Audit Report for task '{}':", self.task);
+        let _ = writeln!(out, "================");
+
+        if !files.is_empty() {
+            let _ = writeln!(out, "\n📁 File Access ({} unique paths):", files.len());
+            for (path, kinds) in &files {
+                let kinds: Vec<String> = kinds.iter().map(ToString::to_string).collect();
+                let _ = writeln!(out, "  • {} ({})", path.display(), kinds.join(", "));
             }
         }
 
-        if !self.network_connections.is_empty() {
-            println!(
+        if !self.connections.is_empty() {
+            let _ = writeln!(
+                out,
                 "\n🌐 Network Access ({} unique connections):",
-                self.network_connections.len()
+                self.connections.len()
             );
-            for conn in &self.network_connections {
-                println!("  • {conn}");
+            for addr in &self.connections {
+                let _ = writeln!(out, "  • {addr}");
             }
         }
 
-        if self.accessed_files.is_empty() && self.network_connections.is_empty() {
-            println!("  No file or network access detected");
+        if files.is_empty() && self.connections.is_empty() {
+            let _ = writeln!(out, "  No file or network access detected");
         }
 
-        println!("\n💡 Recommendations:");
-        if !self.accessed_files.is_empty() {
-            println!("  Add to security.readOnlyPaths or security.readWritePaths:");
-            for file in &self.accessed_files {
-                println!("    - \"{file}\"");
+        let _ = writeln!(out, "\n💡 Recommendations:");
+        let (written, read): (Vec<_>, Vec<_>) = files
+            .into_iter()
+            .partition(|(_, kinds)| kinds.contains(&AccessKind::Write));
+        if !read.is_empty() {
+            let _ = writeln!(out, "  Add to security.readOnlyPaths:");
+            for (path, _) in read {
+                let _ = writeln!(out, "    - \"{}\"", path.display());
+            }
+        }
+        if !written.is_empty() {
+            let _ = writeln!(out, "  Add to security.readWritePaths:");
+            for (path, _) in written {
+                let _ = writeln!(out, "    - \"{}\"", path.display());
             }
         }
 
-        if !self.network_connections.is_empty() {
-            println!("  Add to security.allowedHosts:");
-            for conn in &self.network_connections {
-                println!("    - \"{conn}\"");
+        if !self.connections.is_empty() {
+            let _ = writeln!(out, "  Add to security.allowedHosts:");
+            for addr in &self.connections {
+                let _ = writeln!(out, "    - \"{addr}\"");
             }
         }
+        out
     }
 }
 
@@ -76,6 +180,8 @@ pub struct AccessRestrictions {
     pub allowed_hosts: Vec<String>,
     /// Audit mode - collect access information instead of restricting
     pub audit_mode: bool,
+    /// Refuse to run unless the restrictions are fully enforced
+    pub require_enforcement: bool,
 }
 
 impl AccessRestrictions {
@@ -91,6 +197,57 @@ impl AccessRestrictions {
     pub fn is_landlock_supported() -> bool {
         false
     }
+
+    /// Landlock ABI version of the running kernel, 0 without Landlock
+    #[cfg(target_os = "linux")]
+    pub fn landlock_abi() -> i32 {
+        const LANDLOCK_CREATE_RULESET_VERSION: libc::c_uint = 1;
+        // SAFETY: Without a ruleset attribute the call only returns the ABI
+        // version, or -1 if Landlock is unsupported or disabled
+        let abi = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                std::ptr::null::<libc::c_void>(),
+                0usize,
+                LANDLOCK_CREATE_RULESET_VERSION,
+            )
+        };
+        i32::try_from(abi.max(0)).unwrap_or(0)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn landlock_abi() -> i32 {
+        0
+    }
+
+    /// How much of these restrictions a kernel with Landlock ABI `abi` can
+    /// enforce. Hosts filtered by the network proxy don't depend on Landlock.
+    pub fn enforcement_level_for(&self, abi: i32) -> EnforcementLevel {
+        let mut levels = Vec::new();
+        if cfg!(not(target_os = "linux")) {
+            levels.push(EnforcementLevel::None);
+        }
+        if self.restrict_disk {
+            levels.push(match abi {
+                abi if abi >= LANDLOCK_MAX_ABI => EnforcementLevel::Full,
+                1.. => EnforcementLevel::Partial,
+                _ => EnforcementLevel::None,
+            });
+        }
+        if self.restrict_network && !self.uses_network_proxy() {
+            levels.push(if abi >= 4 {
+                EnforcementLevel::Full
+            } else {
+                EnforcementLevel::None
+            });
+        }
+        EnforcementLevel::combine(&levels)
+    }
+
+    /// How much of these restrictions the running kernel can enforce
+    pub fn enforcement_level(&self) -> EnforcementLevel {
+        self.enforcement_level_for(Self::landlock_abi())
+    }
     /// Create new restrictions configuration
     pub fn new(restrict_disk: bool, restrict_network: bool) -> Self {
         Self {
@@ -101,6 +258,7 @@ impl AccessRestrictions {
             deny_paths: Vec::new(),
             allowed_hosts: Vec::new(),
             audit_mode: false,
+            require_enforcement: false,
         }
     }
 
@@ -121,6 +279,7 @@ impl AccessRestrictions {
             deny_paths,
             allowed_hosts,
             audit_mode: false,
+            require_enforcement: false,
         }
     }
 
@@ -144,91 +303,25 @@ impl AccessRestrictions {
         self.audit_mode = true;
     }
 
-    /// Run command with audit monitoring using strace
-    pub fn run_with_audit(&self, cmd: &mut Command) -> Result<(i32, AuditReport)> {
-        if !cfg!(target_os = "linux") {
-            return Err(Error::configuration(
-                "Audit mode is only supported on Linux systems".to_string(),
-            ));
-        }
-
-        // Use strace to monitor file and network access
-        let mut strace_cmd = Command::new("strace");
-        strace_cmd
-            .arg("-f") // Follow forks
-            .arg("-e") // Filter system calls
-            .arg("trace=file,network") // Monitor file and network calls
-            .arg("-o") // Output to file
-            .arg(AUDIT_LOG_PATH)
-            .arg("--");
-
-        // Add the original command and arguments
-        if let Some(program) = cmd.get_program().to_str() {
-            strace_cmd.arg(program);
-        }
-
-        for arg in cmd.get_args() {
-            if let Some(arg_str) = arg.to_str() {
-                strace_cmd.arg(arg_str);
-            }
-        }
-
-        // Copy environment and working directory
-        strace_cmd.envs(cmd.get_envs().filter_map(|(k, v)| Some((k, v?))));
-
-        if let Some(current_dir) = cmd.get_current_dir() {
-            strace_cmd.current_dir(current_dir);
-        }
-
-        // Execute with strace
-        let output = strace_cmd.output().map_err(|e| {
-            Error::command_execution(
-                "strace",
-                vec!["monitoring command".to_string()],
-                format!("Failed to run audit command: {e}"),
-                None,
-            )
-        })?;
-
-        // Parse the audit log
-        let audit_report = self.parse_audit_log(AUDIT_LOG_PATH)?;
-
-        // Clean up the audit log
-        let _ = std::fs::remove_file(AUDIT_LOG_PATH);
-
-        Ok((output.status.code().unwrap_or(1), audit_report))
-    }
-
-    /// Parse strace output to generate audit report
-    fn parse_audit_log(&self, log_path: &str) -> Result<AuditReport> {
-        use std::collections::HashSet;
-        use std::fs;
-
-        let mut accessed_files = HashSet::new();
-        let mut network_connections = HashSet::new();
-
-        if let Ok(content) = fs::read_to_string(log_path) {
-            for line in content.lines() {
-                // Parse file access patterns
-                if line.contains("openat(") || line.contains("open(") {
-                    if let Some(path) = extract_file_path(line) {
-                        accessed_files.insert(path);
-                    }
-                }
+    /// Run command with audit monitoring, tracing the accesses of `task`.
+    /// The command is killed once `timeout` passes or `cancel` is set.
+    pub fn run_with_audit(
+        &self,
+        task: &str,
+        cmd: &mut Command,
+        timeout: Duration,
+        cancel: &AtomicBool,
+    ) -> Result<(i32, AuditReport)> {
+        #[cfg(target_os = "linux")]
+        return crate::syscall_tracer::trace(cmd, task, timeout, cancel);
 
-                // Parse network connection patterns
-                if line.contains("connect(") || line.contains("bind(") {
-                    if let Some(addr) = extract_network_address(line) {
-                        network_connections.insert(addr);
-                    }
-                }
-            }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = (task, cmd, timeout, cancel);
+            Err(Error::configuration(
+                "Audit mode is only supported on Linux systems".to_string(),
+            ))
         }
-
-        Ok(AuditReport {
-            accessed_files: accessed_files.into_iter().collect(),
-            network_connections: network_connections.into_iter().collect(),
-        })
     }
 
     /// Add an allowed network host/CIDR
@@ -260,6 +353,7 @@ impl AccessRestrictions {
                 .unwrap_or_default(),
             allowed_hosts: security.allowed_hosts.as_ref().cloned().unwrap_or_default(),
             audit_mode: false,
+            require_enforcement: security.require_enforcement.unwrap_or(false),
         }
     }
 
@@ -299,13 +393,17 @@ impl AccessRestrictions {
 
     /// Apply restrictions to a command before execution
     /// This is the main entry point for applying platform-specific restrictions
+    ///
+    /// Hosts and networks in `allowed_hosts` are only reachable through the
+    /// proxy of [`Self::apply_to_command_with_proxy`], without it a command
+    /// restricted to them has no network access at all.
     pub fn apply_to_command(&self, cmd: &mut Command) -> Result<()> {
         if !self.has_any_restrictions() {
             return Ok(());
         }
         // Apply platform-specific restrictions
         #[cfg(target_os = "linux")]
-        self.apply_landlock_restrictions(cmd)?;
+        drop(self.apply_to_command_with_proxy(cmd)?);
 
         #[cfg(not(target_os = "linux"))]
         self.apply_fallback_restrictions(cmd)?;
@@ -318,21 +416,82 @@ impl AccessRestrictions {
         self.restrict_disk || self.restrict_network
     }
 
+    /// Check if network access is filtered by a proxy, as `allowed_hosts`
+    /// names hosts or networks rather than only ports
+    pub fn uses_network_proxy(&self) -> bool {
+        self.restrict_network && HostAllowlist::needs_proxy(&self.allowed_hosts)
+    }
+
+    /// Apply restrictions to a command before execution, isolating it in its
+    /// own network namespace if it uses a network proxy. The returned proxy
+    /// has to be started once the command is spawned.
+    #[cfg(target_os = "linux")]
+    pub fn apply_to_command_with_proxy(&self, cmd: &mut Command) -> Result<Option<PendingProxy>> {
+        if !self.has_any_restrictions() {
+            return Ok(None);
+        }
+        self.check_enforcement()?;
+
+        // The namespace is set up first, Landlock would deny writing the id maps
+        let proxy = if self.uses_network_proxy() {
+            let allowlist = HostAllowlist::parse(&self.allowed_hosts)?;
+            Some(isolate_network(cmd, allowlist)?)
+        } else {
+            None
+        };
+        if self.restrict_disk || proxy.is_none() {
+            self.apply_landlock_restrictions(cmd)?;
+        }
+
+        Ok(proxy)
+    }
+
+    /// Fail if enforcement is required but the kernel can't fully enforce
+    /// these restrictions
+    pub fn check_enforcement(&self) -> Result<()> {
+        if !self.require_enforcement {
+            return Ok(());
+        }
+        match self.enforcement_level() {
+            EnforcementLevel::Full => Ok(()),
+            level => Err(Error::configuration(format!(
+                "Restrictions are required to be enforced, but are {level} with Landlock ABI {} of the running kernel (ABI {LANDLOCK_MAX_ABI} needed)",
+                Self::landlock_abi()
+            ))),
+        }
+    }
+
     /// Apply Landlock-based restrictions on Linux
     #[cfg(target_os = "linux")]
     fn apply_landlock_restrictions(&self, cmd: &mut Command) -> Result<()> {
         use landlock::{
-            Access, AccessFs, AccessNet, NetPort, PathBeneath, PathFd, Ruleset, RulesetAttr,
-            RulesetCreatedAttr, RulesetStatus, ABI,
+            Access, AccessFs, AccessNet, CompatLevel, Compatible, NetPort, PathBeneath, PathFd,
+            Ruleset, RulesetAttr, RulesetCreatedAttr, RulesetStatus, ABI,
         };
         use std::os::unix::process::CommandExt;
 
         // Clone the necessary data for the pre_exec closure
         let restrict_disk = self.restrict_disk;
-        let restrict_network = self.restrict_network;
+        let restrict_network = self.restrict_network && !self.uses_network_proxy();
         let read_only_paths = self.read_only_paths.clone();
         let read_write_paths = self.read_write_paths.clone();
         let allowed_hosts = self.allowed_hosts.clone();
+        let require_enforcement = self.require_enforcement;
+
+        // Without Landlock the command runs unrestricted, which is reported
+        // by the enforcement level and refused by `check_enforcement`
+        let kernel_abi = Self::landlock_abi();
+        if kernel_abi == 0 {
+            log::warn!("Landlock is not supported by the running kernel");
+            return Ok(());
+        }
+        // Handle every access right the kernel knows, up to the ABI we build for
+        let abi = ABI::from(kernel_abi.min(LANDLOCK_MAX_ABI));
+        let compatibility = if require_enforcement {
+            CompatLevel::HardRequirement
+        } else {
+            CompatLevel::BestEffort
+        };
 
         // SAFETY: The pre_exec closure is only executed in the child process after fork()
         // but before exec(). The cloned data is moved into the closure, ensuring it
@@ -342,13 +501,10 @@ impl AccessRestrictions {
         // process will terminate without executing the target command.
         unsafe {
             cmd.pre_exec(move || {
-                // Use the highest ABI we want to support
-                let abi = ABI::V4;
-
-                log::debug!("Applying Landlock restrictions in child process");
+                log::debug!("Applying Landlock {abi} restrictions in child process");
 
                 // Build the ruleset
-                let mut ruleset = Ruleset::default();
+                let mut ruleset = Ruleset::default().set_compatibility(compatibility);
 
                 // Add filesystem access handling if needed
                 if restrict_disk {
@@ -360,7 +516,7 @@ impl AccessRestrictions {
                     })?;
                 }
 
-                // Add network access handling if needed (V2 or higher required)
+                // Add network access handling if needed (V4 or higher required)
                 if restrict_network {
                     // Handle both TCP bind and connect
                     ruleset = ruleset
@@ -408,7 +564,7 @@ impl AccessRestrictions {
                     }
                 }
 
-                // Add network rules (requires ABI V2 or higher)
+                // Add network rules (requires ABI V4 or higher)
                 if restrict_network {
                     // Parse and add allowed hosts (ports)
                     for host in &allowed_hosts {
@@ -438,9 +594,9 @@ impl AccessRestrictions {
                     std::io::Error::other(format!("Failed to apply Landlock restrictions: {e}"))
                 })?;
 
-                if status.ruleset == RulesetStatus::NotEnforced {
+                if require_enforcement && status.ruleset != RulesetStatus::FullyEnforced {
                     return Err(std::io::Error::other(
-                        "Landlock is not supported by the running kernel.",
+                        "Landlock restrictions are not fully enforced by the running kernel.",
                     ));
                 }
 
@@ -463,39 +619,6 @@ impl AccessRestrictions {
     }
 }
 
-/// Extract file path from strace output line
-fn extract_file_path(line: &str) -> Option<String> {
-    // Look for patterns like: openat(AT_FDCWD, "/path/to/file", O_RDONLY) = 3
-    if let Some(start) = line.find('"') {
-        if let Some(end) = line[start + 1..].find('"') {
-            let path = &line[start + 1..start + 1 + end];
-            // Filter out common system paths that aren't interesting for user restrictions
-            if !AUDIT_IGNORED_PATH_PREFIXES
-                .iter()
-                .any(|prefix| path.starts_with(prefix))
-                && path != LD_SO_CACHE
-            {
-                return Some(path.to_string());
-            }
-        }
-    }
-    None
-}
-
-/// Extract network address from strace output line
-fn extract_network_address(line: &str) -> Option<String> {
-    // Look for patterns like: connect(3, {sa_family=AF_INET, sin_port=htons(80), sin_addr=inet_addr("127.0.0.1")}, 16) = 0
-    if line.contains("AF_INET") {
-        if let Some(addr_start) = line.find("inet_addr(\"") {
-            if let Some(addr_end) = line[addr_start + 11..].find('"') {
-                let addr = &line[addr_start + 11..addr_start + 11 + addr_end];
-                return Some(addr.to_string());
-            }
-        }
-    }
-    None
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -560,6 +683,33 @@ mod tests {
         assert_eq!(cmd.get_program(), "echo");
     }
 
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_enforcement_level_by_abi() {
+        let disk = AccessRestrictions::new(true, false);
+        assert_eq!(disk.enforcement_level_for(5), EnforcementLevel::Full);
+        assert_eq!(disk.enforcement_level_for(3), EnforcementLevel::Partial);
+        assert_eq!(disk.enforcement_level_for(0), EnforcementLevel::None);
+
+        let mut ports = AccessRestrictions::new(true, true);
+        ports.add_allowed_host("443");
+        assert_eq!(ports.enforcement_level_for(6), EnforcementLevel::Full);
+        assert_eq!(ports.enforcement_level_for(3), EnforcementLevel::Partial);
+
+        let mut hosts = AccessRestrictions::new(false, true);
+        hosts.add_allowed_host("example.com");
+        assert_eq!(hosts.enforcement_level_for(0), EnforcementLevel::Full);
+        assert_eq!(EnforcementLevel::Partial.to_string(), "partially enforced");
+
+        let mut required = AccessRestrictions::new(true, false);
+        required.require_enforcement = true;
+        let level = required.enforcement_level();
+        assert_eq!(
+            required.check_enforcement().is_ok(),
+            level == EnforcementLevel::Full
+        );
+    }
+
     #[cfg(target_os = "linux")]
     #[test]
     fn test_landlock_restrictions_available() {
@@ -11,6 +11,7 @@ pub struct AccessRestrictionsBuilder {
     deny_paths: Vec<PathBuf>,
     allowed_hosts: Vec<String>,
     audit_mode: bool,
+    require_enforcement: bool,
 }
 
 impl AccessRestrictionsBuilder {
@@ -104,6 +105,12 @@ impl AccessRestrictionsBuilder {
         self
     }
 
+    /// Refuse to run unless the restrictions are fully enforced
+    pub fn require_enforcement(mut self, require: bool) -> Self {
+        self.require_enforcement = require;
+        self
+    }
+
     /// Build the AccessRestrictions
     pub fn build(self) -> AccessRestrictions {
         let mut restrictions = AccessRestrictions::with_allowlists(
@@ -115,6 +122,7 @@ impl AccessRestrictionsBuilder {
             self.allowed_hosts,
         );
 
+        restrictions.require_enforcement = self.require_enforcement;
         if self.audit_mode {
             restrictions.enable_audit_mode();
         }
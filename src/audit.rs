@@ -34,6 +34,8 @@ pub enum AuditEventType {
         success: bool,
         error: Option<String>,
     },
+    /// Secret cache lookups, telling cached values apart from live resolutions
+    SecretCacheLookup { key: String, hit: bool },
     /// File operation events
     FileOperation {
         operation: String,
@@ -48,6 +50,21 @@ pub enum AuditEventType {
         allowed: bool,
         reason: Option<String>,
     },
+    /// Task runs, one per attempt, with `exit_code` unset for runs killed at their timeout
+    TaskExecution {
+        task: String,
+        attempt: u32,
+        exit_code: Option<i32>,
+        timed_out: bool,
+        duration_ms: u64,
+    },
+    /// Connections a sandboxed task asked its network proxy for
+    NetworkAccess {
+        task: String,
+        host: String,
+        port: u16,
+        allowed: bool,
+    },
     /// Security validation events
     SecurityValidation {
         validation_type: String,
@@ -184,6 +201,59 @@ impl AuditLogger {
         .await
     }
 
+    /// Log a task run, `exit_code` is `None` if it timed out
+    pub async fn log_task_execution(
+        &self,
+        task: &str,
+        attempt: u32,
+        exit_code: Option<i32>,
+        duration_ms: u64,
+    ) -> Result<()> {
+        let level = if exit_code == Some(0) {
+            AuditLevel::Info
+        } else {
+            AuditLevel::Warning
+        };
+
+        self.log(
+            level,
+            AuditEventType::TaskExecution {
+                task: task.to_string(),
+                attempt,
+                exit_code,
+                timed_out: exit_code.is_none(),
+                duration_ms,
+            },
+        )
+        .await
+    }
+
+    /// Log a connection of a sandboxed task, allowed or blocked by `allowedHosts`
+    pub async fn log_network_access(
+        &self,
+        task: &str,
+        host: &str,
+        port: u16,
+        allowed: bool,
+    ) -> Result<()> {
+        let level = if allowed {
+            AuditLevel::Info
+        } else {
+            AuditLevel::Warning
+        };
+
+        self.log(
+            level,
+            AuditEventType::NetworkAccess {
+                task: task.to_string(),
+                host: host.to_string(),
+                port,
+                allowed,
+            },
+        )
+        .await
+    }
+
     /// Log a secret resolution event
     pub async fn log_secret_resolution(
         &self,
@@ -210,6 +280,18 @@ impl AuditLogger {
         .await
     }
 
+    /// Log whether a secret was served from the secret cache
+    pub async fn log_secret_cache_lookup(&self, key: &str, hit: bool) -> Result<()> {
+        self.log(
+            AuditLevel::Info,
+            AuditEventType::SecretCacheLookup {
+                key: key.to_string(),
+                hit,
+            },
+        )
+        .await
+    }
+
     /// Log a file operation event
     pub async fn log_file_operation(
         &self,
@@ -3,15 +3,18 @@
 //! This module provides caching for task actions, including memoization
 //! of results and integration with content-addressed storage.
 
+use crate::atomic_file::write_atomic_string;
 use crate::cache::concurrent_cache::ConcurrentCache;
 use crate::cache::content_addressed_store::ContentAddressedStore;
 use crate::cache::signing::{CacheSigner, SignedCacheEntry};
+use crate::cache::OutputFileMetadata;
 use crate::cue_parser::TaskConfig;
 use crate::errors::{Error, Result};
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::fs;
 use std::io::Cursor;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
@@ -28,10 +31,19 @@ pub struct ActionResult {
     pub stderr_hash: Option<String>,
     /// Output file hashes (path -> CAS hash)
     pub output_files: HashMap<String, String>,
+    /// Permissions and modification times of the files in `output_files`
+    #[serde(default)]
+    pub output_metadata: HashMap<String, OutputFileMetadata>,
+    /// Output directory tree hashes (path -> CAS hash)
+    #[serde(default)]
+    pub output_directories: HashMap<String, String>,
     /// When this action was executed
     pub executed_at: SystemTime,
     /// Duration of execution in milliseconds
     pub duration_ms: u64,
+    /// Encoded Remote Execution API `ExecutedActionMetadata` of remote clients
+    #[serde(default)]
+    pub execution_metadata: Vec<u8>,
 }
 
 /// Action digest computation
@@ -70,6 +82,8 @@ pub struct ActionCache {
     in_flight: Arc<DashMap<String, Arc<tokio::sync::Notify>>>,
     /// Cryptographic signer for cache entries
     signer: Arc<CacheSigner>,
+    /// Directory for persisted action results
+    results_dir: PathBuf,
 }
 
 impl ActionCache {
@@ -80,11 +94,16 @@ impl ActionCache {
         cache_dir: &Path,
     ) -> Result<Self> {
         let signer = Arc::new(CacheSigner::new(cache_dir)?);
+        let results_dir = cache_dir.join("actions");
+        fs::create_dir_all(&results_dir)
+            .map_err(|e| Error::file_system(&results_dir, "create action results directory", e))?;
+
         Ok(Self {
             result_cache: Arc::new(ConcurrentCache::new(max_cache_size)),
             cas,
             in_flight: Arc::new(DashMap::new()),
             signer,
+            results_dir,
         })
     }
 
@@ -133,53 +152,129 @@ impl ActionCache {
 
     /// Get cached action result from storage with signature verification
     fn get_cached_action_result(&self, hash: &str) -> Option<ActionResult> {
-        self.result_cache.get(hash).and_then(|cached| {
-            // Deserialize signed cache entry from stdout field
-            if let Some(stdout_bytes) = &cached.stdout {
-                if let Ok(stdout_str) = String::from_utf8(stdout_bytes.clone()) {
-                    if let Ok(signed_entry) =
-                        serde_json::from_str::<SignedCacheEntry<ActionResult>>(&stdout_str)
-                    {
-                        // Verify signature
-                        match self.signer.verify(&signed_entry) {
-                            Ok(true) => return Some(signed_entry.data),
-                            Ok(false) => {
-                                log::warn!(
-                                    "Cache entry signature verification failed for hash: {}",
-                                    hash
-                                );
-                                return None;
-                            }
-                            Err(e) => {
-                                log::error!("Error verifying cache entry signature: {}", e);
-                                return None;
-                            }
-                        }
-                    }
+        let Some(cached) = self.result_cache.get(hash) else {
+            return self.load_persisted_result(hash);
+        };
+
+        // Deserialize signed cache entry from stdout field
+        if let Some(stdout_bytes) = &cached.stdout {
+            if let Ok(stdout_str) = std::str::from_utf8(stdout_bytes) {
+                if let Some(verified) = self.verify_signed_result(hash, stdout_str) {
+                    return verified;
                 }
             }
+        }
 
-            // Fallback to legacy format for backward compatibility
-            let stdout_hash = cached
-                .stdout
-                .as_ref()
-                .map(|bytes| String::from_utf8_lossy(bytes).to_string());
-            let stderr_hash = cached
-                .stderr
-                .as_ref()
-                .map(|bytes| String::from_utf8_lossy(bytes).to_string());
-
-            Some(ActionResult {
-                exit_code: cached.exit_code,
-                stdout_hash,
-                stderr_hash,
-                output_files: cached.output_files.clone(),
-                executed_at: cached.executed_at,
-                duration_ms: 0, // Not stored in CachedTaskResult
-            })
+        // Fallback to legacy format for backward compatibility
+        let stdout_hash = cached
+            .stdout
+            .as_ref()
+            .map(|bytes| String::from_utf8_lossy(bytes).to_string());
+        let stderr_hash = cached
+            .stderr
+            .as_ref()
+            .map(|bytes| String::from_utf8_lossy(bytes).to_string());
+
+        Some(ActionResult {
+            exit_code: cached.exit_code,
+            stdout_hash,
+            stderr_hash,
+            output_files: cached.output_files.clone(),
+            output_metadata: cached.output_metadata.clone(),
+            output_directories: HashMap::new(),
+            executed_at: cached.executed_at,
+            duration_ms: 0, // Not stored in CachedTaskResult
+            execution_metadata: Vec::new(),
         })
     }
 
+    /// Verify a signed result.
+    ///
+    /// Returns `None` if the content is not a signed entry, and `Some(None)`
+    /// if it is one whose signature does not verify.
+    fn verify_signed_result(&self, hash: &str, content: &str) -> Option<Option<ActionResult>> {
+        // Verify the JSON value so map ordering cannot break the signature
+        let signed_entry =
+            serde_json::from_str::<SignedCacheEntry<serde_json::Value>>(content).ok()?;
+
+        match self.signer.verify(&signed_entry) {
+            Ok(true) => Some(serde_json::from_value(signed_entry.data).ok()),
+            Ok(false) => {
+                log::warn!(
+                    "Cache entry signature verification failed for hash: {}",
+                    hash
+                );
+                Some(None)
+            }
+            Err(e) => {
+                log::error!("Error verifying cache entry signature: {}", e);
+                Some(None)
+            }
+        }
+    }
+
+    /// Path of the persisted result for an action hash
+    fn result_path(&self, hash: &str) -> Option<PathBuf> {
+        // Action hashes are hex digests; anything else must not become a path
+        if hash.len() < 3 || !hash.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return None;
+        }
+
+        Some(
+            self.results_dir
+                .join(&hash[..2])
+                .join(format!("{}.json", &hash[2..])),
+        )
+    }
+
+    /// Load a result persisted by an earlier process into memory
+    fn load_persisted_result(&self, hash: &str) -> Option<ActionResult> {
+        let signed_json = fs::read_to_string(self.result_path(hash)?).ok()?;
+        let result = self.verify_signed_result(hash, &signed_json).flatten()?;
+
+        if let Err(e) = self
+            .result_cache
+            .insert(hash.to_string(), cached_entry(hash, &result, signed_json))
+        {
+            log::debug!("Failed to keep action result {hash} in memory: {e}");
+        }
+
+        Some(result)
+    }
+
+    /// Look up an action result by its digest hash
+    pub fn get_result(&self, hash: &str) -> Option<ActionResult> {
+        self.get_cached_action_result(hash)
+    }
+
+    /// Sign, persist and cache an action result under `hash`
+    pub fn store_result(&self, hash: &str, result: &ActionResult) -> Result<()> {
+        let value = serde_json::to_value(result).map_err(|e| Error::Json {
+            message: "Failed to serialize action result".to_string(),
+            source: e,
+        })?;
+        let signed_result = self
+            .signer
+            .sign(&value)
+            .map_err(|e| Error::configuration(format!("Failed to sign cache entry: {}", e)))?;
+
+        let signed_json = serde_json::to_string(&signed_result).map_err(|e| Error::Json {
+            message: "Failed to serialize signed cache entry".to_string(),
+            source: e,
+        })?;
+
+        if let Some(path) = self.result_path(hash) {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|e| Error::file_system(parent, "create action result directory", e))?;
+            }
+            write_atomic_string(&path, &signed_json)?;
+        }
+
+        self.result_cache
+            .insert(hash.to_string(), cached_entry(hash, result, signed_json))
+    }
+
     /// Check if an action result is cached
     pub async fn get_cached_result(&self, digest: &ActionDigest) -> Option<ActionResult> {
         // Just check cache, don't wait for in-flight actions
@@ -292,27 +387,7 @@ impl ActionCache {
         };
 
         // Cache the result with cryptographic signing
-        let signed_result = self
-            .signer
-            .sign(&result)
-            .map_err(|e| Error::configuration(format!("Failed to sign cache entry: {}", e)))?;
-
-        let signed_json = serde_json::to_string(&signed_result).map_err(|e| Error::Json {
-            message: "Failed to serialize signed cache entry".to_string(),
-            source: e,
-        })?;
-
-        let cached_result = crate::cache::CachedTaskResult {
-            cache_key: digest.hash.clone(),
-            executed_at: result.executed_at,
-            exit_code: result.exit_code,
-            stdout: Some(signed_json.as_bytes().to_vec()),
-            stderr: None, // Not used in signed format
-            output_files: result.output_files.clone(),
-        };
-
-        self.result_cache
-            .insert(digest.hash.clone(), cached_result)?;
+        self.store_result(&digest.hash, &result)?;
 
         // Remove from in-flight and notify waiters
         self.in_flight.remove(&digest.hash);
@@ -356,6 +431,30 @@ impl ActionCache {
     pub fn clear(&self) {
         self.result_cache.clear();
         self.in_flight.clear();
+
+        if let Err(e) = fs::remove_dir_all(&self.results_dir)
+            .and_then(|_| fs::create_dir_all(&self.results_dir))
+        {
+            log::warn!("Failed to clear persisted action results: {e}");
+        }
+    }
+}
+
+/// In-memory cache entry holding a signed action result
+fn cached_entry(
+    hash: &str,
+    result: &ActionResult,
+    signed_json: String,
+) -> crate::cache::CachedTaskResult {
+    crate::cache::CachedTaskResult {
+        cache_key: hash.to_string(),
+        executed_at: result.executed_at,
+        exit_code: result.exit_code,
+        stdout: Some(signed_json.into_bytes()),
+        stderr: None, // Not used in signed format
+        output_files: result.output_files.clone(),
+        output_metadata: result.output_metadata.clone(),
+        resource_usage: None,
     }
 }
 
@@ -428,16 +527,8 @@ mod tests {
         let task_config = TaskConfig {
             description: Some("Test task".to_string()),
             command: Some("echo hello".to_string()),
-            script: None,
-            dependencies: None,
-            working_dir: None,
-            shell: None,
-            inputs: None,
-            outputs: None,
             cache: Some(true),
-            cache_key: None,
-            timeout: None,
-            security: None,
+            ..Default::default()
         };
 
         let digest = cache
@@ -460,16 +551,8 @@ mod tests {
         let task_config = TaskConfig {
             description: Some("Test task".to_string()),
             command: Some("echo hello".to_string()),
-            script: None,
-            dependencies: None,
-            working_dir: None,
-            shell: None,
-            inputs: None,
-            outputs: None,
             cache: Some(true),
-            cache_key: None,
-            timeout: None,
-            security: None,
+            ..Default::default()
         };
 
         let digest = cache
@@ -485,8 +568,11 @@ mod tests {
                     stdout_hash: Some("hello\n".to_string()),
                     stderr_hash: None,
                     output_files: HashMap::new(),
+                    output_metadata: HashMap::new(),
+                    output_directories: HashMap::new(),
                     executed_at: SystemTime::now(),
                     duration_ms: 10,
+                    execution_metadata: Vec::new(),
                 })
             })
             .await
@@ -514,16 +600,8 @@ mod tests {
         let task_config = TaskConfig {
             description: Some("Test task".to_string()),
             command: Some("echo hello".to_string()),
-            script: None,
-            dependencies: None,
-            working_dir: None,
-            shell: None,
-            inputs: None,
-            outputs: None,
             cache: Some(true),
-            cache_key: None,
-            timeout: None,
-            security: None,
+            ..Default::default()
         };
 
         let digest = cache
@@ -546,8 +624,11 @@ mod tests {
                         stdout_hash: Some("hello from task 1\n".to_string()),
                         stderr_hash: None,
                         output_files: HashMap::new(),
+                        output_metadata: HashMap::new(),
+                        output_directories: HashMap::new(),
                         executed_at: SystemTime::now(),
                         duration_ms: 100,
+                        execution_metadata: Vec::new(),
                     })
                 })
                 .await;
@@ -571,8 +652,11 @@ mod tests {
                         stdout_hash: Some("hello from task 2\n".to_string()),
                         stderr_hash: None,
                         output_files: HashMap::new(),
+                        output_metadata: HashMap::new(),
+                        output_directories: HashMap::new(),
                         executed_at: SystemTime::now(),
                         duration_ms: 10,
+                        execution_metadata: Vec::new(),
                     })
                 })
                 .await;
@@ -0,0 +1,475 @@
+//! Components that make up a task cache key
+//!
+//! A cache key is derived from the task configuration, the arguments appended
+//! to its command, the contents of its declared inputs, an explicit allowlist
+//! of environment variables, the resolved shell binary and the results of the
+//! tasks it depends on. Paths are kept relative to the workspace root, so
+//! checkouts in different places share keys. The components are kept so that
+//! `cuenv cache explain` can show why a key changed between two runs.
+
+use super::hash_engine::ContentHasher;
+use crate::cue_parser::TaskConfig;
+use crate::errors::{Error, Result};
+use crate::workspace;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+/// Everything that contributes to the cache key of a task
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheKeyComponents {
+    /// Name of the task
+    pub task: String,
+    /// Digest of the serialized task configuration
+    pub config: String,
+    /// Digest of the arguments appended to the command
+    #[serde(default)]
+    pub args: String,
+    /// Directory the task was resolved from, relative to the workspace root
+    pub working_dir: String,
+    /// Canonical path and content digest of the resolved shell binary
+    pub shell: String,
+    /// Digests of the input files, keyed by path relative to the task directory
+    pub inputs: BTreeMap<String, String>,
+    /// Digests of the allowlisted environment variables, `None` when unset
+    pub env: BTreeMap<String, Option<String>>,
+    /// Result digests of the dependency tasks
+    pub dependencies: BTreeMap<String, String>,
+}
+
+impl CacheKeyComponents {
+    /// Collect the key components of a task
+    pub fn collect(
+        task_name: &str,
+        task_config: &TaskConfig,
+        args: &[String],
+        env_vars: &HashMap<String, String>,
+        working_dir: &Path,
+        dependencies: BTreeMap<String, String>,
+    ) -> Result<Self> {
+        // Going through a `Value` sorts map keys, so `env` and `params` hash
+        // the same in every run
+        let config = serde_json::to_value(task_config)
+            .map(|config| config.to_string())
+            .map_err(|e| Error::Json {
+                message: "Failed to serialize task configuration".to_string(),
+                source: e,
+            })?;
+
+        let task_dir = match &task_config.working_dir {
+            Some(task_wd) => working_dir.join(task_wd),
+            None => working_dir.to_path_buf(),
+        };
+
+        let mut inputs = BTreeMap::new();
+        for pattern in task_config.inputs.iter().flatten() {
+            let mut hasher = ContentHasher::new(pattern);
+            hasher.hash_glob(pattern, &task_dir)?;
+            for (file, digest) in hasher.manifest.files {
+                let relative = Path::new(&file)
+                    .strip_prefix(&task_dir)
+                    .map(|path| path.to_string_lossy().into_owned())
+                    .unwrap_or(file);
+                inputs.insert(relative, digest);
+            }
+        }
+
+        // Values are stored as digests so secrets never end up in the cache
+        let env = task_config
+            .env_inputs
+            .iter()
+            .flatten()
+            .map(|name| (name.clone(), env_vars.get(name).map(|value| sha256(value))))
+            .collect();
+
+        Ok(Self {
+            task: task_name.to_string(),
+            config: sha256(&config),
+            // Stored as a digest like env values, as arguments may be secrets
+            args: sha256(&args.join("\0")),
+            working_dir: workspace_path(working_dir),
+            shell: resolve_shell(task_config.shell.as_deref().unwrap_or("sh")),
+            inputs,
+            env,
+            dependencies,
+        })
+    }
+
+    /// Compute the cache key from the components
+    pub fn key(&self) -> String {
+        let mut hasher = Sha256::new();
+        let mut field = |name: &str, value: &str| {
+            hasher.update(name.as_bytes());
+            hasher.update([0]);
+            hasher.update(value.as_bytes());
+            hasher.update([0]);
+        };
+
+        field("task", &self.task);
+        field("config", &self.config);
+        field("args", &self.args);
+        field("working_dir", &self.working_dir);
+        field("shell", &self.shell);
+        for (path, digest) in &self.inputs {
+            field("input", path);
+            field("digest", digest);
+        }
+        for (name, digest) in &self.env {
+            field("env", name);
+            field("digest", digest.as_deref().unwrap_or("<unset>"));
+        }
+        for (name, digest) in &self.dependencies {
+            field("dependency", name);
+            field("digest", digest);
+        }
+
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// List the components that differ from an earlier run
+    pub fn diff(&self, previous: &Self) -> Vec<ComponentChange> {
+        let mut changes = Vec::new();
+
+        let scalars = [
+            ("config", &previous.config, &self.config),
+            ("args", &previous.args, &self.args),
+            ("working dir", &previous.working_dir, &self.working_dir),
+            ("shell", &previous.shell, &self.shell),
+        ];
+        for (component, before, after) in scalars {
+            if before != after {
+                changes.push(ComponentChange {
+                    component,
+                    name: None,
+                    before: Some(before.clone()),
+                    after: Some(after.clone()),
+                });
+            }
+        }
+
+        diff_maps("input", &previous.inputs, &self.inputs, &mut changes);
+        diff_maps(
+            "env",
+            &unset_as_text(&previous.env),
+            &unset_as_text(&self.env),
+            &mut changes,
+        );
+        diff_maps(
+            "dependency",
+            &previous.dependencies,
+            &self.dependencies,
+            &mut changes,
+        );
+
+        changes
+    }
+}
+
+/// A single difference between the key components of two runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentChange {
+    /// Kind of component, e.g. `input` or `env`
+    pub component: &'static str,
+    /// Name of the entry for map components
+    pub name: Option<String>,
+    /// Value in the earlier run, `None` if it was added
+    pub before: Option<String>,
+    /// Value in the later run, `None` if it was removed
+    pub after: Option<String>,
+}
+
+impl fmt::Display for ComponentChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let marker = match (&self.before, &self.after) {
+            (None, _) => '+',
+            (_, None) => '-',
+            _ => '~',
+        };
+        write!(f, "{marker} {}", self.component)?;
+        if let Some(name) = &self.name {
+            write!(f, " {name}")?;
+        }
+
+        match (&self.before, &self.after) {
+            (Some(before), Some(after)) => {
+                write!(f, ": {} → {}", abbreviate(before), abbreviate(after))
+            }
+            (None, Some(value)) | (Some(value), None) => write!(f, ": {}", abbreviate(value)),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+fn diff_maps(
+    component: &'static str,
+    before: &BTreeMap<String, String>,
+    after: &BTreeMap<String, String>,
+    changes: &mut Vec<ComponentChange>,
+) {
+    let mut names: Vec<&String> = before.keys().chain(after.keys()).collect();
+    names.sort();
+    names.dedup();
+
+    for name in names {
+        let (old, new) = (before.get(name), after.get(name));
+        if old != new {
+            changes.push(ComponentChange {
+                component,
+                name: Some(name.clone()),
+                before: old.cloned(),
+                after: new.cloned(),
+            });
+        }
+    }
+}
+
+fn unset_as_text(env: &BTreeMap<String, Option<String>>) -> BTreeMap<String, String> {
+    env.iter()
+        .map(|(name, digest)| {
+            (
+                name.clone(),
+                digest.clone().unwrap_or_else(|| "<unset>".to_string()),
+            )
+        })
+        .collect()
+}
+
+/// Shorten digests for display, leaving paths and markers intact
+fn abbreviate(value: &str) -> &str {
+    let is_digest = value.len() == 64 && value.chars().all(|c| c.is_ascii_hexdigit());
+    if is_digest {
+        &value[..12]
+    } else {
+        value
+    }
+}
+
+fn sha256(value: &str) -> String {
+    format!("{:x}", Sha256::digest(value.as_bytes()))
+}
+
+/// `dir` as `//path` relative to its workspace root, or the absolute path
+/// outside of a workspace
+fn workspace_path(dir: &Path) -> String {
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let relative = workspace::find_root(&dir).and_then(|root| {
+        let root = root.canonicalize().unwrap_or(root);
+        dir.strip_prefix(root).ok().map(Path::to_path_buf)
+    });
+
+    match relative {
+        Some(path) => format!("//{}", path.to_string_lossy()),
+        None => dir.to_string_lossy().into_owned(),
+    }
+}
+
+/// Resolve a shell name to the binary that will be executed, identified by its
+/// canonical path and the digest of its contents, like `/usr/bin/bash <sha256>`.
+/// Shells of different versions or builds thus get different keys, even when
+/// they are found under the same name.
+fn resolve_shell(shell: &str) -> String {
+    // Shells are hashed once per process, not for every task
+    static DIGESTS: OnceLock<Mutex<HashMap<PathBuf, Option<String>>>> = OnceLock::new();
+
+    let candidate = if shell.contains(std::path::MAIN_SEPARATOR) {
+        Some(PathBuf::from(shell))
+    } else {
+        std::env::var_os("PATH").and_then(|path| {
+            std::env::split_paths(&path)
+                .map(|dir| dir.join(shell))
+                .find(|path| path.is_file())
+        })
+    };
+    let Some(path) = candidate.map(|path| path.canonicalize().unwrap_or(path)) else {
+        return shell.to_string();
+    };
+
+    let mut digests = DIGESTS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let digest = digests
+        .entry(path.clone())
+        .or_insert_with(|| file_digest(&path));
+    match digest {
+        Some(digest) => format!("{} {digest}", path.display()),
+        None => path.display().to_string(),
+    }
+}
+
+fn file_digest(path: &Path) -> Option<String> {
+    let mut file = File::open(path).ok()?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).ok()?;
+    Some(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn task(inputs: &[&str], env_inputs: &[&str]) -> TaskConfig {
+        TaskConfig {
+            command: Some("true".to_string()),
+            inputs: Some(inputs.iter().map(|s| s.to_string()).collect()),
+            env_inputs: Some(env_inputs.iter().map(|s| s.to_string()).collect()),
+            ..Default::default()
+        }
+    }
+
+    fn collect(config: &TaskConfig, dir: &Path, env: &[(&str, &str)]) -> CacheKeyComponents {
+        let env_vars = env
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        CacheKeyComponents::collect("build", config, &[], &env_vars, dir, BTreeMap::new()).unwrap()
+    }
+
+    #[test]
+    fn test_key_tracks_input_contents() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("main.rs"), "fn main() {}").unwrap();
+        let config = task(&["*.rs"], &[]);
+
+        let first = collect(&config, temp_dir.path(), &[]);
+        assert_eq!(first.key(), collect(&config, temp_dir.path(), &[]).key());
+
+        fs::write(temp_dir.path().join("main.rs"), "fn main() { run() }").unwrap();
+        let second = collect(&config, temp_dir.path(), &[]);
+        assert_ne!(first.key(), second.key());
+
+        let changes = second.diff(&first);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].component, "input");
+        assert_eq!(changes[0].name.as_deref(), Some("main.rs"));
+    }
+
+    #[test]
+    fn test_key_only_uses_allowlisted_env() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = task(&[], &["RUSTFLAGS"]);
+
+        let base = collect(&config, temp_dir.path(), &[("SHLVL", "1")]);
+        let unrelated = collect(&config, temp_dir.path(), &[("SHLVL", "2"), ("PWD", "/")]);
+        assert_eq!(base.key(), unrelated.key());
+
+        let allowed = collect(&config, temp_dir.path(), &[("RUSTFLAGS", "-O")]);
+        assert_ne!(base.key(), allowed.key());
+
+        let changes = allowed.diff(&base);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(
+            changes[0].to_string().split(':').next(),
+            Some("~ env RUSTFLAGS")
+        );
+        assert!(!changes[0].to_string().contains("-O"));
+    }
+
+    #[test]
+    fn test_key_includes_dependency_results() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = task(&[], &[]);
+        let env_vars = HashMap::new();
+
+        let first = CacheKeyComponents::collect(
+            "build",
+            &config,
+            &[],
+            &env_vars,
+            temp_dir.path(),
+            BTreeMap::from([("codegen".to_string(), "a".repeat(64))]),
+        )
+        .unwrap();
+        let second = CacheKeyComponents::collect(
+            "build",
+            &config,
+            &[],
+            &env_vars,
+            temp_dir.path(),
+            BTreeMap::from([("codegen".to_string(), "b".repeat(64))]),
+        )
+        .unwrap();
+
+        assert_ne!(first.key(), second.key());
+        assert_eq!(
+            second.diff(&first)[0].to_string(),
+            format!(
+                "~ dependency codegen: {} → {}",
+                "a".repeat(12),
+                "b".repeat(12)
+            )
+        );
+    }
+
+    #[test]
+    fn test_key_includes_passthrough_args() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = task(&[], &[]);
+        let env_vars = HashMap::new();
+        let collect_with = |args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            CacheKeyComponents::collect(
+                "test",
+                &config,
+                &args,
+                &env_vars,
+                temp_dir.path(),
+                BTreeMap::new(),
+            )
+            .unwrap()
+        };
+
+        let a = collect_with(&["--filter", "a"]);
+        let b = collect_with(&["--filter", "b"]);
+        assert_eq!(a.key(), collect_with(&["--filter", "a"]).key());
+        assert_ne!(a.key(), b.key());
+        assert_ne!(a.key(), collect_with(&["--filter a"]).key());
+        assert_eq!(b.diff(&a)[0].component, "args");
+    }
+
+    #[test]
+    fn test_key_is_independent_of_checkout_location() {
+        let checkouts = [TempDir::new().unwrap(), TempDir::new().unwrap()];
+        let config = TaskConfig {
+            shell: Some("sh".to_string()),
+            ..task(&["src/*.rs"], &[])
+        };
+        let components: Vec<_> = checkouts
+            .iter()
+            .map(|checkout| {
+                let project = checkout.path().join("services/api");
+                fs::create_dir(checkout.path().join(".git")).unwrap();
+                fs::create_dir_all(project.join("src")).unwrap();
+                fs::write(project.join("src/main.rs"), "fn main() {}").unwrap();
+                collect(&config, &project, &[])
+            })
+            .collect();
+
+        assert_eq!(components[0].working_dir, "//services/api");
+        assert!(components[0].shell.starts_with('/'));
+        assert_eq!(components[0].key(), components[1].key());
+    }
+
+    #[test]
+    fn test_shell_is_identified_by_path_and_contents() {
+        let temp_dir = TempDir::new().unwrap();
+        let shell = |name: &str, contents: &str| {
+            let path = temp_dir.path().join(name);
+            fs::write(&path, contents).unwrap();
+            resolve_shell(&path.display().to_string())
+        };
+
+        let old = shell("bash-3.2", "3.2");
+        let dir = temp_dir.path().canonicalize().unwrap();
+        assert!(old.starts_with(&dir.join("bash-3.2").display().to_string()));
+        assert_ne!(old, shell("bash-5.2", "5.2"));
+        assert_eq!(resolve_shell("no-such-shell"), "no-such-shell");
+    }
+}
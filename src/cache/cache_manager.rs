@@ -1,14 +1,18 @@
 //! Unified cache manager with security and remote cache support
-use super::{ActionCache, CacheConfig, CacheEngine, CachedTaskResult, ContentAddressedStore};
+use super::{
+    capture_outputs, restore_outputs, ActionCache, CacheConfig, CacheEngine, CacheKeyComponents,
+    CachedTaskResult, CapturedOutputs, ContentAddressedStore,
+};
 use crate::async_runtime::{run_async, AsyncRuntime};
 use crate::atomic_file::write_atomic_string;
-use crate::cache::signing::CacheSigner;
+use crate::cache::signing::{CacheSigner, SignedCacheEntry};
 use crate::cue_parser::TaskConfig;
 use crate::errors::{Error, Result};
+use crate::remote_cache::RemoteCacheClient;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex, RwLock};
 use std::time::SystemTime;
 
@@ -20,6 +24,9 @@ const CACHE_VERSION: u32 = 1;
 pub struct CacheStatistics {
     pub hits: u64,
     pub misses: u64,
+    /// Local misses that were served by the remote cache
+    #[serde(default)]
+    pub remote_hits: u64,
     pub writes: u64,
     pub errors: u64,
     pub lock_contentions: u64,
@@ -43,6 +50,8 @@ pub struct CacheManager {
     signer: Arc<CacheSigner>,
     /// Simple in-memory cache for task results
     memory_cache: Arc<Mutex<HashMap<String, CachedTaskResult>>>,
+    /// Remote tier consulted after local misses
+    remote: Option<Arc<RemoteCacheClient>>,
 }
 
 impl CacheManager {
@@ -77,9 +86,11 @@ impl CacheManager {
 
         let cas_dir = config.base_dir.join("cas");
         let action_dir = config.base_dir.join("actions");
+        let results_dir = config.base_dir.join("results");
 
         std::fs::create_dir_all(&cas_dir)?;
         std::fs::create_dir_all(&action_dir)?;
+        std::fs::create_dir_all(&results_dir)?;
 
         // Initialize content-addressed store
         let content_store = Arc::new(ContentAddressedStore::new(
@@ -108,6 +119,7 @@ impl CacheManager {
             version: CACHE_VERSION,
             signer,
             memory_cache: Arc::new(Mutex::new(HashMap::new())),
+            remote: None,
         };
 
         // Check and migrate cache if needed
@@ -116,6 +128,14 @@ impl CacheManager {
         Ok(manager)
     }
 
+    /// Share task results with a remote cache.
+    ///
+    /// The remote tier follows its own mode, independent of the local one.
+    pub fn with_remote(mut self, remote: RemoteCacheClient) -> Self {
+        self.remote = Some(Arc::new(remote));
+        self
+    }
+
     /// Check cache version and migrate if necessary
     fn check_and_migrate(&self) -> Result<()> {
         let version_file = self.config.base_dir.join("VERSION");
@@ -168,6 +188,18 @@ impl CacheManager {
         // Clear action cache
         self.action_cache.clear();
 
+        // Clear task results
+        if let Ok(mut cache) = self.memory_cache.lock() {
+            cache.clear();
+        }
+        let results_dir = self.config.base_dir.join("results");
+        if results_dir.exists() {
+            fs::remove_dir_all(&results_dir)
+                .map_err(|e| Error::file_system(&results_dir, "remove cached results", e))?;
+        }
+        fs::create_dir_all(&results_dir)
+            .map_err(|e| Error::file_system(&results_dir, "create results directory", e))?;
+
         // Clear content store would require more complex logic
         log::info!("Cache cleared");
 
@@ -196,29 +228,57 @@ impl CacheManager {
 
     /// Get cached result for a task
     pub fn get_cached_result(&self, cache_key: &str) -> Option<CachedTaskResult> {
+        if !self.config.mode.is_readable() {
+            return None;
+        }
+
+        let mut found = None;
         if let Ok(cache) = self.memory_cache.lock() {
-            if let Some(result) = cache.get(cache_key) {
-                // Only return successful results (exit_code == 0)
-                if result.exit_code == 0 {
-                    let mut stats = self.stats.write().unwrap();
-                    stats.hits += 1;
-                    return Some(result.clone());
-                }
+            found = cache.get(cache_key).cloned();
+        }
+
+        // Fall back to results persisted by earlier runs
+        if found.is_none() {
+            found = self.load_persisted_result(cache_key);
+            if let (Some(result), Ok(mut cache)) = (&found, self.memory_cache.lock()) {
+                cache.insert(cache_key.to_string(), result.clone());
             }
         }
 
+        // Only return successful results (exit_code == 0)
+        if let Some(result) = found.filter(|result| result.exit_code == 0) {
+            let mut stats = self.stats.write().unwrap();
+            stats.hits += 1;
+            return Some(result);
+        }
+
         let mut stats = self.stats.write().unwrap();
         stats.misses += 1;
         None
     }
 
+    /// Look up a successful cached result without counting a hit or miss
+    pub fn peek_cached_result(&self, cache_key: &str) -> Option<CachedTaskResult> {
+        if !self.config.mode.is_readable() {
+            return None;
+        }
+
+        let cached = self
+            .memory_cache
+            .lock()
+            .ok()
+            .and_then(|cache| cache.get(cache_key).cloned());
+        cached
+            .or_else(|| self.load_persisted_result(cache_key))
+            .filter(|result| result.exit_code == 0)
+    }
+
     /// Store a cached result
     pub fn store_result(&self, cache_key: String, result: CachedTaskResult) -> Result<()> {
-        // Sign the result for integrity
-        let _signed_result = self.signer.sign(&result)?;
-
         // Only cache successful results (exit_code == 0)
-        if result.exit_code == 0 {
+        if result.exit_code == 0 && self.config.mode.is_writable() {
+            self.persist_result(&cache_key, &result)?;
+
             if let Ok(mut cache) = self.memory_cache.lock() {
                 cache.insert(cache_key, result);
             }
@@ -230,6 +290,147 @@ impl CacheManager {
         Ok(())
     }
 
+    /// Look up a task result in the remote cache.
+    ///
+    /// Output files of a hit are copied into the local content store so they
+    /// can be restored. The result itself is only kept locally if the local
+    /// cache is writable. Remote failures are logged and treated as a miss.
+    pub async fn get_remote_result(&self, cache_key: &str) -> Option<CachedTaskResult> {
+        let remote = self.remote.as_ref().filter(|r| r.mode().is_readable())?;
+
+        match remote.fetch(cache_key, &self.content_store).await {
+            Ok(Some(result)) => {
+                self.stats.write().unwrap().remote_hits += 1;
+                if self.config.mode.is_writable() {
+                    if let Err(e) = self.persist_result(cache_key, &result) {
+                        log::warn!("Failed to keep remote result {cache_key} locally: {e}");
+                    }
+                    if let Ok(mut cache) = self.memory_cache.lock() {
+                        cache.insert(cache_key.to_string(), result.clone());
+                    }
+                }
+                Some(result)
+            }
+            Ok(None) => None,
+            Err(e) => {
+                log::warn!("Remote cache lookup at {} failed: {e}", remote.endpoint());
+                self.stats.write().unwrap().errors += 1;
+                None
+            }
+        }
+    }
+
+    /// Whether the remote cache has a usable result for a cache key.
+    ///
+    /// Unlike [`get_remote_result`](Self::get_remote_result) nothing is
+    /// downloaded, kept locally or counted. Remote failures are logged and
+    /// treated as a miss.
+    pub async fn has_remote_result(&self, cache_key: &str) -> bool {
+        let Some(remote) = self.remote.as_ref().filter(|r| r.mode().is_readable()) else {
+            return false;
+        };
+
+        remote.contains(cache_key).await.unwrap_or_else(|e| {
+            log::warn!("Remote cache lookup at {} failed: {e}", remote.endpoint());
+            false
+        })
+    }
+
+    /// Upload a successful task result to the remote cache if it is writable
+    pub async fn store_remote_result(&self, result: &CachedTaskResult) -> Result<()> {
+        let Some(remote) = self.remote.as_ref().filter(|r| r.mode().is_writable()) else {
+            return Ok(());
+        };
+        if result.exit_code != 0 {
+            return Ok(());
+        }
+
+        remote.upload(result, &self.content_store).await
+    }
+
+    /// Path of the persisted result for a cache key
+    fn result_path(&self, cache_key: &str) -> Option<PathBuf> {
+        // Cache keys are hex digests; anything else must not become a path
+        if cache_key.len() < 3 || !cache_key.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return None;
+        }
+
+        Some(
+            self.config
+                .base_dir
+                .join("results")
+                .join(&cache_key[..2])
+                .join(format!("{}.json", &cache_key[2..])),
+        )
+    }
+
+    /// Sign and write a result so later runs can reuse it
+    fn persist_result(&self, cache_key: &str, result: &CachedTaskResult) -> Result<()> {
+        let Some(path) = self.result_path(cache_key) else {
+            return Ok(());
+        };
+
+        // Sign the JSON value so the signature does not depend on map iteration order
+        let value = serde_json::to_value(result).map_err(|e| Error::Json {
+            message: "Failed to serialize cached result".to_string(),
+            source: e,
+        })?;
+        let signed = self.signer.sign(&value)?;
+        let json = serde_json::to_string(&signed).map_err(|e| Error::Json {
+            message: "Failed to serialize signed cached result".to_string(),
+            source: e,
+        })?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| Error::file_system(parent, "create results directory", e))?;
+        }
+        write_atomic_string(&path, &json)
+    }
+
+    /// Read a persisted result, ignoring entries that fail verification
+    fn load_persisted_result(&self, cache_key: &str) -> Option<CachedTaskResult> {
+        let path = self.result_path(cache_key)?;
+        let content = fs::read_to_string(&path).ok()?;
+
+        let signed: SignedCacheEntry<serde_json::Value> = match serde_json::from_str(&content) {
+            Ok(signed) => signed,
+            Err(e) => {
+                log::warn!("Ignoring unreadable cached result {}: {e}", path.display());
+                return None;
+            }
+        };
+
+        match self.signer.verify(&signed) {
+            Ok(true) => {}
+            Ok(false) => {
+                log::warn!("Ignoring cached result with invalid signature: {cache_key}");
+                return None;
+            }
+            Err(e) => {
+                log::warn!("Failed to verify cached result {cache_key}: {e}");
+                return None;
+            }
+        }
+
+        serde_json::from_value(signed.data).ok()
+    }
+
+    /// Store the files matched by `patterns` in the content store
+    pub fn capture_outputs(&self, patterns: &[String], base_dir: &Path) -> Result<CapturedOutputs> {
+        capture_outputs(&self.content_store, patterns, base_dir)
+    }
+
+    /// Restore the output files of a cached result below `base_dir`
+    pub fn restore_outputs(
+        &self,
+        result: &CachedTaskResult,
+        patterns: &[String],
+        base_dir: &Path,
+    ) -> Result<usize> {
+        restore_outputs(&self.content_store, result, patterns, base_dir)
+    }
+
     /// Generate cache key for a task
     pub fn generate_cache_key(
         &self,
@@ -238,25 +439,66 @@ impl CacheManager {
         env_vars: &HashMap<String, String>,
         working_dir: &Path,
     ) -> Result<String> {
-        // Generate a simple cache key based on task name and config
+        let components = CacheKeyComponents::collect(
+            task_name,
+            task_config,
+            &[],
+            env_vars,
+            working_dir,
+            BTreeMap::new(),
+        )?;
+        Ok(components.key())
+    }
+
+    /// Remember the key components of a run in `working_dir` for `cache explain`.
+    ///
+    /// Only the two most recent runs of a task are kept.
+    pub fn record_key_components(
+        &self,
+        working_dir: &Path,
+        components: &CacheKeyComponents,
+    ) -> Result<()> {
+        let path = self.key_history_path(&components.task, working_dir);
+        let mut history = self.load_key_history(&path);
+        history.push(components.clone());
+        if history.len() > 2 {
+            history.drain(..history.len() - 2);
+        }
+
+        let json = serde_json::to_string_pretty(&history).map_err(|e| Error::Json {
+            message: "Failed to serialize cache key history".to_string(),
+            source: e,
+        })?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| Error::file_system(parent, "create key history directory", e))?;
+        }
+        write_atomic_string(&path, &json)
+    }
+
+    /// Key components of the most recent runs of a task, oldest first
+    pub fn key_history(&self, task_name: &str, working_dir: &Path) -> Vec<CacheKeyComponents> {
+        self.load_key_history(&self.key_history_path(task_name, working_dir))
+    }
+
+    fn key_history_path(&self, task_name: &str, working_dir: &Path) -> PathBuf {
         use sha2::{Digest, Sha256};
 
         let mut hasher = Sha256::new();
-        hasher.update(task_name.as_bytes());
-        hasher.update(
-            serde_json::to_string(task_config)
-                .unwrap_or_default()
-                .as_bytes(),
-        );
         hasher.update(working_dir.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(task_name.as_bytes());
+        self.config
+            .base_dir
+            .join("keys")
+            .join(format!("{:x}.json", hasher.finalize()))
+    }
 
-        // Include relevant environment variables
-        for (key, value) in env_vars {
-            hasher.update(key.as_bytes());
-            hasher.update(value.as_bytes());
-        }
-
-        Ok(format!("{:x}", hasher.finalize()))
+    fn load_key_history(&self, path: &Path) -> Vec<CacheKeyComponents> {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
     }
 
     /// Legacy API for backward compatibility with tests
@@ -286,6 +528,8 @@ impl CacheManager {
             stdout: None,
             stderr: None,
             output_files: std::collections::HashMap::new(),
+            output_metadata: std::collections::HashMap::new(),
+            resource_usage: None,
         };
         self.store_result(cache_key.to_string(), result)
     }
@@ -344,6 +344,8 @@ mod tests {
             stdout: None,
             stderr: None,
             output_files: HashMap::new(),
+            output_metadata: HashMap::new(),
+            resource_usage: None,
         };
 
         // Insert
@@ -382,6 +384,8 @@ mod tests {
                             stdout: None,
                             stderr: None,
                             output_files: HashMap::new(),
+                            output_metadata: HashMap::new(),
+                            resource_usage: None,
                         };
 
                         // Write
@@ -420,6 +424,8 @@ mod tests {
                     ("file1.txt".to_string(), "hash1".to_string()),
                     ("file2.txt".to_string(), "hash2".to_string()),
                 ]),
+                output_metadata: HashMap::new(),
+                resource_usage: None,
             };
             cache.insert(format!("key_{}", i), result).unwrap();
         }
@@ -443,6 +449,8 @@ mod tests {
                 stdout: None,
                 stderr: None,
                 output_files: HashMap::new(),
+                output_metadata: HashMap::new(),
+                resource_usage: None,
             };
             cache.insert(format!("key_{}", i), result).unwrap();
         }
@@ -1,5 +1,5 @@
 //! Cache configuration types
-use super::CacheMode;
+use super::{get_cache_mode, CacheMode};
 use std::path::PathBuf;
 
 /// Configuration for cache systems
@@ -22,7 +22,7 @@ impl Default for CacheConfig {
         Self {
             base_dir: XdgPaths::cache_dir(),
             max_size: 10 * 1024 * 1024 * 1024, // 10GB
-            mode: CacheMode::ReadWrite,
+            mode: get_cache_mode(),
             inline_threshold: 1024, // 1KB
         }
     }
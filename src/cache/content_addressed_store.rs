@@ -9,12 +9,13 @@ use crate::errors::{Error, Result};
 use dashmap::DashMap;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
-use std::fs;
-use std::io::Read;
-use std::path::PathBuf;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant, SystemTime};
+use uuid::Uuid;
 
 /// Metadata for a stored object
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -193,6 +194,89 @@ impl ContentAddressedStore {
         Ok(content)
     }
 
+    /// Path for a new temporary file on the store's filesystem, which
+    /// [`Self::store_file`] can move into the store
+    pub fn temp_path(&self) -> Result<PathBuf> {
+        let temp_dir = self.base_dir.join("tmp");
+        fs::create_dir_all(&temp_dir)
+            .map_err(|e| Error::file_system(&temp_dir, "create CAS temporary directory", e))?;
+        Ok(temp_dir.join(format!("{}.tmp", Uuid::new_v4())))
+    }
+
+    /// Store the content of a file without reading it into memory and return
+    /// its hash. The file is moved into the store, or removed if the content
+    /// is already stored or small enough to be inlined.
+    pub fn store_file(&self, path: &Path) -> Result<String> {
+        let size = fs::metadata(path)
+            .map_err(|e| Error::file_system(path, "read CAS file metadata", e))?
+            .len();
+        if size as usize <= self.inline_threshold {
+            let file = File::open(path).map_err(|e| Error::file_system(path, "open", e))?;
+            let hash = self.store(file)?;
+            let _ = fs::remove_file(path);
+            return Ok(hash);
+        }
+
+        self.maybe_garbage_collect()?;
+
+        let hash = {
+            use sha2::{Digest, Sha256};
+            let mut hasher = Sha256::new();
+            hasher.update(size.to_le_bytes());
+            let mut file = File::open(path).map_err(|e| Error::file_system(path, "open", e))?;
+            io::copy(&mut file, &mut hasher)
+                .map_err(|e| Error::file_system(path, "read content for CAS", e))?;
+            format!("{:x}", hasher.finalize())
+        };
+
+        if let Some(mut entry) = self.index.get_mut(&hash) {
+            entry.ref_count += 1;
+            drop(entry);
+            let _ = fs::remove_file(path);
+            self.persist_index()?;
+            return Ok(hash);
+        }
+
+        let object_path = self.get_object_path(&hash);
+        if let Some(parent) = object_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| {
+                Error::file_system(parent.to_path_buf(), "create CAS object directory", e)
+            })?;
+        }
+        fs::rename(path, &object_path)
+            .map_err(|e| Error::file_system(&object_path, "move file into CAS", e))?;
+
+        self.index.insert(
+            hash.clone(),
+            ObjectMetadata {
+                hash: hash.clone(),
+                size,
+                stored_at: SystemTime::now(),
+                ref_count: 1,
+                inlined: false,
+            },
+        );
+        self.total_bytes.fetch_add(size, Ordering::Relaxed);
+        self.persist_index()?;
+
+        Ok(hash)
+    }
+
+    /// Open a stored object for reading in chunks. Unlike
+    /// [`Self::retrieve`], the content is not verified.
+    pub fn open(&self, hash: &str) -> Result<File> {
+        let metadata = self
+            .index
+            .get(hash)
+            .ok_or_else(|| Error::configuration(format!("Object not found in CAS: {hash}")))?;
+        let path = if metadata.inlined {
+            self.get_inline_path(hash)
+        } else {
+            self.get_object_path(hash)
+        };
+        File::open(&path).map_err(|e| Error::file_system(&path, "open CAS object", e))
+    }
+
     /// Compute hash of content with length prefix to prevent collisions
     fn hash_content(&self, content: &[u8]) -> String {
         use sha2::{Digest, Sha256};
@@ -508,6 +592,24 @@ mod tests {
         assert_eq!(cas.retrieve(&large_hash).unwrap(), large_content);
     }
 
+    #[test]
+    fn test_cas_store_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let cas = ContentAddressedStore::new(temp_dir.path().to_path_buf(), 10).unwrap();
+        let content = b"This is a larger piece of content that exceeds the inline threshold";
+
+        let path = cas.temp_path().unwrap();
+        fs::write(&path, content).unwrap();
+        let hash = cas.store_file(&path).unwrap();
+        assert!(!path.exists());
+        assert_eq!(hash, cas.store(Cursor::new(content)).unwrap());
+        assert_eq!(cas.get_metadata(&hash).unwrap().ref_count, 2);
+
+        let mut stored = Vec::new();
+        cas.open(&hash).unwrap().read_to_end(&mut stored).unwrap();
+        assert_eq!(stored, content);
+    }
+
     #[test]
     fn test_cas_garbage_collection() {
         let temp_dir = TempDir::new().unwrap();
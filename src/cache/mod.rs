@@ -11,9 +11,11 @@ mod mode;
 
 // Advanced caching modules
 mod action_cache;
+mod cache_key;
 mod cache_manager;
 mod concurrent_cache;
 mod content_addressed_store;
+mod outputs;
 pub mod signing;
 mod types;
 
@@ -25,10 +27,12 @@ pub use mode::{get_cache_mode, CacheMode};
 
 // Export advanced caching components
 pub use action_cache::{ActionCache, ActionDigest, ActionResult};
+pub use cache_key::{CacheKeyComponents, ComponentChange};
 pub use cache_manager::{CacheManager, CacheStatistics};
 pub use concurrent_cache::{ConcurrentCache, ConcurrentCacheBuilder};
 pub use content_addressed_store::{ContentAddressedStore, ObjectMetadata};
-pub use types::CachedTaskResult;
+pub use outputs::{capture_outputs, restore_outputs, CapturedOutputs};
+pub use types::{CachedTaskResult, OutputFileMetadata};
 
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
@@ -0,0 +1,294 @@
+//! Capture and restoration of task output files
+//!
+//! Output files declared by a task are stored in the content-addressed store
+//! after a successful run and written back, with their permissions and
+//! modification times, when a later run is answered from the cache.
+
+use super::{expand_glob_pattern, CachedTaskResult, ContentAddressedStore, OutputFileMetadata};
+use crate::atomic_file::write_atomic;
+use crate::errors::{Error, Result};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Digests and attributes of captured output files, keyed by relative path
+pub type CapturedOutputs = (HashMap<String, String>, HashMap<String, OutputFileMetadata>);
+
+/// Expand output patterns into the files currently present below `base_dir`
+fn expand_outputs(patterns: &[String], base_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for pattern in patterns {
+        files.extend(expand_glob_pattern(pattern, base_dir)?);
+    }
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// Relative cache path of an output file
+fn relative_key(file: &Path, base_dir: &Path) -> Result<String> {
+    file.strip_prefix(base_dir)
+        .map(|relative| relative.to_string_lossy().into_owned())
+        .map_err(|_| {
+            Error::configuration(format!(
+                "Output file {} is outside of {}",
+                file.display(),
+                base_dir.display()
+            ))
+        })
+}
+
+/// Resolve a cached relative path, rejecting anything that escapes `base_dir`
+fn resolve_key(key: &str, base_dir: &Path) -> Result<PathBuf> {
+    let relative = Path::new(key);
+    let is_safe = relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+
+    if !is_safe || key.is_empty() {
+        return Err(Error::security(format!(
+            "Refusing to restore cached output outside of the task directory: {key}"
+        )));
+    }
+
+    Ok(base_dir.join(relative))
+}
+
+#[cfg(unix)]
+fn file_mode(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn file_mode(metadata: &fs::Metadata) -> u32 {
+    if metadata.permissions().readonly() {
+        0o444
+    } else {
+        0o644
+    }
+}
+
+#[cfg(unix)]
+fn set_file_mode(path: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+        .map_err(|e| Error::file_system(path, "set output file permissions", e))
+}
+
+#[cfg(not(unix))]
+fn set_file_mode(path: &Path, mode: u32) -> Result<()> {
+    let mut permissions = fs::metadata(path)
+        .map_err(|e| Error::file_system(path, "read output file metadata", e))?
+        .permissions();
+    permissions.set_readonly(mode & 0o222 == 0);
+    fs::set_permissions(path, permissions)
+        .map_err(|e| Error::file_system(path, "set output file permissions", e))
+}
+
+/// Store every file matched by the output patterns in the content-addressed store
+pub fn capture_outputs(
+    store: &ContentAddressedStore,
+    patterns: &[String],
+    base_dir: &Path,
+) -> Result<CapturedOutputs> {
+    let mut digests = HashMap::new();
+    let mut attributes = HashMap::new();
+
+    for file in expand_outputs(patterns, base_dir)? {
+        let key = relative_key(&file, base_dir)?;
+        let handle =
+            fs::File::open(&file).map_err(|e| Error::file_system(&file, "open output file", e))?;
+        let metadata = handle
+            .metadata()
+            .map_err(|e| Error::file_system(&file, "read output file metadata", e))?;
+        let modified = metadata
+            .modified()
+            .map_err(|e| Error::file_system(&file, "read output file mtime", e))?;
+
+        let digest = store.store(handle)?;
+        attributes.insert(
+            key.clone(),
+            OutputFileMetadata {
+                mode: file_mode(&metadata),
+                modified,
+            },
+        );
+        digests.insert(key, digest);
+    }
+
+    Ok((digests, attributes))
+}
+
+/// Write the outputs recorded in `result` back below `base_dir`.
+///
+/// Files matching the output patterns that are not part of the cached result
+/// are removed. Fails without touching the file system if any recorded output
+/// is missing from the store, so the caller can fall back to executing the task.
+/// Returns the number of restored files.
+pub fn restore_outputs(
+    store: &ContentAddressedStore,
+    result: &CachedTaskResult,
+    patterns: &[String],
+    base_dir: &Path,
+) -> Result<usize> {
+    // Resolve and load everything first so a partial restore never happens
+    let mut restored = Vec::with_capacity(result.output_files.len());
+    for (key, digest) in &result.output_files {
+        let path = resolve_key(key, base_dir)?;
+        if !store.contains(digest) {
+            return Err(Error::configuration(format!(
+                "Cached output '{key}' is missing from the content store"
+            )));
+        }
+        let content = store.retrieve(digest)?;
+        restored.push((key, path, content));
+    }
+
+    // Remove stale outputs that the cached result does not contain
+    let expected: HashSet<&str> = result.output_files.keys().map(String::as_str).collect();
+    for file in expand_outputs(patterns, base_dir)? {
+        let key = relative_key(&file, base_dir)?;
+        if !expected.contains(key.as_str()) {
+            log::debug!("Removing stale output {}", file.display());
+            fs::remove_file(&file)
+                .map_err(|e| Error::file_system(&file, "remove stale output file", e))?;
+        }
+    }
+
+    let count = restored.len();
+    for (key, path, content) in restored {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| Error::file_system(parent, "create output directory", e))?;
+        }
+        write_atomic(&path, &content)?;
+
+        if let Some(attributes) = result.output_metadata.get(key) {
+            // Set the mtime before the mode, which may make the file read-only
+            fs::OpenOptions::new()
+                .write(true)
+                .open(&path)
+                .and_then(|handle| handle.set_modified(attributes.modified))
+                .map_err(|e| Error::file_system(&path, "set output file mtime", e))?;
+            set_file_mode(&path, attributes.mode)?;
+        }
+    }
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+    use tempfile::TempDir;
+
+    fn result_with(outputs: CapturedOutputs) -> CachedTaskResult {
+        CachedTaskResult {
+            cache_key: "key".to_string(),
+            executed_at: SystemTime::now(),
+            exit_code: 0,
+            stdout: None,
+            stderr: None,
+            output_files: outputs.0,
+            output_metadata: outputs.1,
+            resource_usage: None,
+        }
+    }
+
+    #[test]
+    fn test_capture_and_restore_outputs() {
+        let cache_dir = TempDir::new().unwrap();
+        let work_dir = TempDir::new().unwrap();
+        let store = ContentAddressedStore::new(cache_dir.path().to_path_buf(), 16).unwrap();
+        let patterns = vec!["dist".to_string()];
+
+        let binary = work_dir.path().join("dist/bin/app");
+        fs::create_dir_all(binary.parent().unwrap()).unwrap();
+        fs::write(&binary, "a fairly large binary payload").unwrap();
+        fs::write(work_dir.path().join("dist/small.txt"), "tiny").unwrap();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&binary, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        fs::File::options()
+            .write(true)
+            .open(&binary)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+
+        let result = result_with(capture_outputs(&store, &patterns, work_dir.path()).unwrap());
+        assert_eq!(result.output_files.len(), 2);
+
+        // Simulate a fresh checkout with a leftover file from another build
+        fs::remove_dir_all(work_dir.path().join("dist")).unwrap();
+        fs::create_dir_all(work_dir.path().join("dist")).unwrap();
+        fs::write(work_dir.path().join("dist/stale.txt"), "old").unwrap();
+
+        let restored = restore_outputs(&store, &result, &patterns, work_dir.path()).unwrap();
+        assert_eq!(restored, 2);
+
+        assert_eq!(
+            fs::read_to_string(&binary).unwrap(),
+            "a fairly large binary payload"
+        );
+        assert_eq!(
+            fs::read_to_string(work_dir.path().join("dist/small.txt")).unwrap(),
+            "tiny"
+        );
+        assert!(!work_dir.path().join("dist/stale.txt").exists());
+        assert_eq!(fs::metadata(&binary).unwrap().modified().unwrap(), mtime);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&binary).unwrap().permissions().mode() & 0o7777;
+            assert_eq!(mode, 0o755);
+        }
+    }
+
+    #[test]
+    fn test_restore_rejects_escaping_paths() {
+        let cache_dir = TempDir::new().unwrap();
+        let work_dir = TempDir::new().unwrap();
+        let store = ContentAddressedStore::new(cache_dir.path().to_path_buf(), 16).unwrap();
+        let digest = store.store(&b"payload"[..]).unwrap();
+
+        let result = result_with((
+            HashMap::from([("../escape.txt".to_string(), digest)]),
+            HashMap::new(),
+        ));
+
+        assert!(restore_outputs(&store, &result, &[], work_dir.path()).is_err());
+        assert!(!work_dir
+            .path()
+            .parent()
+            .unwrap()
+            .join("escape.txt")
+            .exists());
+    }
+
+    #[test]
+    fn test_restore_fails_when_object_missing() {
+        let cache_dir = TempDir::new().unwrap();
+        let work_dir = TempDir::new().unwrap();
+        let store = ContentAddressedStore::new(cache_dir.path().to_path_buf(), 16).unwrap();
+        let existing = work_dir.path().join("out.txt");
+        fs::write(&existing, "current").unwrap();
+
+        let result = result_with((
+            HashMap::from([("out.txt".to_string(), "0".repeat(64))]),
+            HashMap::new(),
+        ));
+
+        assert!(
+            restore_outputs(&store, &result, &["out.txt".to_string()], work_dir.path()).is_err()
+        );
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "current");
+    }
+}
@@ -1,6 +1,8 @@
 //! Common types used across the cache implementation
 
+use crate::resource_limits::ResourceUsage;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::time::SystemTime;
 
@@ -17,6 +19,39 @@ pub struct CachedTaskResult {
     pub stdout: Option<Vec<u8>>,
     /// Standard error (if captured)
     pub stderr: Option<Vec<u8>>,
-    /// Output files produced by the task
+    /// Output files produced by the task, mapping relative paths to content digests
     pub output_files: HashMap<String, String>,
+    /// Permissions and modification times of the files in `output_files`
+    #[serde(default)]
+    pub output_metadata: HashMap<String, OutputFileMetadata>,
+    /// Peak memory and CPU time of the run, for tasks with a cgroup
+    #[serde(default)]
+    pub resource_usage: Option<ResourceUsage>,
+}
+
+impl CachedTaskResult {
+    /// Digest identifying this result and the contents of its outputs
+    pub fn digest(&self) -> String {
+        let mut outputs: Vec<_> = self.output_files.iter().collect();
+        outputs.sort();
+
+        let mut hasher = Sha256::new();
+        hasher.update(self.cache_key.as_bytes());
+        for (path, digest) in outputs {
+            hasher.update([0]);
+            hasher.update(path.as_bytes());
+            hasher.update([0]);
+            hasher.update(digest.as_bytes());
+        }
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+/// File attributes restored together with a cached output file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputFileMetadata {
+    /// Permission bits of the file
+    pub mode: u32,
+    /// Last modification time of the file
+    pub modified: SystemTime,
 }
@@ -0,0 +1,320 @@
+//! Transient cgroup v2 of a task
+//!
+//! A task with a `resources` block runs in a cgroup of its own, created below
+//! the closest ancestor of cuenv's cgroup that is delegated to the user, like
+//! `user@1000.service` of systemd. The cgroup enforces the limits and measures
+//! what the task used, and is removed once the task exits.
+use crate::errors::{Error, Result};
+use crate::resource_limits::{ResourceUsage, TaskResources};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::os::fd::AsRawFd;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+/// Period of the CPU bandwidth limit in microseconds
+const CPU_PERIOD_USEC: u64 = 100_000;
+
+/// Smallest CPU quota the kernel accepts in microseconds
+const CPU_MIN_QUOTA_USEC: u64 = 1_000;
+
+/// Distinguishes cgroups of tasks run at the same time
+static NEXT_ID: AtomicU32 = AtomicU32::new(0);
+
+/// The cgroup a task runs in, killed and removed on drop. Dropping waits for
+/// the killed processes to exit, so it belongs off async worker threads.
+#[derive(Debug)]
+pub struct TaskCgroup {
+    dir: PathBuf,
+    procs: File,
+}
+
+impl TaskCgroup {
+    /// Create a cgroup enforcing `resources` for `task`. Fails if cgroup v2,
+    /// a delegated subtree or a controller the limits need is unavailable.
+    pub fn create(task: &str, resources: &TaskResources) -> Result<Self> {
+        let base = delegated_base()?;
+        let name: String = task
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect();
+        let name = format!(
+            "cuenv-{}-{}-{}",
+            name.trim_matches('-'),
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        );
+        Self::create_in(&base, &name, resources)
+    }
+
+    /// Create the cgroup `name` below `base`, enabling the controllers it needs
+    fn create_in(base: &Path, name: &str, resources: &TaskResources) -> Result<Self> {
+        let mut required = Vec::new();
+        for (controller, needed) in [
+            ("memory", resources.memory.is_some()),
+            ("cpu", resources.cpus.is_some()),
+            ("pids", resources.pids.is_some()),
+            ("io", resources.io_weight.is_some()),
+        ] {
+            if needed {
+                required.push(controller);
+            }
+        }
+
+        // Memory is enabled even when unlimited, to report its peak usage
+        let available = read_list(&base.join("cgroup.controllers"));
+        let enabled = read_list(&base.join("cgroup.subtree_control"));
+        for controller in required.iter().copied().chain(["memory"]) {
+            let controller = controller.to_string();
+            if available.contains(&controller) && !enabled.contains(&controller) {
+                let control = base.join("cgroup.subtree_control");
+                if let Err(e) = fs::write(&control, format!("+{controller}")) {
+                    log::debug!("Failed to enable cgroup controller {controller}: {e}");
+                }
+            }
+        }
+
+        let dir = base.join(name);
+        fs::create_dir(&dir).map_err(|e| Error::file_system(&dir, "create cgroup", e))?;
+        let cgroup = Self::open(dir.clone()).inspect_err(|_| {
+            let _ = fs::remove_dir(&dir);
+        })?;
+        cgroup.limit(resources, &required)?;
+        Ok(cgroup)
+    }
+
+    /// Take ownership of the existing cgroup `dir`
+    fn open(dir: PathBuf) -> Result<Self> {
+        let path = dir.join("cgroup.procs");
+        let procs = OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .map_err(|e| Error::file_system(path, "open", e))?;
+        Ok(Self { dir, procs })
+    }
+
+    /// Write the limits of `resources`, which need the `required` controllers
+    fn limit(&self, resources: &TaskResources, required: &[&str]) -> Result<()> {
+        let controllers = read_list(&self.dir.join("cgroup.controllers"));
+        if let Some(missing) = required
+            .iter()
+            .find(|controller| !controllers.iter().any(|c| c == *controller))
+        {
+            return Err(Error::unsupported(
+                "cgroup resource limits",
+                format!("the {missing} controller is not delegated to cuenv"),
+            ));
+        }
+
+        if let Some(memory) = resources.memory {
+            self.write("memory.max", &memory.to_string())?;
+        }
+        if let Some(cpus) = resources.cpus {
+            let quota = ((cpus * CPU_PERIOD_USEC as f64).round() as u64).max(CPU_MIN_QUOTA_USEC);
+            self.write("cpu.max", &format!("{quota} {CPU_PERIOD_USEC}"))?;
+        }
+        if let Some(pids) = resources.pids {
+            self.write("pids.max", &pids.to_string())?;
+        }
+        if let Some(weight) = resources.io_weight {
+            self.write("io.weight", &format!("default {weight}"))?;
+        }
+        Ok(())
+    }
+
+    /// Move the command into this cgroup before it executes
+    pub fn add_to_command(&self, cmd: &mut Command) {
+        let procs = self.procs.as_raw_fd();
+        // SAFETY: The closure only calls write(2), which is async-signal-safe,
+        // on a descriptor that stays open until the cgroup is dropped, after
+        // the command was spawned. Writing "0" moves the writing process.
+        unsafe {
+            cmd.pre_exec(move || {
+                if libc::write(procs, b"0".as_ptr().cast(), 1) < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+
+    /// Peak memory and CPU time used by the processes of this cgroup
+    pub fn usage(&self) -> ResourceUsage {
+        let peak_memory = fs::read_to_string(self.dir.join("memory.peak"))
+            .ok()
+            .and_then(|peak| peak.trim().parse().ok());
+        let cpu_usec = fs::read_to_string(self.dir.join("cpu.stat"))
+            .ok()
+            .and_then(|stat| {
+                stat.lines()
+                    .find_map(|line| line.strip_prefix("usage_usec "))
+                    .and_then(|usec| usec.trim().parse().ok())
+            })
+            .unwrap_or(0);
+
+        ResourceUsage {
+            peak_memory,
+            cpu_time: Duration::from_micros(cpu_usec),
+        }
+    }
+
+    fn write(&self, file: &str, value: &str) -> Result<()> {
+        let path = self.dir.join(file);
+        fs::write(&path, value).map_err(|e| Error::file_system(path, "write cgroup limit", e))
+    }
+}
+
+impl Drop for TaskCgroup {
+    fn drop(&mut self) {
+        // Processes that escaped the task's process group are killed too
+        let kill = OpenOptions::new()
+            .write(true)
+            .open(self.dir.join("cgroup.kill"));
+        if let Ok(mut kill) = kill {
+            let _ = kill.write_all(b"1");
+        }
+        for _ in 0..50 {
+            match fs::remove_dir(&self.dir) {
+                Ok(()) => return,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+                Err(_) => std::thread::sleep(Duration::from_millis(10)),
+            }
+        }
+        log::warn!("Failed to remove cgroup {}", self.dir.display());
+    }
+}
+
+/// Closest cgroup above cuenv's own that it may create cgroups in and move
+/// processes into, or the root cgroup when cuenv runs in it
+fn delegated_base() -> Result<PathBuf> {
+    let unavailable = |message: &str| Error::unsupported("cgroup v2", message.to_string());
+
+    let mounts = fs::read_to_string("/proc/self/mounts")
+        .map_err(|e| Error::file_system("/proc/self/mounts", "read", e))?;
+    let mount = mounts
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .find(|fields| fields.len() > 2 && fields[2] == "cgroup2")
+        .map(|fields| PathBuf::from(fields[1]))
+        .ok_or_else(|| unavailable("no cgroup2 filesystem is mounted"))?;
+
+    let cgroups = fs::read_to_string("/proc/self/cgroup")
+        .map_err(|e| Error::file_system("/proc/self/cgroup", "read", e))?;
+    let own = cgroups
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .ok_or_else(|| unavailable("cuenv is not in a cgroup v2 hierarchy"))?;
+    let own = Path::new(own.trim());
+
+    // Processes may only live in leaf cgroups, except for the root
+    let mut candidates: Vec<&Path> = own.ancestors().skip(1).collect();
+    if own == Path::new("/") {
+        candidates.push(own);
+    }
+    candidates
+        .into_iter()
+        .map(|cgroup| mount.join(cgroup.strip_prefix("/").unwrap_or(cgroup)))
+        .find(|dir| {
+            is_writable(&dir.join("cgroup.procs"))
+                && is_writable(&dir.join("cgroup.subtree_control"))
+        })
+        .ok_or_else(|| unavailable("no cgroup is delegated to the user"))
+}
+
+fn is_writable(path: &Path) -> bool {
+    OpenOptions::new().write(true).open(path).is_ok()
+}
+
+/// Space separated entries of a cgroup file, like `cgroup.controllers`
+fn read_list(path: &Path) -> Vec<String> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// A plain directory standing in for a cgroup with some controllers
+    fn fake_cgroup(temp_dir: &TempDir) -> (PathBuf, TaskCgroup) {
+        let dir = temp_dir.path().join("cuenv-build-1-0");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("cgroup.procs"), "").unwrap();
+        fs::write(dir.join("cgroup.controllers"), "cpu memory pids").unwrap();
+        let cgroup = TaskCgroup::open(dir.clone()).unwrap();
+        (dir, cgroup)
+    }
+
+    /// Remove the stand-in first, dropping only removes empty cgroup directories
+    fn discard(dir: &Path, cgroup: TaskCgroup) {
+        fs::remove_dir_all(dir).unwrap();
+        drop(cgroup);
+    }
+
+    #[test]
+    fn test_limits_written() {
+        let temp_dir = TempDir::new().unwrap();
+        let (dir, cgroup) = fake_cgroup(&temp_dir);
+        let resources = TaskResources {
+            memory: Some(1 << 30),
+            cpus: Some(1.5),
+            pids: Some(64),
+            io_weight: None,
+        };
+
+        cgroup
+            .limit(&resources, &["memory", "cpu", "pids"])
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("memory.max")).unwrap(),
+            "1073741824"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("cpu.max")).unwrap(),
+            "150000 100000"
+        );
+        assert_eq!(fs::read_to_string(dir.join("pids.max")).unwrap(), "64");
+        discard(&dir, cgroup);
+    }
+
+    #[test]
+    fn test_missing_controller_is_unsupported() {
+        let temp_dir = TempDir::new().unwrap();
+        let (dir, cgroup) = fake_cgroup(&temp_dir);
+        let resources = TaskResources {
+            io_weight: Some(200),
+            ..TaskResources::default()
+        };
+
+        assert!(cgroup.limit(&resources, &["io"]).is_err());
+        discard(&dir, cgroup);
+    }
+
+    #[test]
+    fn test_usage() {
+        let temp_dir = TempDir::new().unwrap();
+        let (dir, cgroup) = fake_cgroup(&temp_dir);
+        fs::write(dir.join("memory.peak"), "1048576\n").unwrap();
+        fs::write(
+            dir.join("cpu.stat"),
+            "usage_usec 2500000\nuser_usec 2000000\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            cgroup.usage(),
+            ResourceUsage {
+                peak_memory: Some(1 << 20),
+                cpu_time: Duration::from_millis(2500),
+            }
+        );
+        discard(&dir, cgroup);
+    }
+}
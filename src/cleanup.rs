@@ -8,6 +8,7 @@ use once_cell::sync::Lazy;
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
@@ -220,6 +221,7 @@ pub struct ProcessGuard {
     registry_id: Option<u64>,
     timeout: Duration,
     started_at: Instant,
+    cancel: Option<Arc<AtomicBool>>,
 }
 
 impl ProcessGuard {
@@ -261,18 +263,29 @@ impl ProcessGuard {
             registry_id,
             timeout,
             started_at: Instant::now(),
+            cancel: None,
         }
     }
 
+    /// Abort the wait and kill the process group when `cancel` is set
+    pub fn with_cancellation(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
     /// Wait for the process to complete with timeout
+    ///
+    /// On timeout the process group is killed and [`Error::Timeout`] returned.
     pub fn wait_with_timeout(&mut self) -> Result<std::process::ExitStatus> {
+        let cancel = self.cancel.clone();
         if let Some(child) = self.child.as_mut() {
+            let pid = child.id();
             let remaining = self.timeout.saturating_sub(self.started_at.elapsed());
 
             // Check if already timed out
             if remaining.is_zero() {
-                self.kill()?;
-                return Err(Error::configuration("Process timed out"));
+                self.kill_process_group()?;
+                return Err(Error::timeout(format!("process {pid}"), self.timeout));
             }
 
             // Try to wait with timeout
@@ -311,9 +324,19 @@ impl ProcessGuard {
                                 return Ok(status);
                             }
                             Ok(None) => {
+                                if cancel
+                                    .as_ref()
+                                    .is_some_and(|cancel| cancel.load(Ordering::SeqCst))
+                                {
+                                    self.kill_process_group()?;
+                                    return Err(Error::configuration("Process cancelled"));
+                                }
                                 if Instant::now() >= deadline {
-                                    self.kill()?;
-                                    return Err(Error::configuration("Process timed out"));
+                                    self.kill_process_group()?;
+                                    return Err(Error::timeout(
+                                        format!("process {pid}"),
+                                        self.timeout,
+                                    ));
                                 }
                             }
                             Err(e) => {
@@ -351,6 +374,33 @@ impl ProcessGuard {
         Ok(())
     }
 
+    /// Kill the process and every process in its group.
+    ///
+    /// The child should have been spawned as a process group leader. Sends SIGTERM
+    /// to the group first and SIGKILL if it is still running shortly after.
+    pub fn kill_process_group(&mut self) -> Result<()> {
+        #[cfg(unix)]
+        if let Some(child) = self.child.as_mut() {
+            let pgid = child.id() as libc::pid_t;
+
+            // Safety: killpg only sends a signal to the given process group.
+            // It fails if the child doesn't lead a group, and only the child is killed
+            if unsafe { libc::killpg(pgid, libc::SIGTERM) } == 0 {
+                std::thread::sleep(Duration::from_millis(100));
+
+                if child.try_wait().ok().flatten().is_none() {
+                    // Safety: see above
+                    unsafe {
+                        libc::killpg(pgid, libc::SIGKILL);
+                    }
+                }
+                let _ = child.wait();
+            }
+        }
+
+        self.kill()
+    }
+
     /// Take ownership of the child process
     pub fn into_inner(mut self) -> Option<std::process::Child> {
         if let Some(id) = self.registry_id.take() {
@@ -585,7 +635,57 @@ mod tests {
 
         // Should timeout
         let result = guard.wait_with_timeout();
-        assert!(result.is_err());
+        assert!(matches!(result, Err(Error::Timeout { .. })));
         assert!(result.unwrap_err().to_string().contains("timed out"));
     }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_process_guard_cancellation_kills_group() {
+        use std::os::unix::process::CommandExt;
+
+        let child = std::process::Command::new("sh")
+            .arg("-c")
+            .arg("sleep 30 & sleep 31 & wait")
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let pgid = child.id();
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        let mut guard = ProcessGuard::new(child, Duration::from_secs(60))
+            .with_cancellation(Arc::clone(&cancel));
+
+        let trigger = Arc::clone(&cancel);
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(300));
+            trigger.store(true, Ordering::SeqCst);
+        });
+
+        let result = guard.wait_with_timeout();
+        assert!(result.unwrap_err().to_string().contains("cancelled"));
+
+        // Killed processes may linger as zombies when nothing reaps orphans,
+        // as in containers, so only processes in another state count as alive
+        let alive = || {
+            std::fs::read_dir("/proc")
+                .unwrap()
+                .flatten()
+                .filter_map(|entry| std::fs::read_to_string(entry.path().join("stat")).ok())
+                .filter(|stat| {
+                    // Fields after the command name: state, ppid, pgrp
+                    let fields: Vec<&str> = stat
+                        .rsplit_once(')')
+                        .map(|(_, rest)| rest.split_whitespace().collect())
+                        .unwrap_or_default();
+                    fields.get(2) == Some(&pgid.to_string().as_str()) && fields[0] != "Z"
+                })
+                .count()
+        };
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while alive() > 0 && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        assert_eq!(alive(), 0, "process group should be gone");
+    }
 }
@@ -2,14 +2,33 @@
 // CUE package constants
 pub const ENV_PACKAGE_NAME: &str = "env";
 pub const ENV_CUE_FILENAME: &str = "env.cue";
+pub const ENV_INSTANCE_NAME: &str = "env";
+
+// Project configuration files, in order of precedence
+pub const PROJECT_CONFIG_FILENAMES: &[&str] = &["cuenv.toml", ".cuenvrc"];
 
 // Resolver prefix
 pub const CUENV_RESOLVER_PREFIX: &str = "cuenv-resolver://";
 
+// Prefix of #Expand and #PathList values
+pub const CUENV_EXPAND_PREFIX: &str = "cuenv-expand://";
+
+// Separator joining list values, unless a variable sets @separator
+pub const DEFAULT_LIST_SEPARATOR: &str = ":";
+
 // Environment variable names
 pub const CUENV_ENV_VAR: &str = "CUENV_ENV";
 pub const CUENV_CAPABILITIES_VAR: &str = "CUENV_CAPABILITIES";
 pub const CUENV_LOG_VAR: &str = "CUENV_LOG";
+pub const CUENV_HIERARCHICAL_VAR: &str = "CUENV_HIERARCHICAL";
+pub const CUENV_PACKAGE_VAR: &str = "CUENV_PACKAGE";
+pub const CUENV_INSTANCE_VAR: &str = "CUENV_INSTANCE";
+
+// Markers that terminate the upward search for parent env packages
+pub const HIERARCHY_ROOT_MARKERS: &[&str] = &["cue.mod", ".git"];
+
+// Exit status of `cuenv run` when tasks failed only by timing out, as with coreutils timeout
+pub const TASK_TIMEOUT_EXIT_CODE: i32 = 124;
 
 // Default shell
 pub const DEFAULT_SHELL: &str = "bash";
@@ -20,8 +39,5 @@ pub const AUDIT_IGNORED_PATH_PREFIXES: &[&str] = &["/proc/", "/sys/", "/dev/", "
 // Common system files
 pub const LD_SO_CACHE: &str = "/etc/ld.so.cache";
 
-// Audit log path
-pub const AUDIT_LOG_PATH: &str = "/tmp/cuenv_audit.log";
-
 // Network access patterns
 pub const LOCALHOST_PATTERN: &str = "localhost";
@@ -0,0 +1,267 @@
+//! Source-located CUE evaluation errors
+//!
+//! The bridge reports every CUE error with the file, position and value path
+//! it refers to. They are rendered as miette diagnostics showing the offending
+//! source, so a failing `env.cue` doesn't have to be searched by hand.
+use miette::{
+    Diagnostic, GraphicalReportHandler, GraphicalTheme, LabeledSpan, NamedSource, SourceCode,
+    SourceSpan,
+};
+use serde::Deserialize;
+use std::fmt;
+use std::path::PathBuf;
+
+/// A CUE error as reported by the bridge
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct CueDiagnostic {
+    pub message: String,
+    pub file: Option<PathBuf>,
+    /// 1-based line
+    pub line: Option<usize>,
+    /// 1-based byte column
+    pub column: Option<usize>,
+    /// Path of the value in error, e.g. `env.PORT`
+    pub path: Option<String>,
+}
+
+impl fmt::Display for CueDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}", file.display())?;
+            if let (Some(line), Some(column)) = (self.line, self.column) {
+                write!(f, ":{line}:{column}")?;
+            }
+            f.write_str(": ")?;
+        }
+        match &self.path {
+            Some(path) if !path.is_empty() => write!(f, "{path}: {}", self.message),
+            _ => f.write_str(&self.message),
+        }
+    }
+}
+
+/// A [`CueDiagnostic`] together with the source it points into
+#[derive(Debug)]
+struct LocatedDiagnostic {
+    diagnostic: CueDiagnostic,
+    source: Option<NamedSource<String>>,
+    span: Option<SourceSpan>,
+}
+
+impl LocatedDiagnostic {
+    fn new(diagnostic: CueDiagnostic) -> Self {
+        let contents = diagnostic
+            .file
+            .as_ref()
+            .and_then(|file| std::fs::read_to_string(file).ok());
+        let span = match (&contents, diagnostic.line, diagnostic.column) {
+            (Some(contents), Some(line), Some(column)) => span_at(contents, line, column),
+            _ => None,
+        };
+        let source = match (&diagnostic.file, contents) {
+            (Some(file), Some(contents)) if span.is_some() => {
+                Some(NamedSource::new(file.display().to_string(), contents).with_language("cue"))
+            }
+            _ => None,
+        };
+
+        Self {
+            diagnostic,
+            source,
+            span,
+        }
+    }
+}
+
+impl fmt::Display for LocatedDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.diagnostic.path {
+            Some(path) if !path.is_empty() => write!(f, "{path}: {}", self.diagnostic.message),
+            _ => f.write_str(&self.diagnostic.message),
+        }
+    }
+}
+
+impl std::error::Error for LocatedDiagnostic {}
+
+impl Diagnostic for LocatedDiagnostic {
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        self.source.as_ref().map(|source| source as &dyn SourceCode)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let span = self.span?;
+        Some(Box::new(std::iter::once(LabeledSpan::new_with_span(
+            None, span,
+        ))))
+    }
+}
+
+/// Span from a 1-based line and column to the end of the expression on that line
+fn span_at(contents: &str, line: usize, column: usize) -> Option<SourceSpan> {
+    let line_start: usize = contents
+        .split_inclusive('\n')
+        .take(line.checked_sub(1)?)
+        .map(str::len)
+        .sum();
+    let text = contents[line_start..].lines().next()?;
+    let start = column.checked_sub(1)?.min(text.len());
+    let rest = text.get(start..)?;
+    let len = rest.trim_end().trim_end_matches(',').len();
+    Some(SourceSpan::new((line_start + start).into(), len))
+}
+
+/// All errors from evaluating a CUE package
+#[derive(Debug)]
+pub struct CueDiagnostics {
+    diagnostics: Vec<LocatedDiagnostic>,
+    help: Option<String>,
+}
+
+impl CueDiagnostics {
+    pub fn new(diagnostics: Vec<CueDiagnostic>) -> Self {
+        Self {
+            diagnostics: diagnostics
+                .into_iter()
+                .map(LocatedDiagnostic::new)
+                .collect(),
+            help: None,
+        }
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    pub fn diagnostics(&self) -> impl Iterator<Item = &CueDiagnostic> {
+        self.diagnostics.iter().map(|located| &located.diagnostic)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+
+    /// The first error and how many followed it, on one line
+    pub fn summary(&self) -> String {
+        let Some(first) = self.diagnostics.first() else {
+            return "CUE evaluation failed".to_string();
+        };
+        match self.diagnostics.len() - 1 {
+            0 => first.diagnostic.to_string(),
+            1 => format!("{} (and 1 more error)", first.diagnostic),
+            more => format!("{} (and {more} more errors)", first.diagnostic),
+        }
+    }
+
+    /// Render the errors with their source snippets, without colors
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+        let handler = GraphicalReportHandler::new_themed(GraphicalTheme::unicode_nocolor());
+        for diagnostic in &self.diagnostics {
+            if handler.render_report(&mut output, diagnostic).is_err() {
+                output.push_str(&diagnostic.diagnostic.to_string());
+                output.push('\n');
+            }
+        }
+        output
+    }
+}
+
+impl fmt::Display for CueDiagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.summary())
+    }
+}
+
+impl std::error::Error for CueDiagnostics {}
+
+impl Diagnostic for CueDiagnostics {
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.help
+            .as_ref()
+            .map(|help| Box::new(help) as Box<dyn fmt::Display>)
+    }
+
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        if self.diagnostics.is_empty() {
+            return None;
+        }
+        Some(Box::new(
+            self.diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic as &dyn Diagnostic),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// A type conflict located in `env.cue`, followed by an error without a
+    /// location
+    fn diagnostics(temp_dir: &TempDir) -> (PathBuf, CueDiagnostics) {
+        let file = temp_dir.path().join("env.cue");
+        fs::write(
+            &file,
+            "package env\n\nenv: {\n\tPORT: \"eighty\",\n\tPORT: 80\n}\n",
+        )
+        .unwrap();
+
+        let diagnostics: Vec<CueDiagnostic> = serde_json::from_value(serde_json::json!([
+            {
+                "message": "conflicting values 80 and \"eighty\" (mismatched types int and string)",
+                "file": file,
+                "line": 4,
+                "column": 8,
+                "path": "env.PORT",
+            },
+            {"message": "cannot find package"},
+        ]))
+        .unwrap();
+        let diagnostics = CueDiagnostics::new(diagnostics).with_help("Fix the types");
+        (file, diagnostics)
+    }
+
+    #[test]
+    fn test_summary_names_first_error() {
+        let temp_dir = TempDir::new().unwrap();
+        let (file, diagnostics) = diagnostics(&temp_dir);
+
+        assert_eq!(
+            diagnostics.summary(),
+            format!(
+                "{}:4:8: env.PORT: conflicting values 80 and \"eighty\" \
+                 (mismatched types int and string) (and 1 more error)",
+                file.display()
+            )
+        );
+        assert_eq!(diagnostics.help().unwrap().to_string(), "Fix the types");
+    }
+
+    #[test]
+    fn test_diagnostics_point_into_source() {
+        let temp_dir = TempDir::new().unwrap();
+        let (_, diagnostics) = diagnostics(&temp_dir);
+
+        assert_eq!(
+            diagnostics.diagnostics[0].span,
+            Some(SourceSpan::new(27.into(), 8))
+        );
+        assert!(diagnostics.diagnostics[1].source.is_none());
+    }
+
+    #[test]
+    fn test_render_includes_source_and_unlocated_errors() {
+        let temp_dir = TempDir::new().unwrap();
+        let (_, diagnostics) = diagnostics(&temp_dir);
+
+        let rendered = diagnostics.render();
+        assert!(rendered.contains("PORT: \"eighty\""), "{rendered}");
+        assert!(rendered.contains("cannot find package"), "{rendered}");
+    }
+}
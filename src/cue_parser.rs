@@ -1,8 +1,9 @@
-use crate::constants::ENV_PACKAGE_NAME;
+use crate::constants::{DEFAULT_LIST_SEPARATOR, ENV_INSTANCE_NAME};
+use crate::cue_diagnostics::{CueDiagnostic, CueDiagnostics};
 use crate::errors::{Error, Result};
 use crate::resilience::suggest_recovery;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
 use std::path::Path;
@@ -65,7 +66,16 @@ impl Drop for CStringPtr {
 
 #[link(name = "cue_bridge")]
 extern "C" {
-    fn cue_eval_package(dir_path: *const c_char, package_name: *const c_char) -> *mut c_char;
+    fn cue_eval_package(
+        dir_path: *const c_char,
+        package_name: *const c_char,
+        instance_name: *const c_char,
+    ) -> *mut c_char;
+    fn cue_eval_package_local(
+        dir_path: *const c_char,
+        package_name: *const c_char,
+        instance_name: *const c_char,
+    ) -> *mut c_char;
     fn cue_free_string(s: *mut c_char);
 }
 
@@ -91,6 +101,9 @@ struct HooksConfig {
 #[derive(Debug, Deserialize)]
 struct VariableMetadata {
     capability: Option<String>,
+    /// Separator joining the items of a list value
+    #[serde(default)]
+    separator: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -98,7 +111,7 @@ pub struct CommandConfig {
     pub capabilities: Option<Vec<String>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TaskConfig {
     pub description: Option<String>,
     pub command: Option<String>,
@@ -108,6 +121,13 @@ pub struct TaskConfig {
     pub working_dir: Option<String>,
     pub shell: Option<String>,
     pub inputs: Option<Vec<String>>,
+    /// Environment variables whose values contribute to the cache key
+    #[serde(rename = "envInputs")]
+    pub env_inputs: Option<Vec<String>>,
+    /// Environment variables set for this task only, may be secret references
+    pub env: Option<HashMap<String, String>>,
+    /// Capabilities whose tagged variables the task receives
+    pub capabilities: Option<Vec<String>>,
     pub outputs: Option<Vec<String>>,
     pub security: Option<SecurityConfig>,
     /// Enable build cache for this task (Bazel-style caching)
@@ -117,9 +137,63 @@ pub struct TaskConfig {
     pub cache_key: Option<String>,
     /// Timeout for task execution in seconds
     pub timeout: Option<u32>,
+    /// Number of times a failed or timed out run is retried
+    pub retries: Option<u32>,
+    /// Seconds to wait before the first retry, doubled for each further one
+    #[serde(rename = "retryDelay")]
+    pub retry_delay: Option<u32>,
+    /// Keep running dependent tasks when this one fails
+    #[serde(rename = "allowFailure")]
+    pub allow_failure: Option<bool>,
+    /// Parameters accepted as `--name value` arguments, by name
+    pub params: Option<BTreeMap<String, ParamConfig>>,
+    /// Memory, CPU, process and IO limits of the task
+    pub resources: Option<ResourcesConfig>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResourcesConfig {
+    /// Memory limit in bytes, or a size like "512Mi" or "2G"
+    pub memory: Option<MemorySize>,
+    /// Number of CPUs the task may use, like 1.5
+    pub cpus: Option<f64>,
+    /// Maximum number of processes and threads
+    pub pids: Option<u64>,
+    /// Weight of the task's IO against other tasks, from 1 to 10000 (default 100)
+    #[serde(rename = "ioWeight")]
+    pub io_weight: Option<u16>,
+}
+
+/// A size in bytes, or with a unit like "512Mi"
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MemorySize {
+    Bytes(u64),
+    Text(String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParamConfig {
+    #[serde(rename = "type", default)]
+    pub param_type: ParamType,
+    /// Value used when the argument is not given, the parameter is required without one
+    pub default: Option<serde_json::Value>,
+    /// Allowed values
+    #[serde(rename = "enum")]
+    pub choices: Option<Vec<serde_json::Value>>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamType {
+    #[default]
+    String,
+    Int,
+    Bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SecurityConfig {
     #[serde(rename = "restrictDisk")]
     pub restrict_disk: Option<bool>,
@@ -136,6 +210,9 @@ pub struct SecurityConfig {
     /// Automatically infer disk restrictions from task inputs/outputs
     #[serde(rename = "inferFromInputsOutputs")]
     pub infer_from_inputs_outputs: Option<bool>,
+    /// Refuse to run the task unless the kernel fully enforces its restrictions
+    #[serde(rename = "requireEnforcement")]
+    pub require_enforcement: Option<bool>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
@@ -187,6 +264,8 @@ impl Default for CueParser {
 pub struct ParseOptions {
     pub environment: Option<String>,
     pub capabilities: Vec<String>,
+    /// Top-level field of the package to load, `env` if unset
+    pub instance: Option<String>,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -195,6 +274,9 @@ pub struct ParseResult {
     pub commands: HashMap<String, CommandConfig>,
     pub tasks: HashMap<String, TaskConfig>,
     pub hooks: HashMap<String, HookConfig>,
+    /// Capability each capability-tagged variable belongs to
+    #[serde(default)]
+    pub variable_capabilities: HashMap<String, String>,
 }
 
 // Input validation functions
@@ -205,16 +287,32 @@ fn validate_package_name(package_name: &str) -> Result<()> {
         ));
     }
 
-    // Only allow loading the "env" package
-    if package_name != ENV_PACKAGE_NAME {
+    if !is_identifier(package_name) {
         return Err(Error::configuration(format!(
-            "Only 'env' package is supported, got '{package_name}'. Please ensure your .cue files use 'package env'"
+            "Invalid package name '{package_name}', expected a CUE identifier such as 'env'"
         )));
     }
 
     Ok(())
 }
 
+fn validate_instance_name(instance: &str) -> Result<()> {
+    if !is_identifier(instance) {
+        return Err(Error::configuration(format!(
+            "Invalid instance name '{instance}', expected a top-level field name such as 'env'"
+        )));
+    }
+    Ok(())
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
 fn validate_directory_path(dir: &Path) -> Result<String> {
     let dir_str = dir.to_string_lossy();
     if dir_str.is_empty() {
@@ -235,13 +333,32 @@ fn create_ffi_string(value: &str, context: &str) -> Result<CString> {
     })
 }
 
-fn call_cue_eval_package(dir_path: &CStr, package_name: &CStr) -> *mut c_char {
-    // Safety: cue_eval_package is an external C function that:
-    // - Takes two non-null C string pointers as arguments
-    // - Returns a heap-allocated C string that must be freed with cue_free_string
-    // - Returns null on allocation failure
+fn call_cue_eval_package(
+    dir_path: &CStr,
+    package_name: &CStr,
+    instance_name: &CStr,
+    local_only: bool,
+) -> *mut c_char {
+    // Safety: cue_eval_package and cue_eval_package_local are external C functions that:
+    // - Take three non-null C string pointers as arguments
+    // - Return a heap-allocated C string that must be freed with cue_free_string
+    // - Return null on allocation failure
     // We ensure the input pointers are valid for the duration of the call
-    unsafe { cue_eval_package(dir_path.as_ptr(), package_name.as_ptr()) }
+    unsafe {
+        if local_only {
+            cue_eval_package_local(
+                dir_path.as_ptr(),
+                package_name.as_ptr(),
+                instance_name.as_ptr(),
+            )
+        } else {
+            cue_eval_package(
+                dir_path.as_ptr(),
+                package_name.as_ptr(),
+                instance_name.as_ptr(),
+            )
+        }
+    }
 }
 
 // JSON parsing utilities
@@ -260,14 +377,23 @@ fn parse_json_response(json_str: &str) -> Result<serde_json::Value> {
 fn check_for_error_response(json_value: &serde_json::Value, dir: &Path) -> Result<()> {
     if let serde_json::Value::Object(ref map) = json_value {
         if let Some(serde_json::Value::String(error)) = map.get("error") {
-            let cue_error = Error::cue_parse(dir, error.clone());
-
             // Provide specific recovery suggestions based on error content
             let recovery_hint = get_recovery_hint(error);
 
             log::error!("CUE parsing error: {error}");
             log::error!("Recovery suggestion: {recovery_hint}");
-            return Err(cue_error);
+
+            let diagnostics: Vec<CueDiagnostic> = map
+                .get("diagnostics")
+                .and_then(|value| serde_json::from_value(value.clone()).ok())
+                .unwrap_or_default();
+            if diagnostics.is_empty() {
+                return Err(Error::cue_parse(dir, error.clone()));
+            }
+            return Err(Error::cue_diagnostics(
+                dir,
+                CueDiagnostics::new(diagnostics).with_help(recovery_hint),
+            ));
         }
     }
     Ok(())
@@ -311,17 +437,42 @@ impl CueParser {
         dir: &Path,
         package_name: &str,
         options: &ParseOptions,
+    ) -> Result<ParseResult> {
+        Self::eval(dir, package_name, options, false)
+    }
+
+    /// Evaluate only the package files located directly in `dir`.
+    ///
+    /// Unlike [`CueParser::eval_package_with_options`], files of the same package in
+    /// parent directories of the CUE module are not unified into the result. This is
+    /// used for hierarchical loading, where parent packages are merged separately.
+    pub fn eval_local_package_with_options(
+        dir: &Path,
+        package_name: &str,
+        options: &ParseOptions,
+    ) -> Result<ParseResult> {
+        Self::eval(dir, package_name, options, true)
+    }
+
+    fn eval(
+        dir: &Path,
+        package_name: &str,
+        options: &ParseOptions,
+        local_only: bool,
     ) -> Result<ParseResult> {
         // Validate inputs
         validate_package_name(package_name)?;
+        let instance = options.instance.as_deref().unwrap_or(ENV_INSTANCE_NAME);
+        validate_instance_name(instance)?;
         let dir_str = validate_directory_path(dir)?;
 
         // Create FFI strings
         let c_dir = create_ffi_string(&dir_str, "invalid directory path")?;
         let c_package = create_ffi_string(package_name, "invalid package name")?;
+        let c_instance = create_ffi_string(instance, "invalid instance name")?;
 
         // Call CUE evaluation
-        let result_ptr = call_cue_eval_package(&c_dir, &c_package);
+        let result_ptr = call_cue_eval_package(&c_dir, &c_package, &c_instance, local_only);
 
         // Wrap the result pointer for automatic cleanup
         // Safety: result_ptr is either null or a valid pointer returned from cue_eval_package
@@ -355,6 +506,13 @@ impl CueParser {
         options: &ParseOptions,
     ) -> Result<ParseResult> {
         let final_vars = build_filtered_variables(&cue_result, options);
+        let variable_capabilities = final_vars
+            .keys()
+            .filter_map(|key| {
+                let capability = cue_result.metadata.get(key)?.capability.clone()?;
+                Some((key.clone(), capability))
+            })
+            .collect();
         let hooks = extract_hooks(cue_result.hooks);
 
         Ok(ParseResult {
@@ -362,19 +520,39 @@ impl CueParser {
             commands: std::mem::take(&mut cue_result.commands),
             tasks: std::mem::take(&mut cue_result.tasks),
             hooks,
+            variable_capabilities,
         })
     }
 
-    fn value_to_string(val: &serde_json::Value) -> Option<String> {
+    /// Render variable `key` as a string, lists joined with `separator`.
+    /// Values without a string form are skipped with a warning.
+    fn value_to_string(key: &str, val: &serde_json::Value, separator: &str) -> Option<String> {
+        let rendered = match val {
+            serde_json::Value::Array(items) => items
+                .iter()
+                .map(Self::primitive_to_string)
+                .collect::<Option<Vec<_>>>()
+                .map(|items| items.join(separator)),
+            _ => Self::primitive_to_string(val),
+        };
+
+        if rendered.is_none() {
+            let reason = match val {
+                serde_json::Value::Null => "it has no concrete value",
+                serde_json::Value::Array(_) => "lists may only hold strings, numbers and booleans",
+                _ => "structured values must be wrapped in #JSON",
+            };
+            log::warn!("Skipping variable {key}: {reason}");
+        }
+        rendered
+    }
+
+    fn primitive_to_string(val: &serde_json::Value) -> Option<String> {
         match val {
             serde_json::Value::String(s) => Some(s.clone()),
             serde_json::Value::Number(n) => Some(n.to_string()),
             serde_json::Value::Bool(b) => Some(b.to_string()),
-            serde_json::Value::Null => None,
-            _ => {
-                log::warn!("Skipping non-primitive value");
-                None
-            }
+            _ => None,
         }
     }
 }
@@ -409,7 +587,11 @@ fn process_variables(
 
     for (key, val) in variables {
         if should_include_variable(key, metadata, capabilities) {
-            if let Some(str_val) = CueParser::value_to_string(val) {
+            let separator = metadata
+                .get(key)
+                .and_then(|meta| meta.separator.as_deref())
+                .unwrap_or(DEFAULT_LIST_SEPARATOR);
+            if let Some(str_val) = CueParser::value_to_string(key, val, separator) {
                 result.insert(key.clone(), str_val);
             }
         }
@@ -471,11 +653,13 @@ mod tests {
         // Empty package name should fail
         assert!(validate_package_name("").is_err());
 
-        // Non-env package should fail
-        assert!(validate_package_name("mypackage").is_err());
+        // Package names must be CUE identifiers
+        assert!(validate_package_name("my-package").is_err());
+        assert!(validate_package_name("../env").is_err());
 
-        // Only "env" package should succeed
         assert!(validate_package_name("env").is_ok());
+        assert!(validate_package_name("mypackage").is_ok());
+        assert!(validate_package_name("_deploy2").is_ok());
     }
 
     #[test]
@@ -502,9 +686,16 @@ mod tests {
             "AWS_KEY".to_string(),
             VariableMetadata {
                 capability: Some("aws".to_string()),
+                separator: None,
+            },
+        );
+        metadata.insert(
+            "DB_URL".to_string(),
+            VariableMetadata {
+                capability: None,
+                separator: None,
             },
         );
-        metadata.insert("DB_URL".to_string(), VariableMetadata { capability: None });
 
         // Variable with no metadata should always be included
         assert!(should_include_variable("UNKNOWN", &metadata, &[]));
@@ -536,6 +727,37 @@ mod tests {
         )); // Non-matching capability
     }
 
+    #[test]
+    fn test_value_to_string() {
+        use serde_json::json;
+
+        assert_eq!(
+            CueParser::value_to_string("PORT", &json!(8080), ":"),
+            Some("8080".to_string())
+        );
+        assert_eq!(
+            CueParser::value_to_string("DEBUG", &json!(false), ":"),
+            Some("false".to_string())
+        );
+        assert_eq!(
+            CueParser::value_to_string("HOSTS", &json!(["a", 1, true]), ","),
+            Some("a,1,true".to_string())
+        );
+        assert_eq!(
+            CueParser::value_to_string("EMPTY", &json!([]), ":"),
+            Some(String::new())
+        );
+        assert_eq!(
+            CueParser::value_to_string("NESTED", &json!([["a"]]), ":"),
+            None
+        );
+        assert_eq!(
+            CueParser::value_to_string("OBJECT", &json!({"a": 1}), ":"),
+            None
+        );
+        assert_eq!(CueParser::value_to_string("UNSET", &json!(null), ":"), None);
+    }
+
     #[test]
     fn test_get_recovery_hint() {
         use super::get_recovery_hint;
@@ -561,6 +783,7 @@ mod tests {
             "Run 'cue vet' on your files to check for syntax errors"
         );
     }
+
     use super::*;
     use std::fs;
     use tempfile::TempDir;
@@ -578,33 +801,73 @@ mod tests {
     }
 
     #[test]
-    fn test_only_env_package_allowed() {
-        // Test that non-env packages are rejected
-        let content = r#"
-        package mypackage
-        
-        env: {
-            DATABASE_URL: "postgresql://localhost/mydb"
-        }"#;
-        let temp_dir = create_test_env(content);
-        let result = CueParser::eval_package(temp_dir.path(), "mypackage");
-        assert!(result.is_err());
-        let err_msg = result.unwrap_err().to_string();
-        assert!(
-            err_msg.contains("Only 'env' package is supported"),
-            "Error message was: {err_msg}"
+    fn test_error_response_with_diagnostics() {
+        use miette::Diagnostic;
+
+        let response = serde_json::json!({
+            "error": "env.PORT: incomplete value int",
+            "diagnostics": [{
+                "message": "incomplete value int",
+                "file": "/project/env.cue",
+                "line": 4,
+                "column": 8,
+                "path": "env.PORT",
+            }],
+        });
+        let error = check_for_error_response(&response, Path::new("/project")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "failed to parse CUE file '/project': \
+             /project/env.cue:4:8: env.PORT: incomplete value int"
         );
+        assert_eq!(
+            error.help().unwrap().to_string(),
+            "Some required fields are missing in your CUE configuration"
+        );
+        assert_eq!(error.related().unwrap().count(), 1);
 
-        // Test that env package is accepted
+        let response = serde_json::json!({"error": "No CUE instances found in directory"});
+        let error = check_for_error_response(&response, Path::new("/project")).unwrap_err();
+        assert!(error.related().is_none());
+    }
+
+    #[test]
+    fn test_other_packages_and_instances() {
         let content = r#"
-        package env
-        
+        package deploy
+
         env: {
             DATABASE_URL: "postgresql://localhost/mydb"
-        }"#;
+        }
+
+        backend: {
+            DATABASE_URL: "postgresql://localhost/backend"
+        }
+
+        replicas: 3"#;
         let temp_dir = create_test_env(content);
-        let result = CueParser::eval_package(temp_dir.path(), "env");
-        assert!(result.is_ok());
+        let result = CueParser::eval_package(temp_dir.path(), "deploy").unwrap();
+        assert_eq!(result["DATABASE_URL"], "postgresql://localhost/mydb");
+
+        let options = ParseOptions {
+            instance: Some("backend".to_string()),
+            ..ParseOptions::default()
+        };
+        let result =
+            CueParser::eval_package_with_options(temp_dir.path(), "deploy", &options).unwrap();
+        assert_eq!(
+            result.variables["DATABASE_URL"],
+            "postgresql://localhost/backend"
+        );
+
+        let options = ParseOptions {
+            instance: Some("frontend".to_string()),
+            ..ParseOptions::default()
+        };
+        let err = CueParser::eval_package_with_options(temp_dir.path(), "deploy", &options)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("frontend"), "Error message was: {err}");
     }
 
     #[test]
@@ -729,6 +992,7 @@ mod tests {
         let options = ParseOptions {
             environment: Some("production".to_string()),
             capabilities: Vec::new(),
+            instance: None,
         };
         let result =
             CueParser::eval_package_with_options(temp_dir.path(), "env", &options).unwrap();
@@ -743,6 +1007,7 @@ mod tests {
         let options = ParseOptions {
             environment: Some("staging".to_string()),
             capabilities: Vec::new(),
+            instance: None,
         };
         let result =
             CueParser::eval_package_with_options(temp_dir.path(), "env", &options).unwrap();
@@ -781,6 +1046,7 @@ mod tests {
         let options = ParseOptions {
             environment: None,
             capabilities: vec!["aws".to_string()],
+            instance: None,
         };
         let result =
             CueParser::eval_package_with_options(temp_dir.path(), "env", &options).unwrap();
@@ -790,6 +1056,7 @@ mod tests {
         let options = ParseOptions {
             environment: None,
             capabilities: vec!["gcp".to_string()],
+            instance: None,
         };
         let result =
             CueParser::eval_package_with_options(temp_dir.path(), "env", &options).unwrap();
@@ -874,6 +1141,7 @@ mod tests {
         let options = ParseOptions {
             environment: Some("production".to_string()),
             capabilities: vec!["aws".to_string()],
+            instance: None,
         };
         let result =
             CueParser::eval_package_with_options(temp_dir.path(), "env", &options).unwrap();
@@ -974,6 +1242,8 @@ mod tests {
             BOOL_VAL:   true
             NULL_VAL:   null
             ARRAY_VAL: [1, 2, 3]
+            CSV_VAL: ["a", "b"] @separator(",")
+            JSON_VAL: {json: {nested: "value", list: [1]}}
             OBJECT_VAL: {nested: "value"}
         }
         "#;
@@ -983,9 +1253,14 @@ mod tests {
         assert_eq!(result.get("INT_VAL").unwrap(), "42");
         assert_eq!(result.get("FLOAT_VAL").unwrap(), "3.14");
         assert_eq!(result.get("BOOL_VAL").unwrap(), "true");
-        // null, arrays, and objects should be skipped
+        assert_eq!(result.get("ARRAY_VAL").unwrap(), "1:2:3");
+        assert_eq!(result.get("CSV_VAL").unwrap(), "a,b");
+        assert_eq!(
+            result.get("JSON_VAL").unwrap(),
+            r#"{"list":[1],"nested":"value"}"#
+        );
+        // null and bare objects should be skipped
         assert!(!result.contains_key("NULL_VAL"));
-        assert!(!result.contains_key("ARRAY_VAL"));
         assert!(!result.contains_key("OBJECT_VAL"));
     }
 
@@ -1167,6 +1442,7 @@ mod tests {
         let options = ParseOptions {
             environment: Some("production".to_string()),
             capabilities: Vec::new(),
+            instance: None,
         };
         let result =
             CueParser::eval_package_with_options(temp_dir.path(), "env", &options).unwrap();
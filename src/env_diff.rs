@@ -109,6 +109,22 @@ impl EnvDiff {
         }
     }
 
+    /// Undo this diff on a snapshot of the environment, restoring the values it
+    /// changed or removed
+    pub fn undo(&self, env: &mut HashMap<String, String>) {
+        for key in self.added_or_changed().into_keys() {
+            match self.prev.get(key) {
+                Some(value) => env.insert(key.to_string(), value.clone()),
+                None => env.remove(key),
+            };
+        }
+        for key in self.removed() {
+            if let Some(value) = self.prev.get(key) {
+                env.insert(key.to_string(), value.clone());
+            }
+        }
+    }
+
     /// Check if this diff is empty (no changes)
     pub fn is_empty(&self) -> bool {
         self.added_or_changed().is_empty() && self.removed().is_empty()
@@ -198,6 +214,29 @@ mod tests {
         assert_eq!(reversed.next, prev);
     }
 
+    #[test]
+    fn test_undo() {
+        let prev = HashMap::from([
+            ("PATH".to_string(), "/bin".to_string()),
+            ("OLD".to_string(), "gone".to_string()),
+        ]);
+        let next = HashMap::from([
+            ("PATH".to_string(), "/app/bin:/bin".to_string()),
+            ("NEW".to_string(), "1".to_string()),
+        ]);
+        let diff = EnvDiff::new(prev, next.clone());
+
+        // Variables set after loading are kept
+        let mut env = next;
+        env.insert("EDITOR".to_string(), "vi".to_string());
+        diff.undo(&mut env);
+
+        assert_eq!(env.get("PATH").map(String::as_str), Some("/bin"));
+        assert_eq!(env.get("OLD").map(String::as_str), Some("gone"));
+        assert_eq!(env.get("EDITOR").map(String::as_str), Some("vi"));
+        assert!(!env.contains_key("NEW"));
+    }
+
     #[test]
     fn test_is_empty() {
         let env = HashMap::new();
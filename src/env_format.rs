@@ -0,0 +1,335 @@
+//! Structured output of loaded environments
+//!
+//! Backs both `cuenv status --json` and `cuenv env --format`, so editor
+//! integrations and scripts see the same shapes and quoting everywhere.
+use crate::env_diff::EnvDiff;
+use crate::errors::{Error, Result};
+use crate::state::CuenvState;
+use crate::types::SecretValues;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Output format of `cuenv env`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvFormat {
+    Json,
+    Dotenv,
+    Yaml,
+    /// A systemd `EnvironmentFile`
+    Systemd,
+}
+
+impl FromStr for EnvFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "dotenv" | "env" => Ok(Self::Dotenv),
+            "yaml" | "yml" => Ok(Self::Yaml),
+            "systemd" => Ok(Self::Systemd),
+            other => Err(Error::unsupported(
+                "output format",
+                format!("Unknown format '{other}', expected json, dotenv, yaml or systemd"),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for EnvFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Json => "json",
+            Self::Dotenv => "dotenv",
+            Self::Yaml => "yaml",
+            Self::Systemd => "systemd",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Render environment variables, sorted by name
+pub fn render_env(vars: &HashMap<String, String>, format: EnvFormat) -> Result<String> {
+    let sorted: BTreeMap<&str, &str> = vars.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+
+    let mut output = match format {
+        EnvFormat::Json => to_json(&sorted)?,
+        EnvFormat::Dotenv => render_lines(&sorted, "=", dotenv_quote),
+        EnvFormat::Yaml if sorted.is_empty() => "{}".to_string(),
+        // JSON strings are valid YAML double-quoted scalars
+        EnvFormat::Yaml => render_lines(&sorted, ": ", |value| {
+            serde_json::Value::from(value).to_string()
+        }),
+        EnvFormat::Systemd => render_lines(&sorted, "=", systemd_quote),
+    };
+    output.push('\n');
+    Ok(output)
+}
+
+fn render_lines(
+    vars: &BTreeMap<&str, &str>,
+    separator: &str,
+    quote: impl Fn(&str) -> String,
+) -> String {
+    vars.iter()
+        .map(|(key, value)| format!("{key}{separator}{}", quote(value)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Double-quote a value the way dotenv loaders unescape it
+fn dotenv_quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '$' => quoted.push_str("\\$"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Double-quote a value for systemd, which keeps newlines inside quotes literal
+fn systemd_quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        if matches!(c, '\\' | '"' | '$' | '`') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string_pretty(value).map_err(|e| Error::Json {
+        message: "Failed to serialize environment".to_string(),
+        source: e,
+    })
+}
+
+/// How a variable differs from the environment before loading
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Removed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VariableChange {
+    pub name: String,
+    pub change: ChangeKind,
+    /// Value after loading, absent for removed variables
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    /// Value before loading, absent for added variables
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous: Option<String>,
+    /// Directory whose env package set the variable
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<PathBuf>,
+}
+
+/// The loaded environment and the changes it made, as reported by `cuenv status`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct EnvStatus {
+    pub loaded: bool,
+    pub dir: Option<PathBuf>,
+    pub file: Option<PathBuf>,
+    pub environment: Option<String>,
+    pub capabilities: Vec<String>,
+    pub changes: Vec<VariableChange>,
+}
+
+impl EnvStatus {
+    /// Describe a diff, with changed variables before removed ones and each sorted by name
+    pub fn from_diff(diff: &EnvDiff, sources: &HashMap<String, PathBuf>) -> Self {
+        let mut changes: Vec<VariableChange> = diff
+            .added_or_changed()
+            .into_iter()
+            .map(|(key, value)| {
+                let previous = diff.prev.get(key).cloned();
+                VariableChange {
+                    name: key.to_string(),
+                    change: if previous.is_some() {
+                        ChangeKind::Modified
+                    } else {
+                        ChangeKind::Added
+                    },
+                    value: Some(value.to_string()),
+                    previous,
+                    source: sources.get(key).cloned(),
+                }
+            })
+            .collect();
+        changes.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut removed: Vec<VariableChange> = diff
+            .removed()
+            .into_iter()
+            .map(|key| VariableChange {
+                name: key.to_string(),
+                change: ChangeKind::Removed,
+                value: None,
+                previous: diff.prev.get(key).cloned(),
+                source: None,
+            })
+            .collect();
+        removed.sort_by(|a, b| a.name.cmp(&b.name));
+        changes.extend(removed);
+
+        Self {
+            loaded: true,
+            changes,
+            ..Self::default()
+        }
+    }
+
+    /// Attach where the environment was loaded from
+    pub fn with_state(mut self, state: &CuenvState) -> Self {
+        self.dir = Some(state.dir.clone());
+        self.file = Some(state.file.clone());
+        self.environment = state.environment.clone();
+        self.capabilities = state.capabilities.clone();
+        self
+    }
+
+    /// Mask every occurrence of the given secrets in values
+    pub fn redact(&mut self, secrets: &SecretValues) {
+        let redact = |value: &mut String| {
+            for secret in secrets.iter() {
+                if !secret.is_empty() && value.contains(secret) {
+                    let mask = "*".repeat(secret.len().min(8)) + "***";
+                    *value = value.replace(secret, &mask);
+                }
+            }
+        };
+
+        for change in &mut self.changes {
+            for value in [&mut change.value, &mut change.previous]
+                .into_iter()
+                .flatten()
+            {
+                redact(value);
+            }
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        to_json(self)
+    }
+
+    /// Human-readable listing of the changes
+    pub fn to_text(&self) -> String {
+        let mut output = String::from("Environment changes:\n");
+        for change in &self.changes {
+            let source = change
+                .source
+                .as_ref()
+                .map(|dir| format!(" [{}]", dir.display()))
+                .unwrap_or_default();
+            let value = change.value.as_deref().unwrap_or_default();
+            let previous = change.previous.as_deref().unwrap_or_default();
+            let line = match change.change {
+                ChangeKind::Added => format!("  {} (new): {value}{source}", change.name),
+                ChangeKind::Modified => {
+                    format!(
+                        "  {} (modified): {previous} -> {value}{source}",
+                        change.name
+                    )
+                }
+                ChangeKind::Removed => format!("  {} (removed): {previous}", change.name),
+            };
+            output.push_str(&line);
+            output.push('\n');
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_render_env_formats() {
+        let env = vars(&[("B", "two words"), ("A", "say \"hi\" $HOME\nbye")]);
+
+        assert_eq!(
+            render_env(&env, EnvFormat::Dotenv).unwrap(),
+            "A=\"say \\\"hi\\\" \\$HOME\\nbye\"\nB=\"two words\"\n"
+        );
+        assert_eq!(
+            render_env(&env, EnvFormat::Systemd).unwrap(),
+            "A=\"say \\\"hi\\\" \\$HOME\nbye\"\nB=\"two words\"\n"
+        );
+        assert_eq!(
+            render_env(&env, EnvFormat::Yaml).unwrap(),
+            "A: \"say \\\"hi\\\" $HOME\\nbye\"\nB: \"two words\"\n"
+        );
+
+        let json: HashMap<String, String> =
+            serde_json::from_str(&render_env(&env, EnvFormat::Json).unwrap()).unwrap();
+        assert_eq!(json, env);
+
+        assert_eq!("yml".parse::<EnvFormat>().unwrap(), EnvFormat::Yaml);
+        assert!("toml".parse::<EnvFormat>().is_err());
+    }
+
+    #[test]
+    fn test_status_from_diff_is_redacted() {
+        let diff = EnvDiff::new(
+            vars(&[("PATH", "/bin"), ("OLD", "gone")]),
+            vars(&[
+                ("PATH", "/opt/bin:/bin"),
+                ("DATABASE_URL", "postgres://app:hunter2@db"),
+            ]),
+        );
+        let sources = HashMap::from([("DATABASE_URL".to_string(), PathBuf::from("/project"))]);
+
+        let mut status = EnvStatus::from_diff(&diff, &sources);
+        let mut secrets = SecretValues::new();
+        secrets.insert("hunter2");
+        status.redact(&secrets);
+
+        let names: Vec<_> = status
+            .changes
+            .iter()
+            .map(|c| (c.name.as_str(), c.change))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("DATABASE_URL", ChangeKind::Added),
+                ("PATH", ChangeKind::Modified),
+                ("OLD", ChangeKind::Removed),
+            ]
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&status.to_json().unwrap()).unwrap();
+        assert_eq!(json["changes"][0]["value"], "postgres://app:**********@db");
+        assert_eq!(json["changes"][0]["source"], "/project");
+        assert_eq!(json["changes"][1]["previous"], "/bin");
+        assert!(json["changes"][2].get("value").is_none());
+    }
+}
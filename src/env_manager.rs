@@ -2,21 +2,30 @@ use crate::errors::{Error, Result};
 use crate::sync_env::SyncEnv;
 use std::collections::{HashMap, HashSet};
 use std::io::{self, BufReader};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use std::sync::{Arc, RwLock};
 
 use crate::access_restrictions::AccessRestrictions;
 use crate::command_executor::CommandExecutor;
-use crate::cue_parser::{CommandConfig, CueParser, HookConfig, HookType, ParseOptions, TaskConfig};
+use crate::constants::{
+    CUENV_EXPAND_PREFIX, CUENV_HIERARCHICAL_VAR, CUENV_RESOLVER_PREFIX, ENV_CUE_FILENAME,
+    ENV_PACKAGE_NAME, HIERARCHY_ROOT_MARKERS,
+};
+use crate::cue_parser::{
+    CommandConfig, CueParser, HookConfig, HookType, ParseOptions, ParseResult, TaskConfig,
+};
 use crate::env_diff::EnvDiff;
+use crate::env_format::EnvStatus;
 use crate::file_times::FileTimes;
 use crate::hook_manager::HookManager;
+use crate::interpolation::interpolate;
 use crate::output_filter::OutputFilter;
 use crate::platform::{PlatformOps, Shell};
+use crate::project_config::{has_env_package, watched_files, ProjectConfig};
 use crate::secrets::SecretManager;
-use crate::state::StateManager;
-use crate::types::{CommandArguments, EnvironmentVariables};
+use crate::state::{CuenvState, StateManager};
+use crate::types::{CommandArguments, EnvironmentVariables, SecretValues};
 use async_trait::async_trait;
 
 // Import the platform-specific implementation
@@ -25,16 +34,54 @@ use crate::platform::UnixPlatform as Platform;
 #[cfg(windows)]
 use crate::platform::WindowsPlatform as Platform;
 
+/// Environment of a single task
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TaskEnv {
+    /// Variables to set for the task, with secret references unresolved
+    pub vars: HashMap<String, String>,
+    /// Loaded variables withheld because the task lacks their capability
+    pub withheld: Vec<String>,
+}
+
+impl TaskEnv {
+    /// The environment the task sees on top of the given process environment
+    pub fn apply_to(&self, mut env: HashMap<String, String>) -> HashMap<String, String> {
+        for key in &self.withheld {
+            env.remove(key);
+        }
+        env.extend(self.vars.clone());
+        env
+    }
+}
+
 pub struct EnvManager {
     original_env: HashMap<String, String>,
     cue_vars: HashMap<String, String>,
     commands: HashMap<String, CommandConfig>,
     tasks: HashMap<String, TaskConfig>,
     hooks: HashMap<String, HookConfig>,
+    /// Directory each CUE variable was loaded from
+    sources: HashMap<String, PathBuf>,
+    /// Capability each capability-tagged CUE variable belongs to
+    variable_capabilities: HashMap<String, String>,
+    /// Capabilities the environment was loaded with
+    capabilities: Vec<String>,
+    /// Environment the package was loaded with
+    environment: Option<String>,
+    /// Merge env packages from parent directories into the loaded environment
+    hierarchical: bool,
+    /// CUE package holding the environment
+    package: String,
+    /// Instance selected on the command line, overriding the project config
+    instance: Option<String>,
 }
 
 impl EnvManager {
     pub fn new() -> Self {
+        let hierarchical = std::env::var(CUENV_HIERARCHICAL_VAR)
+            .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes"))
+            .unwrap_or(false);
+
         Self {
             // Pre-allocate with reasonable initial capacities to reduce rehashing
             original_env: HashMap::with_capacity(100), // Environment typically has many vars
@@ -42,8 +89,59 @@ impl EnvManager {
             commands: HashMap::with_capacity(20),      // Commands are limited
             tasks: HashMap::with_capacity(20),         // Tasks are also limited
             hooks: HashMap::with_capacity(4),          // Usually only a few hooks
+            sources: HashMap::with_capacity(50),
+            variable_capabilities: HashMap::new(),
+            capabilities: Vec::new(),
+            environment: None,
+            hierarchical,
+            package: ENV_PACKAGE_NAME.to_string(),
+            instance: None,
         }
     }
+
+    /// Enable or disable merging of parent directories' env packages
+    pub fn with_hierarchical(mut self, hierarchical: bool) -> Self {
+        self.hierarchical = hierarchical;
+        self
+    }
+
+    /// Load the given top-level field of the package instead of `env`
+    pub fn with_instance(mut self, instance: Option<String>) -> Self {
+        self.instance = instance;
+        self
+    }
+}
+
+/// Find the directories whose env packages make up the environment for `dir`.
+///
+/// Walks up from `dir` until a directory containing one of the
+/// [`HIERARCHY_ROOT_MARKERS`] is reached and returns every directory along the
+/// way that contains an `env.cue` or a project config, ordered from outermost
+/// to innermost. If no marker is found, only `dir` itself is returned.
+pub fn discover_env_hierarchy(dir: &Path) -> Vec<PathBuf> {
+    let mut chain = Vec::new();
+    let mut found_root = false;
+
+    for ancestor in dir.ancestors() {
+        if ancestor == dir || has_env_package(ancestor) {
+            chain.push(ancestor.to_path_buf());
+        }
+
+        if HIERARCHY_ROOT_MARKERS
+            .iter()
+            .any(|marker| ancestor.join(marker).exists())
+        {
+            found_root = true;
+            break;
+        }
+    }
+
+    if !found_root {
+        return vec![dir.to_path_buf()];
+    }
+
+    chain.reverse();
+    chain
 }
 
 impl Default for EnvManager {
@@ -67,13 +165,18 @@ impl EnvManager {
     ) -> Result<()> {
         self.save_original_env()?;
 
+        let project = ProjectConfig::load(dir)?;
+        self.package = project.package().to_string();
+        let instance = self.instance.clone().or(project.instance);
+
         // First pass: load package to get command mappings
         let temp_options = ParseOptions {
             environment: environment.clone(),
             capabilities: Vec::new(), // Empty for now to get all commands
+            instance: instance.clone(),
         };
 
-        let parse_result = CueParser::eval_package_with_options(dir, "env", &temp_options)?;
+        let (parse_result, _) = self.eval_env_hierarchy(dir, &temp_options)?;
         self.commands.extend(parse_result.commands);
         self.tasks.extend(parse_result.tasks);
         self.hooks.extend(parse_result.hooks);
@@ -98,22 +201,23 @@ impl EnvManager {
         }
 
         // Second pass: load with actual capabilities
+        self.environment = environment.clone();
         let options = ParseOptions {
             environment,
             capabilities,
+            instance,
         };
 
         log::info!(
-            "Loading CUE package from: {} with env={:?}, capabilities={:?}",
+            "Loading CUE package '{}' from: {} with instance={:?}, env={:?}, capabilities={:?}",
+            self.package,
             dir.display(),
+            options.instance,
             options.environment,
             options.capabilities
         );
 
-        match self
-            .apply_cue_package_with_options(dir, "env", &options)
-            .await
-        {
+        match self.apply_cue_package_with_options(dir, &options).await {
             Ok(()) => {
                 // Execute onEnter hooks after environment variables are set
                 self.execute_on_enter_hooks()?;
@@ -193,6 +297,8 @@ impl EnvManager {
 
         // Clear CUE vars
         self.cue_vars.clear();
+        self.sources.clear();
+        self.variable_capabilities.clear();
 
         Ok(())
     }
@@ -204,31 +310,21 @@ impl EnvManager {
             })?
             .into_iter()
             .collect();
+
+        // When reloading, start from the environment before the loaded one, so
+        // path prepends don't stack and unloading restores the real original
+        if let Ok(Some(diff)) = StateManager::get_diff() {
+            diff.undo(&mut self.original_env);
+        }
         Ok(())
     }
 
     async fn apply_cue_package_with_options(
         &mut self,
         dir: &Path,
-        package_name: &str,
         options: &ParseOptions,
     ) -> Result<()> {
-        // Only allow loading the "env" package
-        if package_name != "env" {
-            return Err(Error::configuration(format!(
-                "Only 'env' package is supported, got '{package_name}'. Please ensure your .cue files use 'package env'"
-            )));
-        }
-        let parse_result = match CueParser::eval_package_with_options(dir, package_name, options) {
-            Ok(result) => result,
-            Err(e) => {
-                return Err(Error::cue_parse_with_source(
-                    dir,
-                    format!("Failed to evaluate CUE package: {}", dir.display()),
-                    e,
-                ));
-            }
-        };
+        let (parse_result, sources) = self.eval_env_hierarchy(dir, options)?;
 
         // Store commands, tasks and hooks for later use
         self.commands.extend(parse_result.commands);
@@ -238,16 +334,11 @@ impl EnvManager {
         // Build the new environment
         let mut new_env = self.original_env.clone();
         self.cue_vars.clear();
+        self.sources = sources;
+        self.variable_capabilities = parse_result.variable_capabilities;
+        self.capabilities = options.capabilities.clone();
         for (key, value) in parse_result.variables {
-            let expanded_value = match shellexpand::full(&value) {
-                Ok(expanded) => expanded.to_string(),
-                Err(e) => {
-                    return Err(Error::shell_expansion(
-                        &value,
-                        format!("Failed to expand value for {key}: {e}"),
-                    ));
-                }
-            };
+            let expanded_value = self.expand_value(&key, &value)?;
 
             log::debug!("Setting {key}={expanded_value}");
             new_env.insert(key.clone(), expanded_value.clone());
@@ -260,25 +351,216 @@ impl EnvManager {
         // Create environment diff
         let diff = EnvDiff::new(self.original_env.clone(), new_env);
 
-        // Create file watches
+        // Create file watches for every package that contributed to the environment
         let mut watches = FileTimes::new();
-        let env_cue = dir.join("env.cue");
-        if env_cue.exists() {
-            watches.watch(&env_cue);
+        for package_dir in self.package_dirs(dir) {
+            for file in watched_files(&package_dir, &self.package) {
+                watches.watch(&file);
+            }
         }
+        // The package files of the directory itself, env.cue when it has none
+        let files: Vec<PathBuf> = watched_files(dir, &self.package)
+            .into_iter()
+            .filter(|file| file.extension().is_some_and(|ext| ext == "cue"))
+            .collect();
+        let file = files
+            .first()
+            .cloned()
+            .unwrap_or_else(|| dir.join(ENV_CUE_FILENAME));
 
         // Save state
-        StateManager::load(
-            dir,
-            &env_cue,
-            options.environment.as_deref(),
-            &options.capabilities,
-            &diff,
-            &watches,
-        )
-        .await
-        .map_err(|e| Error::configuration(format!("Failed to save state: {e}")))?;
+        let state = CuenvState {
+            dir: dir.to_path_buf(),
+            file,
+            environment: options.environment.clone(),
+            capabilities: options.capabilities.clone(),
+            sources: self.sources.clone(),
+            // Only the command line selection, the project config is reread
+            instance: self.instance.clone(),
+            files,
+        };
+        StateManager::load_state(&state, &diff, &watches)
+            .await
+            .map_err(|e| Error::configuration(format!("Failed to save state: {e}")))?;
+
+        Ok(())
+    }
+
+    /// Evaluate the env package of `dir` like [`EnvManager::load_env_with_options`],
+    /// without applying it to the process, saving state or running hooks
+    pub fn evaluate(
+        &mut self,
+        dir: &Path,
+        environment: Option<String>,
+        capabilities: Vec<String>,
+    ) -> Result<()> {
+        self.save_original_env()?;
+        self.environment = environment;
+        self.capabilities = capabilities;
+        let instance = self.instance.clone();
+        self.evaluate_package(dir, instance)
+    }
+
+    /// Evaluate the env package of another project with the environment and
+    /// capabilities this one was loaded with, without applying it to the
+    /// process or running its hooks
+    pub fn load_project(&self, dir: &Path) -> Result<EnvManager> {
+        let mut manager = EnvManager::new().with_hierarchical(self.hierarchical);
+        manager.original_env = self.original_env.clone();
+        manager.capabilities = self.capabilities.clone();
+        manager.environment = self.environment.clone();
+        manager.evaluate_package(dir, None)?;
+        Ok(manager)
+    }
+
+    /// Evaluate the env package of `dir` with the environment and capabilities
+    /// set, and `instance` or the one the project selects
+    fn evaluate_package(&mut self, dir: &Path, instance: Option<String>) -> Result<()> {
+        let project = ProjectConfig::load(dir)?;
+        self.package = project.package().to_string();
+
+        let options = ParseOptions {
+            environment: self.environment.clone(),
+            capabilities: self.capabilities.clone(),
+            instance: instance.or(project.instance),
+        };
+        let (parse_result, sources) = self.eval_env_hierarchy(dir, &options)?;
+        self.commands = parse_result.commands;
+        self.tasks = parse_result.tasks;
+        self.hooks = parse_result.hooks;
+        self.sources = sources;
+        self.variable_capabilities = parse_result.variable_capabilities;
+        self.cue_vars.clear();
+        for (key, value) in parse_result.variables {
+            let expanded_value = self.expand_value(&key, &value)?;
+            self.cue_vars.insert(key, expanded_value);
+        }
+        Ok(())
+    }
+
+    /// Evaluate interpolations and expand shell syntax in a loaded value
+    fn expand_value(&self, key: &str, value: &str) -> Result<String> {
+        if value.starts_with(CUENV_EXPAND_PREFIX) {
+            return interpolate(key, value, &self.original_env);
+        }
+        match shellexpand::full(value) {
+            Ok(expanded) => Ok(expanded.to_string()),
+            Err(e) => Err(Error::shell_expansion(
+                value,
+                format!("Failed to expand value for {key}: {e}"),
+            )),
+        }
+    }
+
+    /// Directories whose env packages are evaluated when loading `dir`
+    fn package_dirs(&self, dir: &Path) -> Vec<PathBuf> {
+        if self.hierarchical {
+            discover_env_hierarchy(dir)
+        } else {
+            vec![dir.to_path_buf()]
+        }
+    }
+
+    /// Evaluate the env package for `dir`, merging parent packages from outermost
+    /// to innermost when hierarchical loading is enabled. Values defined closer to
+    /// `dir` win. Returns the merged result along with the source directory of
+    /// each variable.
+    fn eval_env_hierarchy(
+        &self,
+        dir: &Path,
+        options: &ParseOptions,
+    ) -> Result<(ParseResult, HashMap<String, PathBuf>)> {
+        let mut merged = ParseResult::default();
+        let mut sources = HashMap::new();
+
+        for package_dir in self.package_dirs(dir) {
+            log::debug!(
+                "Evaluating package '{}' in {}",
+                self.package,
+                package_dir.display()
+            );
+
+            let evaluated = if self.hierarchical {
+                CueParser::eval_local_package_with_options(&package_dir, &self.package, options)
+            } else {
+                CueParser::eval_package_with_options(&package_dir, &self.package, options)
+            };
+            let result = match evaluated {
+                Ok(result) => result,
+                // Already names the package directory and keeps CUE's diagnostics
+                Err(e @ Error::CueParse { .. }) => return Err(e),
+                Err(e) => {
+                    return Err(Error::cue_parse_with_source(
+                        &package_dir,
+                        format!("Failed to evaluate CUE package: {}", package_dir.display()),
+                        e,
+                    ));
+                }
+            };
+
+            for (key, value) in result.variables {
+                sources.insert(key.clone(), package_dir.clone());
+                match result.variable_capabilities.get(&key) {
+                    Some(capability) => {
+                        merged
+                            .variable_capabilities
+                            .insert(key.clone(), capability.clone());
+                    }
+                    None => {
+                        merged.variable_capabilities.remove(&key);
+                    }
+                }
+                merged.variables.insert(key, value);
+            }
+            merged.commands.extend(result.commands);
+            merged.tasks.extend(result.tasks);
+            merged.hooks.extend(result.hooks);
+        }
+
+        Ok((merged, sources))
+    }
 
+    /// Get the directory a CUE variable was loaded from
+    pub fn variable_source(&self, key: &str) -> Option<&Path> {
+        self.sources.get(key).map(PathBuf::as_path)
+    }
+
+    /// Describe the currently loaded environment and the changes it made.
+    ///
+    /// Unresolved secret references are redacted from the reported values.
+    pub fn status(&self) -> Result<EnvStatus> {
+        let state = StateManager::get_state()
+            .map_err(|e| Error::configuration(format!("Failed to read state: {e}")))?;
+        let diff = StateManager::get_diff()
+            .map_err(|e| Error::configuration(format!("Failed to read state: {e}")))?;
+
+        let (state, diff) = match (state, diff) {
+            (Some(state), Some(diff)) => (state, diff),
+            _ => return Ok(EnvStatus::default()),
+        };
+
+        let mut secrets = SecretValues::new();
+        for value in diff.next.values() {
+            if value.starts_with(CUENV_RESOLVER_PREFIX) {
+                secrets.insert(value.clone());
+            }
+        }
+
+        let mut status = EnvStatus::from_diff(&diff, &state.sources).with_state(&state);
+        status.redact(&secrets);
+        Ok(status)
+    }
+
+    /// Print the changes made by the currently loaded environment, including the
+    /// directory each variable came from. Falls back to comparing against this
+    /// manager's saved environment when no state is recorded.
+    pub fn print_status(&self) -> Result<()> {
+        let status = self.status()?;
+        if !status.loaded {
+            return self.print_env_diff();
+        }
+
+        print!("{}", status.to_text());
         Ok(())
     }
 
@@ -293,12 +575,16 @@ impl EnvManager {
         println!("Environment changes:");
 
         for (key, value) in &current_env {
+            let source = self
+                .variable_source(key)
+                .map(|dir| format!(" [{}]", dir.display()))
+                .unwrap_or_default();
             if let Some(original) = self.original_env.get(key) {
                 if original != value {
-                    println!("  {key} (modified): {original} -> {value}");
+                    println!("  {key} (modified): {original} -> {value}{source}");
                 }
             } else {
-                println!("  {key} (new): {value}");
+                println!("  {key} (new): {value}{source}");
             }
         }
 
@@ -362,7 +648,6 @@ impl EnvManager {
         // Resolve secrets in the environment variables
         let (resolved_env, secret_values) = if cfg!(test) {
             // Skip secret resolution in tests
-            use crate::types::SecretValues;
             (env_from_cue, SecretValues::new())
         } else {
             let secret_manager = SecretManager::new();
@@ -554,7 +839,6 @@ impl EnvManager {
         // Resolve secrets in the environment variables
         let (resolved_env, secret_values) = if cfg!(test) {
             // Skip secret resolution in tests
-            use crate::types::SecretValues;
             (env_from_cue, SecretValues::new())
         } else {
             let secret_manager = SecretManager::new();
@@ -616,6 +900,9 @@ impl EnvManager {
             .stderr(Stdio::piped());
 
         // Apply access restrictions before spawning the process
+        #[cfg(target_os = "linux")]
+        let network_proxy = restrictions.apply_to_command_with_proxy(&mut cmd)?;
+        #[cfg(not(target_os = "linux"))]
         restrictions.apply_to_command(&mut cmd)?;
 
         let mut child = match cmd.spawn() {
@@ -630,6 +917,17 @@ impl EnvManager {
             }
         };
 
+        // Connections of the command go through its proxy until it exits
+        #[cfg(target_os = "linux")]
+        let _network_proxy = match network_proxy.map(|proxy| proxy.start(command)).transpose() {
+            Ok(proxy) => proxy,
+            Err(e) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(e);
+            }
+        };
+
         // Set up filtered output streams
         let stdout = match child.stdout.take() {
             Some(s) => s,
@@ -729,6 +1027,45 @@ impl EnvManager {
         Ok(status.code().unwrap_or(1))
     }
 
+    /// Variables loaded from CUE, with secret references unresolved
+    pub fn cue_vars(&self) -> &HashMap<String, String> {
+        &self.cue_vars
+    }
+
+    /// Environment of a task.
+    ///
+    /// Capability-tagged variables are only passed to tasks that list their
+    /// capability, or, for tasks without `capabilities`, when the capability
+    /// was selected while loading. The task's own `env` is applied last, with
+    /// interpolations evaluated against the loaded environment.
+    pub fn task_env(&self, task: &TaskConfig) -> Result<TaskEnv> {
+        let capabilities = task.capabilities.as_ref().unwrap_or(&self.capabilities);
+
+        let mut env = TaskEnv::default();
+        for (key, value) in &self.cue_vars {
+            match self.variable_capabilities.get(key) {
+                Some(capability) if !capabilities.contains(capability) => {
+                    env.withheld.push(key.clone());
+                }
+                _ => {
+                    env.vars.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        env.withheld.sort();
+
+        if let Some(task_vars) = &task.env {
+            let mut loaded = self.original_env.clone();
+            loaded.extend(self.cue_vars.clone());
+            for (key, value) in task_vars {
+                env.withheld.retain(|withheld| withheld != key);
+                env.vars
+                    .insert(key.clone(), interpolate(key, value, &loaded)?);
+            }
+        }
+        Ok(env)
+    }
+
     /// Get a task by name
     pub fn get_task(&self, task_name: &str) -> Option<&TaskConfig> {
         self.tasks.get(task_name)
@@ -916,6 +1253,84 @@ env: {
         }
     }
 
+    #[test]
+    fn test_discover_env_hierarchy() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join("cue.mod")).unwrap();
+        fs::write(root.join("env.cue"), "package env").unwrap();
+
+        let middle = root.join("services");
+        let child = middle.join("api");
+        fs::create_dir_all(&child).unwrap();
+        fs::write(child.join("env.cue"), "package env").unwrap();
+
+        // Directories without an env.cue are skipped, the root marker stops the walk
+        assert_eq!(
+            discover_env_hierarchy(&child),
+            vec![root.to_path_buf(), child.clone()]
+        );
+        assert_eq!(discover_env_hierarchy(root), vec![root.to_path_buf()]);
+    }
+
+    #[tokio::test]
+    async fn test_load_hierarchical_env() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join("cue.mod")).unwrap();
+        fs::write(
+            root.join("cue.mod").join("module.cue"),
+            r#"module: "example.com/hierarchy""#,
+        )
+        .unwrap();
+        fs::write(
+            root.join("env.cue"),
+            r#"package env
+
+env: {
+    CUENV_HIER_PARENT: "parent"
+    CUENV_HIER_SHARED: "parent"
+}"#,
+        )
+        .unwrap();
+
+        let child = root.join("child");
+        fs::create_dir(&child).unwrap();
+        fs::write(
+            child.join("env.cue"),
+            r#"package env
+
+env: {
+    CUENV_HIER_CHILD: "child"
+    CUENV_HIER_SHARED: "child"
+}"#,
+        )
+        .unwrap();
+
+        let mut manager = EnvManager::new().with_hierarchical(true);
+        manager.load_env(&child).await.unwrap();
+
+        assert_eq!(
+            SyncEnv::var("CUENV_HIER_PARENT").unwrap(),
+            Some("parent".to_string())
+        );
+        assert_eq!(
+            SyncEnv::var("CUENV_HIER_CHILD").unwrap(),
+            Some("child".to_string())
+        );
+        assert_eq!(
+            SyncEnv::var("CUENV_HIER_SHARED").unwrap(),
+            Some("child".to_string())
+        );
+        assert_eq!(manager.variable_source("CUENV_HIER_PARENT"), Some(root));
+        assert_eq!(
+            manager.variable_source("CUENV_HIER_CHILD"),
+            Some(child.as_path())
+        );
+
+        manager.unload_env().unwrap();
+    }
+
     #[tokio::test]
     async fn test_run_command_hermetic() {
         let temp_dir = TempDir::new().unwrap();
@@ -1119,4 +1534,59 @@ env: {
         let restrictions = AccessRestrictions::new(false, false);
         assert!(!restrictions.has_any_restrictions());
     }
+
+    #[test]
+    fn test_task_env_filters_capabilities() {
+        let mut manager = EnvManager::new();
+        for (key, value) in [("AWS_KEY", "aws"), ("GCP_KEY", "gcp"), ("APP_ENV", "dev")] {
+            manager.cue_vars.insert(key.to_string(), value.to_string());
+        }
+        manager
+            .variable_capabilities
+            .insert("AWS_KEY".to_string(), "aws".to_string());
+        manager
+            .variable_capabilities
+            .insert("GCP_KEY".to_string(), "gcp".to_string());
+        manager.capabilities = vec!["gcp".to_string()];
+
+        let task =
+            |json: serde_json::Value| -> TaskConfig { serde_json::from_value(json).unwrap() };
+
+        // Without task capabilities the load-time selection applies
+        let env = manager
+            .task_env(&task(serde_json::json!({"command": "true"})))
+            .unwrap();
+        assert_eq!(env.withheld, ["AWS_KEY"]);
+        assert_eq!(env.vars.get("GCP_KEY").map(String::as_str), Some("gcp"));
+        assert_eq!(env.vars.get("APP_ENV").map(String::as_str), Some("dev"));
+
+        manager
+            .original_env
+            .insert("PATH".to_string(), "/bin".to_string());
+        let env = manager
+            .task_env(&task(serde_json::json!({
+                "command": "true",
+                "capabilities": ["aws"],
+                "env": {
+                    "GCP_KEY": "override",
+                    "EXTRA": "1",
+                    "PATH": format!("{CUENV_EXPAND_PREFIX}{{\"prepend\": [\"/opt/$APP_ENV/bin\"]}}"),
+                },
+            })))
+            .unwrap();
+        assert!(env.withheld.is_empty());
+        assert_eq!(env.vars.get("AWS_KEY").map(String::as_str), Some("aws"));
+        assert_eq!(
+            env.vars.get("GCP_KEY").map(String::as_str),
+            Some("override")
+        );
+        assert_eq!(env.vars.get("EXTRA").map(String::as_str), Some("1"));
+        assert_eq!(
+            env.vars.get("PATH").map(String::as_str),
+            Some("/opt/dev/bin:/bin")
+        );
+
+        let applied = env.apply_to(HashMap::from([("AWS_KEY".to_string(), "host".to_string())]));
+        assert_eq!(applied.get("AWS_KEY").map(String::as_str), Some("aws"));
+    }
 }
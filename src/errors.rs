@@ -1,3 +1,4 @@
+use crate::cue_diagnostics::CueDiagnostics;
 use std::fmt;
 use std::path::PathBuf;
 
@@ -12,6 +13,8 @@ pub enum Error {
         path: PathBuf,
         message: String,
         source: Option<Box<dyn std::error::Error + Send + Sync>>,
+        /// Source-located errors reported by CUE
+        diagnostics: Option<Box<CueDiagnostics>>,
     },
 
     /// Environment variable related errors
@@ -182,6 +185,28 @@ impl std::error::Error for Error {
     }
 }
 
+impl miette::Diagnostic for Error {
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        match self {
+            Error::CueParse {
+                diagnostics: Some(diagnostics),
+                ..
+            } => diagnostics.help(),
+            _ => None,
+        }
+    }
+
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn miette::Diagnostic> + 'a>> {
+        match self {
+            Error::CueParse {
+                diagnostics: Some(diagnostics),
+                ..
+            } => diagnostics.related(),
+            _ => None,
+        }
+    }
+}
+
 // Conversion implementations
 impl From<std::io::Error> for Error {
     fn from(error: std::io::Error) -> Self {
@@ -219,6 +244,7 @@ impl Error {
             path: path.into(),
             message: message.into(),
             source: None,
+            diagnostics: None,
         }
     }
 
@@ -233,6 +259,18 @@ impl Error {
             path: path.into(),
             message: message.into(),
             source: Some(source.into()),
+            diagnostics: None,
+        }
+    }
+
+    /// Create a CUE parse error from the errors CUE reported
+    #[must_use]
+    pub fn cue_diagnostics(path: impl Into<PathBuf>, diagnostics: CueDiagnostics) -> Self {
+        Error::CueParse {
+            path: path.into(),
+            message: diagnostics.summary(),
+            source: None,
+            diagnostics: Some(Box::new(diagnostics)),
         }
     }
 
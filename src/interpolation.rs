@@ -0,0 +1,150 @@
+//! Interpolation of `#Expand` and `#PathList` values
+//!
+//! The bridge passes both as `cuenv-expand://` references. They are evaluated
+//! against the environment cuenv was loaded from rather than one it already
+//! modified, so reloading doesn't prepend the same entries twice.
+use crate::constants::CUENV_EXPAND_PREFIX;
+use crate::errors::{Error, Result};
+use crate::security::SecurityValidator;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+fn default_separator() -> String {
+    ":".to_string()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Interpolation {
+    /// `$VAR` and `~` are expanded in this value, `${...}` is rejected
+    #[serde(default)]
+    pub expand: Option<String>,
+    /// Entries placed before the variable's current value
+    #[serde(default)]
+    pub prepend: Vec<String>,
+    /// Entries placed after the variable's current value
+    #[serde(default)]
+    pub append: Vec<String>,
+    #[serde(default = "default_separator")]
+    pub separator: String,
+}
+
+impl Interpolation {
+    /// Decode a `cuenv-expand://` reference, `None` for any other value
+    pub fn from_reference(value: &str) -> Option<Result<Self>> {
+        let json = value.strip_prefix(CUENV_EXPAND_PREFIX)?;
+        Some(serde_json::from_str(json).map_err(|e| Error::Json {
+            message: format!("Invalid interpolation: {json}"),
+            source: e,
+        }))
+    }
+
+    /// The value of variable `name`, with entries and references taken from `env`
+    pub fn evaluate(&self, name: &str, env: &HashMap<String, String>) -> Result<String> {
+        if let Some(expand) = &self.expand {
+            return expand_value(expand, env);
+        }
+
+        let mut entries = Vec::with_capacity(self.prepend.len() + self.append.len() + 1);
+        for entry in &self.prepend {
+            entries.push(expand_value(entry, env)?);
+        }
+        if let Some(current) = env.get(name).filter(|value| !value.is_empty()) {
+            entries.push(current.clone());
+        }
+        for entry in &self.append {
+            entries.push(expand_value(entry, env)?);
+        }
+        Ok(entries.join(&self.separator))
+    }
+}
+
+/// Evaluate `value` if it is an interpolation, other values are returned unchanged
+pub fn interpolate(name: &str, value: &str, env: &HashMap<String, String>) -> Result<String> {
+    match Interpolation::from_reference(value) {
+        Some(interpolation) => interpolation?.evaluate(name, env),
+        None => Ok(value.to_string()),
+    }
+}
+
+fn expand_value(value: &str, env: &HashMap<String, String>) -> Result<String> {
+    SecurityValidator::validate_shell_expansion(value)?;
+
+    let home_dir = || {
+        env.get("HOME")
+            .cloned()
+            .or_else(|| dirs::home_dir().map(|dir| dir.to_string_lossy().into_owned()))
+    };
+    let lookup = |var: &str| -> std::result::Result<Option<&String>, &'static str> {
+        env.get(var).map(Some).ok_or("variable is not set")
+    };
+
+    shellexpand::full_with_context(value, home_dir, lookup)
+        .map(|expanded| expanded.into_owned())
+        .map_err(|e| Error::shell_expansion(value, format!("{}: {}", e.var_name, e.cause)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reference(json: serde_json::Value) -> String {
+        format!("{CUENV_EXPAND_PREFIX}{json}")
+    }
+
+    fn env() -> HashMap<String, String> {
+        HashMap::from([
+            ("HOME".to_string(), "/home/dev".to_string()),
+            ("PWD".to_string(), "/work/app".to_string()),
+            ("PATH".to_string(), "/usr/bin:/bin".to_string()),
+        ])
+    }
+
+    #[test]
+    fn test_expand_home_and_variables() {
+        let expand = reference(serde_json::json!({"expand": "~/go:$PWD/bin"}));
+        assert_eq!(
+            interpolate("GOPATH", &expand, &env()).unwrap(),
+            "/home/dev/go:/work/app/bin"
+        );
+    }
+
+    #[test]
+    fn test_prepend_and_append_path_entries() {
+        let path = reference(serde_json::json!({
+            "prepend": ["$PWD/bin"],
+            "append": ["/opt/tools"],
+        }));
+        assert_eq!(
+            interpolate("PATH", &path, &env()).unwrap(),
+            "/work/app/bin:/usr/bin:/bin:/opt/tools"
+        );
+    }
+
+    #[test]
+    fn test_unset_path_gets_only_new_entries() {
+        let library = reference(serde_json::json!({"prepend": ["$PWD/lib"], "separator": ";"}));
+        assert_eq!(
+            interpolate("LD_LIBRARY_PATH", &library, &env()).unwrap(),
+            "/work/app/lib"
+        );
+    }
+
+    #[test]
+    fn test_plain_values_are_untouched() {
+        assert_eq!(interpolate("PLAIN", "$HOME", &env()).unwrap(), "$HOME");
+    }
+
+    #[test]
+    fn test_unset_variable_is_an_error() {
+        let unset = reference(serde_json::json!({"expand": "$MISSING/bin"}));
+        assert!(interpolate("BIN", &unset, &env()).is_err());
+    }
+
+    #[test]
+    fn test_command_substitution_and_braces_rejected() {
+        for rejected in ["$(whoami)", "${PATH}"] {
+            let value = reference(serde_json::json!({ "expand": rejected }));
+            assert!(interpolate("VALUE", &value, &env()).is_err());
+        }
+    }
+}
@@ -4,21 +4,28 @@ pub mod async_runtime;
 pub mod atomic_file;
 pub mod audit;
 pub mod cache;
+#[cfg(target_os = "linux")]
+pub mod cgroup;
 pub mod cleanup;
 pub mod command_executor;
 pub mod constants;
 pub mod cue_cache;
+pub mod cue_diagnostics;
 pub mod cue_parser;
 pub mod directory;
 pub mod env_diff;
+pub mod env_format;
 pub mod env_manager;
 pub mod errors;
 pub mod file_times;
 pub mod gzenv;
 pub mod hook_manager;
+pub mod interpolation;
 pub mod memory;
+pub mod network_proxy;
 pub mod output_filter;
 pub mod platform;
+pub mod project_config;
 pub mod rate_limit;
 pub mod remote_cache;
 pub mod resilience;
@@ -26,10 +33,18 @@ pub mod resource_limits;
 pub mod retry;
 pub mod secrets;
 pub mod security;
+pub mod security_policy;
 pub mod shell;
 pub mod shell_hook;
 pub mod state;
 pub mod sync_env;
+#[cfg(target_os = "linux")]
+pub mod syscall_tracer;
 pub mod task_executor;
+pub mod task_graph;
+pub mod task_output;
+pub mod task_params;
+pub mod task_watcher;
 pub mod types;
+pub mod workspace;
 pub mod xdg;
@@ -1,16 +1,24 @@
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
 
-use cuenv::constants::{CUENV_CAPABILITIES_VAR, CUENV_ENV_VAR, ENV_CUE_FILENAME};
+use cuenv::access_restrictions::{AccessRestrictions, AuditReport};
+use cuenv::constants::{CUENV_CAPABILITIES_VAR, CUENV_ENV_VAR, TASK_TIMEOUT_EXIT_CODE};
+use cuenv::env_format::{render_env, EnvFormat};
 use cuenv::errors::{Error, Result};
 use cuenv::platform::{PlatformOps, Shell};
+use cuenv::project_config::has_env_package;
+use cuenv::security_policy::SuggestedPolicy;
 use cuenv::shell::ShellType;
 use cuenv::state::StateManager;
 use cuenv::sync_env::InstanceLock;
+use cuenv::task_graph::GraphFormat;
+use cuenv::task_params;
+use cuenv::workspace::TaskRef;
 use cuenv::{
     directory::DirectoryManager, env_manager::EnvManager, shell_hook::ShellHook,
-    task_executor::TaskExecutor,
+    task_executor::TaskExecutor, task_watcher::TaskWatcher,
 };
 use std::env;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
 
 // Import the platform-specific implementation
@@ -41,9 +49,35 @@ enum Commands {
         /// Capabilities to enable (can be specified multiple times)
         #[arg(short = 'c', long = "capability")]
         capabilities: Vec<String>,
+
+        /// Named instance to load instead of `env` (a top-level field of the package)
+        #[arg(long)]
+        instance: Option<String>,
     },
     Unload,
-    Status,
+    Status {
+        /// Print the status as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the resolved environment of the current directory
+    Env {
+        /// Environment to use (e.g., dev, staging, production)
+        #[arg(short = 'e', long = "env")]
+        environment: Option<String>,
+
+        /// Capabilities to enable (can be specified multiple times)
+        #[arg(short = 'c', long = "capability")]
+        capabilities: Vec<String>,
+
+        /// Named instance to load instead of `env` (a top-level field of the package)
+        #[arg(long)]
+        instance: Option<String>,
+
+        /// Output format: json, dotenv, yaml or systemd
+        #[arg(long, default_value = "dotenv")]
+        format: EnvFormat,
+    },
     Init {
         shell: String,
     },
@@ -55,6 +89,8 @@ enum Commands {
         #[arg(default_value = ".")]
         directory: PathBuf,
     },
+    /// Run a task or a command in the environment
+    #[command(disable_help_flag = true)]
     Run {
         /// Environment to use (e.g., dev, staging, production)
         #[arg(short = 'e', long = "env")]
@@ -64,16 +100,48 @@ enum Commands {
         #[arg(short = 'c', long = "capability")]
         capabilities: Vec<String>,
 
-        /// Task name to execute
+        /// Named instance to load instead of `env` (a top-level field of the package)
+        #[arg(long)]
+        instance: Option<String>,
+
+        /// Task name to execute, or a task of other projects like `//libs/proto:generate`
         task_name: Option<String>,
 
-        /// Arguments to pass to the task (after --)
-        #[arg(last = true)]
+        /// Task parameters as `--name value`, other arguments are appended to the command
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         task_args: Vec<String>,
 
+        /// Print help, or the parameters of the given task
+        #[arg(short, long)]
+        help: bool,
+
         /// Run in audit mode to see file and network access without restrictions
         #[arg(long)]
         audit: bool,
+
+        /// With --audit, print a security policy allowing what each task accessed
+        #[arg(long, requires = "audit")]
+        suggest_policy: bool,
+
+        /// With --suggest-policy, write the policies into the task definitions
+        #[arg(long, requires = "suggest_policy")]
+        write: bool,
+
+        /// Re-run the task whenever its inputs change
+        #[arg(long, conflicts_with = "audit")]
+        watch: bool,
+
+        /// Maximum number of tasks running at once (defaults to the number of CPUs)
+        #[arg(short = 'j', long)]
+        jobs: Option<NonZeroUsize>,
+
+        /// Keep running tasks that don't depend on a failed task
+        #[arg(long)]
+        keep_going: bool,
+
+        /// Print the execution plan and predicted cache hits without running anything
+        #[arg(long, conflicts_with_all = ["audit", "watch"])]
+        dry_run: bool,
     },
     Exec {
         /// Environment to use (e.g., dev, staging, production)
@@ -84,6 +152,10 @@ enum Commands {
         #[arg(short = 'c', long = "capability")]
         capabilities: Vec<String>,
 
+        /// Named instance to load instead of `env` (a top-level field of the package)
+        #[arg(long)]
+        instance: Option<String>,
+
         /// Command to run
         command: String,
 
@@ -109,6 +181,8 @@ enum Commands {
         /// Shell format (defaults to current shell)
         shell: Option<String>,
     },
+    /// Check which sandboxing features the system supports
+    Doctor,
     /// Prune stale state
     Prune,
     /// Clear task cache
@@ -118,6 +192,16 @@ enum Commands {
         #[command(subcommand)]
         command: CacheCommands,
     },
+    /// Task commands
+    Task {
+        #[command(subcommand)]
+        command: TaskCommands,
+    },
+    /// Secret cache management commands
+    Secrets {
+        #[command(subcommand)]
+        command: SecretsCommands,
+    },
     /// Start remote cache server for Bazel/Buck2
     RemoteCacheServer {
         /// Address to listen on
@@ -146,10 +230,64 @@ enum CacheCommands {
         #[arg(long, default_value = "168")]
         max_age_hours: u64,
     },
+    /// Show which cache key components of a task changed between its last two runs
+    Explain {
+        /// Name of the task
+        task: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum TaskCommands {
+    /// Print the task dependency graph, marking tasks with cached results
+    Graph {
+        /// Tasks to include with their dependencies (defaults to all tasks)
+        tasks: Vec<String>,
+
+        /// Output format: dot, mermaid or json
+        #[arg(long, default_value = "dot")]
+        format: GraphFormat,
+
+        /// Environment to use (e.g., dev, staging, production)
+        #[arg(short = 'e', long = "env")]
+        environment: Option<String>,
+
+        /// Capabilities to enable (can be specified multiple times)
+        #[arg(short = 'c', long = "capability")]
+        capabilities: Vec<String>,
+
+        /// Named instance to load instead of `env` (a top-level field of the package)
+        #[arg(long)]
+        instance: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum SecretsCommands {
+    /// Resolve the current directory's cached secrets again
+    Refresh {
+        /// Environment to use (e.g., dev, staging, production)
+        #[arg(short = 'e', long = "env")]
+        environment: Option<String>,
+    },
+    /// Remove the current directory's secrets from the secret cache
+    Forget {
+        /// Environment to use (e.g., dev, staging, production)
+        #[arg(short = 'e', long = "env")]
+        environment: Option<String>,
+
+        /// Remove every cached secret, not only those of the current directory
+        #[arg(long, conflicts_with = "environment")]
+        all: bool,
+    },
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> miette::Result<()> {
+    run().await.map_err(miette::Report::new)
+}
+
+async fn run() -> Result<()> {
     env_logger::init();
 
     // Initialize cleanup handling for proper resource management
@@ -162,6 +300,7 @@ async fn main() -> Result<()> {
             directory,
             environment,
             capabilities,
+            instance,
         }) => {
             // Acquire instance lock to prevent concurrent modifications
             let _lock = match InstanceLock::acquire() {
@@ -186,7 +325,7 @@ async fn main() -> Result<()> {
                     }
                 },
             };
-            let mut env_manager = EnvManager::new();
+            let mut env_manager = EnvManager::new().with_instance(instance);
 
             // Use environment variables as fallback if CLI args not provided
             let env_name = environment.or_else(|| env::var(CUENV_ENV_VAR).ok());
@@ -240,13 +379,44 @@ async fn main() -> Result<()> {
                 Err(e) => return Err(e),
             }
         }
-        Some(Commands::Status) => {
+        Some(Commands::Status { json }) => {
             let env_manager = EnvManager::new();
-            match env_manager.print_env_diff() {
-                Ok(()) => {}
-                Err(e) => return Err(e),
+            if json {
+                println!("{}", env_manager.status()?.to_json()?);
+            } else {
+                env_manager.print_status()?;
             }
         }
+        Some(Commands::Env {
+            environment,
+            capabilities,
+            instance,
+            format,
+        }) => {
+            use cuenv::secrets::SecretManager;
+
+            let current_dir = env::current_dir()
+                .map_err(|e| Error::file_system(PathBuf::from("."), "get current directory", e))?;
+            let env_name = environment.or_else(|| env::var(CUENV_ENV_VAR).ok());
+
+            // Evaluating runs the secret resolvers, so it needs the same
+            // permission as loading the environment
+            if !DirectoryManager::new().is_directory_allowed(&current_dir)? {
+                return Err(Error::configuration(format!(
+                    "Directory not allowed. Run 'cuenv allow {}' to allow this directory.",
+                    current_dir.display()
+                )));
+            }
+
+            // Printing the environment neither applies it nor runs hooks
+            let mut env_manager = EnvManager::new().with_instance(instance);
+            env_manager.evaluate(&current_dir, env_name, capabilities)?;
+
+            let resolved = SecretManager::new()
+                .resolve_secrets(env_manager.cue_vars().clone().into())
+                .await?;
+            print!("{}", render_env(&resolved.env_vars.into_inner(), format)?);
+        }
         Some(Commands::Init { shell }) => match ShellHook::generate_hook(&shell) {
             Ok(output) => print!("{output}"),
             Err(e) => return Err(e),
@@ -278,59 +448,92 @@ async fn main() -> Result<()> {
         Some(Commands::Run {
             environment,
             capabilities,
+            instance,
             task_name,
             task_args,
             audit,
+            suggest_policy,
+            write,
+            watch,
+            jobs,
+            keep_going,
+            dry_run,
+            help,
         }) => {
-            let current_dir = match env::current_dir() {
-                Ok(d) => d,
-                Err(e) => {
-                    return Err(Error::file_system(
-                        PathBuf::from("."),
-                        "get current directory",
-                        e,
-                    ));
-                }
-            };
-            let mut env_manager = EnvManager::new();
-
-            // Use environment variables as fallback if CLI args not provided
-            let env_name = environment.or_else(|| env::var(CUENV_ENV_VAR).ok());
-
-            let mut caps = capabilities;
-            if caps.is_empty() {
-                // Check for CUENV_CAPABILITIES env var (comma-separated)
-                if let Ok(env_caps) = env::var(CUENV_CAPABILITIES_VAR) {
-                    caps = env_caps
-                        .split(',')
-                        .map(|s| s.trim().to_string())
-                        .filter(|s| !s.is_empty())
-                        .collect();
+            if help && task_name.is_none() {
+                if let Some(run) = Cli::command().find_subcommand_mut("run") {
+                    run.print_help()?;
                 }
+                return Ok(());
             }
 
-            // Load environment with options
-            env_manager
-                .load_env_with_options(&current_dir, env_name, caps, None)
-                .await?;
+            let (env_manager, current_dir) =
+                load_task_environment(environment, capabilities, instance).await?;
 
             match task_name {
                 Some(name) => {
-                    // Check if this is a defined task first
-                    if env_manager.get_task(&name).is_some() {
+                    // Check if this is a defined task or a task of other projects first
+                    if env_manager.get_task(&name).is_some() || TaskRef::parse(&name).is_some() {
+                        if let Some(task) = env_manager.get_task(&name) {
+                            if help || task_params::wants_help(&task_args) {
+                                print!("{}", task_params::help(&name, task));
+                                return Ok(());
+                            }
+                        }
+
+                        let mut executor = TaskExecutor::new(env_manager, current_dir)
+                            .await?
+                            .with_keep_going(keep_going);
+                        if let Some(jobs) = jobs {
+                            executor = executor.with_jobs(jobs.get());
+                        }
+
+                        if dry_run {
+                            let graph = executor.task_graph(&[name], Some(&task_args)).await?;
+                            print!("{}", graph.render_plan());
+                            return Ok(());
+                        }
+
+                        if watch {
+                            // Cached results would hide changes, so always execute
+                            let executor = executor.without_cache();
+                            TaskWatcher::new(executor, name, task_args).run().await?;
+                            return Ok(());
+                        }
+
                         // Execute the specified task
-                        let executor = TaskExecutor::new(env_manager, current_dir).await?;
-                        let status = if audit {
-                            executor.execute_task_with_audit(&name, &task_args).await?
+                        let result = if suggest_policy {
+                            executor.audit_task(&name, &task_args).await.and_then(
+                                |(status, reports)| {
+                                    suggest_policies(&reports, write)?;
+                                    Ok(status)
+                                },
+                            )
+                        } else if audit {
+                            executor.execute_task_with_audit(&name, &task_args).await
                         } else {
-                            executor.execute_task(&name, &task_args).await?
+                            executor.execute_task(&name, &task_args).await
+                        };
+                        let status = match result {
+                            Err(e @ Error::Timeout { .. }) => {
+                                eprintln!("{:?}", miette::Report::new(e));
+                                TASK_TIMEOUT_EXIT_CODE
+                            }
+                            result => result?,
                         };
                         std::process::exit(status);
+                    } else if watch {
+                        return Err(Error::configuration(format!(
+                            "--watch requires a task, but '{name}' is not defined"
+                        )));
                     } else {
                         // Treat as direct command execution without restrictions
                         // For restrictions, use task definitions with security config
                         let mut args = vec![name];
                         args.extend(task_args);
+                        if help {
+                            args.push("--help".to_string());
+                        }
 
                         // For direct command execution, use the first argument as command
                         if args.is_empty() {
@@ -365,6 +568,7 @@ async fn main() -> Result<()> {
         Some(Commands::Exec {
             environment,
             capabilities,
+            instance,
             command,
             args,
             audit,
@@ -379,7 +583,7 @@ async fn main() -> Result<()> {
                     ));
                 }
             };
-            let mut env_manager = EnvManager::new();
+            let mut env_manager = EnvManager::new().with_instance(instance);
 
             // Use environment variables as fallback if CLI args not provided
             let env_name = environment.or_else(|| env::var(CUENV_ENV_VAR).ok());
@@ -452,7 +656,10 @@ async fn main() -> Result<()> {
                 // Output unload commands
                 if let Ok(Some(diff)) = StateManager::get_diff() {
                     for key in diff.removed() {
-                        println!("{}", shell_impl.unset(key));
+                        // Restore variables the environment removed
+                        if let Some(orig_value) = diff.prev.get(key) {
+                            println!("{}", shell_impl.export(key, orig_value));
+                        }
                     }
                     for (key, _) in diff.added_or_changed() {
                         if diff.prev.contains_key(key) {
@@ -469,7 +676,7 @@ async fn main() -> Result<()> {
                 StateManager::unload()
                     .await
                     .map_err(|e| Error::configuration(format!("Failed to unload state: {e}")))?;
-            } else if current_dir.join(ENV_CUE_FILENAME).exists() {
+            } else if has_env_package(&current_dir) {
                 // Check if directory is allowed
                 let dir_manager = DirectoryManager::new();
                 if dir_manager
@@ -479,18 +686,49 @@ async fn main() -> Result<()> {
                     // Check if files have changed and reload if needed
                     if StateManager::files_changed() || StateManager::should_load(&current_dir) {
                         // Need to load/reload
-                        let mut env_manager = EnvManager::new();
-                        if let Err(e) = env_manager.load_env(&current_dir).await {
+                        let previous = StateManager::get_diff().ok().flatten();
+                        // A reload keeps the instance, environment and capabilities
+                        // the directory was loaded with
+                        let state = StateManager::get_state()
+                            .ok()
+                            .flatten()
+                            .filter(|state| state.dir == current_dir);
+                        let (instance, environment, capabilities) = match state {
+                            Some(state) => (state.instance, state.environment, state.capabilities),
+                            None => (None, None, Vec::new()),
+                        };
+                        let mut env_manager = EnvManager::new().with_instance(instance);
+                        if let Err(e) = env_manager
+                            .load_env_with_options(&current_dir, environment, capabilities, None)
+                            .await
+                        {
                             eprintln!("# cuenv: failed to load environment: {e}");
                         } else {
                             // Output export commands
                             if let Ok(Some(diff)) = StateManager::get_diff() {
-                                for (key, value) in diff.added_or_changed() {
+                                let changes = diff.added_or_changed();
+                                for (key, value) in &changes {
                                     println!("{}", shell_impl.export(key, value));
                                 }
                                 for key in diff.removed() {
                                     println!("{}", shell_impl.unset(key));
                                 }
+
+                                // Variables of the previous environment that the
+                                // reloaded one leaves alone go back to their originals
+                                if let Some(previous) = previous {
+                                    for key in previous.added_or_changed().into_keys() {
+                                        if changes.contains_key(key) {
+                                            continue;
+                                        }
+                                        match diff.next.get(key) {
+                                            Some(value) => {
+                                                println!("{}", shell_impl.export(key, value))
+                                            }
+                                            None => println!("{}", shell_impl.unset(key)),
+                                        }
+                                    }
+                                }
                             }
                         }
                     }
@@ -544,6 +782,7 @@ async fn main() -> Result<()> {
             let current_env: std::collections::HashMap<String, String> = env::vars().collect();
             println!("{}", shell_impl.dump(&current_env));
         }
+        Some(Commands::Doctor) => doctor(),
         Some(Commands::Prune) => {
             // For now, just unload if there's state
             if StateManager::is_loaded() {
@@ -566,8 +805,27 @@ async fn main() -> Result<()> {
                 }
             }
         }
+        Some(Commands::Task { command }) => match command {
+            TaskCommands::Graph {
+                tasks,
+                format,
+                environment,
+                capabilities,
+                instance,
+            } => {
+                let (env_manager, current_dir) =
+                    load_task_environment(environment, capabilities, instance).await?;
+                let executor = TaskExecutor::new(env_manager, current_dir).await?;
+                print!(
+                    "{}",
+                    executor.task_graph(&tasks, None).await?.render(format)?
+                );
+            }
+        },
         Some(Commands::Cache { command }) => {
-            let cache_manager = cuenv::cache::CacheManager::new_sync()?;
+            // Use the same cache as the task executor
+            let cache_manager =
+                cuenv::cache::CacheManager::new(cuenv::cache::CacheConfig::default()).await?;
 
             match command {
                 CacheCommands::Clear => match cache_manager.clear_cache() {
@@ -582,6 +840,7 @@ async fn main() -> Result<()> {
                     println!("Cache Statistics:");
                     println!("  Hits: {}", stats.hits);
                     println!("  Misses: {}", stats.misses);
+                    println!("  Remote hits: {}", stats.remote_hits);
                     println!("  Writes: {}", stats.writes);
                     println!("  Errors: {}", stats.errors);
                     println!("  Lock contentions: {}", stats.lock_contentions);
@@ -601,6 +860,80 @@ async fn main() -> Result<()> {
                         }
                     }
                 }
+                CacheCommands::Explain { task } => {
+                    let current_dir = env::current_dir()?;
+                    match cache_manager.key_history(&task, &current_dir).as_slice() {
+                        [] => println!("No recorded runs of task '{task}'"),
+                        [only] => println!(
+                            "Only one run of task '{task}' recorded (key {})",
+                            &only.key()[..12]
+                        ),
+                        [.., previous, latest] => {
+                            let (before, after) = (previous.key(), latest.key());
+                            if before == after {
+                                println!("Cache key of task '{task}' unchanged ({})", &after[..12]);
+                            } else {
+                                println!(
+                                    "Cache key of task '{task}' changed: {} → {}",
+                                    &before[..12],
+                                    &after[..12]
+                                );
+                                for change in latest.diff(previous) {
+                                    println!("  {change}");
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Some(Commands::Secrets { command }) => {
+            use cuenv::secrets::{SecretManager, SECRET_CACHE_PASSPHRASE_VAR};
+
+            let secret_manager = SecretManager::new();
+            let Some(cache) = secret_manager.cache() else {
+                return Err(Error::unsupported(
+                    "secret cache",
+                    format!("no cache key available, set {SECRET_CACHE_PASSPHRASE_VAR}"),
+                ));
+            };
+
+            let (environment, refresh) = match command {
+                SecretsCommands::Refresh { environment } => (environment, true),
+                SecretsCommands::Forget { all: true, .. } => {
+                    let removed = cache.clear()?;
+                    println!("✓ Removed {removed} cached secret(s)");
+                    return Ok(());
+                }
+                SecretsCommands::Forget { environment, .. } => (environment, false),
+            };
+
+            let current_dir = env::current_dir()
+                .map_err(|e| Error::file_system(PathBuf::from("."), "get current directory", e))?;
+            let env_name = environment.or_else(|| env::var(CUENV_ENV_VAR).ok());
+
+            let mut env_manager = EnvManager::new();
+            env_manager
+                .load_env_with_options(&current_dir, env_name, Vec::new(), None)
+                .await?;
+            let env_vars = env_manager.cue_vars().clone().into();
+
+            if refresh {
+                let refreshed = secret_manager.refresh_secrets(env_vars).await?;
+                if refreshed.is_empty() {
+                    println!("No secrets with a ttl to refresh");
+                }
+                for key in refreshed {
+                    println!("✓ Refreshed {key}");
+                }
+            } else {
+                let forgotten = secret_manager.forget_secrets(&env_vars)?;
+                if forgotten.is_empty() {
+                    println!("No cached secrets to forget");
+                }
+                for key in forgotten {
+                    println!("✓ Forgot {key}");
+                }
             }
         }
         Some(Commands::RemoteCacheServer {
@@ -670,3 +1003,85 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Landlock features of each ABI version, and the Linux version adding them
+const LANDLOCK_FEATURES: &[(i32, &str, &str)] = &[
+    (1, "filesystem restrictions", "5.13"),
+    (2, "renaming and linking across directories", "5.19"),
+    (3, "truncating files", "6.2"),
+    (4, "TCP port restrictions", "6.7"),
+    (5, "ioctl on devices", "6.10"),
+];
+
+/// Print the Landlock ABI of the running kernel and the features it supports
+fn doctor() {
+    let abi = AccessRestrictions::landlock_abi();
+    if abi == 0 {
+        println!(
+            "✗ Landlock is not supported by the running kernel, restrictions are not enforced"
+        );
+        return;
+    }
+
+    println!("✓ Landlock ABI {abi}");
+    for (version, feature, linux) in LANDLOCK_FEATURES {
+        if abi >= *version {
+            println!("  ✓ {feature}");
+        } else {
+            println!("  ✗ {feature} (ABI {version}, Linux {linux}+)");
+        }
+    }
+}
+
+/// Print the security policy suggested for each audited task, and patch it
+/// into the task definitions with `write`
+fn suggest_policies(reports: &[AuditReport], write: bool) -> Result<()> {
+    for report in reports {
+        let policy = SuggestedPolicy::from_report(report);
+        println!(
+            "\n📋 Suggested policy for task '{}':\n{}",
+            policy.task,
+            policy.to_cue()
+        );
+        if write {
+            let file = policy.write_to_task()?;
+            println!(
+                "✏️  Wrote the policy of task '{}' to {}",
+                policy.task,
+                file.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Load the environment of the current directory for running tasks, taking
+/// the environment and capabilities from `CUENV_ENV` and `CUENV_CAPABILITIES`
+/// if not given
+async fn load_task_environment(
+    environment: Option<String>,
+    capabilities: Vec<String>,
+    instance: Option<String>,
+) -> Result<(EnvManager, PathBuf)> {
+    let current_dir = env::current_dir()
+        .map_err(|e| Error::file_system(PathBuf::from("."), "get current directory", e))?;
+    let mut env_manager = EnvManager::new().with_instance(instance);
+
+    let env_name = environment.or_else(|| env::var(CUENV_ENV_VAR).ok());
+    let mut caps = capabilities;
+    if caps.is_empty() {
+        // Comma-separated list
+        if let Ok(env_caps) = env::var(CUENV_CAPABILITIES_VAR) {
+            caps = env_caps
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect();
+        }
+    }
+
+    env_manager
+        .load_env_with_options(&current_dir, env_name, caps, None)
+        .await?;
+    Ok((env_manager, current_dir))
+}
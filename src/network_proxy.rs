@@ -0,0 +1,823 @@
+//! Host and network allowlists for sandboxed tasks
+//!
+//! Landlock only filters TCP connections by port. Tasks whose `allowedHosts`
+//! name hosts or CIDR ranges therefore run in their own network namespace,
+//! which has nothing but a loopback interface. An HTTP proxy listens on it,
+//! served by cuenv from outside the namespace, and forwards `CONNECT` tunnels
+//! and plain HTTP requests to allowed destinations only. `HTTP_PROXY` and
+//! `HTTPS_PROXY` point the task at the proxy, and every connection it asks
+//! for is recorded in the audit log.
+use crate::errors::{Error, Result};
+use std::net::{IpAddr, SocketAddr};
+
+/// Port the proxy listens on inside the task's network namespace
+pub const PROXY_PORT: u16 = 3128;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum HostRule {
+    /// Any host on a port
+    Port(u16),
+    /// A host name, `*.example.com` for all of its subdomains, optionally on
+    /// a single port
+    Host { pattern: String, port: Option<u16> },
+    /// Addresses in a network, a single address has the full prefix length
+    Network { addr: IpAddr, prefix: u8 },
+    /// An address on a single port, as suggested from audit runs
+    Address(SocketAddr),
+}
+
+/// The hosts, networks and ports a task may connect to
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostAllowlist {
+    rules: Vec<HostRule>,
+}
+
+impl HostAllowlist {
+    /// Parse `allowedHosts` entries: ports, host names and addresses with an
+    /// optional port, `*.` wildcards and CIDR ranges
+    pub fn parse(entries: &[String]) -> Result<Self> {
+        let rules = entries
+            .iter()
+            .map(|entry| parse_rule(entry.trim()))
+            .collect::<Result<_>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Whether `entries` name hosts or networks, which Landlock can't enforce
+    pub fn needs_proxy(entries: &[String]) -> bool {
+        entries
+            .iter()
+            .any(|entry| entry.trim().parse::<u16>().is_err())
+    }
+
+    /// Whether connecting to `host` on `port` is allowed. Networks can only
+    /// allow a connection once `addr`, the address `host` resolved to, is known.
+    pub fn allows(&self, host: &str, addr: Option<IpAddr>, port: u16) -> bool {
+        let host = host.trim_end_matches('.').to_lowercase();
+        let addr = addr.or_else(|| host.parse().ok());
+
+        self.rules.iter().any(|rule| match rule {
+            HostRule::Port(allowed) => *allowed == port,
+            HostRule::Host {
+                pattern,
+                port: allowed,
+            } => {
+                allowed.is_none_or(|allowed| allowed == port)
+                    && match pattern.strip_prefix("*.") {
+                        Some(domain) => host
+                            .strip_suffix(domain)
+                            .is_some_and(|sub| sub.ends_with('.')),
+                        None => host == *pattern,
+                    }
+            }
+            HostRule::Network {
+                addr: network,
+                prefix,
+            } => addr.is_some_and(|addr| in_network(addr, *network, *prefix)),
+            HostRule::Address(allowed) => {
+                allowed.port() == port
+                    && addr.is_some_and(|addr| addr.to_canonical() == allowed.ip().to_canonical())
+            }
+        })
+    }
+
+    /// Whether any rule allows addresses, so a host's addresses are worth resolving
+    pub fn has_networks(&self) -> bool {
+        self.rules
+            .iter()
+            .any(|rule| matches!(rule, HostRule::Network { .. } | HostRule::Address(_)))
+    }
+}
+
+fn parse_rule(entry: &str) -> Result<HostRule> {
+    let invalid = |reason: &str| {
+        Error::configuration(format!("Invalid allowedHosts entry '{entry}': {reason}"))
+    };
+
+    if let Ok(port) = entry.parse::<u16>() {
+        return Ok(HostRule::Port(port));
+    }
+
+    if let Some((addr, prefix)) = entry.split_once('/') {
+        let addr: IpAddr = addr.parse().map_err(|_| invalid("expected an address"))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix
+            .parse::<u8>()
+            .ok()
+            .filter(|prefix| *prefix <= max)
+            .ok_or_else(|| invalid(&format!("expected a prefix length up to {max}")))?;
+        return Ok(HostRule::Network { addr, prefix });
+    }
+
+    if let Ok(addr) = entry.parse::<SocketAddr>() {
+        return Ok(HostRule::Address(addr));
+    }
+
+    if let Ok(addr) = entry.parse::<IpAddr>() {
+        let prefix = if addr.is_ipv4() { 32 } else { 128 };
+        return Ok(HostRule::Network { addr, prefix });
+    }
+
+    let (host, port) = match entry.rsplit_once(':') {
+        Some((host, port)) => {
+            let port = port
+                .parse::<u16>()
+                .map_err(|_| invalid("expected a port"))?;
+            (host, Some(port))
+        }
+        None => (entry, None),
+    };
+    let name = host.strip_prefix("*.").unwrap_or(host);
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+    {
+        return Err(invalid("expected a host name"));
+    }
+
+    Ok(HostRule::Host {
+        pattern: host.trim_end_matches('.').to_lowercase(),
+        port,
+    })
+}
+
+fn in_network(addr: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    match (addr.to_canonical(), network) {
+        (IpAddr::V4(addr), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+            u32::from(addr) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(addr), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+            u128::from(addr) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use linux::{isolate_network, NetworkProxy, PendingProxy};
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::{HostAllowlist, PROXY_PORT};
+    use crate::audit::audit_logger;
+    use crate::errors::{Error, Result};
+    use std::ffi::CStr;
+    use std::io::{self, BufRead, BufReader, Read, Write};
+    use std::net::ToSocketAddrs;
+    use std::net::{Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream};
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+    use std::os::unix::process::CommandExt;
+    use std::process::Command;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+    /// Time a task has to send the head of a request
+    const HEAD_TIMEOUT: Duration = Duration::from_secs(30);
+    /// Longest request head accepted from a task
+    const MAX_HEAD_LEN: usize = 64 * 1024;
+
+    /// Proxy of a command isolated with [`isolate_network`], started once the
+    /// command is spawned. Until then, or without it, the command has no
+    /// network access at all.
+    pub struct PendingProxy {
+        socket: OwnedFd,
+        child_socket: OwnedFd,
+        allowlist: HostAllowlist,
+    }
+
+    /// Proxy serving a task's connections, stopped when dropped
+    pub struct NetworkProxy {
+        listener: Arc<TcpListener>,
+        stopped: Arc<AtomicBool>,
+    }
+
+    /// Isolate `cmd` in a new network namespace with only a loopback
+    /// interface, and point it at the proxy listening there
+    pub fn isolate_network(cmd: &mut Command, allowlist: HostAllowlist) -> Result<PendingProxy> {
+        let mut fds = [0; 2];
+        // Safety: socketpair writes two new descriptors into fds
+        let result = unsafe {
+            libc::socketpair(
+                libc::AF_UNIX,
+                libc::SOCK_STREAM | libc::SOCK_CLOEXEC,
+                0,
+                fds.as_mut_ptr(),
+            )
+        };
+        if result == -1 {
+            return Err(Error::configuration(format!(
+                "Failed to create network proxy socket: {}",
+                io::Error::last_os_error()
+            )));
+        }
+        // Safety: both descriptors were just created and nothing else owns them
+        let (socket, child_socket) =
+            unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+
+        let setup = NamespaceSetup::new(child_socket.as_raw_fd());
+        // Safety: the closure runs in the forked child and only makes system
+        // calls on data prepared before the fork
+        unsafe {
+            cmd.pre_exec(move || setup.run());
+        }
+
+        let proxy = format!("http://127.0.0.1:{PROXY_PORT}");
+        for var in ["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"] {
+            cmd.env(var, &proxy);
+        }
+        for var in ["NO_PROXY", "no_proxy"] {
+            cmd.env(var, "localhost,127.0.0.1,::1");
+        }
+
+        Ok(PendingProxy {
+            socket,
+            child_socket,
+            allowlist,
+        })
+    }
+
+    impl PendingProxy {
+        /// Serve the connections of the spawned command on behalf of `task`.
+        /// Fails if the listener of the command's namespace is not received,
+        /// in which case the caller should stop the command.
+        pub fn start(self, task: &str) -> Result<NetworkProxy> {
+            drop(self.child_socket);
+            let stopped = Arc::new(AtomicBool::new(false));
+
+            let listener = receive_fd(&self.socket).map_err(|e| {
+                Error::configuration(format!(
+                    "Failed to start the network proxy of '{task}': {e}"
+                ))
+            })?;
+            let listener = Arc::new(TcpListener::from(listener));
+
+            let allowlist = Arc::new(self.allowlist);
+            let runtime = tokio::runtime::Handle::try_current().ok();
+            let task = task.to_string();
+            let accepting = Arc::clone(&listener);
+            let stop = Arc::clone(&stopped);
+            thread::spawn(move || {
+                for client in accepting.incoming() {
+                    let client = match client {
+                        Ok(client) => client,
+                        Err(_) if stop.load(Ordering::SeqCst) => break,
+                        Err(e) => {
+                            log::warn!("Network proxy of task '{task}' failed to accept: {e}");
+                            thread::sleep(Duration::from_millis(100));
+                            continue;
+                        }
+                    };
+                    let allowlist = Arc::clone(&allowlist);
+                    let runtime = runtime.clone();
+                    let task = task.clone();
+                    thread::spawn(move || {
+                        if let Err(e) = serve(client, &allowlist, &task, runtime.as_ref()) {
+                            log::debug!("Proxied connection of task '{task}' failed: {e}");
+                        }
+                    });
+                }
+            });
+
+            Ok(NetworkProxy { listener, stopped })
+        }
+    }
+
+    impl Drop for NetworkProxy {
+        fn drop(&mut self) {
+            self.stopped.store(true, Ordering::SeqCst);
+            // Safety: shutdown only wakes up the accept loop, which then stops
+            unsafe {
+                libc::shutdown(self.listener.as_raw_fd(), libc::SHUT_RDWR);
+            }
+        }
+    }
+
+    /// A connection refused by the proxy
+    enum Refusal {
+        Blocked,
+        Failed(String),
+    }
+
+    /// Serve one connection of a task: a `CONNECT` tunnel or a plain HTTP request
+    fn serve(
+        mut client: TcpStream,
+        allowlist: &HostAllowlist,
+        task: &str,
+        runtime: Option<&tokio::runtime::Handle>,
+    ) -> io::Result<()> {
+        client.set_read_timeout(Some(HEAD_TIMEOUT))?;
+        let mut reader = BufReader::new(client.try_clone()?);
+        let head = read_head(&mut reader)?;
+        let Some(request) = Request::parse(&head) else {
+            return respond(&mut client, "400 Bad Request", "unsupported proxy request");
+        };
+
+        let upstream = connect(allowlist, &request.host, request.port);
+        record(
+            task,
+            &request.host,
+            request.port,
+            !matches!(upstream, Err(Refusal::Blocked)),
+            runtime,
+        );
+        let mut upstream = match upstream {
+            Ok(upstream) => upstream,
+            Err(Refusal::Blocked) => {
+                let message = format!(
+                    "connection to {}:{} blocked, it is not in security.allowedHosts",
+                    request.host, request.port
+                );
+                return respond(&mut client, "403 Forbidden", &message);
+            }
+            Err(Refusal::Failed(message)) => {
+                return respond(&mut client, "502 Bad Gateway", &message);
+            }
+        };
+
+        match &request.forward {
+            None => client.write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")?,
+            Some(head) => upstream.write_all(head.as_bytes())?,
+        }
+        upstream.write_all(reader.buffer())?;
+        client.set_read_timeout(None)?;
+        tunnel(client, upstream);
+        Ok(())
+    }
+
+    /// Where a task wants to connect to
+    struct Request {
+        host: String,
+        port: u16,
+        /// Head to send upstream for plain HTTP requests, `None` for tunnels
+        forward: Option<String>,
+    }
+
+    impl Request {
+        fn parse(head: &[String]) -> Option<Self> {
+            let mut first = head.first()?.split_whitespace();
+            let (method, target, version) = (first.next()?, first.next()?, first.next()?);
+
+            if method.eq_ignore_ascii_case("CONNECT") {
+                let (host, port) = split_authority(target)?;
+                return Some(Self {
+                    host,
+                    port,
+                    forward: None,
+                });
+            }
+
+            let url = url::Url::parse(target).ok()?;
+            if url.scheme() != "http" {
+                return None;
+            }
+            let host = url.host_str()?.trim_matches(['[', ']']).to_string();
+            let port = url.port_or_known_default()?;
+
+            // One request per connection, so every connection is checked
+            let mut forward = format!(
+                "{method} {} {version}\r\n",
+                &url[url::Position::BeforePath..]
+            );
+            for line in &head[1..] {
+                let name = line.split(':').next().unwrap_or_default().trim();
+                if ![
+                    "connection",
+                    "keep-alive",
+                    "proxy-connection",
+                    "proxy-authorization",
+                ]
+                .iter()
+                .any(|skipped| name.eq_ignore_ascii_case(skipped))
+                {
+                    forward.push_str(line);
+                    forward.push_str("\r\n");
+                }
+            }
+            forward.push_str("Connection: close\r\n\r\n");
+
+            Some(Self {
+                host,
+                port,
+                forward: Some(forward),
+            })
+        }
+    }
+
+    /// Split `host:port`, with IPv6 addresses in brackets
+    fn split_authority(authority: &str) -> Option<(String, u16)> {
+        let (host, port) = authority.rsplit_once(':')?;
+        let host = host.trim_matches(['[', ']']);
+        Some((host.to_string(), port.parse().ok()?))
+    }
+
+    /// Lines of a request head, up to the empty line ending it
+    fn read_head(reader: &mut BufReader<TcpStream>) -> io::Result<Vec<String>> {
+        let mut head = Vec::new();
+        let mut len = 0;
+        loop {
+            let mut line = String::new();
+            let read = reader
+                .by_ref()
+                .take(MAX_HEAD_LEN as u64)
+                .read_line(&mut line)?;
+            len += read;
+            if read == 0 || len > MAX_HEAD_LEN {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "incomplete request head",
+                ));
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line.is_empty() {
+                return Ok(head);
+            }
+            head.push(line.to_string());
+        }
+    }
+
+    /// Connect to `host` if the allowlist allows it, only resolving hosts a
+    /// network could allow
+    fn connect(
+        allowlist: &HostAllowlist,
+        host: &str,
+        port: u16,
+    ) -> std::result::Result<TcpStream, Refusal> {
+        let by_name = allowlist.allows(host, None, port);
+        if !by_name && !allowlist.has_networks() {
+            return Err(Refusal::Blocked);
+        }
+
+        let addrs: Vec<SocketAddr> = (host, port)
+            .to_socket_addrs()
+            .map_err(|e| Refusal::Failed(format!("failed to resolve {host}: {e}")))?
+            .collect();
+        let addr = addrs
+            .into_iter()
+            .find(|addr| by_name || allowlist.allows(host, Some(addr.ip()), port))
+            .ok_or(Refusal::Blocked)?;
+
+        TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)
+            .map_err(|e| Refusal::Failed(format!("failed to connect to {host}:{port}: {e}")))
+    }
+
+    fn respond(client: &mut TcpStream, status: &str, message: &str) -> io::Result<()> {
+        let body = format!("cuenv: {message}\n");
+        write!(
+            client,
+            "HTTP/1.1 {status}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\
+             Connection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    /// Copy data both ways until the upstream server is done
+    fn tunnel(client: TcpStream, upstream: TcpStream) {
+        let (Ok(mut client_read), Ok(mut upstream_write)) =
+            (client.try_clone(), upstream.try_clone())
+        else {
+            return;
+        };
+        let forward = thread::spawn(move || {
+            let _ = io::copy(&mut client_read, &mut upstream_write);
+            let _ = upstream_write.shutdown(Shutdown::Write);
+        });
+
+        let (mut upstream_read, mut client_write) = (upstream, client);
+        let _ = io::copy(&mut upstream_read, &mut client_write);
+        let _ = client_write.shutdown(Shutdown::Both);
+        let _ = forward.join();
+    }
+
+    fn record(
+        task: &str,
+        host: &str,
+        port: u16,
+        allowed: bool,
+        runtime: Option<&tokio::runtime::Handle>,
+    ) {
+        if !allowed {
+            log::warn!("Blocked connection of task '{task}' to {host}:{port}");
+        }
+        let (Some(runtime), Some(logger)) = (runtime, audit_logger()) else {
+            return;
+        };
+        let (task, host) = (task.to_string(), host.to_string());
+        runtime.spawn(async move {
+            if let Err(e) = logger.log_network_access(&task, &host, port, allowed).await {
+                log::warn!("Failed to audit log connection of task '{task}': {e}");
+            }
+        });
+    }
+
+    /// Namespace setup run in the forked child before `exec`
+    struct NamespaceSetup {
+        /// Socket the proxy listener is sent back over
+        socket: RawFd,
+        /// Contents of `uid_map` and `gid_map`, without a user namespace as root
+        id_maps: Option<(Vec<u8>, Vec<u8>)>,
+    }
+
+    impl NamespaceSetup {
+        fn new(socket: RawFd) -> Self {
+            // Safety: getuid and getgid can't fail
+            let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+            let id_maps = (uid != 0).then(|| {
+                (
+                    format!("{uid} {uid} 1").into(),
+                    format!("{gid} {gid} 1").into(),
+                )
+            });
+            Self { socket, id_maps }
+        }
+
+        fn run(&self) -> io::Result<()> {
+            // Unprivileged users need a user namespace to own the network namespace
+            let flags = match self.id_maps {
+                Some(_) => libc::CLONE_NEWUSER | libc::CLONE_NEWNET,
+                None => libc::CLONE_NEWNET,
+            };
+            // Safety: only changes the namespaces of this process
+            check(unsafe { libc::unshare(flags) })?;
+
+            if let Some((uid_map, gid_map)) = &self.id_maps {
+                write_proc(c"/proc/self/setgroups", b"deny")?;
+                write_proc(c"/proc/self/uid_map", uid_map)?;
+                write_proc(c"/proc/self/gid_map", gid_map)?;
+            }
+
+            bring_up_loopback()?;
+            let listener = listen_on_loopback()?;
+            // Without the listener the proxy can't start, so the spawn fails
+            let sent = send_fd(self.socket, listener);
+            // Safety: the listener was created above and is only used by the proxy
+            unsafe {
+                libc::close(listener);
+            }
+            sent
+        }
+    }
+
+    fn check(result: libc::c_int) -> io::Result<libc::c_int> {
+        if result == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(result)
+        }
+    }
+
+    fn write_proc(path: &CStr, contents: &[u8]) -> io::Result<()> {
+        // Safety: path is a valid C string and contents outlive the write
+        unsafe {
+            let fd = check(libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC))?;
+            let written = libc::write(fd, contents.as_ptr().cast(), contents.len());
+            let error = io::Error::last_os_error();
+            libc::close(fd);
+            if written != contents.len() as isize {
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    fn bring_up_loopback() -> io::Result<()> {
+        // Safety: ifreq is plain data and the ioctls only access the request
+        unsafe {
+            let fd = check(libc::socket(
+                libc::AF_INET,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                0,
+            ))?;
+            let mut request: libc::ifreq = std::mem::zeroed();
+            for (dst, src) in request.ifr_name.iter_mut().zip(b"lo") {
+                *dst = *src as libc::c_char;
+            }
+
+            let mut result = libc::ioctl(fd, libc::SIOCGIFFLAGS as _, &mut request);
+            if result != -1 {
+                request.ifr_ifru.ifru_flags |= libc::IFF_UP as libc::c_short;
+                result = libc::ioctl(fd, libc::SIOCSIFFLAGS as _, &mut request);
+            }
+            let error = io::Error::last_os_error();
+            libc::close(fd);
+            if result == -1 {
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    fn listen_on_loopback() -> io::Result<RawFd> {
+        let addr = libc::sockaddr_in {
+            sin_family: libc::AF_INET as libc::sa_family_t,
+            sin_port: PROXY_PORT.to_be(),
+            sin_addr: libc::in_addr {
+                s_addr: u32::from(Ipv4Addr::LOCALHOST).to_be(),
+            },
+            sin_zero: [0; 8],
+        };
+        // Safety: addr is a valid sockaddr_in of the given length
+        unsafe {
+            let fd = check(libc::socket(
+                libc::AF_INET,
+                libc::SOCK_STREAM | libc::SOCK_CLOEXEC,
+                0,
+            ))?;
+            let bound = libc::bind(
+                fd,
+                (&addr as *const libc::sockaddr_in).cast(),
+                std::mem::size_of::<libc::sockaddr_in>() as libc::socklen_t,
+            );
+            if bound == -1 || libc::listen(fd, 128) == -1 {
+                let error = io::Error::last_os_error();
+                libc::close(fd);
+                return Err(error);
+            }
+            Ok(fd)
+        }
+    }
+
+    /// Buffer for a control message carrying one descriptor
+    #[repr(C, align(8))]
+    struct FdMessage([u8; 32]);
+
+    fn send_fd(socket: RawFd, fd: RawFd) -> io::Result<()> {
+        let mut byte = [0u8; 1];
+        let mut control = FdMessage([0; 32]);
+        // Safety: the message only points at the buffers above, which are
+        // large enough for one byte and one descriptor
+        unsafe {
+            let mut iov = libc::iovec {
+                iov_base: byte.as_mut_ptr().cast(),
+                iov_len: byte.len(),
+            };
+            let mut msg: libc::msghdr = std::mem::zeroed();
+            msg.msg_iov = &mut iov;
+            msg.msg_iovlen = 1;
+            msg.msg_control = control.0.as_mut_ptr().cast();
+            msg.msg_controllen = libc::CMSG_SPACE(std::mem::size_of::<RawFd>() as u32) as _;
+
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(std::mem::size_of::<RawFd>() as u32) as _;
+            std::ptr::write_unaligned(libc::CMSG_DATA(cmsg).cast::<RawFd>(), fd);
+
+            if libc::sendmsg(socket, &msg, libc::MSG_NOSIGNAL) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    /// Receive the descriptor the child sent before it was executed
+    fn receive_fd(socket: &OwnedFd) -> io::Result<OwnedFd> {
+        let mut byte = [0u8; 1];
+        let mut control = FdMessage([0; 32]);
+        // Safety: as in send_fd, and the received descriptor is owned from here on
+        unsafe {
+            let mut iov = libc::iovec {
+                iov_base: byte.as_mut_ptr().cast(),
+                iov_len: byte.len(),
+            };
+            let mut msg: libc::msghdr = std::mem::zeroed();
+            msg.msg_iov = &mut iov;
+            msg.msg_iovlen = 1;
+            msg.msg_control = control.0.as_mut_ptr().cast();
+            msg.msg_controllen = control.0.len() as _;
+
+            let flags = libc::MSG_DONTWAIT | libc::MSG_CMSG_CLOEXEC;
+            if libc::recvmsg(socket.as_raw_fd(), &mut msg, flags) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            if cmsg.is_null()
+                || (*cmsg).cmsg_level != libc::SOL_SOCKET
+                || (*cmsg).cmsg_type != libc::SCM_RIGHTS
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "no proxy listener received",
+                ));
+            }
+            let fd = std::ptr::read_unaligned(libc::CMSG_DATA(cmsg).cast::<RawFd>());
+            Ok(OwnedFd::from_raw_fd(fd))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn head(lines: &[&str]) -> Vec<String> {
+            lines.iter().map(|l| l.to_string()).collect()
+        }
+
+        #[test]
+        fn test_parse_connect_request() {
+            let connect =
+                Request::parse(&head(&["CONNECT registry.npmjs.org:443 HTTP/1.1"])).unwrap();
+            assert_eq!(
+                (connect.host.as_str(), connect.port),
+                ("registry.npmjs.org", 443)
+            );
+            assert!(connect.forward.is_none());
+        }
+
+        #[test]
+        fn test_parse_plain_request() {
+            let get = Request::parse(&head(&[
+                "GET http://[::1]:8080/a?b=c HTTP/1.1",
+                "Host: [::1]:8080",
+                "Proxy-Connection: keep-alive",
+            ]))
+            .unwrap();
+            assert_eq!((get.host.as_str(), get.port), ("::1", 8080));
+            assert_eq!(
+                get.forward.unwrap(),
+                "GET /a?b=c HTTP/1.1\r\nHost: [::1]:8080\r\nConnection: close\r\n\r\n"
+            );
+        }
+
+        #[test]
+        fn test_parse_https_request_without_connect() {
+            assert!(Request::parse(&head(&["GET https://example.com/ HTTP/1.1"])).is_none());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(entries: &[&str]) -> Vec<String> {
+        entries.iter().map(|entry| entry.to_string()).collect()
+    }
+
+    #[test]
+    fn test_needs_proxy() {
+        assert!(!HostAllowlist::needs_proxy(&entries(&["8080"])));
+        assert!(HostAllowlist::needs_proxy(&entries(&[
+            "8080",
+            "registry.npmjs.org"
+        ])));
+    }
+
+    #[test]
+    fn test_allowlist_ports() {
+        let allowlist = HostAllowlist::parse(&entries(&["8080"])).unwrap();
+        assert!(allowlist.allows("example.com", None, 8080));
+        assert!(!allowlist.allows("example.com", None, 443));
+    }
+
+    #[test]
+    fn test_allowlist_host_names() {
+        let allowlist =
+            HostAllowlist::parse(&entries(&["registry.npmjs.org", "*.github.com:443"])).unwrap();
+        assert!(allowlist.allows("Registry.npmjs.org.", None, 443));
+        assert!(allowlist.allows("api.github.com", None, 443));
+        assert!(!allowlist.allows("api.github.com", None, 80));
+        assert!(!allowlist.allows("github.com", None, 443));
+        assert!(!allowlist.allows("evilgithub.com", None, 443));
+    }
+
+    #[test]
+    fn test_allowlist_networks() {
+        let allowlist =
+            HostAllowlist::parse(&entries(&["10.0.0.0/8", "fd00::/8", "192.168.1.5"])).unwrap();
+        assert!(allowlist.allows("10.1.2.3", None, 22));
+        assert!(allowlist.allows("internal", "10.9.9.9".parse().ok(), 443));
+        assert!(allowlist.allows("::ffff:192.168.1.5", None, 443));
+        assert!(allowlist.allows("fd12::1", None, 443));
+        assert!(!allowlist.allows("11.0.0.1", None, 443));
+    }
+
+    #[test]
+    fn test_allowlist_rejects_invalid_entries() {
+        for invalid in ["10.0.0.0/33", "exa mple.com", "host:http", "*."] {
+            assert!(
+                HostAllowlist::parse(&[invalid.to_string()]).is_err(),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_allowlist_address_with_port() {
+        let entries = [
+            "140.82.112.3:443".to_string(),
+            "[2606:4700::1]:443".to_string(),
+        ];
+        let allowlist = HostAllowlist::parse(&entries).unwrap();
+        assert!(allowlist.has_networks());
+        assert!(allowlist.allows("140.82.112.3", None, 443));
+        assert!(allowlist.allows("github.com", "140.82.112.3".parse().ok(), 443));
+        assert!(!allowlist.allows("140.82.112.3", None, 22));
+        assert!(allowlist.allows("2606:4700::1", None, 443));
+    }
+}
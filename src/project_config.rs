@@ -0,0 +1,181 @@
+//! Per-directory cuenv settings
+//!
+//! A `cuenv.toml` or `.cuenvrc` next to the CUE files selects which package
+//! holds the environment and which instance of it is loaded, so cuenv can
+//! share a directory with an existing CUE package:
+//!
+//! ```toml
+//! package = "deploy"
+//! instance = "backend"
+//! ```
+use crate::constants::{
+    CUENV_INSTANCE_VAR, CUENV_PACKAGE_VAR, ENV_CUE_FILENAME, ENV_INSTANCE_NAME, ENV_PACKAGE_NAME,
+    PROJECT_CONFIG_FILENAMES,
+};
+use crate::errors::{Error, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// CUE package holding the environment, `env` if unset
+    pub package: Option<String>,
+    /// Top-level field of the package to load, `env` if unset
+    pub instance: Option<String>,
+}
+
+impl ProjectConfig {
+    /// Read the project config of `dir`, with `CUENV_PACKAGE` and
+    /// `CUENV_INSTANCE` taking precedence over the file
+    pub fn load(dir: &Path) -> Result<Self> {
+        let mut config = match config_file(dir) {
+            Some(path) => Self::from_file(&path)?,
+            None => Self::default(),
+        };
+
+        if let Some(package) = non_empty_var(CUENV_PACKAGE_VAR) {
+            config.package = Some(package);
+        }
+        if let Some(instance) = non_empty_var(CUENV_INSTANCE_VAR) {
+            config.instance = Some(instance);
+        }
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path).map_err(|e| Error::file_system(path, "read", e))?;
+        toml::from_str(&contents).map_err(|e| {
+            Error::configuration(format!("Invalid project config {}: {e}", path.display()))
+        })
+    }
+
+    pub fn package(&self) -> &str {
+        self.package.as_deref().unwrap_or(ENV_PACKAGE_NAME)
+    }
+
+    pub fn instance(&self) -> &str {
+        self.instance.as_deref().unwrap_or(ENV_INSTANCE_NAME)
+    }
+}
+
+/// The project config file of `dir`, if any
+pub fn config_file(dir: &Path) -> Option<PathBuf> {
+    PROJECT_CONFIG_FILENAMES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// The project config file and the CUE files of `package` in `dir`, whose
+/// changes require reloading the environment
+pub fn watched_files(dir: &Path, package: &str) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = config_file(dir).into_iter().collect();
+
+    let Ok(entries) = fs::read_dir(dir) else {
+        return files;
+    };
+    let mut cue_files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension().is_some_and(|ext| ext == "cue")
+                && package_clause(path).as_deref() == Some(package)
+        })
+        .collect();
+    cue_files.sort();
+    files.extend(cue_files);
+    files
+}
+
+/// Whether `dir` defines a cuenv environment, through an `env.cue` or a project config
+pub fn has_env_package(dir: &Path) -> bool {
+    dir.join(ENV_CUE_FILENAME).is_file() || config_file(dir).is_some()
+}
+
+/// Name in the `package` clause of a CUE file
+fn package_clause(path: &Path) -> Option<String> {
+    let contents = fs::read_to_string(path).ok()?;
+    contents
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with("//") && !line.starts_with('@'))?
+        .strip_prefix("package ")
+        .map(|name| name.trim().to_string())
+}
+
+fn non_empty_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// A project selecting the `deploy` package, next to a file of another one
+    fn deploy_project(dir: &Path) {
+        fs::write(dir.join(".cuenvrc"), "package = \"deploy\"\n").unwrap();
+        fs::write(
+            dir.join("env.cue"),
+            "// Deployment environment\npackage deploy\n",
+        )
+        .unwrap();
+        fs::write(dir.join("k8s.cue"), "package deploy\n").unwrap();
+        fs::write(dir.join("other.cue"), "package other\n").unwrap();
+    }
+
+    #[test]
+    fn test_has_env_package() {
+        let temp_dir = TempDir::new().unwrap();
+        assert!(!has_env_package(temp_dir.path()));
+
+        deploy_project(temp_dir.path());
+        assert!(has_env_package(temp_dir.path()));
+    }
+
+    #[test]
+    fn test_project_config_selects_package() {
+        let temp_dir = TempDir::new().unwrap();
+        deploy_project(temp_dir.path());
+
+        let config = ProjectConfig::from_file(&temp_dir.path().join(".cuenvrc")).unwrap();
+        assert_eq!(config.package(), "deploy");
+        assert_eq!(config.instance(), ENV_INSTANCE_NAME);
+    }
+
+    #[test]
+    fn test_watched_files_of_package() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        deploy_project(dir);
+
+        assert_eq!(
+            watched_files(dir, "deploy"),
+            [
+                dir.join(".cuenvrc"),
+                dir.join("env.cue"),
+                dir.join("k8s.cue")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cuenv_toml_takes_precedence() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        deploy_project(dir);
+
+        fs::write(dir.join("cuenv.toml"), "instance = \"backend\"\n").unwrap();
+        assert_eq!(config_file(dir), Some(dir.join("cuenv.toml")));
+    }
+
+    #[test]
+    fn test_unknown_keys_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("cuenv.toml");
+
+        fs::write(&file, "packages = \"typo\"\n").unwrap();
+        assert!(ProjectConfig::from_file(&file).is_err());
+    }
+}
@@ -0,0 +1,243 @@
+//! Blob storage keyed by Remote Execution API digests
+//!
+//! Remote Execution API clients address blobs by the plain SHA256 of their
+//! content, while the content-addressed store uses its own length-prefixed
+//! hash. This store keeps a persisted index between the two, as a log that
+//! every new blob appends a line to.
+
+use crate::cache::ContentAddressedStore;
+use crate::errors::{Error, Result};
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// SHA256 of the empty blob, which is always considered present
+pub const EMPTY_BLOB_HASH: &str =
+    "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+/// Whether `data` has the given Remote Execution API digest
+pub fn digest_matches(hash: &str, size: i64, data: &[u8]) -> bool {
+    data.len() as i64 == size && format!("{:x}", Sha256::digest(data)) == hash
+}
+
+/// Remote Execution API digest of `data`
+pub fn digest_of(data: &[u8]) -> super::grpc_proto::proto::Digest {
+    super::grpc_proto::proto::Digest {
+        hash: format!("{:x}", Sha256::digest(data)),
+        size_bytes: data.len() as i64,
+    }
+}
+
+/// Blobs stored in the content-addressed store under their REAPI digest
+pub struct BlobStore {
+    cas: Arc<ContentAddressedStore>,
+    /// REAPI digest hash -> CAS hash
+    index: DashMap<String, String>,
+    /// Index log, one `{hash} {cas hash}` line per blob
+    index_file: Mutex<File>,
+    index_path: PathBuf,
+}
+
+impl BlobStore {
+    /// Open the blob index below `base_dir`
+    pub fn new(cas: Arc<ContentAddressedStore>, base_dir: &Path) -> Result<Self> {
+        let index_path = base_dir.join("blob_index.log");
+        let index = DashMap::new();
+        let mut torn = false;
+
+        if index_path.exists() {
+            let content = fs::read_to_string(&index_path)
+                .map_err(|e| Error::file_system(&index_path, "read blob index", e))?;
+            torn = !content.is_empty() && !content.ends_with('\n');
+            // A line cut short by a crash is skipped, its blob is uploaded again
+            for line in content.lines() {
+                if let [hash, cas_hash] = line.split(' ').collect::<Vec<_>>()[..] {
+                    if hash.len() == 64 && cas_hash.len() == 64 {
+                        index.insert(hash.to_string(), cas_hash.to_string());
+                    }
+                }
+            }
+        }
+
+        let mut index_file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&index_path)
+            .map_err(|e| Error::file_system(&index_path, "open blob index", e))?;
+        if torn {
+            index_file
+                .write_all(b"\n")
+                .map_err(|e| Error::file_system(&index_path, "append to blob index", e))?;
+        }
+
+        Ok(Self {
+            cas,
+            index,
+            index_file: Mutex::new(index_file),
+            index_path,
+        })
+    }
+
+    /// Whether a blob with the given digest is stored
+    pub fn contains(&self, hash: &str, size: i64) -> bool {
+        if hash == EMPTY_BLOB_HASH {
+            return size == 0;
+        }
+
+        self.index
+            .get(hash)
+            .and_then(|cas_hash| self.cas.get_metadata(cas_hash.value()))
+            .is_some_and(|metadata| metadata.size as i64 == size)
+    }
+
+    /// Read a blob by its digest hash
+    pub fn read(&self, hash: &str) -> Result<Vec<u8>> {
+        if hash == EMPTY_BLOB_HASH {
+            return Ok(Vec::new());
+        }
+
+        self.cas.retrieve(&self.cas_hash(hash)?)
+    }
+
+    /// Open a blob for reading in chunks. The content is not verified, so
+    /// readers of the whole blob should check it against the digest.
+    pub fn open(&self, hash: &str) -> Result<Box<dyn Read + Send>> {
+        if hash == EMPTY_BLOB_HASH {
+            return Ok(Box::new(io::empty()));
+        }
+
+        Ok(Box::new(self.cas.open(&self.cas_hash(hash)?)?))
+    }
+
+    /// Store a blob whose digest has already been verified
+    pub fn write(&self, hash: &str, data: &[u8]) -> Result<()> {
+        if hash == EMPTY_BLOB_HASH {
+            return Ok(());
+        }
+
+        let cas_hash = self.cas.store(Cursor::new(data))?;
+        self.record(hash, cas_hash)
+    }
+
+    /// Path for a temporary file to receive a large blob in, before it is
+    /// moved into the store with [`Self::write_file`]
+    pub fn temp_path(&self) -> Result<PathBuf> {
+        self.cas.temp_path()
+    }
+
+    /// Move a file holding a blob whose digest has already been verified
+    /// into the store
+    pub fn write_file(&self, hash: &str, path: &Path) -> Result<()> {
+        if hash == EMPTY_BLOB_HASH {
+            let _ = fs::remove_file(path);
+            return Ok(());
+        }
+
+        let cas_hash = self.cas.store_file(path)?;
+        self.record(hash, cas_hash)
+    }
+
+    fn cas_hash(&self, hash: &str) -> Result<String> {
+        self.index
+            .get(hash)
+            .map(|entry| entry.value().clone())
+            .ok_or_else(|| Error::configuration(format!("Blob not found: {hash}")))
+    }
+
+    /// Add a blob to the index, appending it to the log if it is new
+    fn record(&self, hash: &str, cas_hash: String) -> Result<()> {
+        let mut index_file = self.index_file.lock();
+        if self
+            .index
+            .get(hash)
+            .is_some_and(|entry| *entry.value() == cas_hash)
+        {
+            return Ok(());
+        }
+
+        index_file
+            .write_all(format!("{hash} {cas_hash}\n").as_bytes())
+            .map_err(|e| Error::file_system(&self.index_path, "append to blob index", e))?;
+        self.index.insert(hash.to_string(), cas_hash);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_blob_round_trip_and_reload() {
+        let temp_dir = TempDir::new().unwrap();
+        let cas = Arc::new(ContentAddressedStore::new(temp_dir.path().join("cas"), 16).unwrap());
+        let data = b"some build output that is not inlined";
+        let hash = format!("{:x}", Sha256::digest(data));
+
+        assert!(digest_matches(&hash, data.len() as i64, data));
+        assert!(!digest_matches(&hash, data.len() as i64 + 1, data));
+
+        let store = BlobStore::new(Arc::clone(&cas), temp_dir.path()).unwrap();
+        assert!(!store.contains(&hash, data.len() as i64));
+        store.write(&hash, data).unwrap();
+        assert!(store.contains(&hash, data.len() as i64));
+        assert!(!store.contains(&hash, 1));
+
+        let reopened = BlobStore::new(cas, temp_dir.path()).unwrap();
+        assert_eq!(reopened.read(&hash).unwrap(), data);
+        assert!(reopened.contains(EMPTY_BLOB_HASH, 0));
+        assert!(reopened.read(EMPTY_BLOB_HASH).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_large_blob_written_from_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let cas = Arc::new(ContentAddressedStore::new(temp_dir.path().join("cas"), 16).unwrap());
+        let store = BlobStore::new(cas, temp_dir.path()).unwrap();
+        let data = b"a blob that was uploaded in chunks";
+        let hash = format!("{:x}", Sha256::digest(data));
+
+        let path = store.temp_path().unwrap();
+        fs::write(&path, data).unwrap();
+        store.write_file(&hash, &path).unwrap();
+        assert!(store.contains(&hash, data.len() as i64));
+
+        let mut stored = Vec::new();
+        store.open(&hash).unwrap().read_to_end(&mut stored).unwrap();
+        assert_eq!(stored, data);
+    }
+
+    #[test]
+    fn test_index_skips_torn_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let cas = Arc::new(ContentAddressedStore::new(temp_dir.path().join("cas"), 16).unwrap());
+        let data = b"some build output that is not inlined";
+        let hash = format!("{:x}", Sha256::digest(data));
+
+        let store = BlobStore::new(Arc::clone(&cas), temp_dir.path()).unwrap();
+        store.write(&hash, data).unwrap();
+        drop(store);
+        let mut log = OpenOptions::new()
+            .append(true)
+            .open(temp_dir.path().join("blob_index.log"))
+            .unwrap();
+        log.write_all(b"e3b0c442").unwrap();
+
+        let reopened = BlobStore::new(Arc::clone(&cas), temp_dir.path()).unwrap();
+        assert_eq!(reopened.index.len(), 1);
+        assert_eq!(reopened.read(&hash).unwrap(), data);
+
+        // Blobs written after the torn line are kept
+        let other = b"another build output, written after a crash";
+        let other_hash = format!("{:x}", Sha256::digest(other));
+        reopened.write(&other_hash, other).unwrap();
+        drop(reopened);
+        let reopened = BlobStore::new(cas, temp_dir.path()).unwrap();
+        assert_eq!(reopened.read(&other_hash).unwrap(), other);
+    }
+}
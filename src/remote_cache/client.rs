@@ -0,0 +1,598 @@
+//! Remote cache client for sharing task results across machines
+//!
+//! Task results are stored in a Remote Execution API action cache under the
+//! digest of their cache key. Output files and captured output are kept as
+//! blobs in the remote CAS and copied into the local content store on a hit.
+
+use prost::Message;
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::time::{Duration, SystemTime};
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Code, Status};
+
+use super::blob_store::{digest_matches, digest_of};
+use super::grpc_proto::bytestream::{
+    byte_stream_client::ByteStreamClient, ReadRequest, WriteRequest,
+};
+use super::grpc_proto::proto::{
+    action_cache_client::ActionCacheClient, batch_update_blobs_request,
+    content_addressable_storage_client::ContentAddressableStorageClient, ActionResult, Any,
+    BatchReadBlobsRequest, BatchUpdateBlobsRequest, Digest, ExecutedActionMetadata,
+    FindMissingBlobsRequest, GetActionResultRequest, OutputFile, UpdateActionResultRequest,
+};
+use super::simple_server::{MAX_BATCH_TOTAL_SIZE_BYTES, MAX_MESSAGE_SIZE};
+use crate::cache::{CacheMode, CachedTaskResult, ContentAddressedStore, OutputFileMetadata};
+use crate::errors::{Error, Result};
+use crate::resource_limits::ResourceUsage;
+
+/// Environment variable holding the remote cache endpoint (`grpc://host:port`)
+pub const REMOTE_CACHE_ENV: &str = "CUENV_REMOTE_CACHE";
+
+/// Environment variable holding the remote cache mode (`off`, `read`, `write`, `read-write`)
+pub const REMOTE_CACHE_MODE_ENV: &str = "CUENV_REMOTE_CACHE_MODE";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Size of the chunks sent by ByteStream writes
+const WRITE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Type of the auxiliary metadata holding the resource usage of a task
+const RESOURCE_USAGE_TYPE_URL: &str = "type.googleapis.com/cuenv.ResourceUsage";
+
+/// Resource usage of a task, as stored in the metadata of its action result
+#[derive(Clone, PartialEq, Message)]
+struct ResourceUsageMetadata {
+    #[prost(uint64, optional, tag = "1")]
+    peak_memory_bytes: Option<u64>,
+    #[prost(uint64, tag = "2")]
+    cpu_time_micros: u64,
+}
+
+impl From<&ResourceUsage> for ResourceUsageMetadata {
+    fn from(usage: &ResourceUsage) -> Self {
+        Self {
+            peak_memory_bytes: usage.peak_memory,
+            cpu_time_micros: usage.cpu_time.as_micros() as u64,
+        }
+    }
+}
+
+impl From<ResourceUsageMetadata> for ResourceUsage {
+    fn from(metadata: ResourceUsageMetadata) -> Self {
+        Self {
+            peak_memory: metadata.peak_memory_bytes,
+            cpu_time: Duration::from_micros(metadata.cpu_time_micros),
+        }
+    }
+}
+
+/// Client for a Remote Execution API cache such as `cuenv remote-cache-server`
+pub struct RemoteCacheClient {
+    endpoint: String,
+    mode: CacheMode,
+    channel: Channel,
+}
+
+impl RemoteCacheClient {
+    /// Create a client from `CUENV_REMOTE_CACHE` and `CUENV_REMOTE_CACHE_MODE`.
+    ///
+    /// Returns `None` if no remote cache is configured or its mode is `off`.
+    pub fn from_env() -> Result<Option<Self>> {
+        let url = match std::env::var(REMOTE_CACHE_ENV) {
+            Ok(url) if !url.is_empty() => url,
+            _ => return Ok(None),
+        };
+        let mode = std::env::var(REMOTE_CACHE_MODE_ENV)
+            .map(CacheMode::from)
+            .unwrap_or_default();
+
+        if mode == CacheMode::Off {
+            return Ok(None);
+        }
+        Self::connect(&url, mode).map(Some)
+    }
+
+    /// Create a client for a `grpc://host:port` endpoint.
+    ///
+    /// The connection is established on the first request, so an unreachable
+    /// cache only shows up as failed lookups and uploads.
+    pub fn connect(url: &str, mode: CacheMode) -> Result<Self> {
+        let address = url
+            .strip_prefix("grpc://")
+            .map(|authority| format!("http://{authority}"))
+            .ok_or_else(|| {
+                Error::configuration(format!(
+                    "Unsupported remote cache URL '{url}', expected grpc://host:port"
+                ))
+            })?;
+
+        let channel = Endpoint::from_shared(address)
+            .map_err(|e| Error::configuration(format!("Invalid remote cache URL '{url}': {e}")))?
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(REQUEST_TIMEOUT)
+            .connect_lazy();
+
+        Ok(Self {
+            endpoint: url.to_string(),
+            mode,
+            channel,
+        })
+    }
+
+    /// Read/write semantics of the remote tier
+    pub fn mode(&self) -> CacheMode {
+        self.mode
+    }
+
+    /// Endpoint the client talks to
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Whether a usable result for `cache_key` is available, without
+    /// downloading its blobs or keeping anything locally
+    pub async fn contains(&self, cache_key: &str) -> Result<bool> {
+        match self.action_result(cache_key, false).await? {
+            Some(result) => Ok(self
+                .find_missing_blobs(blob_digests(&result))
+                .await?
+                .is_empty()),
+            None => Ok(false),
+        }
+    }
+
+    /// Look up a task result and copy its blobs into `cas`.
+    ///
+    /// Results whose blobs are no longer all available are treated as a miss.
+    pub async fn fetch(
+        &self,
+        cache_key: &str,
+        cas: &ContentAddressedStore,
+    ) -> Result<Option<CachedTaskResult>> {
+        let Some(result) = self.action_result(cache_key, true).await? else {
+            return Ok(None);
+        };
+
+        let digests = blob_digests(&result);
+        if !self.find_missing_blobs(digests.clone()).await?.is_empty() {
+            log::debug!("Remote result for {cache_key} references missing blobs");
+            return Ok(None);
+        }
+        let blobs = self.read_blobs(digests).await?;
+        let take_blob = |digest: &Option<Digest>| -> Result<Vec<u8>> {
+            let digest = digest.as_ref().ok_or_else(|| {
+                Error::network(&self.endpoint, "Remote result is missing a digest")
+            })?;
+            Ok(blobs.get(&digest.hash).cloned().unwrap_or_default())
+        };
+
+        let executed_at = SystemTime::now();
+        let mut output_files = HashMap::new();
+        let mut output_metadata = HashMap::new();
+        for file in &result.output_files {
+            let data = if file.contents.is_empty() {
+                take_blob(&file.digest)?
+            } else {
+                file.contents.clone()
+            };
+            let cas_hash = cas.store(Cursor::new(data))?;
+
+            output_files.insert(file.path.clone(), cas_hash);
+            output_metadata.insert(
+                file.path.clone(),
+                OutputFileMetadata {
+                    mode: if file.is_executable { 0o755 } else { 0o644 },
+                    modified: executed_at,
+                },
+            );
+        }
+
+        let stdout = match (&result.stdout_digest, result.stdout_raw.is_empty()) {
+            (_, false) => Some(result.stdout_raw.clone()),
+            (Some(_), true) => Some(take_blob(&result.stdout_digest)?),
+            (None, true) => None,
+        };
+        let stderr = match (&result.stderr_digest, result.stderr_raw.is_empty()) {
+            (_, false) => Some(result.stderr_raw.clone()),
+            (Some(_), true) => Some(take_blob(&result.stderr_digest)?),
+            (None, true) => None,
+        };
+
+        Ok(Some(CachedTaskResult {
+            cache_key: cache_key.to_string(),
+            executed_at,
+            exit_code: result.exit_code,
+            stdout,
+            stderr,
+            output_files,
+            output_metadata,
+            resource_usage: result.execution_metadata.as_ref().and_then(resource_usage),
+        }))
+    }
+
+    /// Upload a task result whose output files are stored in `cas`
+    pub async fn upload(
+        &self,
+        result: &CachedTaskResult,
+        cas: &ContentAddressedStore,
+    ) -> Result<()> {
+        let mut blobs = HashMap::new();
+
+        let mut output_files = Vec::with_capacity(result.output_files.len());
+        for (path, cas_hash) in &result.output_files {
+            let data = cas.retrieve(cas_hash)?;
+            let digest = digest_of(&data);
+            let is_executable = result
+                .output_metadata
+                .get(path)
+                .is_some_and(|metadata| metadata.mode & 0o111 != 0);
+
+            output_files.push(OutputFile {
+                path: path.clone(),
+                digest: Some(digest.clone()),
+                is_executable,
+                contents: Vec::new(),
+            });
+            blobs.insert(digest.hash.clone(), (digest, data));
+        }
+        output_files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let mut output_digest = |output: &Option<Vec<u8>>| {
+            output.as_ref().map(|data| {
+                let digest = digest_of(data);
+                blobs.insert(digest.hash.clone(), (digest.clone(), data.clone()));
+                digest
+            })
+        };
+        let stdout_digest = output_digest(&result.stdout);
+        let stderr_digest = output_digest(&result.stderr);
+
+        // Only upload what the remote does not have yet
+        let digests = blobs.values().map(|(digest, _)| digest.clone()).collect();
+        let missing = self.find_missing_blobs(digests).await?;
+        let uploads = missing
+            .into_iter()
+            .filter_map(|digest| blobs.remove(&digest.hash))
+            .collect();
+        self.write_blobs(uploads).await?;
+
+        let request = UpdateActionResultRequest {
+            instance_name: String::new(),
+            action_digest: Some(action_digest(&result.cache_key)),
+            action_result: Some(ActionResult {
+                output_files,
+                exit_code: result.exit_code,
+                stdout_digest,
+                stderr_digest,
+                execution_metadata: result.resource_usage.as_ref().map(|usage| {
+                    ExecutedActionMetadata {
+                        auxiliary_metadata: vec![Any {
+                            type_url: RESOURCE_USAGE_TYPE_URL.to_string(),
+                            value: ResourceUsageMetadata::from(usage).encode_to_vec(),
+                        }],
+                    }
+                }),
+                ..Default::default()
+            }),
+        };
+        self.action_cache()
+            .update_action_result(request)
+            .await
+            .map_err(|status| self.error("UpdateActionResult", status))?;
+
+        Ok(())
+    }
+
+    /// The action result stored for `cache_key`, if it is one cuenv could
+    /// have uploaded. `inline_output` asks for stdout and stderr in the response.
+    async fn action_result(
+        &self,
+        cache_key: &str,
+        inline_output: bool,
+    ) -> Result<Option<ActionResult>> {
+        let request = GetActionResultRequest {
+            instance_name: String::new(),
+            action_digest: Some(action_digest(cache_key)),
+            inline_stdout: inline_output,
+            inline_stderr: inline_output,
+            inline_output_files: Vec::new(),
+        };
+        let result = match self.action_cache().get_action_result(request).await {
+            Ok(response) => response.into_inner(),
+            Err(status) if status.code() == Code::NotFound => return Ok(None),
+            Err(status) => return Err(self.error("GetActionResult", status)),
+        };
+
+        // Only successful results without directories or symlinks come from cuenv
+        if result.exit_code != 0
+            || !result.output_directories.is_empty()
+            || !result.output_symlinks.is_empty()
+            || !result.output_file_symlinks.is_empty()
+            || !result.output_directory_symlinks.is_empty()
+        {
+            return Ok(None);
+        }
+        Ok(Some(result))
+    }
+
+    async fn find_missing_blobs(&self, digests: Vec<Digest>) -> Result<Vec<Digest>> {
+        if digests.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let request = FindMissingBlobsRequest {
+            instance_name: String::new(),
+            blob_digests: digests,
+        };
+        let response = self
+            .cas()
+            .find_missing_blobs(request)
+            .await
+            .map_err(|status| self.error("FindMissingBlobs", status))?;
+
+        Ok(response.into_inner().missing_blob_digests)
+    }
+
+    /// Download blobs, verifying their digests, keyed by hash
+    async fn read_blobs(&self, digests: Vec<Digest>) -> Result<HashMap<String, Vec<u8>>> {
+        let mut blobs = HashMap::new();
+        let (large, small): (Vec<_>, Vec<_>) = dedup(digests)
+            .into_iter()
+            .partition(|digest| digest.size_bytes > MAX_BATCH_TOTAL_SIZE_BYTES);
+
+        for digest in large {
+            let data = self.read_stream(&digest).await?;
+            blobs.insert(digest.hash, data);
+        }
+
+        for batch in batches(small, |digest| digest.size_bytes) {
+            let request = BatchReadBlobsRequest {
+                instance_name: String::new(),
+                digests: batch,
+            };
+            let response = self
+                .cas()
+                .batch_read_blobs(request)
+                .await
+                .map_err(|status| self.error("BatchReadBlobs", status))?;
+
+            for blob in response.into_inner().responses {
+                let digest = blob.digest.unwrap_or_default();
+                if let Some(status) = blob.status.filter(|status| status.code != Code::Ok as i32) {
+                    return Err(self.blob_error("read", &digest, &status.message));
+                }
+                if !digest_matches(&digest.hash, digest.size_bytes, &blob.data) {
+                    return Err(self.blob_error("read", &digest, "content does not match digest"));
+                }
+                blobs.insert(digest.hash, blob.data);
+            }
+        }
+
+        Ok(blobs)
+    }
+
+    async fn read_stream(&self, digest: &Digest) -> Result<Vec<u8>> {
+        let request = ReadRequest {
+            resource_name: format!("blobs/{}/{}", digest.hash, digest.size_bytes),
+            read_offset: 0,
+            read_limit: 0,
+        };
+        let mut stream = self
+            .byte_stream()
+            .read(request)
+            .await
+            .map_err(|status| self.error("ByteStream.Read", status))?
+            .into_inner();
+
+        let mut data = Vec::with_capacity(digest.size_bytes as usize);
+        while let Some(chunk) = stream
+            .message()
+            .await
+            .map_err(|status| self.error("ByteStream.Read", status))?
+        {
+            data.extend_from_slice(&chunk.data);
+        }
+
+        if !digest_matches(&digest.hash, digest.size_bytes, &data) {
+            return Err(self.blob_error("read", digest, "content does not match digest"));
+        }
+        Ok(data)
+    }
+
+    async fn write_blobs(&self, blobs: Vec<(Digest, Vec<u8>)>) -> Result<()> {
+        let (large, small): (Vec<_>, Vec<_>) = blobs
+            .into_iter()
+            .partition(|(digest, _)| digest.size_bytes > MAX_BATCH_TOTAL_SIZE_BYTES);
+
+        for (digest, data) in large {
+            self.write_stream(&digest, data).await?;
+        }
+
+        for batch in batches(small, |(digest, _)| digest.size_bytes) {
+            let request = BatchUpdateBlobsRequest {
+                instance_name: String::new(),
+                requests: batch
+                    .into_iter()
+                    .map(|(digest, data)| batch_update_blobs_request::Request {
+                        digest: Some(digest),
+                        data,
+                    })
+                    .collect(),
+            };
+            let response = self
+                .cas()
+                .batch_update_blobs(request)
+                .await
+                .map_err(|status| self.error("BatchUpdateBlobs", status))?;
+
+            for blob in response.into_inner().responses {
+                if let Some(status) = blob.status.filter(|status| status.code != Code::Ok as i32) {
+                    let digest = blob.digest.unwrap_or_default();
+                    return Err(self.blob_error("upload", &digest, &status.message));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn write_stream(&self, digest: &Digest, data: Vec<u8>) -> Result<()> {
+        let resource_name = format!(
+            "uploads/{}/blobs/{}/{}",
+            uuid::Uuid::new_v4(),
+            digest.hash,
+            digest.size_bytes
+        );
+
+        let chunk_count = data.len().div_ceil(WRITE_CHUNK_SIZE).max(1);
+        let requests: Vec<WriteRequest> = (0..chunk_count)
+            .map(|i| {
+                let start = i * WRITE_CHUNK_SIZE;
+                let end = data.len().min(start + WRITE_CHUNK_SIZE);
+                WriteRequest {
+                    // Only the first request has to name the resource
+                    resource_name: if i == 0 {
+                        resource_name.clone()
+                    } else {
+                        String::new()
+                    },
+                    write_offset: start as i64,
+                    finish_write: i + 1 == chunk_count,
+                    data: data[start..end].to_vec(),
+                }
+            })
+            .collect();
+
+        let response = self
+            .byte_stream()
+            .write(futures::stream::iter(requests))
+            .await
+            .map_err(|status| self.error("ByteStream.Write", status))?;
+
+        if response.into_inner().committed_size != digest.size_bytes {
+            return Err(self.blob_error("upload", digest, "upload was not committed"));
+        }
+        Ok(())
+    }
+
+    fn action_cache(&self) -> ActionCacheClient<Channel> {
+        ActionCacheClient::new(self.channel.clone())
+            .max_decoding_message_size(MAX_MESSAGE_SIZE)
+            .max_encoding_message_size(MAX_MESSAGE_SIZE)
+    }
+
+    fn cas(&self) -> ContentAddressableStorageClient<Channel> {
+        ContentAddressableStorageClient::new(self.channel.clone())
+            .max_decoding_message_size(MAX_MESSAGE_SIZE)
+            .max_encoding_message_size(MAX_MESSAGE_SIZE)
+    }
+
+    fn byte_stream(&self) -> ByteStreamClient<Channel> {
+        ByteStreamClient::new(self.channel.clone()).max_decoding_message_size(MAX_MESSAGE_SIZE)
+    }
+
+    fn error(&self, operation: &str, status: Status) -> Error {
+        Error::network(
+            &self.endpoint,
+            format!("{operation} failed: {}", status.message()),
+        )
+    }
+
+    fn blob_error(&self, operation: &str, digest: &Digest, message: &str) -> Error {
+        Error::network(
+            &self.endpoint,
+            format!(
+                "Failed to {operation} blob {}/{}: {message}",
+                digest.hash, digest.size_bytes
+            ),
+        )
+    }
+}
+
+/// Action digest a task result is stored under
+fn action_digest(cache_key: &str) -> Digest {
+    digest_of(cache_key.as_bytes())
+}
+
+/// Digests of the blobs of a result that were not inlined into it
+fn blob_digests(result: &ActionResult) -> Vec<Digest> {
+    let mut digests: Vec<Digest> = result
+        .output_files
+        .iter()
+        .filter(|file| file.contents.is_empty())
+        .filter_map(|file| file.digest.clone())
+        .collect();
+    if result.stdout_raw.is_empty() {
+        digests.extend(result.stdout_digest.clone());
+    }
+    if result.stderr_raw.is_empty() {
+        digests.extend(result.stderr_digest.clone());
+    }
+    digests
+}
+
+fn dedup(digests: Vec<Digest>) -> Vec<Digest> {
+    let mut seen = std::collections::HashSet::new();
+    digests
+        .into_iter()
+        .filter(|digest| seen.insert(digest.hash.clone()))
+        .collect()
+}
+
+/// Group items into batches that stay within the batch size limit
+fn batches<T>(items: Vec<T>, size: impl Fn(&T) -> i64) -> Vec<Vec<T>> {
+    let mut batches: Vec<Vec<T>> = Vec::new();
+    let mut batch_size = 0;
+
+    for item in items {
+        let item_size = size(&item);
+        match batches.last_mut() {
+            Some(batch) if batch_size + item_size <= MAX_BATCH_TOTAL_SIZE_BYTES => {
+                batch_size += item_size;
+                batch.push(item);
+            }
+            _ => {
+                batch_size = item_size;
+                batches.push(vec![item]);
+            }
+        }
+    }
+
+    batches
+}
+
+/// Resource usage stored in the metadata of an action result by [`RemoteCacheClient::upload`]
+fn resource_usage(metadata: &ExecutedActionMetadata) -> Option<ResourceUsage> {
+    metadata
+        .auxiliary_metadata
+        .iter()
+        .find(|any| any.type_url == RESOURCE_USAGE_TYPE_URL)
+        .and_then(|any| ResourceUsageMetadata::decode(any.value.as_slice()).ok())
+        .map(ResourceUsage::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connect_requires_grpc_url() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+
+        assert!(RemoteCacheClient::connect("grpc://127.0.0.1:50051", CacheMode::Read).is_ok());
+        assert!(RemoteCacheClient::connect("https://cache.example.com", CacheMode::Read).is_err());
+        assert!(RemoteCacheClient::connect("127.0.0.1:50051", CacheMode::Read).is_err());
+    }
+
+    #[test]
+    fn test_batches_respect_size_limit() {
+        let half = MAX_BATCH_TOTAL_SIZE_BYTES / 2;
+        let grouped = batches(vec![half, half, 1, half], |size| *size);
+
+        assert_eq!(grouped, vec![vec![half, half], vec![1, half]]);
+        assert!(batches(Vec::<i64>::new(), |size| *size).is_empty());
+    }
+}
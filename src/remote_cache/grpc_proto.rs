@@ -7,3 +7,7 @@ pub mod proto {
     pub const FILE_DESCRIPTOR_SET: &[u8] =
         tonic::include_file_descriptor_set!("remote_execution_descriptor");
 }
+
+pub mod bytestream {
+    tonic::include_proto!("google.bytestream");
+}
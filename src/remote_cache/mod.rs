@@ -3,8 +3,11 @@
 //! This module implements a remote cache server that exposes cuenv's
 //! cache infrastructure via the Bazel/Buck2 Remote Execution API protocol.
 
+mod blob_store;
+pub mod client;
 pub mod grpc_proto;
 pub mod simple_server;
 
 // Re-export main types
+pub use client::RemoteCacheClient;
 pub use simple_server::{RemoteCacheConfig, RemoteCacheServer};
@@ -1,21 +1,57 @@
 //! Simplified remote cache server implementation
+
+// tonic::Status is large, but it is what every service method returns
+#![allow(clippy::result_large_err)]
+
 use anyhow::Result;
+use futures::Stream;
+use prost::Message;
+use sha2::{Digest as _, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
 use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::pin::Pin;
 use std::sync::Arc;
-use tonic::{transport::Server, Request, Response, Status};
+use std::time::SystemTime;
+use tokio::io::AsyncWriteExt;
+use tonic::{transport::Server, Request, Response, Status, Streaming};
 use tracing::{debug, info};
 
+use super::blob_store::{digest_matches, digest_of, BlobStore};
+use super::grpc_proto::bytestream::{
+    byte_stream_server::{ByteStream as ByteStreamService, ByteStreamServer},
+    QueryWriteStatusRequest, QueryWriteStatusResponse, ReadRequest, ReadResponse, WriteRequest,
+    WriteResponse,
+};
 use super::grpc_proto::proto::{
     action_cache_server::{ActionCache as ActionCacheService, ActionCacheServer},
     capabilities_server::{Capabilities as CapabilitiesService, CapabilitiesServer},
     content_addressable_storage_server::{
         ContentAddressableStorage as CASService, ContentAddressableStorageServer,
     },
-    ActionResult, BatchReadBlobsRequest, BatchReadBlobsResponse, BatchUpdateBlobsRequest,
-    BatchUpdateBlobsResponse, FindMissingBlobsRequest, FindMissingBlobsResponse,
-    GetActionResultRequest, GetCapabilitiesRequest, ServerCapabilities, UpdateActionResultRequest,
+    digest_function, symlink_absolute_path_strategy, ActionCacheUpdateCapabilities, ActionResult,
+    BatchReadBlobsRequest, BatchReadBlobsResponse, BatchUpdateBlobsRequest,
+    BatchUpdateBlobsResponse, CacheCapabilities, Digest, ExecutedActionMetadata,
+    FindMissingBlobsRequest, FindMissingBlobsResponse, GetActionResultRequest,
+    GetCapabilitiesRequest, OutputDirectory, OutputFile, SemVer, ServerCapabilities, Tree,
+    UpdateActionResultRequest,
 };
-use crate::cache::{CacheConfig, ContentAddressedStore};
+use crate::cache::{self, CacheConfig, ContentAddressedStore, OutputFileMetadata};
+
+/// Largest total blob size accepted or returned by a single batch request
+pub const MAX_BATCH_TOTAL_SIZE_BYTES: i64 = 4 * 1024 * 1024;
+
+/// gRPC message limit, leaving room for framing around a full batch
+pub(super) const MAX_MESSAGE_SIZE: usize = MAX_BATCH_TOTAL_SIZE_BYTES as usize + 1024 * 1024;
+
+/// Size of the chunks returned by ByteStream reads
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Most blob bytes inlined into a single action result, so that it stays
+/// below the message limit. Outputs beyond it are fetched by digest.
+const MAX_INLINE_TOTAL_SIZE_BYTES: i64 = MAX_BATCH_TOTAL_SIZE_BYTES;
 
 /// Configuration for the remote cache server
 pub struct RemoteCacheConfig {
@@ -28,15 +64,19 @@ pub struct RemoteCacheConfig {
 /// Remote cache server
 pub struct RemoteCacheServer {
     address: SocketAddr,
-    cas: Arc<ContentAddressedStore>,
+    enable_action_cache: bool,
+    enable_cas: bool,
+    blobs: Arc<BlobStore>,
+    action_cache: Arc<cache::ActionCache>,
 }
 
 impl RemoteCacheServer {
     /// Create a new remote cache server
     pub async fn new(config: RemoteCacheConfig) -> Result<Self> {
         // Create cache directories
-        std::fs::create_dir_all(&config.cache_config.base_dir)?;
-        let cas_dir = config.cache_config.base_dir.join("cas");
+        let base_dir = &config.cache_config.base_dir;
+        std::fs::create_dir_all(base_dir)?;
+        let cas_dir = base_dir.join("cas");
         std::fs::create_dir_all(&cas_dir)?;
 
         // Initialize content-addressed store
@@ -45,25 +85,54 @@ impl RemoteCacheServer {
             config.cache_config.inline_threshold,
         )?);
 
+        let blobs = Arc::new(BlobStore::new(Arc::clone(&cas), base_dir)?);
+        let action_cache = Arc::new(cache::ActionCache::new(
+            cas,
+            config.cache_config.max_size,
+            base_dir,
+        )?);
+
         Ok(Self {
             address: config.address,
-            cas,
+            enable_action_cache: config.enable_action_cache,
+            enable_cas: config.enable_cas,
+            blobs,
+            action_cache,
         })
     }
 
     /// Start serving the remote cache
     pub async fn serve(self) -> Result<()> {
-        let cas_service = SimpleCASService {
-            cas: Arc::clone(&self.cas),
+        let cas_service = self.enable_cas.then(|| {
+            ContentAddressableStorageServer::new(SimpleCASService {
+                blobs: Arc::clone(&self.blobs),
+            })
+            .max_decoding_message_size(MAX_MESSAGE_SIZE)
+            .max_encoding_message_size(MAX_MESSAGE_SIZE)
+        });
+        let byte_stream_service = self.enable_cas.then(|| {
+            ByteStreamServer::new(SimpleByteStreamService {
+                blobs: Arc::clone(&self.blobs),
+            })
+            .max_decoding_message_size(MAX_MESSAGE_SIZE)
+        });
+        let action_cache_service = ActionCacheServer::new(SimpleActionCacheService {
+            enabled: self.enable_action_cache,
+            blobs: Arc::clone(&self.blobs),
+            action_cache: Arc::clone(&self.action_cache),
+        })
+        .max_decoding_message_size(MAX_MESSAGE_SIZE)
+        .max_encoding_message_size(MAX_MESSAGE_SIZE);
+        let capabilities_service = SimpleCapabilitiesService {
+            update_enabled: self.enable_action_cache,
         };
-        let action_cache_service = SimpleActionCacheService;
-        let capabilities_service = SimpleCapabilitiesService;
 
         info!("Starting remote cache server on {}", self.address);
 
         Server::builder()
-            .add_service(ContentAddressableStorageServer::new(cas_service))
-            .add_service(ActionCacheServer::new(action_cache_service))
+            .add_optional_service(cas_service)
+            .add_optional_service(byte_stream_service)
+            .add_service(action_cache_service)
             .add_service(CapabilitiesServer::new(capabilities_service))
             .serve(self.address)
             .await?;
@@ -72,9 +141,64 @@ impl RemoteCacheServer {
     }
 }
 
+/// Run blocking store I/O on the blocking thread pool, off the async runtime
+async fn blocking<T, F>(task: F) -> Result<T, Status>
+where
+    F: FnOnce() -> Result<T, Status> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(task)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+}
+
+/// Reject malformed digests before they reach the stores
+fn validate_digest(digest: &Digest) -> Result<(), Status> {
+    let is_sha256 = digest.hash.len() == 64
+        && digest
+            .hash
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c));
+
+    if !is_sha256 || digest.size_bytes < 0 {
+        return Err(Status::invalid_argument(format!(
+            "Invalid SHA256 digest {}/{}",
+            digest.hash, digest.size_bytes
+        )));
+    }
+    Ok(())
+}
+
+/// Digests are stored in action results as `{hash}/{size}`
+fn digest_key(digest: &Digest) -> String {
+    format!("{}/{}", digest.hash, digest.size_bytes)
+}
+
+fn parse_digest_key(key: &str) -> Option<Digest> {
+    let (hash, size) = key.split_once('/')?;
+    Some(Digest {
+        hash: hash.to_string(),
+        size_bytes: size.parse().ok()?,
+    })
+}
+
+fn ok_status() -> Option<super::grpc_proto::proto::Status> {
+    Some(super::grpc_proto::proto::Status {
+        code: tonic::Code::Ok as i32,
+        message: String::new(),
+    })
+}
+
+fn error_status(code: tonic::Code, message: String) -> Option<super::grpc_proto::proto::Status> {
+    Some(super::grpc_proto::proto::Status {
+        code: code as i32,
+        message,
+    })
+}
+
 /// Simplified CAS service
 struct SimpleCASService {
-    cas: Arc<ContentAddressedStore>,
+    blobs: Arc<BlobStore>,
 }
 
 #[tonic::async_trait]
@@ -91,9 +215,8 @@ impl CASService for SimpleCASService {
 
         let mut missing_digests = Vec::new();
         for digest in req.blob_digests {
-            let hash = hex::encode(&digest.hash);
-            // Check if blob exists by trying to retrieve it
-            if self.cas.retrieve(&hash).is_err() {
+            validate_digest(&digest)?;
+            if !self.blobs.contains(&digest.hash, digest.size_bytes) {
                 missing_digests.push(digest);
             }
         }
@@ -110,37 +233,45 @@ impl CASService for SimpleCASService {
         let req = request.into_inner();
         debug!("BatchUpdateBlobs request for {} blobs", req.requests.len());
 
-        let mut responses = Vec::new();
-        for update_req in req.requests {
-            let digest = update_req
-                .digest
-                .ok_or_else(|| Status::invalid_argument("Missing digest in update request"))?;
-
-            use std::io::Cursor;
-            let cursor = Cursor::new(&update_req.data);
-            match self.cas.store(cursor) {
-                Ok(_stored_hash) => {
-                    responses.push(
-                        super::grpc_proto::proto::batch_update_blobs_response::Response {
-                            digest: Some(digest),
-                            status: Some(Default::default()), // OK status
-                        },
-                    );
-                }
-                Err(e) => {
-                    responses.push(
-                        super::grpc_proto::proto::batch_update_blobs_response::Response {
-                            digest: Some(digest),
-                            status: Some(super::grpc_proto::proto::Status {
-                                code: tonic::Code::Internal as i32,
-                                message: e.to_string(),
-                            }),
-                        },
-                    );
-                }
-            }
+        let total_size: i64 = req.requests.iter().map(|r| r.data.len() as i64).sum();
+        if total_size > MAX_BATCH_TOTAL_SIZE_BYTES {
+            return Err(Status::invalid_argument(format!(
+                "Batch of {total_size} bytes exceeds the limit of {MAX_BATCH_TOTAL_SIZE_BYTES} bytes"
+            )));
         }
 
+        let blobs = Arc::clone(&self.blobs);
+        let responses = blocking(move || {
+            let mut responses = Vec::new();
+            for update_req in req.requests {
+                let digest = update_req
+                    .digest
+                    .ok_or_else(|| Status::invalid_argument("Missing digest in update request"))?;
+                validate_digest(&digest)?;
+
+                let status = if !digest_matches(&digest.hash, digest.size_bytes, &update_req.data) {
+                    error_status(
+                        tonic::Code::InvalidArgument,
+                        format!("Data does not match digest {}", digest_key(&digest)),
+                    )
+                } else {
+                    match blobs.write(&digest.hash, &update_req.data) {
+                        Ok(()) => ok_status(),
+                        Err(e) => error_status(tonic::Code::Internal, e.to_string()),
+                    }
+                };
+
+                responses.push(
+                    super::grpc_proto::proto::batch_update_blobs_response::Response {
+                        digest: Some(digest),
+                        status,
+                    },
+                );
+            }
+            Ok(responses)
+        })
+        .await?;
+
         Ok(Response::new(BatchUpdateBlobsResponse { responses }))
     }
 
@@ -151,60 +282,608 @@ impl CASService for SimpleCASService {
         let req = request.into_inner();
         debug!("BatchReadBlobs request for {} blobs", req.digests.len());
 
-        let mut responses = Vec::new();
-        for digest in req.digests {
-            let hash = hex::encode(&digest.hash);
-            match self.cas.retrieve(&hash) {
-                Ok(data) => {
-                    responses.push(
-                        super::grpc_proto::proto::batch_read_blobs_response::Response {
-                            digest: Some(digest),
-                            data,
-                            status: Some(Default::default()), // OK status
-                        },
-                    );
-                }
-                Err(_) => {
-                    responses.push(
-                        super::grpc_proto::proto::batch_read_blobs_response::Response {
-                            digest: Some(digest),
-                            data: Vec::new(),
-                            status: Some(super::grpc_proto::proto::Status {
-                                code: tonic::Code::NotFound as i32,
-                                message: format!("Blob {} not found", hash),
-                            }),
-                        },
-                    );
-                }
-            }
+        let total_size: i64 = req.digests.iter().map(|d| d.size_bytes).sum();
+        if total_size > MAX_BATCH_TOTAL_SIZE_BYTES {
+            return Err(Status::invalid_argument(format!(
+                "Batch of {total_size} bytes exceeds the limit of {MAX_BATCH_TOTAL_SIZE_BYTES} bytes"
+            )));
         }
 
+        let blobs = Arc::clone(&self.blobs);
+        let responses = blocking(move || {
+            let mut responses = Vec::new();
+            for digest in req.digests {
+                validate_digest(&digest)?;
+
+                let (data, status) = match blobs.read(&digest.hash) {
+                    Ok(data) if data.len() as i64 == digest.size_bytes => (data, ok_status()),
+                    _ => (
+                        Vec::new(),
+                        error_status(
+                            tonic::Code::NotFound,
+                            format!("Blob {} not found", digest_key(&digest)),
+                        ),
+                    ),
+                };
+
+                responses.push(
+                    super::grpc_proto::proto::batch_read_blobs_response::Response {
+                        digest: Some(digest),
+                        data,
+                        status,
+                    },
+                );
+            }
+            Ok(responses)
+        })
+        .await?;
+
         Ok(Response::new(BatchReadBlobsResponse { responses }))
     }
 }
 
-/// Simplified action cache service (no-op for now)
-struct SimpleActionCacheService;
+/// Action cache service backed by the local action cache
+#[derive(Clone)]
+struct SimpleActionCacheService {
+    enabled: bool,
+    blobs: Arc<BlobStore>,
+    action_cache: Arc<cache::ActionCache>,
+}
+
+impl SimpleActionCacheService {
+    /// Store an inlined blob and return its digest
+    fn store_inline(&self, data: &[u8]) -> Result<Digest, Status> {
+        let digest = digest_of(data);
+        if !self.blobs.contains(&digest.hash, digest.size_bytes) {
+            self.blobs
+                .write(&digest.hash, data)
+                .map_err(|e| Status::internal(e.to_string()))?;
+        }
+        Ok(digest)
+    }
+
+    /// Convert a client action result into the local representation
+    fn to_local(&self, result: ActionResult) -> Result<cache::ActionResult, Status> {
+        if !result.output_symlinks.is_empty()
+            || !result.output_file_symlinks.is_empty()
+            || !result.output_directory_symlinks.is_empty()
+        {
+            return Err(Status::invalid_argument(
+                "Symlink outputs are not supported by this cache",
+            ));
+        }
+
+        let executed_at = SystemTime::now();
+        let mut output_files = HashMap::new();
+        let mut output_metadata = HashMap::new();
+        for file in result.output_files {
+            let digest = match file.digest {
+                Some(digest) if file.contents.is_empty() => digest,
+                _ => self.store_inline(&file.contents)?,
+            };
+            validate_digest(&digest)?;
+
+            output_metadata.insert(
+                file.path.clone(),
+                OutputFileMetadata {
+                    mode: if file.is_executable { 0o755 } else { 0o644 },
+                    modified: executed_at,
+                },
+            );
+            output_files.insert(file.path, digest_key(&digest));
+        }
+
+        let mut output_directories = HashMap::new();
+        for directory in result.output_directories {
+            let tree_digest = directory
+                .tree_digest
+                .ok_or_else(|| Status::invalid_argument("Output directory without tree digest"))?;
+            validate_digest(&tree_digest)?;
+            output_directories.insert(directory.path, digest_key(&tree_digest));
+        }
+
+        let stdout = match result.stdout_digest {
+            Some(digest) if result.stdout_raw.is_empty() => Some(digest),
+            _ if !result.stdout_raw.is_empty() => Some(self.store_inline(&result.stdout_raw)?),
+            _ => None,
+        };
+        let stderr = match result.stderr_digest {
+            Some(digest) if result.stderr_raw.is_empty() => Some(digest),
+            _ if !result.stderr_raw.is_empty() => Some(self.store_inline(&result.stderr_raw)?),
+            _ => None,
+        };
+
+        Ok(cache::ActionResult {
+            exit_code: result.exit_code,
+            stdout_hash: stdout.as_ref().map(digest_key),
+            stderr_hash: stderr.as_ref().map(digest_key),
+            output_files,
+            output_metadata,
+            output_directories,
+            executed_at,
+            duration_ms: 0,
+            execution_metadata: result
+                .execution_metadata
+                .map(|metadata| metadata.encode_to_vec())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Whether every file blob in the output directory tree is stored
+    fn tree_available(&self, tree_digest: &Digest) -> bool {
+        let Some(tree) = self
+            .blobs
+            .read(&tree_digest.hash)
+            .ok()
+            .and_then(|data| Tree::decode(data.as_slice()).ok())
+        else {
+            return false;
+        };
+
+        tree.root
+            .iter()
+            .chain(&tree.children)
+            .flat_map(|directory| &directory.files)
+            .all(|file| {
+                file.digest
+                    .as_ref()
+                    .is_some_and(|digest| self.blobs.contains(&digest.hash, digest.size_bytes))
+            })
+    }
+
+    /// Convert a local action result back, or `None` if any blob it
+    /// references is no longer available
+    fn to_remote(
+        &self,
+        result: &cache::ActionResult,
+        request: &GetActionResultRequest,
+    ) -> Option<ActionResult> {
+        let available = |key: &str| {
+            let digest = parse_digest_key(key)?;
+            self.blobs
+                .contains(&digest.hash, digest.size_bytes)
+                .then_some(digest)
+        };
+        // Inlining is best effort and bounded in total, the rest is fetched by digest
+        let mut inlined = 0;
+        let mut inline = |digest: &Digest, requested: bool| {
+            if requested && inlined + digest.size_bytes <= MAX_INLINE_TOTAL_SIZE_BYTES {
+                inlined += digest.size_bytes;
+                self.blobs.read(&digest.hash).unwrap_or_default()
+            } else {
+                Vec::new()
+            }
+        };
+
+        let mut output_files = Vec::with_capacity(result.output_files.len());
+        for (path, key) in &result.output_files {
+            let digest = available(key)?;
+            let is_executable = result
+                .output_metadata
+                .get(path)
+                .is_some_and(|metadata| metadata.mode & 0o111 != 0);
+            let contents = inline(&digest, request.inline_output_files.contains(path));
+            output_files.push(OutputFile {
+                path: path.clone(),
+                digest: Some(digest),
+                is_executable,
+                contents,
+            });
+        }
+        output_files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let mut output_directories = Vec::with_capacity(result.output_directories.len());
+        for (path, key) in &result.output_directories {
+            let tree_digest = available(key)?;
+            if !self.tree_available(&tree_digest) {
+                return None;
+            }
+            output_directories.push(OutputDirectory {
+                path: path.clone(),
+                tree_digest: Some(tree_digest),
+                is_topologically_sorted: false,
+            });
+        }
+        output_directories.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let stdout_digest = match &result.stdout_hash {
+            Some(key) => Some(available(key)?),
+            None => None,
+        };
+        let stderr_digest = match &result.stderr_hash {
+            Some(key) => Some(available(key)?),
+            None => None,
+        };
+
+        Some(ActionResult {
+            output_files,
+            output_directories,
+            exit_code: result.exit_code,
+            stdout_raw: stdout_digest
+                .as_ref()
+                .map(|digest| inline(digest, request.inline_stdout))
+                .unwrap_or_default(),
+            stdout_digest,
+            stderr_raw: stderr_digest
+                .as_ref()
+                .map(|digest| inline(digest, request.inline_stderr))
+                .unwrap_or_default(),
+            stderr_digest,
+            output_file_symlinks: Vec::new(),
+            output_directory_symlinks: Vec::new(),
+            output_symlinks: Vec::new(),
+            execution_metadata: ExecutedActionMetadata::decode(
+                result.execution_metadata.as_slice(),
+            )
+            .ok()
+            .filter(|metadata| !metadata.auxiliary_metadata.is_empty()),
+        })
+    }
+}
 
 #[tonic::async_trait]
 impl ActionCacheService for SimpleActionCacheService {
     async fn get_action_result(
         &self,
-        _request: Request<GetActionResultRequest>,
+        request: Request<GetActionResultRequest>,
     ) -> Result<Response<ActionResult>, Status> {
-        Err(Status::not_found("Action cache not implemented"))
+        let req = request.into_inner();
+        let action_digest = req
+            .action_digest
+            .clone()
+            .ok_or_else(|| Status::invalid_argument("Missing action digest"))?;
+        validate_digest(&action_digest)?;
+        debug!("GetActionResult request for {}", digest_key(&action_digest));
+
+        if !self.enabled {
+            return Err(Status::not_found("Action cache is disabled"));
+        }
+
+        let service = self.clone();
+        blocking(move || {
+            let result = service
+                .action_cache
+                .get_result(&action_digest.hash)
+                .ok_or_else(|| Status::not_found("Action result not found"))?;
+
+            // A result is only usable if every output it references can be downloaded
+            service.to_remote(&result, &req).ok_or_else(|| {
+                debug!(
+                    "Action result {} references missing blobs",
+                    digest_key(&action_digest)
+                );
+                Status::not_found("Action result references missing blobs")
+            })
+        })
+        .await
+        .map(Response::new)
     }
 
     async fn update_action_result(
         &self,
-        _request: Request<UpdateActionResultRequest>,
+        request: Request<UpdateActionResultRequest>,
     ) -> Result<Response<ActionResult>, Status> {
-        Err(Status::unimplemented("Action cache not implemented"))
+        let req = request.into_inner();
+        let action_digest = req
+            .action_digest
+            .ok_or_else(|| Status::invalid_argument("Missing action digest"))?;
+        validate_digest(&action_digest)?;
+        debug!(
+            "UpdateActionResult request for {}",
+            digest_key(&action_digest)
+        );
+
+        if !self.enabled {
+            return Err(Status::permission_denied(
+                "Action cache updates are disabled",
+            ));
+        }
+
+        let result = req
+            .action_result
+            .ok_or_else(|| Status::invalid_argument("Missing action result"))?;
+        let service = self.clone();
+        blocking(move || {
+            let local = service.to_local(result)?;
+
+            service
+                .action_cache
+                .store_result(&action_digest.hash, &local)
+                .map_err(|e| Status::internal(e.to_string()))?;
+
+            let stored = service
+                .to_remote(&local, &GetActionResultRequest::default())
+                .unwrap_or_default();
+            Ok(ActionResult {
+                exit_code: local.exit_code,
+                ..stored
+            })
+        })
+        .await
+        .map(Response::new)
+    }
+}
+
+/// Parse the digest out of a ByteStream resource name.
+///
+/// Reads use `[{instance}/]blobs/{hash}/{size}` and writes use
+/// `[{instance}/]uploads/{uuid}/blobs/{hash}/{size}[/{metadata}]`.
+fn parse_blob_resource(name: &str, upload: bool) -> Result<Digest, Status> {
+    let segments: Vec<&str> = name.split('/').collect();
+
+    if segments.contains(&"compressed-blobs") {
+        return Err(Status::unimplemented("Compressed blobs are not supported"));
+    }
+
+    let position = segments
+        .iter()
+        .position(|segment| *segment == "blobs")
+        .filter(|&i| !upload || (i >= 2 && segments[i - 2] == "uploads"))
+        .ok_or_else(|| Status::invalid_argument(format!("Invalid resource name '{name}'")))?;
+
+    let digest = match (segments.get(position + 1), segments.get(position + 2)) {
+        (Some(hash), Some(size)) => Digest {
+            hash: hash.to_string(),
+            size_bytes: size
+                .parse()
+                .map_err(|_| Status::invalid_argument(format!("Invalid blob size in '{name}'")))?,
+        },
+        _ => {
+            return Err(Status::invalid_argument(format!(
+                "Invalid resource name '{name}'"
+            )))
+        }
+    };
+
+    validate_digest(&digest)?;
+    Ok(digest)
+}
+
+/// Send the `range` of a blob in chunks. When the range reaches the end of
+/// the blob, the whole blob is checked against its digest.
+fn send_chunks(
+    mut reader: Box<dyn Read + Send>,
+    digest: &Digest,
+    range: std::ops::Range<u64>,
+    sender: tokio::sync::mpsc::Sender<Result<ReadResponse, Status>>,
+) {
+    let verify = range.end == digest.size_bytes as u64;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; READ_CHUNK_SIZE];
+    let mut position = 0u64;
+
+    while position < range.end || verify {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) => {
+                let _ = sender.blocking_send(Err(Status::internal(e.to_string())));
+                return;
+            }
+        };
+        let chunk = &buffer[..read];
+        hasher.update(chunk);
+
+        let from = range.start.saturating_sub(position).min(read as u64) as usize;
+        let to = range.end.saturating_sub(position).min(read as u64) as usize;
+        position += read as u64;
+        if from < to
+            && sender
+                .blocking_send(Ok(ReadResponse {
+                    data: chunk[from..to].to_vec(),
+                }))
+                .is_err()
+        {
+            return;
+        }
+    }
+
+    if verify && (position != range.end || format!("{:x}", hasher.finalize()) != digest.hash) {
+        let _ = sender.blocking_send(Err(Status::data_loss(format!(
+            "Blob {} is corrupted",
+            digest_key(digest)
+        ))));
+    }
+}
+
+/// A ByteStream upload, written to a temporary file as it arrives
+struct Upload {
+    digest: Digest,
+    path: PathBuf,
+    file: tokio::fs::File,
+    hasher: Sha256,
+    committed: i64,
+}
+
+impl Upload {
+    async fn create(digest: Digest, blobs: &Arc<BlobStore>) -> Result<Self, Status> {
+        let store = Arc::clone(blobs);
+        let path = blocking(move || {
+            store
+                .temp_path()
+                .map_err(|e| Status::internal(e.to_string()))
+        })
+        .await?;
+        let file = tokio::fs::File::create(&path)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Self {
+            digest,
+            path,
+            file,
+            hasher: Sha256::new(),
+            committed: 0,
+        })
+    }
+
+    async fn append(&mut self, data: &[u8]) -> Result<(), Status> {
+        self.committed += data.len() as i64;
+        if self.committed > self.digest.size_bytes {
+            return Err(Status::invalid_argument("Write exceeds the blob size"));
+        }
+        self.hasher.update(data);
+        self.file
+            .write_all(data)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))
+    }
+
+    /// Verify the upload against its digest and move it into `blobs`
+    async fn finish(&mut self, blobs: &Arc<BlobStore>) -> Result<(), Status> {
+        let hash = format!("{:x}", std::mem::take(&mut self.hasher).finalize());
+        if self.committed != self.digest.size_bytes || hash != self.digest.hash {
+            return Err(Status::invalid_argument(format!(
+                "Data does not match digest {}",
+                digest_key(&self.digest)
+            )));
+        }
+        self.file
+            .flush()
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        let blobs = Arc::clone(blobs);
+        let (hash, path) = (self.digest.hash.clone(), self.path.clone());
+        blocking(move || {
+            blobs
+                .write_file(&hash, &path)
+                .map_err(|e| Status::internal(e.to_string()))
+        })
+        .await
+    }
+}
+
+impl Drop for Upload {
+    fn drop(&mut self) {
+        // Gone already when the upload was moved into the store
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// ByteStream service for blobs that exceed the batch size limit
+struct SimpleByteStreamService {
+    blobs: Arc<BlobStore>,
+}
+
+type ReadStream = Pin<Box<dyn Stream<Item = Result<ReadResponse, Status>> + Send + 'static>>;
+
+#[tonic::async_trait]
+impl ByteStreamService for SimpleByteStreamService {
+    type ReadStream = ReadStream;
+
+    async fn read(
+        &self,
+        request: Request<ReadRequest>,
+    ) -> Result<Response<Self::ReadStream>, Status> {
+        let req = request.into_inner();
+        let digest = parse_blob_resource(&req.resource_name, false)?;
+        debug!("ByteStream read for {}", digest_key(&digest));
+
+        if req.read_offset < 0 || req.read_offset > digest.size_bytes {
+            return Err(Status::out_of_range("Read offset outside of the blob"));
+        }
+        if req.read_limit < 0 {
+            return Err(Status::invalid_argument("Negative read limit"));
+        }
+
+        let not_found = || Status::not_found(format!("Blob {} not found", digest_key(&digest)));
+        if !self.blobs.contains(&digest.hash, digest.size_bytes) {
+            return Err(not_found());
+        }
+        let (blobs, hash) = (Arc::clone(&self.blobs), digest.hash.clone());
+        let reader = blocking(move || {
+            blobs
+                .open(&hash)
+                .map_err(|e| Status::internal(e.to_string()))
+        })
+        .await
+        .map_err(|_| not_found())?;
+
+        let start = req.read_offset as u64;
+        let end = match req.read_limit {
+            0 => digest.size_bytes as u64,
+            limit => (digest.size_bytes as u64).min(start + limit as u64),
+        };
+        let (sender, receiver) = tokio::sync::mpsc::channel(4);
+        tokio::task::spawn_blocking(move || send_chunks(reader, &digest, start..end, sender));
+
+        let chunks = futures::stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|chunk| (chunk, receiver))
+        });
+        Ok(Response::new(Box::pin(chunks)))
+    }
+
+    async fn write(
+        &self,
+        request: Request<Streaming<WriteRequest>>,
+    ) -> Result<Response<WriteResponse>, Status> {
+        let mut stream = request.into_inner();
+        let mut upload: Option<Upload> = None;
+
+        while let Some(req) = stream.message().await? {
+            let upload = match &mut upload {
+                Some(upload) => upload,
+                None => {
+                    let parsed = parse_blob_resource(&req.resource_name, true)?;
+                    debug!("ByteStream write for {}", digest_key(&parsed));
+
+                    // Nothing to upload if the blob is already stored
+                    if self.blobs.contains(&parsed.hash, parsed.size_bytes) {
+                        return Ok(Response::new(WriteResponse {
+                            committed_size: parsed.size_bytes,
+                        }));
+                    }
+                    upload.insert(Upload::create(parsed, &self.blobs).await?)
+                }
+            };
+
+            if req.write_offset != upload.committed {
+                return Err(Status::invalid_argument(format!(
+                    "Write offset {} does not match committed size {}",
+                    req.write_offset, upload.committed
+                )));
+            }
+            upload.append(&req.data).await?;
+
+            if req.finish_write {
+                let committed = upload.committed;
+                upload.finish(&self.blobs).await?;
+                return Ok(Response::new(WriteResponse {
+                    committed_size: committed,
+                }));
+            }
+        }
+
+        Err(Status::invalid_argument(
+            "Write stream ended before finish_write",
+        ))
+    }
+
+    async fn query_write_status(
+        &self,
+        request: Request<QueryWriteStatusRequest>,
+    ) -> Result<Response<QueryWriteStatusResponse>, Status> {
+        let digest = parse_blob_resource(&request.into_inner().resource_name, true)?;
+
+        // Interrupted uploads are not kept, so only completed blobs are known
+        if self.blobs.contains(&digest.hash, digest.size_bytes) {
+            Ok(Response::new(QueryWriteStatusResponse {
+                committed_size: digest.size_bytes,
+                complete: true,
+            }))
+        } else {
+            Err(Status::not_found(format!(
+                "No upload for {}",
+                digest_key(&digest)
+            )))
+        }
     }
 }
 
 /// Capabilities service
-struct SimpleCapabilitiesService;
+struct SimpleCapabilitiesService {
+    update_enabled: bool,
+}
 
 #[tonic::async_trait]
 impl CapabilitiesService for SimpleCapabilitiesService {
@@ -212,12 +891,327 @@ impl CapabilitiesService for SimpleCapabilitiesService {
         &self,
         _request: Request<GetCapabilitiesRequest>,
     ) -> Result<Response<ServerCapabilities>, Status> {
+        let version = |minor| SemVer {
+            major: 2,
+            minor,
+            patch: 0,
+            prerelease: String::new(),
+        };
+
         Ok(Response::new(ServerCapabilities {
-            cache_capabilities: Some(Default::default()),
+            cache_capabilities: Some(CacheCapabilities {
+                digest_functions: vec![digest_function::Value::Sha256 as i32],
+                action_cache_update_capabilities: Some(ActionCacheUpdateCapabilities {
+                    update_enabled: self.update_enabled,
+                }),
+                max_batch_total_size_bytes: MAX_BATCH_TOTAL_SIZE_BYTES,
+                symlink_absolute_path_strategy: symlink_absolute_path_strategy::Value::Disallowed
+                    as i32,
+            }),
             execution_capabilities: None,
             deprecated_api_version: None,
-            low_api_version: Some(Default::default()),
-            high_api_version: Some(Default::default()),
+            low_api_version: Some(version(0)),
+            high_api_version: Some(version(3)),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn services(temp_dir: &TempDir) -> (SimpleCASService, SimpleActionCacheService) {
+        let cas = Arc::new(ContentAddressedStore::new(temp_dir.path().join("cas"), 16).unwrap());
+        let blobs = Arc::new(BlobStore::new(Arc::clone(&cas), temp_dir.path()).unwrap());
+        let action_cache = Arc::new(cache::ActionCache::new(cas, 0, temp_dir.path()).unwrap());
+
+        (
+            SimpleCASService {
+                blobs: Arc::clone(&blobs),
+            },
+            SimpleActionCacheService {
+                enabled: true,
+                blobs,
+                action_cache,
+            },
+        )
+    }
+
+    fn action_request(action: &Digest) -> GetActionResultRequest {
+        GetActionResultRequest {
+            action_digest: Some(action.clone()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_blob_resource() {
+        let hash = "a".repeat(64);
+
+        let digest = parse_blob_resource(&format!("main/blobs/{hash}/42"), false).unwrap();
+        assert_eq!(
+            (digest.hash.as_str(), digest.size_bytes),
+            (hash.as_str(), 42)
+        );
+
+        let upload = format!("uploads/6f1e-uuid/blobs/{hash}/7/extra");
+        assert_eq!(parse_blob_resource(&upload, true).unwrap().size_bytes, 7);
+
+        assert!(parse_blob_resource(&format!("blobs/{hash}/7"), true).is_err());
+        assert!(parse_blob_resource(&format!("blobs/{hash}"), false).is_err());
+        assert!(parse_blob_resource("blobs/not-a-digest/1", false).is_err());
+        assert_eq!(
+            parse_blob_resource(&format!("compressed-blobs/zstd/{hash}/1"), false)
+                .unwrap_err()
+                .code(),
+            tonic::Code::Unimplemented
+        );
+    }
+
+    #[tokio::test]
+    async fn test_action_result_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let (cas, action_cache) = services(&temp_dir);
+        let output = b"#!/bin/sh\necho built\n".to_vec();
+        let output_digest = digest_of(&output);
+        let action = digest_of(b"action");
+
+        cas.batch_update_blobs(Request::new(BatchUpdateBlobsRequest {
+            instance_name: String::new(),
+            requests: vec![
+                super::super::grpc_proto::proto::batch_update_blobs_request::Request {
+                    digest: Some(output_digest.clone()),
+                    data: output.clone(),
+                },
+            ],
         }))
+        .await
+        .unwrap();
+
+        let miss = action_cache
+            .get_action_result(Request::new(action_request(&action)))
+            .await
+            .unwrap_err();
+        assert_eq!(miss.code(), tonic::Code::NotFound);
+
+        action_cache
+            .update_action_result(Request::new(UpdateActionResultRequest {
+                instance_name: String::new(),
+                action_digest: Some(action.clone()),
+                action_result: Some(ActionResult {
+                    output_files: vec![OutputFile {
+                        path: "bin/tool".to_string(),
+                        digest: Some(output_digest.clone()),
+                        is_executable: true,
+                        contents: Vec::new(),
+                    }],
+                    stdout_raw: b"done\n".to_vec(),
+                    ..Default::default()
+                }),
+            }))
+            .await
+            .unwrap();
+
+        let mut request = action_request(&action);
+        request.inline_stdout = true;
+        let hit = action_cache
+            .get_action_result(Request::new(request))
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(hit.output_files.len(), 1);
+        assert_eq!(hit.output_files[0].digest, Some(output_digest));
+        assert!(hit.output_files[0].is_executable);
+        assert_eq!(hit.stdout_raw, b"done\n");
+        assert_eq!(hit.stdout_digest, Some(digest_of(b"done\n")));
+    }
+
+    #[tokio::test]
+    async fn test_inlined_outputs_are_capped() {
+        let temp_dir = TempDir::new().unwrap();
+        let (_, action_cache) = services(&temp_dir);
+        let action = digest_of(b"action");
+        let output = |byte: u8| OutputFile {
+            path: format!("out/{byte}"),
+            digest: None,
+            is_executable: false,
+            contents: vec![byte; 3 << 20],
+        };
+
+        action_cache
+            .update_action_result(Request::new(UpdateActionResultRequest {
+                instance_name: String::new(),
+                action_digest: Some(action.clone()),
+                action_result: Some(ActionResult {
+                    output_files: vec![output(1), output(2)],
+                    ..Default::default()
+                }),
+            }))
+            .await
+            .unwrap();
+
+        let mut request = action_request(&action);
+        request.inline_output_files = vec!["out/1".to_string(), "out/2".to_string()];
+        let hit = action_cache
+            .get_action_result(Request::new(request))
+            .await
+            .unwrap()
+            .into_inner();
+
+        let inlined: Vec<_> = hit
+            .output_files
+            .iter()
+            .filter(|file| !file.contents.is_empty())
+            .collect();
+        assert_eq!(inlined.len(), 1);
+        assert!(inlined[0].contents.len() as i64 <= MAX_INLINE_TOTAL_SIZE_BYTES);
+        assert!(hit.output_files.iter().all(|file| file.digest.is_some()));
+    }
+
+    #[tokio::test]
+    async fn test_action_result_with_missing_blob_is_a_miss() {
+        let temp_dir = TempDir::new().unwrap();
+        let (_cas, action_cache) = services(&temp_dir);
+        let action = digest_of(b"action");
+
+        action_cache
+            .update_action_result(Request::new(UpdateActionResultRequest {
+                instance_name: String::new(),
+                action_digest: Some(action.clone()),
+                action_result: Some(ActionResult {
+                    output_files: vec![OutputFile {
+                        path: "out.o".to_string(),
+                        digest: Some(digest_of(b"never uploaded")),
+                        is_executable: false,
+                        contents: Vec::new(),
+                    }],
+                    ..Default::default()
+                }),
+            }))
+            .await
+            .unwrap();
+
+        let status = action_cache
+            .get_action_result(Request::new(action_request(&action)))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_batch_update_rejects_mismatched_data() {
+        let temp_dir = TempDir::new().unwrap();
+        let (cas, _action_cache) = services(&temp_dir);
+        let digest = digest_of(b"expected");
+
+        let response = cas
+            .batch_update_blobs(Request::new(BatchUpdateBlobsRequest {
+                instance_name: String::new(),
+                requests: vec![
+                    super::super::grpc_proto::proto::batch_update_blobs_request::Request {
+                        digest: Some(digest.clone()),
+                        data: b"tampered".to_vec(),
+                    },
+                ],
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        let status = response.responses[0].status.clone().unwrap();
+        assert_eq!(status.code, tonic::Code::InvalidArgument as i32);
+        assert!(!cas.blobs.contains(&digest.hash, digest.size_bytes));
+    }
+
+    #[tokio::test]
+    async fn test_output_directory_with_missing_file_is_a_miss() {
+        let temp_dir = TempDir::new().unwrap();
+        let (cas, action_cache) = services(&temp_dir);
+        let action = digest_of(b"action");
+        let tree = Tree {
+            root: Some(super::super::grpc_proto::proto::Directory {
+                files: vec![super::super::grpc_proto::proto::FileNode {
+                    name: "lib.a".to_string(),
+                    digest: Some(digest_of(b"never uploaded")),
+                    is_executable: false,
+                }],
+                directories: Vec::new(),
+            }),
+            children: Vec::new(),
+        }
+        .encode_to_vec();
+        let tree_digest = digest_of(&tree);
+        cas.blobs.write(&tree_digest.hash, &tree).unwrap();
+
+        action_cache
+            .update_action_result(Request::new(UpdateActionResultRequest {
+                instance_name: String::new(),
+                action_digest: Some(action.clone()),
+                action_result: Some(ActionResult {
+                    output_directories: vec![OutputDirectory {
+                        path: "lib".to_string(),
+                        tree_digest: Some(tree_digest),
+                        is_topologically_sorted: false,
+                    }],
+                    ..Default::default()
+                }),
+            }))
+            .await
+            .unwrap();
+
+        let status = action_cache
+            .get_action_result(Request::new(action_request(&action)))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+
+        cas.blobs
+            .write(&digest_of(b"never uploaded").hash, b"never uploaded")
+            .unwrap();
+        let hit = action_cache
+            .get_action_result(Request::new(action_request(&action)))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(hit.output_directories.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_byte_stream_read_in_chunks() {
+        use futures::StreamExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let (cas, _action_cache) = services(&temp_dir);
+        let data: Vec<u8> = (0..3 * READ_CHUNK_SIZE).map(|i| i as u8).collect();
+        let digest = digest_of(&data);
+        cas.blobs.write(&digest.hash, &data).unwrap();
+        let byte_stream = SimpleByteStreamService { blobs: cas.blobs };
+
+        let read = |offset: usize, limit: usize| {
+            let request = ReadRequest {
+                resource_name: format!("blobs/{}", digest_key(&digest)),
+                read_offset: offset as i64,
+                read_limit: limit as i64,
+            };
+            let byte_stream = &byte_stream;
+            async move {
+                let chunks: Vec<Vec<u8>> = byte_stream
+                    .read(Request::new(request))
+                    .await
+                    .unwrap()
+                    .into_inner()
+                    .map(|chunk| chunk.unwrap().data)
+                    .collect()
+                    .await;
+                assert!(chunks.iter().all(|chunk| chunk.len() <= READ_CHUNK_SIZE));
+                chunks.concat()
+            }
+        };
+
+        assert_eq!(read(0, 0).await, data);
+        assert_eq!(read(100, 0).await, &data[100..]);
+        assert_eq!(read(100, 150_000).await, &data[100..150_100]);
     }
 }
@@ -100,6 +100,25 @@ impl RetryConfig {
         }
     }
 
+    /// Create a retry config for task runs, retrying failed and timed out ones
+    pub fn for_task(max_retries: usize, base_delay: Duration) -> Self {
+        Self {
+            max_retries,
+            base_delay,
+            max_delay: DEFAULT_MAX_DELAY.max(base_delay),
+            jitter_factor: 0.0,
+            retry_on: RetryOn::Custom(Arc::new(|error| {
+                matches!(
+                    error,
+                    Error::CommandExecution {
+                        exit_code: Some(_),
+                        ..
+                    } | Error::Timeout { .. }
+                )
+            })),
+        }
+    }
+
     /// Check if an error should be retried
     fn should_retry(&self, error: &Error) -> bool {
         match &self.retry_on {
@@ -1,4 +1,8 @@
+use crate::cue_parser::{MemorySize, ResourcesConfig};
 use crate::errors::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
 
 /// Configuration for resource limits
 #[derive(Debug, Clone)]
@@ -50,6 +54,147 @@ impl ResourceLimits {
     }
 }
 
+/// Limits of a task, from the `resources` block of its definition
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TaskResources {
+    /// Memory limit in bytes
+    pub memory: Option<u64>,
+    /// Number of CPUs the task may use
+    pub cpus: Option<f64>,
+    /// Maximum number of processes and threads
+    pub pids: Option<u64>,
+    /// IO weight, from 1 to 10000
+    pub io_weight: Option<u16>,
+}
+
+impl TaskResources {
+    /// Validate the `resources` block of a task
+    pub fn from_config(config: &ResourcesConfig) -> Result<Self> {
+        let memory = match &config.memory {
+            Some(MemorySize::Bytes(bytes)) => Some(*bytes),
+            Some(MemorySize::Text(size)) => Some(parse_memory_size(size)?),
+            None => None,
+        };
+        if memory == Some(0) || config.pids == Some(0) {
+            return Err(Error::configuration(
+                "resources.memory and resources.pids must be greater than 0",
+            ));
+        }
+        if config
+            .cpus
+            .is_some_and(|cpus| !cpus.is_finite() || cpus <= 0.0)
+        {
+            return Err(Error::configuration(
+                "resources.cpus must be a number greater than 0",
+            ));
+        }
+        if config
+            .io_weight
+            .is_some_and(|weight| !(1..=10000).contains(&weight))
+        {
+            return Err(Error::configuration(
+                "resources.ioWeight must be between 1 and 10000",
+            ));
+        }
+
+        Ok(Self {
+            memory,
+            cpus: config.cpus,
+            pids: config.pids,
+            io_weight: config.io_weight,
+        })
+    }
+
+    /// Rlimits of a task with these resources. A configured memory limit
+    /// replaces the default one, and without a cgroup, memory is limited by
+    /// rlimits instead. `RLIMIT_NPROC` counts all processes of the user, so
+    /// it can't stand in for `pids`.
+    pub fn rlimits(&self, cgroup: bool) -> ResourceLimits {
+        let mut limits = ResourceLimits::default();
+        if let Some(memory) = self.memory {
+            limits.memory = (!cgroup).then_some((memory, memory));
+        }
+        limits
+    }
+
+    /// Limits only a cgroup enforces, left unenforced without one
+    pub fn cgroup_only(&self) -> Vec<&'static str> {
+        [
+            ("cpus", self.cpus.is_some()),
+            ("pids", self.pids.is_some()),
+            ("ioWeight", self.io_weight.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(limit, set)| set.then_some(limit))
+        .collect()
+    }
+}
+
+/// Parse a size in bytes like "1048576", "512Mi" or "1.5G". Units are
+/// powers of 1000 (K, M, G, T, P) or of 1024 with an "i" (Ki, Mi, ...).
+pub fn parse_memory_size(size: &str) -> Result<u64> {
+    let invalid = || Error::configuration(format!("Invalid memory size '{size}'"));
+
+    let split = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    let (prefix, base) = match unit.strip_suffix('i') {
+        Some(prefix) if !prefix.is_empty() => (prefix, 1024_f64),
+        _ => (unit, 1000_f64),
+    };
+    let exponent = match prefix {
+        "" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        "P" => 5,
+        _ => return Err(invalid()),
+    };
+
+    let bytes = (number * base.powi(exponent)).round();
+    if bytes < 1.0 || bytes >= u64::MAX as f64 {
+        return Err(invalid());
+    }
+    Ok(bytes as u64)
+}
+
+/// Resources a task used, measured by its cgroup
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    /// Peak memory usage in bytes, if the kernel reports it
+    pub peak_memory: Option<u64>,
+    /// CPU time in user and system mode
+    pub cpu_time: Duration,
+}
+
+impl fmt::Display for ResourceUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(peak_memory) = self.peak_memory {
+            write!(f, "peak memory {}, ", format_bytes(peak_memory))?;
+        }
+        write!(f, "CPU time {:.2}s", self.cpu_time.as_secs_f64())
+    }
+}
+
+/// Format a size in bytes with a binary unit, like "1.5 GiB"
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
 /// Apply default resource limits for task execution
 /// This helps prevent runaway processes from consuming too many resources
 #[cfg(unix)]
@@ -157,6 +302,53 @@ mod tests {
         assert!(limits.file_descriptors.is_none());
     }
 
+    #[test]
+    fn test_parse_memory_size() {
+        assert_eq!(parse_memory_size("512").unwrap(), 512);
+        assert_eq!(parse_memory_size("2Gi").unwrap(), 2 << 30);
+        assert_eq!(parse_memory_size("1.5M").unwrap(), 1_500_000);
+        assert!(parse_memory_size("2GB").is_err());
+        assert!(parse_memory_size("Mi").is_err());
+    }
+
+    #[test]
+    fn test_task_resources_from_config() {
+        let config = ResourcesConfig {
+            memory: Some(MemorySize::Text("1Gi".to_string())),
+            cpus: Some(1.5),
+            pids: Some(64),
+            io_weight: None,
+        };
+        let resources = TaskResources::from_config(&config).unwrap();
+        assert_eq!(resources.memory, Some(1 << 30));
+        assert_eq!(resources.cgroup_only(), ["cpus", "pids"]);
+
+        let config = ResourcesConfig {
+            cpus: Some(0.0),
+            ..ResourcesConfig::default()
+        };
+        assert!(TaskResources::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_memory_falls_back_to_rlimit_without_cgroup() {
+        let resources = TaskResources {
+            memory: Some(1 << 30),
+            ..TaskResources::default()
+        };
+        assert_eq!(resources.rlimits(true).memory, None);
+        assert_eq!(resources.rlimits(false).memory, Some((1 << 30, 1 << 30)));
+    }
+
+    #[test]
+    fn test_display_usage() {
+        let usage = ResourceUsage {
+            peak_memory: Some(3 << 29),
+            cpu_time: Duration::from_millis(1250),
+        };
+        assert_eq!(usage.to_string(), "peak memory 1.5 GiB, CPU time 1.25s");
+    }
+
     #[test]
     #[cfg(windows)]
     fn test_apply_limits_noop_on_windows() {
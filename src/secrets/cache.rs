@@ -0,0 +1,415 @@
+//! Encrypted on-disk cache of resolved secrets
+//!
+//! Only secrets with a `ttl` are cached. Entries live in
+//! `XdgPaths::state_dir()/secrets`, one file per secret reference, sealed
+//! with AES-256-GCM under a key held in the kernel keyring or derived from
+//! `CUENV_SECRET_CACHE_PASSPHRASE`.
+use super::keyring::kernel;
+use crate::atomic_file::write_atomic;
+use crate::constants::CUENV_RESOLVER_PREFIX;
+use crate::errors::{Error, Result};
+use crate::xdg::XdgPaths;
+use once_cell::sync::OnceCell;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+/// Environment variable holding the passphrase the cache key is derived from
+pub const SECRET_CACHE_PASSPHRASE_VAR: &str = "CUENV_SECRET_CACHE_PASSPHRASE";
+
+/// Description of the cache key in the kernel user keyring
+const KEYRING_DESCRIPTION: &str = "cuenv:secret-cache";
+const PBKDF2_ITERATIONS: u32 = 100_000;
+const SALT_FILE: &str = ".salt";
+
+/// Where the cache encryption key comes from
+pub enum SecretCacheKey {
+    /// A random key stored in the kernel user keyring, created on first use
+    Keyring,
+    /// A key derived from a user passphrase and a per-cache salt
+    Passphrase(Zeroizing<String>),
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedSecret {
+    value: String,
+    expires_at: u64,
+}
+
+impl Drop for CachedSecret {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.value);
+    }
+}
+
+pub struct SecretCache {
+    dir: PathBuf,
+    key_source: SecretCacheKey,
+    key: OnceCell<LessSafeKey>,
+}
+
+impl SecretCache {
+    pub fn new(dir: impl Into<PathBuf>, key_source: SecretCacheKey) -> Self {
+        Self {
+            dir: dir.into(),
+            key_source,
+            key: OnceCell::new(),
+        }
+    }
+
+    /// The cache in the state directory, or `None` when no key source is available
+    pub fn from_env() -> Option<Self> {
+        let key_source = match std::env::var(SECRET_CACHE_PASSPHRASE_VAR) {
+            Ok(passphrase) if !passphrase.is_empty() => {
+                SecretCacheKey::Passphrase(Zeroizing::new(passphrase))
+            }
+            _ if cfg!(target_os = "linux") => SecretCacheKey::Keyring,
+            _ => return None,
+        };
+        Some(Self::new(XdgPaths::state_dir().join("secrets"), key_source))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Look up an unexpired secret
+    pub fn get(&self, reference: &str) -> Result<Option<String>> {
+        let path = self.entry_path(reference);
+        let sealed = match std::fs::read(&path) {
+            Ok(sealed) => sealed,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::file_system(&path, "read cached secret", e)),
+        };
+
+        // Entries sealed under a previous key (e.g. after a reboot cleared
+        // the keyring) cannot be opened and are simply dropped
+        let Some(entry) = self.open(reference, sealed)? else {
+            let _ = std::fs::remove_file(&path);
+            return Ok(None);
+        };
+
+        if entry.expires_at <= now() {
+            let _ = std::fs::remove_file(&path);
+            return Ok(None);
+        }
+        Ok(Some(entry.value.clone()))
+    }
+
+    /// Store a secret until its ttl runs out
+    pub fn put(&self, reference: &str, value: &str, ttl: Duration) -> Result<()> {
+        let entry = CachedSecret {
+            value: value.to_string(),
+            expires_at: now().saturating_add(ttl.as_secs()),
+        };
+        let plaintext = Zeroizing::new(serde_json::to_vec(&entry).map_err(|e| Error::Json {
+            message: "Failed to serialize cached secret".to_string(),
+            source: e,
+        })?);
+
+        let sealed = self.seal(reference, &plaintext)?;
+        self.ensure_dir()?;
+        write_atomic(&self.entry_path(reference), &sealed)
+    }
+
+    /// Remove a cached secret, returning whether there was one
+    pub fn forget(&self, reference: &str) -> Result<bool> {
+        let path = self.entry_path(reference);
+        match std::fs::remove_file(&path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(Error::file_system(&path, "remove cached secret", e)),
+        }
+    }
+
+    /// Remove every cached secret, returning how many were removed
+    pub fn clear(&self) -> Result<usize> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(Error::file_system(&self.dir, "read secret cache", e)),
+        };
+
+        let mut removed = 0;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if is_entry_file(&path) {
+                std::fs::remove_file(&path)
+                    .map_err(|e| Error::file_system(&path, "remove cached secret", e))?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    fn entry_name(reference: &str) -> String {
+        hex::encode(Sha256::digest(reference.as_bytes()))
+    }
+
+    fn entry_path(&self, reference: &str) -> PathBuf {
+        self.dir.join(Self::entry_name(reference))
+    }
+
+    fn ensure_dir(&self) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)
+            .map_err(|e| Error::file_system(&self.dir, "create secret cache", e))?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&self.dir, std::fs::Permissions::from_mode(0o700))
+                .map_err(|e| Error::file_system(&self.dir, "restrict secret cache", e))?;
+        }
+        Ok(())
+    }
+
+    fn seal(&self, reference: &str, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| Error::security("failed to generate a secret cache nonce"))?;
+
+        let mut in_out = plaintext.to_vec();
+        self.key()?
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(Self::entry_name(reference).as_bytes()),
+                &mut in_out,
+            )
+            .map_err(|_| Error::security("failed to encrypt cached secret"))?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.append(&mut in_out);
+        Ok(sealed)
+    }
+
+    fn open(&self, reference: &str, sealed: Vec<u8>) -> Result<Option<CachedSecret>> {
+        if sealed.len() < NONCE_LEN {
+            return Ok(None);
+        }
+
+        let mut sealed = Zeroizing::new(sealed);
+        let (nonce, in_out) = sealed.split_at_mut(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce)
+            .map_err(|_| Error::security("invalid secret cache nonce"))?;
+
+        // Bind each entry to its reference so entries cannot be swapped
+        let Ok(plaintext) = self.key()?.open_in_place(
+            nonce,
+            Aad::from(Self::entry_name(reference).as_bytes()),
+            in_out,
+        ) else {
+            return Ok(None);
+        };
+        Ok(serde_json::from_slice(plaintext).ok())
+    }
+
+    fn key(&self) -> Result<&LessSafeKey> {
+        self.key.get_or_try_init(|| {
+            let key_bytes = match &self.key_source {
+                SecretCacheKey::Keyring => Self::keyring_key()?,
+                SecretCacheKey::Passphrase(passphrase) => self.passphrase_key(passphrase)?,
+            };
+            let key = UnboundKey::new(&AES_256_GCM, key_bytes.as_slice())
+                .map_err(|_| Error::security("invalid secret cache key"))?;
+            Ok(LessSafeKey::new(key))
+        })
+    }
+
+    fn keyring_key() -> Result<Zeroizing<Vec<u8>>> {
+        if let Some(encoded) = kernel::lookup_key("user", KEYRING_DESCRIPTION)? {
+            let encoded = Zeroizing::new(encoded);
+            return hex::decode(encoded.as_str())
+                .map(Zeroizing::new)
+                .map_err(|_| Error::security("secret cache key in the keyring is corrupt"));
+        }
+
+        let mut key = Zeroizing::new(vec![0u8; AES_256_GCM.key_len()]);
+        SystemRandom::new()
+            .fill(&mut key)
+            .map_err(|_| Error::security("failed to generate a secret cache key"))?;
+        let encoded = Zeroizing::new(hex::encode(key.as_slice()));
+        kernel::add_user_key("user", KEYRING_DESCRIPTION, encoded.as_bytes())?;
+        Ok(key)
+    }
+
+    fn passphrase_key(&self, passphrase: &str) -> Result<Zeroizing<Vec<u8>>> {
+        let salt = self.salt()?;
+        let mut key = Zeroizing::new(vec![0u8; AES_256_GCM.key_len()]);
+        ring::pbkdf2::derive(
+            ring::pbkdf2::PBKDF2_HMAC_SHA256,
+            NonZeroU32::new(PBKDF2_ITERATIONS).expect("iterations are non-zero"),
+            &salt,
+            passphrase.as_bytes(),
+            &mut key,
+        );
+        Ok(key)
+    }
+
+    fn salt(&self) -> Result<Vec<u8>> {
+        let path = self.dir.join(SALT_FILE);
+        match std::fs::read(&path) {
+            Ok(salt) if !salt.is_empty() => return Ok(salt),
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(Error::file_system(&path, "read secret cache salt", e)),
+        }
+
+        let mut salt = vec![0u8; 16];
+        SystemRandom::new()
+            .fill(&mut salt)
+            .map_err(|_| Error::security("failed to generate a secret cache salt"))?;
+        self.ensure_dir()?;
+        write_atomic(&path, &salt)?;
+        Ok(salt)
+    }
+}
+
+fn is_entry_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.len() == 64 && name.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// The `ttl` of a secret reference, if it should be cached
+pub fn reference_ttl(reference: &str) -> Result<Option<Duration>> {
+    let Some(json) = reference.strip_prefix(CUENV_RESOLVER_PREFIX) else {
+        return Ok(None);
+    };
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(json) else {
+        return Ok(None);
+    };
+
+    match value.get("ttl") {
+        None | Some(serde_json::Value::Null) => Ok(None),
+        Some(serde_json::Value::String(ttl)) => parse_ttl(ttl).map(Some),
+        Some(other) => Err(Error::configuration(format!(
+            "secret ttl must be a duration string like \"1h\", got {other}"
+        ))),
+    }
+}
+
+/// Parse a duration like `90s`, `15m`, `1h30m` or `7d`
+pub fn parse_ttl(ttl: &str) -> Result<Duration> {
+    let invalid = || {
+        Error::configuration(format!(
+            "invalid secret ttl '{ttl}', expected e.g. \"30s\", \"15m\", \"1h30m\" or \"7d\""
+        ))
+    };
+
+    let mut total: u64 = 0;
+    let mut digits = String::new();
+    for c in ttl.trim().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => return Err(invalid()),
+        };
+        let amount: u64 = digits.parse().map_err(|_| invalid())?;
+        total = amount
+            .checked_mul(unit)
+            .and_then(|seconds| total.checked_add(seconds))
+            .ok_or_else(invalid)?;
+        digits.clear();
+    }
+
+    if !digits.is_empty() || total == 0 {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn passphrase_cache(dir: &Path, passphrase: &str) -> SecretCache {
+        SecretCache::new(
+            dir,
+            SecretCacheKey::Passphrase(Zeroizing::new(passphrase.to_string())),
+        )
+    }
+
+    #[test]
+    fn test_parse_ttl() {
+        assert_eq!(parse_ttl("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_ttl("1h30m").unwrap(), Duration::from_secs(5400));
+        assert_eq!(parse_ttl("7d").unwrap(), Duration::from_secs(604_800));
+        for invalid in ["", "0s", "10", "h", "1w", "-1h"] {
+            assert!(parse_ttl(invalid).is_err(), "{invalid} should be rejected");
+        }
+
+        let reference = r#"cuenv-resolver://{"cmd":"op","args":["read","op://a/b"],"ttl":"15m"}"#;
+        assert_eq!(
+            reference_ttl(reference).unwrap(),
+            Some(Duration::from_secs(900))
+        );
+        assert_eq!(
+            reference_ttl(r#"cuenv-resolver://{"cmd":"op","args":[]}"#).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_round_trip_is_encrypted() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = passphrase_cache(temp_dir.path(), "correct horse");
+        let reference = r#"cuenv-resolver://{"cmd":"op","args":["read","op://a/b"],"ttl":"1h"}"#;
+
+        assert_eq!(cache.get(reference).unwrap(), None);
+        cache
+            .put(reference, "super-secret-value", Duration::from_secs(3600))
+            .unwrap();
+        assert_eq!(
+            cache.get(reference).unwrap().as_deref(),
+            Some("super-secret-value")
+        );
+
+        let on_disk = std::fs::read(cache.entry_path(reference)).unwrap();
+        assert!(!String::from_utf8_lossy(&on_disk).contains("super-secret-value"));
+
+        // Another passphrase cannot open the entry, treats it as a miss and drops it
+        let other = passphrase_cache(temp_dir.path(), "wrong horse");
+        assert_eq!(other.get(reference).unwrap(), None);
+        assert_eq!(cache.get(reference).unwrap(), None);
+    }
+
+    #[test]
+    fn test_expiry_and_forget() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = passphrase_cache(temp_dir.path(), "passphrase");
+
+        cache.put("expired", "old", Duration::ZERO).unwrap();
+        assert_eq!(cache.get("expired").unwrap(), None);
+
+        cache.put("a", "1", Duration::from_secs(60)).unwrap();
+        cache.put("b", "2", Duration::from_secs(60)).unwrap();
+        assert!(cache.forget("a").unwrap());
+        assert!(!cache.forget("a").unwrap());
+
+        // The salt survives clearing so the key stays stable
+        assert_eq!(cache.clear().unwrap(), 1);
+        assert_eq!(cache.get("b").unwrap(), None);
+        assert!(temp_dir.path().join(SALT_FILE).exists());
+    }
+}
@@ -0,0 +1,181 @@
+//! Secret resolver reading files and Kubernetes-style secret mounts
+use super::{parse_typed_reference, ResolverResilience, SecretResolver};
+use crate::errors::{Error, Result};
+use crate::resilience::{CircuitBreakerConfig, RetryConfig};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+
+/// Configuration of a `type: "file"` resolver
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FileResolverConfig {
+    /// File holding the secret, or a directory with one file per key
+    pub path: String,
+    /// File name within `path` when it is a directory
+    #[serde(default)]
+    pub key: Option<String>,
+}
+
+/// Reads secrets from files.
+///
+/// A directory `path` is treated like a mounted Kubernetes secret where every
+/// key is a file. A single trailing newline is stripped from the value.
+pub struct FileResolver {
+    resilience: ResolverResilience,
+}
+
+impl FileResolver {
+    pub fn new() -> Self {
+        Self {
+            resilience: ResolverResilience::new(
+                RetryConfig::for_filesystem(),
+                CircuitBreakerConfig::default(),
+            ),
+        }
+    }
+
+    pub fn with_resilience(
+        mut self,
+        retry_config: RetryConfig,
+        circuit_breaker_config: CircuitBreakerConfig,
+    ) -> Self {
+        self.resilience = ResolverResilience::new(retry_config, circuit_breaker_config);
+        self
+    }
+
+    fn secret_path(config: &FileResolverConfig) -> Result<PathBuf> {
+        let path = PathBuf::from(shellexpand::tilde(&config.path).as_ref());
+
+        match &config.key {
+            None if path.is_dir() => Err(Error::secret_resolution(
+                &config.path,
+                "path is a directory, set `key` to select a file",
+            )),
+            None => Ok(path),
+            Some(key) => {
+                // Keys name files directly inside the mount
+                let mut components = Path::new(key).components();
+                match (components.next(), components.next()) {
+                    (Some(Component::Normal(_)), None) => Ok(path.join(key)),
+                    _ => Err(Error::secret_resolution(
+                        &config.path,
+                        format!("invalid secret key '{key}'"),
+                    )),
+                }
+            }
+        }
+    }
+
+    async fn read_secret(&self, config: &FileResolverConfig) -> Result<String> {
+        let path = Self::secret_path(config)?;
+
+        self.resilience
+            .call(|| async {
+                let content = match tokio::fs::read_to_string(&path).await {
+                    Ok(content) => content,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                        return Err(Error::secret_resolution(
+                            path.display().to_string(),
+                            "secret file does not exist",
+                        ))
+                    }
+                    Err(e) => return Err(Error::file_system(&path, "read secret", e)),
+                };
+
+                let value = content
+                    .strip_suffix('\n')
+                    .map(|value| value.strip_suffix('\r').unwrap_or(value))
+                    .unwrap_or(&content);
+                Ok(value.to_string())
+            })
+            .await
+    }
+}
+
+impl Default for FileResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl SecretResolver for FileResolver {
+    async fn resolve(&self, reference: &str) -> Result<Option<String>> {
+        let Some(config) = parse_typed_reference::<FileResolverConfig>(reference, "file")? else {
+            return Ok(None);
+        };
+
+        self.read_secret(&config).await.map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn reference(path: &Path, key: Option<&str>) -> String {
+        let config = FileResolverConfig {
+            path: path.display().to_string(),
+            key: key.map(str::to_string),
+        };
+        let mut json = serde_json::to_value(config).unwrap();
+        json["type"] = "file".into();
+        format!("cuenv-resolver://{json}")
+    }
+
+    /// A mounted secret directory holding a `password` file
+    fn mount(temp_dir: &TempDir) -> PathBuf {
+        let mount = temp_dir.path().join("db-credentials");
+        std::fs::create_dir(&mount).unwrap();
+        std::fs::write(mount.join("password"), "multi\nline").unwrap();
+        mount
+    }
+
+    #[tokio::test]
+    async fn test_reads_file_without_trailing_newline() {
+        let temp_dir = TempDir::new().unwrap();
+        let token = temp_dir.path().join("token");
+        std::fs::write(&token, "abc123\n").unwrap();
+
+        assert_eq!(
+            FileResolver::new()
+                .resolve(&reference(&token, None))
+                .await
+                .unwrap(),
+            Some("abc123".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reads_key_of_mounted_secret() {
+        let temp_dir = TempDir::new().unwrap();
+        let mount = mount(&temp_dir);
+
+        assert_eq!(
+            FileResolver::new()
+                .resolve(&reference(&mount, Some("password")))
+                .await
+                .unwrap(),
+            Some("multi\nline".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mounted_secret_needs_key_inside_it() {
+        let temp_dir = TempDir::new().unwrap();
+        let mount = mount(&temp_dir);
+        std::fs::write(temp_dir.path().join("token"), "abc123").unwrap();
+        let resolver = FileResolver::new();
+
+        assert!(resolver.resolve(&reference(&mount, None)).await.is_err());
+        assert!(resolver
+            .resolve(&reference(&mount, Some("../token")))
+            .await
+            .is_err());
+        assert!(resolver
+            .resolve(&reference(&mount, Some("missing")))
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,498 @@
+//! Secret resolver for the Linux kernel keyring and the Secret Service
+use super::{parse_typed_reference, ResolverResilience, SecretResolver};
+use crate::command_executor::{CommandExecutor, CommandExecutorFactory};
+use crate::errors::{Error, Result};
+use crate::resilience::{CircuitBreakerConfig, RetryConfig, RetryOn};
+use crate::types::CommandArguments;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Where a `type: "keyring"` resolver looks up secrets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyringBackend {
+    /// Linux kernel key retention service (`keyctl`)
+    Kernel,
+    /// freedesktop.org Secret Service (GNOME Keyring, KWallet)
+    SecretService,
+}
+
+/// Configuration of a `type: "keyring"` resolver
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct KeyringResolverConfig {
+    pub backend: KeyringBackend,
+    /// Description of the kernel key
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Type of the kernel key
+    #[serde(default = "default_key_type", rename = "keyType")]
+    pub key_type: String,
+    /// Attributes identifying the Secret Service item
+    #[serde(default)]
+    pub attributes: BTreeMap<String, String>,
+    /// Retries and circuit breaker of the lookups, defaults when unset
+    #[serde(default)]
+    pub resilience: Option<KeyringResilience>,
+}
+
+fn default_key_type() -> String {
+    "user".to_string()
+}
+
+/// Retries and circuit breaker of a `type: "keyring"` resolver
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct KeyringResilience {
+    /// Times a failed lookup is retried, while a locked keyring is unlocked
+    #[serde(default = "default_retries")]
+    pub retries: usize,
+    /// Failed lookups after which further ones fail right away
+    #[serde(default = "default_failure_threshold", rename = "failureThreshold")]
+    pub failure_threshold: usize,
+    /// Seconds until lookups are tried again after the threshold was reached
+    #[serde(default = "default_break_duration", rename = "breakDuration")]
+    pub break_duration: u64,
+}
+
+fn default_retries() -> usize {
+    2
+}
+
+fn default_failure_threshold() -> usize {
+    CircuitBreakerConfig::default().failure_threshold
+}
+
+fn default_break_duration() -> u64 {
+    CircuitBreakerConfig::default().break_duration.as_secs()
+}
+
+impl Default for KeyringResilience {
+    fn default() -> Self {
+        Self {
+            retries: default_retries(),
+            failure_threshold: default_failure_threshold(),
+            break_duration: default_break_duration(),
+        }
+    }
+}
+
+impl KeyringResilience {
+    fn retry_config(&self) -> RetryConfig {
+        // A locked keyring may need a moment to be unlocked by the session
+        RetryConfig {
+            max_retries: self.retries,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+            jitter_factor: 0.1,
+            retry_on: RetryOn::Custom(Arc::new(|error| {
+                matches!(error, Error::CommandExecution { .. })
+            })),
+        }
+    }
+
+    fn circuit_breaker_config(&self) -> CircuitBreakerConfig {
+        CircuitBreakerConfig {
+            failure_threshold: self.failure_threshold,
+            break_duration: Duration::from_secs(self.break_duration),
+            ..CircuitBreakerConfig::default()
+        }
+    }
+}
+
+/// Reads secrets from the kernel keyring or the Secret Service.
+///
+/// Kernel keys are read with the `keyctl` system calls. Secret Service items
+/// are looked up over D-Bus through libsecret's `secret-tool`, which has to
+/// be installed. Resolvers with their own `resilience` share a circuit
+/// breaker with the others that configure the same.
+pub struct KeyringResolver {
+    executor: Box<dyn CommandExecutor>,
+    resilience: Arc<ResolverResilience>,
+    configured: Mutex<HashMap<KeyringResilience, Arc<ResolverResilience>>>,
+}
+
+impl KeyringResolver {
+    pub fn new() -> Self {
+        let allowed = HashSet::from(["secret-tool".to_string()]);
+        Self::with_executor(CommandExecutorFactory::system_with_allowed_commands(
+            allowed,
+        ))
+    }
+
+    pub fn with_executor(executor: Box<dyn CommandExecutor>) -> Self {
+        let defaults = KeyringResilience::default();
+        Self {
+            executor,
+            resilience: Arc::new(ResolverResilience::new(
+                defaults.retry_config(),
+                defaults.circuit_breaker_config(),
+            )),
+            configured: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Resilience of resolvers without a `resilience` field
+    pub fn with_resilience(
+        mut self,
+        retry_config: RetryConfig,
+        circuit_breaker_config: CircuitBreakerConfig,
+    ) -> Self {
+        self.resilience = Arc::new(ResolverResilience::new(
+            retry_config,
+            circuit_breaker_config,
+        ));
+        self
+    }
+
+    fn resilience(&self, config: &KeyringResolverConfig) -> Arc<ResolverResilience> {
+        let Some(resilience) = config.resilience else {
+            return Arc::clone(&self.resilience);
+        };
+
+        let mut configured = self.configured.lock().unwrap_or_else(|e| e.into_inner());
+        Arc::clone(configured.entry(resilience).or_insert_with(|| {
+            Arc::new(ResolverResilience::new(
+                resilience.retry_config(),
+                resilience.circuit_breaker_config(),
+            ))
+        }))
+    }
+
+    async fn lookup_secret_service(&self, config: &KeyringResolverConfig) -> Result<String> {
+        if config.attributes.is_empty() {
+            return Err(Error::configuration(
+                "secret-service keyring resolver needs `attributes`",
+            ));
+        }
+
+        let mut args = vec!["lookup".to_string()];
+        for (key, value) in &config.attributes {
+            args.push(key.clone());
+            args.push(value.clone());
+        }
+        let args = CommandArguments::from_vec(args);
+
+        self.resilience(config)
+            .call(|| async {
+                let output = self.executor.execute("secret-tool", &args).await?;
+                if !output.status.success() {
+                    return Err(Error::command_execution(
+                        "secret-tool",
+                        args.as_slice().to_vec(),
+                        "no matching Secret Service item or the keyring is locked",
+                        output.status.code(),
+                    ));
+                }
+
+                String::from_utf8(output.stdout).map_err(|e| {
+                    Error::configuration(format!("keyring secret is not valid UTF-8: {e}"))
+                })
+            })
+            .await
+    }
+
+    async fn read_kernel_key(&self, config: &KeyringResolverConfig) -> Result<String> {
+        let description = config
+            .description
+            .as_deref()
+            .ok_or_else(|| Error::configuration("kernel keyring resolver needs a `description`"))?;
+
+        self.resilience(config)
+            .call(|| async { kernel::read_key(&config.key_type, description) })
+            .await
+    }
+}
+
+impl Default for KeyringResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl SecretResolver for KeyringResolver {
+    async fn resolve(&self, reference: &str) -> Result<Option<String>> {
+        let Some(config) = parse_typed_reference::<KeyringResolverConfig>(reference, "keyring")?
+        else {
+            return Ok(None);
+        };
+
+        let secret = match config.backend {
+            KeyringBackend::Kernel => self.read_kernel_key(&config).await?,
+            KeyringBackend::SecretService => self.lookup_secret_service(&config).await?,
+        };
+        Ok(Some(secret))
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub(super) mod kernel {
+    use crate::errors::{Error, Result};
+    use std::ffi::CString;
+
+    // From <linux/keyctl.h>
+    const KEY_SPEC_USER_KEYRING: libc::c_long = -4;
+    const KEYCTL_SEARCH: libc::c_long = 10;
+    const KEYCTL_READ: libc::c_long = 11;
+
+    fn c_strings(key_type: &str, description: &str) -> Result<(CString, CString)> {
+        let invalid =
+            |_| Error::configuration("keyring key type and description cannot contain NUL");
+        Ok((
+            CString::new(key_type).map_err(invalid)?,
+            CString::new(description).map_err(invalid)?,
+        ))
+    }
+
+    /// Find a key in the calling process' keyrings, falling back to the user keyring
+    fn find_key(key_type: &CString, description: &CString) -> std::io::Result<libc::c_long> {
+        // SAFETY: both strings are NUL-terminated and outlive the calls
+        let serial = unsafe {
+            libc::syscall(
+                libc::SYS_request_key,
+                key_type.as_ptr(),
+                description.as_ptr(),
+                std::ptr::null::<libc::c_char>(),
+                0 as libc::c_long,
+            )
+        };
+        if serial >= 0 {
+            return Ok(serial);
+        }
+
+        // SAFETY: as above
+        let serial = unsafe {
+            libc::syscall(
+                libc::SYS_keyctl,
+                KEYCTL_SEARCH,
+                KEY_SPEC_USER_KEYRING,
+                key_type.as_ptr(),
+                description.as_ptr(),
+                0 as libc::c_long,
+            )
+        };
+        if serial < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(serial)
+    }
+
+    fn read_payload(serial: libc::c_long, description: &str) -> Result<Vec<u8>> {
+        // The key may change size between calls, so read until the buffer fits
+        let mut buffer = vec![0u8; 256];
+        loop {
+            // SAFETY: the buffer is valid for writes of its full length
+            let length = unsafe {
+                libc::syscall(
+                    libc::SYS_keyctl,
+                    KEYCTL_READ,
+                    serial,
+                    buffer.as_mut_ptr(),
+                    buffer.len(),
+                )
+            };
+            if length < 0 {
+                return Err(Error::secret_resolution(
+                    description,
+                    format!(
+                        "failed to read kernel key: {}",
+                        std::io::Error::last_os_error()
+                    ),
+                ));
+            }
+
+            let length = length as usize;
+            if length <= buffer.len() {
+                buffer.truncate(length);
+                return Ok(buffer);
+            }
+            buffer.resize(length, 0);
+        }
+    }
+
+    /// Read a key, returning `None` when no such key exists
+    pub(in crate::secrets) fn lookup_key(
+        key_type: &str,
+        description: &str,
+    ) -> Result<Option<String>> {
+        let (key_type, description_c) = c_strings(key_type, description)?;
+
+        let serial = match find_key(&key_type, &description_c) {
+            Ok(serial) => serial,
+            Err(e) if e.raw_os_error() == Some(libc::ENOKEY) => return Ok(None),
+            Err(e) => {
+                return Err(Error::secret_resolution(
+                    description,
+                    format!("failed to search the kernel keyring: {e}"),
+                ))
+            }
+        };
+
+        let payload = read_payload(serial, description)?;
+        String::from_utf8(payload)
+            .map(Some)
+            .map_err(|e| Error::configuration(format!("kernel key is not valid UTF-8: {e}")))
+    }
+
+    pub(super) fn read_key(key_type: &str, description: &str) -> Result<String> {
+        lookup_key(key_type, description)?.ok_or_else(|| {
+            Error::secret_resolution(description, "key not found in the kernel keyring")
+        })
+    }
+
+    /// Add or update a key in the user keyring
+    pub(in crate::secrets) fn add_user_key(
+        key_type: &str,
+        description: &str,
+        payload: &[u8],
+    ) -> Result<()> {
+        let (key_type, description_c) = c_strings(key_type, description)?;
+
+        // SAFETY: all pointers are valid for the duration of the call
+        let serial = unsafe {
+            libc::syscall(
+                libc::SYS_add_key,
+                key_type.as_ptr(),
+                description_c.as_ptr(),
+                payload.as_ptr(),
+                payload.len(),
+                KEY_SPEC_USER_KEYRING,
+            )
+        };
+        if serial < 0 {
+            return Err(Error::secret_resolution(
+                description,
+                format!(
+                    "failed to add kernel key: {}",
+                    std::io::Error::last_os_error()
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(super) mod kernel {
+    use crate::errors::{Error, Result};
+
+    pub(super) fn read_key(_key_type: &str, _description: &str) -> Result<String> {
+        Err(Error::unsupported(
+            "kernel keyring",
+            "the kernel keyring is only available on Linux",
+        ))
+    }
+
+    pub(in crate::secrets) fn lookup_key(
+        key_type: &str,
+        description: &str,
+    ) -> Result<Option<String>> {
+        read_key(key_type, description).map(Some)
+    }
+
+    pub(in crate::secrets) fn add_user_key(
+        _key_type: &str,
+        _description: &str,
+        _payload: &[u8],
+    ) -> Result<()> {
+        Err(Error::unsupported(
+            "kernel keyring",
+            "the kernel keyring is only available on Linux",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_secret_service_lookup() {
+        let executor = CommandExecutorFactory::test();
+        executor.add_simple_response(
+            "secret-tool",
+            &[
+                "lookup".to_string(),
+                "account".to_string(),
+                "db".to_string(),
+                "service".to_string(),
+                "myapp".to_string(),
+            ],
+            "from-keyring",
+        );
+        let resolver = KeyringResolver::with_executor(Box::new(executor));
+
+        let reference = r#"cuenv-resolver://{"type":"keyring","backend":"secret-service","attributes":{"service":"myapp","account":"db"}}"#;
+        assert_eq!(
+            resolver.resolve(reference).await.unwrap(),
+            Some("from-keyring".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_configured_resilience() {
+        let executor = CommandExecutorFactory::test();
+        let args = [
+            "lookup".to_string(),
+            "service".to_string(),
+            "npm".to_string(),
+        ];
+        executor.add_error_response("secret-tool", &args, "locked");
+        let resolver = KeyringResolver::with_executor(Box::new(executor));
+
+        let reference = r#"cuenv-resolver://{"type":"keyring","backend":"secret-service","attributes":{"service":"npm"},"resilience":{"retries":0,"failureThreshold":1}}"#;
+        let first = resolver.resolve(reference).await.unwrap_err();
+        assert!(matches!(first, Error::CommandExecution { .. }));
+        let second = resolver.resolve(reference).await.unwrap_err();
+        assert!(second.to_string().contains("Circuit breaker is open"));
+
+        // Resolvers without their own resilience keep the default breaker
+        let reference = r#"cuenv-resolver://{"type":"keyring","backend":"secret-service","attributes":{"service":"npm"}}"#;
+        let other = resolver.resolve(reference).await.unwrap_err();
+        assert!(matches!(other, Error::CommandExecution { .. }));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_kernel_keyring_lookup() {
+        use std::ffi::CString;
+
+        // From <linux/keyctl.h>
+        const KEY_SPEC_PROCESS_KEYRING: libc::c_long = -2;
+
+        let description = format!("cuenv-test:{}", std::process::id());
+        let key_type = CString::new("user").unwrap();
+        let description_c = CString::new(description.clone()).unwrap();
+        let payload = b"kernel-secret";
+
+        // SAFETY: all pointers are valid for the duration of the call
+        let serial = unsafe {
+            libc::syscall(
+                libc::SYS_add_key,
+                key_type.as_ptr(),
+                description_c.as_ptr(),
+                payload.as_ptr(),
+                payload.len(),
+                KEY_SPEC_PROCESS_KEYRING,
+            )
+        };
+        if serial < 0 {
+            eprintln!(
+                "Skipping kernel keyring test: {}",
+                std::io::Error::last_os_error()
+            );
+            return;
+        }
+
+        let resolver = KeyringResolver::new();
+        let reference = format!(
+            r#"cuenv-resolver://{{"type":"keyring","backend":"kernel","description":"{description}"}}"#
+        );
+        assert_eq!(
+            resolver.resolve(&reference).await.unwrap(),
+            Some("kernel-secret".to_string())
+        );
+    }
+}
@@ -1,14 +1,34 @@
 use crate::audit::{audit_logger, AuditLogger};
 use crate::command_executor::{CommandExecutor, CommandExecutorFactory};
+use crate::constants::CUENV_RESOLVER_PREFIX;
 use crate::errors::{Error, Result};
 use crate::rate_limit::RateLimitManager;
-use crate::resilience::{retry, CircuitBreaker, CircuitBreakerConfig, RetryConfig};
+use crate::resilience::{
+    retry, retry_with_circuit_breaker, CircuitBreaker, CircuitBreakerConfig, RetryConfig,
+};
 use crate::types::{CommandArguments, EnvironmentVariables, SecretValues};
 use async_trait::async_trait;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::future::Future;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::{Mutex, Semaphore};
 
+mod cache;
+mod file;
+mod keyring;
+mod pass;
+mod vault;
+
+pub use cache::{
+    parse_ttl, reference_ttl, SecretCache, SecretCacheKey, SECRET_CACHE_PASSPHRASE_VAR,
+};
+pub use file::{FileResolver, FileResolverConfig};
+pub use keyring::{KeyringBackend, KeyringResilience, KeyringResolver, KeyringResolverConfig};
+pub use pass::{PassResolver, PassResolverConfig};
+pub use vault::{VaultResolver, VaultResolverConfig};
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ResolverConfig {
     pub cmd: String,
@@ -20,6 +40,60 @@ pub trait SecretResolver: Send + Sync {
     async fn resolve(&self, reference: &str) -> Result<Option<String>>;
 }
 
+/// Resolver a secret reference is meant for, taken from its `type` field.
+///
+/// References without a type are resolved by running a command.
+pub fn resolver_type(reference: &str) -> Option<String> {
+    let json = reference.strip_prefix(CUENV_RESOLVER_PREFIX)?;
+    let value: serde_json::Value = serde_json::from_str(json).ok()?;
+
+    match value.get("type") {
+        Some(serde_json::Value::String(resolver_type)) => Some(resolver_type.clone()),
+        Some(_) => None,
+        None => Some("exec".to_string()),
+    }
+}
+
+/// Parse the configuration of a reference if it is meant for `expected_type`
+fn parse_typed_reference<T: DeserializeOwned>(
+    reference: &str,
+    expected_type: &str,
+) -> Result<Option<T>> {
+    if resolver_type(reference).as_deref() != Some(expected_type) {
+        return Ok(None);
+    }
+
+    let json = &reference[CUENV_RESOLVER_PREFIX.len()..];
+    serde_json::from_str(json).map(Some).map_err(|e| {
+        Error::configuration(format!(
+            "invalid {expected_type} resolver configuration: {e}"
+        ))
+    })
+}
+
+/// Retry and circuit breaker protection of a resolver backend
+pub struct ResolverResilience {
+    retry_config: RetryConfig,
+    circuit_breaker: CircuitBreaker,
+}
+
+impl ResolverResilience {
+    pub fn new(retry_config: RetryConfig, circuit_breaker_config: CircuitBreakerConfig) -> Self {
+        Self {
+            retry_config,
+            circuit_breaker: CircuitBreaker::new(circuit_breaker_config),
+        }
+    }
+
+    async fn call<F, Fut, T>(&self, operation: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        retry_with_circuit_breaker(&self.retry_config, &self.circuit_breaker, operation).await
+    }
+}
+
 /// Generic command-based secret resolver that uses CUE-defined resolver configurations
 pub struct CommandResolver {
     // Semaphore to limit concurrent secret resolutions
@@ -194,6 +268,8 @@ impl SecretResolver for CommandResolver {
 
 pub struct SecretManager {
     resolver: Box<dyn SecretResolver>,
+    cache: Option<SecretCache>,
+    audit_logger: Option<Arc<AuditLogger>>,
 }
 
 pub struct ResolvedSecrets {
@@ -201,11 +277,96 @@ pub struct ResolvedSecrets {
     pub secret_values: SecretValues,
 }
 
+/// Dispatches secret references to the built-in resolver for their `type`
+pub struct BuiltinResolver {
+    command: CommandResolver,
+    vault: VaultResolver,
+    file: FileResolver,
+    pass: PassResolver,
+    keyring: KeyringResolver,
+    audit_logger: Option<Arc<AuditLogger>>,
+}
+
+impl BuiltinResolver {
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            command: CommandResolver::new(max_concurrent),
+            vault: VaultResolver::new(),
+            file: FileResolver::new(),
+            pass: PassResolver::new(),
+            keyring: KeyringResolver::new(),
+            audit_logger: audit_logger(),
+        }
+    }
+
+    /// Replace the resolver used for references without a type
+    pub fn with_command_resolver(mut self, command: CommandResolver) -> Self {
+        self.command = command;
+        self
+    }
+
+    pub fn with_vault_resolver(mut self, vault: VaultResolver) -> Self {
+        self.vault = vault;
+        self
+    }
+
+    pub fn with_file_resolver(mut self, file: FileResolver) -> Self {
+        self.file = file;
+        self
+    }
+
+    pub fn with_pass_resolver(mut self, pass: PassResolver) -> Self {
+        self.pass = pass;
+        self
+    }
+
+    pub fn with_keyring_resolver(mut self, keyring: KeyringResolver) -> Self {
+        self.keyring = keyring;
+        self
+    }
+}
+
+#[async_trait]
+impl SecretResolver for BuiltinResolver {
+    async fn resolve(&self, reference: &str) -> Result<Option<String>> {
+        let Some(resolver_type) = resolver_type(reference) else {
+            return Ok(None);
+        };
+
+        let result = match resolver_type.as_str() {
+            // The command resolver does its own audit logging
+            "exec" => return self.command.resolve(reference).await,
+            "vault" => self.vault.resolve(reference).await,
+            "file" => self.file.resolve(reference).await,
+            "pass" => self.pass.resolve(reference).await,
+            "keyring" => self.keyring.resolve(reference).await,
+            other => Err(Error::configuration(format!(
+                "unknown secret resolver type '{other}'"
+            ))),
+        };
+
+        if let Some(ref logger) = self.audit_logger {
+            let _ = logger
+                .log_secret_resolution(
+                    &reference[0..20.min(reference.len())], // Truncate for security
+                    &resolver_type,
+                    result.is_ok(),
+                    result.as_ref().err().map(|e| e.to_string()),
+                )
+                .await;
+        }
+
+        result
+    }
+}
+
 impl SecretManager {
     pub fn new() -> Self {
         Self {
             // Use up to 10 concurrent secret resolutions
-            resolver: Box::new(CommandResolver::new(10)),
+            resolver: Box::new(BuiltinResolver::new(10)),
+            cache: SecretCache::from_env(),
+            audit_logger: audit_logger(),
         }
     }
 }
@@ -218,7 +379,60 @@ impl Default for SecretManager {
 
 impl SecretManager {
     pub fn with_resolver(resolver: Box<dyn SecretResolver>) -> Self {
-        Self { resolver }
+        Self {
+            resolver,
+            cache: None,
+            audit_logger: audit_logger(),
+        }
+    }
+
+    /// Cache secrets that have a `ttl` in the given cache
+    pub fn with_cache(mut self, cache: SecretCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn cache(&self) -> Option<&SecretCache> {
+        self.cache.as_ref()
+    }
+
+    /// The cache and ttl to use for a reference, if it should be cached
+    fn cache_for(&self, key: &str, reference: &str) -> Option<(&SecretCache, Duration)> {
+        let cache = self.cache.as_ref()?;
+        match reference_ttl(reference) {
+            Ok(ttl) => ttl.map(|ttl| (cache, ttl)),
+            Err(e) => {
+                log::warn!("Not caching secret for {key}: {e}");
+                None
+            }
+        }
+    }
+
+    /// Resolve a secret, going through the cache when it has a `ttl`
+    async fn resolve_cached(&self, key: &str, reference: &str) -> Result<Option<String>> {
+        let Some((cache, ttl)) = self.cache_for(key, reference) else {
+            return self.resolver.resolve(reference).await;
+        };
+
+        let cached = cache.get(reference).unwrap_or_else(|e| {
+            log::warn!("Failed to read cached secret for {key}: {e}");
+            None
+        });
+        if let Some(ref logger) = self.audit_logger {
+            let _ = logger.log_secret_cache_lookup(key, cached.is_some()).await;
+        }
+        if cached.is_some() {
+            log::debug!("Using cached secret for {key}");
+            return Ok(cached);
+        }
+
+        let resolved = self.resolver.resolve(reference).await?;
+        if let Some(ref secret) = resolved {
+            if let Err(e) = cache.put(reference, secret, ttl) {
+                log::warn!("Failed to cache secret for {key}: {e}");
+            }
+        }
+        Ok(resolved)
     }
 
     pub async fn resolve_secrets(&self, env_vars: EnvironmentVariables) -> Result<ResolvedSecrets> {
@@ -232,10 +446,9 @@ impl SecretManager {
             if value.starts_with("cuenv-resolver://") {
                 let key_clone = key.clone();
                 let value_clone = value.clone();
-                let resolver = &self.resolver;
 
                 tasks.push(async move {
-                    let result = resolver.resolve(&value_clone).await;
+                    let result = self.resolve_cached(&key_clone, &value_clone).await;
                     match result {
                         Ok(opt) => Ok((key_clone, value_clone, opt)),
                         Err(e) => {
@@ -272,6 +485,50 @@ impl SecretManager {
             secret_values,
         })
     }
+
+    /// Resolve every cacheable secret again and replace its cache entry.
+    ///
+    /// Returns the names of the refreshed variables.
+    pub async fn refresh_secrets(&self, env_vars: EnvironmentVariables) -> Result<Vec<String>> {
+        let tasks = env_vars
+            .iter()
+            .filter_map(|(key, reference)| {
+                let (cache, ttl) = self.cache_for(key, reference)?;
+                Some(async move {
+                    let secret = self.resolver.resolve(reference).await?.ok_or_else(|| {
+                        Error::secret_resolution(key.as_str(), "no resolver handled the secret")
+                    })?;
+                    cache.put(reference, &secret, ttl)?;
+                    Ok::<_, Error>(key.clone())
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut refreshed = Vec::new();
+        for result in futures::future::join_all(tasks).await {
+            refreshed.push(result?);
+        }
+        refreshed.sort();
+        Ok(refreshed)
+    }
+
+    /// Drop the cache entries of the given secrets.
+    ///
+    /// Returns the names of the variables that had a cached value.
+    pub fn forget_secrets(&self, env_vars: &EnvironmentVariables) -> Result<Vec<String>> {
+        let Some(cache) = &self.cache else {
+            return Ok(Vec::new());
+        };
+
+        let mut forgotten = Vec::new();
+        for (key, reference) in env_vars.iter() {
+            if reference.starts_with(CUENV_RESOLVER_PREFIX) && cache.forget(reference)? {
+                forgotten.push(key.clone());
+            }
+        }
+        forgotten.sort();
+        Ok(forgotten)
+    }
 }
 
 #[cfg(test)]
@@ -293,6 +550,57 @@ mod tests {
         assert_eq!(config.args, vec!["read", "op://vault/item/field"]);
     }
 
+    #[test]
+    fn test_resolver_type() {
+        assert_eq!(
+            resolver_type(r#"cuenv-resolver://{"cmd":"op","args":[]}"#).as_deref(),
+            Some("exec")
+        );
+        assert_eq!(
+            resolver_type(r#"cuenv-resolver://{"type":"vault","path":"a","field":"b"}"#).as_deref(),
+            Some("vault")
+        );
+        assert_eq!(resolver_type("op://vault/item/field"), None);
+        assert_eq!(resolver_type(r#"cuenv-resolver://{"type":1}"#), None);
+    }
+
+    #[tokio::test]
+    async fn test_builtin_resolver_dispatches_on_type() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let secret_file = temp_dir.path().join("token");
+        std::fs::write(&secret_file, "from-file\n").unwrap();
+
+        let test_executor = CommandExecutorFactory::test();
+        test_executor.add_simple_response("op", &["read".to_string()], "from-command");
+        let resolver = BuiltinResolver::new(10)
+            .with_command_resolver(CommandResolver::with_executor(10, Box::new(test_executor)));
+        let manager = SecretManager::with_resolver(Box::new(resolver));
+
+        let mut env_vars = EnvironmentVariables::new();
+        env_vars.insert(
+            "FROM_COMMAND".to_string(),
+            r#"cuenv-resolver://{"cmd":"op","args":["read"]}"#.to_string(),
+        );
+        env_vars.insert(
+            "FROM_FILE".to_string(),
+            format!(
+                r#"cuenv-resolver://{{"type":"file","path":"{}"}}"#,
+                secret_file.display()
+            ),
+        );
+        let unknown = r#"cuenv-resolver://{"type":"smoke-signals"}"#.to_string();
+        env_vars.insert("UNKNOWN".to_string(), unknown.clone());
+
+        let resolved = manager.resolve_secrets(env_vars).await.unwrap();
+        assert_eq!(
+            resolved.env_vars.get("FROM_COMMAND").unwrap(),
+            "from-command"
+        );
+        assert_eq!(resolved.env_vars.get("FROM_FILE").unwrap(), "from-file");
+        assert_eq!(resolved.env_vars.get("UNKNOWN").unwrap(), &unknown);
+        assert_eq!(resolved.secret_values.len(), 2);
+    }
+
     #[test]
     fn test_parse_invalid_reference() {
         let reference = "op://vault/item/field";
@@ -350,9 +658,7 @@ mod tests {
             delay: Duration::from_millis(50),
         };
 
-        let manager = SecretManager {
-            resolver: Box::new(resolver),
-        };
+        let manager = SecretManager::with_resolver(Box::new(resolver));
 
         let mut env_vars = EnvironmentVariables::new();
         for i in 0..20 {
@@ -403,9 +709,7 @@ mod tests {
         // Create resolver with only 2 concurrent executions allowed
         let resolver = CommandResolver::with_executor(2, Box::new(test_executor));
 
-        let manager = SecretManager {
-            resolver: Box::new(resolver),
-        };
+        let manager = SecretManager::with_resolver(Box::new(resolver));
 
         // Create 10 secrets that will be resolved
         let mut env_vars = EnvironmentVariables::new();
@@ -461,9 +765,7 @@ mod tests {
         fail_indices.insert(15);
 
         let resolver = FailingResolver { fail_indices };
-        let manager = SecretManager {
-            resolver: Box::new(resolver),
-        };
+        let manager = SecretManager::with_resolver(Box::new(resolver));
 
         let mut env_vars = EnvironmentVariables::new();
         for i in 0..20 {
@@ -497,6 +799,58 @@ mod tests {
         assert_eq!(resolved.env_vars.get("SECRET_10").unwrap(), "secret-10");
     }
 
+    #[tokio::test]
+    async fn test_secrets_with_ttl_are_cached() {
+        struct CountingResolver(Arc<AtomicUsize>);
+
+        #[async_trait]
+        impl SecretResolver for CountingResolver {
+            async fn resolve(&self, _reference: &str) -> Result<Option<String>> {
+                let calls = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+                Ok(Some(format!("secret-{calls}")))
+            }
+        }
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let manager = SecretManager::with_resolver(Box::new(CountingResolver(calls.clone())))
+            .with_cache(SecretCache::new(
+                temp_dir.path(),
+                SecretCacheKey::Passphrase("test".to_string().into()),
+            ));
+
+        let mut env_vars = EnvironmentVariables::new();
+        env_vars.insert(
+            "CACHED",
+            r#"cuenv-resolver://{"cmd":"op","args":["read","op://a/b"],"ttl":"1h"}"#,
+        );
+        let resolved = manager.resolve_secrets(env_vars.clone()).await.unwrap();
+        assert_eq!(resolved.env_vars.get("CACHED").unwrap(), "secret-1");
+        let resolved = manager.resolve_secrets(env_vars.clone()).await.unwrap();
+        assert_eq!(resolved.env_vars.get("CACHED").unwrap(), "secret-1");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Refreshing resolves again and replaces the cached value
+        let refreshed = manager.refresh_secrets(env_vars.clone()).await.unwrap();
+        assert_eq!(refreshed, vec!["CACHED".to_string()]);
+        let resolved = manager.resolve_secrets(env_vars.clone()).await.unwrap();
+        assert_eq!(resolved.env_vars.get("CACHED").unwrap(), "secret-2");
+
+        assert_eq!(
+            manager.forget_secrets(&env_vars).unwrap(),
+            vec!["CACHED".to_string()]
+        );
+        let resolved = manager.resolve_secrets(env_vars).await.unwrap();
+        assert_eq!(resolved.env_vars.get("CACHED").unwrap(), "secret-3");
+
+        // Secrets without a ttl always resolve live
+        let mut uncached = EnvironmentVariables::new();
+        uncached.insert("LIVE", r#"cuenv-resolver://{"cmd":"op","args":[]}"#);
+        manager.resolve_secrets(uncached.clone()).await.unwrap();
+        manager.resolve_secrets(uncached).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 5);
+    }
+
     // Test that approval is only shown once
     #[tokio::test]
     async fn test_approval_shown_once() {
@@ -531,9 +885,7 @@ mod tests {
             approval_count: approval_count.clone(),
         };
 
-        let manager = SecretManager {
-            resolver: Box::new(counting_resolver),
-        };
+        let manager = SecretManager::with_resolver(Box::new(counting_resolver));
 
         // Resolve multiple secrets
         let mut env_vars = EnvironmentVariables::new();
@@ -601,9 +953,7 @@ mod tests {
             }
         }
 
-        let manager = SecretManager {
-            resolver: Box::new(FastResolver),
-        };
+        let manager = SecretManager::with_resolver(Box::new(FastResolver));
 
         let mut env_vars = EnvironmentVariables::new();
         for i in 0..1000 {
@@ -0,0 +1,242 @@
+//! Secret resolver for `pass` password stores and other GPG-encrypted files
+use super::{parse_typed_reference, ResolverResilience, SecretResolver};
+use crate::command_executor::{CommandExecutor, CommandExecutorFactory};
+use crate::errors::{Error, Result};
+use crate::resilience::{CircuitBreakerConfig, RetryConfig, RetryOn};
+use crate::types::CommandArguments;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+/// Configuration of a `type: "pass"` resolver
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PassResolverConfig {
+    /// Entry name within the store, e.g. `work/database`
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Password store directory, defaults to `PASSWORD_STORE_DIR` or `~/.password-store`
+    #[serde(default)]
+    pub store: Option<String>,
+    /// Encrypted file to read instead of a store entry
+    #[serde(default)]
+    pub file: Option<String>,
+    /// `key: value` line to read instead of the password on the first line
+    #[serde(default)]
+    pub field: Option<String>,
+}
+
+/// Decrypts `pass` entries and GPG-encrypted files with `gpg`.
+///
+/// Only `gpg` itself is run, so the decrypted value never passes through a
+/// shell and the usual `gpg-agent` caching applies.
+pub struct PassResolver {
+    executor: Box<dyn CommandExecutor>,
+    resilience: ResolverResilience,
+}
+
+impl PassResolver {
+    pub fn new() -> Self {
+        let allowed = HashSet::from(["gpg".to_string()]);
+        Self::with_executor(CommandExecutorFactory::system_with_allowed_commands(
+            allowed,
+        ))
+    }
+
+    pub fn with_executor(executor: Box<dyn CommandExecutor>) -> Self {
+        // A failed decryption (wrong key, cancelled pinentry) will not succeed on retry
+        let retry_config = RetryConfig {
+            max_retries: 1,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+            jitter_factor: 0.1,
+            retry_on: RetryOn::FileSystem,
+        };
+
+        Self {
+            executor,
+            resilience: ResolverResilience::new(retry_config, CircuitBreakerConfig::default()),
+        }
+    }
+
+    pub fn with_resilience(
+        mut self,
+        retry_config: RetryConfig,
+        circuit_breaker_config: CircuitBreakerConfig,
+    ) -> Self {
+        self.resilience = ResolverResilience::new(retry_config, circuit_breaker_config);
+        self
+    }
+
+    fn encrypted_path(config: &PassResolverConfig) -> Result<PathBuf> {
+        match (&config.name, &config.file) {
+            (Some(name), None) => {
+                let valid = Path::new(name)
+                    .components()
+                    .all(|component| matches!(component, Component::Normal(_)));
+                if !valid || name.is_empty() {
+                    return Err(Error::secret_resolution(
+                        name,
+                        "invalid password store entry name",
+                    ));
+                }
+
+                let store = match &config.store {
+                    Some(store) => shellexpand::tilde(store).to_string(),
+                    None => std::env::var("PASSWORD_STORE_DIR")
+                        .unwrap_or_else(|_| shellexpand::tilde("~/.password-store").to_string()),
+                };
+                Ok(Path::new(&store).join(format!("{name}.gpg")))
+            }
+            (None, Some(file)) => Ok(PathBuf::from(shellexpand::tilde(file).as_ref())),
+            _ => Err(Error::configuration(
+                "pass resolver needs exactly one of `name` or `file`",
+            )),
+        }
+    }
+
+    /// Pick the password or a `key: value` field out of decrypted content
+    fn select_value(content: &str, field: Option<&str>) -> Option<String> {
+        let mut lines = content.lines();
+        match field {
+            None => lines.next().map(str::to_string),
+            Some(field) => lines.skip(1).find_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                (key.trim() == field).then(|| value.trim().to_string())
+            }),
+        }
+    }
+
+    async fn decrypt(&self, config: &PassResolverConfig) -> Result<String> {
+        let path = Self::encrypted_path(config)?;
+        if !path.is_file() {
+            return Err(Error::secret_resolution(
+                path.display().to_string(),
+                "encrypted secret does not exist",
+            ));
+        }
+
+        let args = CommandArguments::from_vec(vec![
+            "--quiet".to_string(),
+            "--batch".to_string(),
+            "--decrypt".to_string(),
+            path.display().to_string(),
+        ]);
+        let output = self
+            .resilience
+            .call(|| self.executor.execute("gpg", &args))
+            .await?;
+
+        if !output.status.success() {
+            return Err(Error::command_execution(
+                "gpg",
+                args.as_slice().to_vec(),
+                format!(
+                    "decryption failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+                output.status.code(),
+            ));
+        }
+
+        let content = String::from_utf8(output.stdout).map_err(|e| {
+            Error::configuration(format!("decrypted secret is not valid UTF-8: {e}"))
+        })?;
+        Self::select_value(&content, config.field.as_deref()).ok_or_else(|| {
+            Error::secret_resolution(
+                path.display().to_string(),
+                match &config.field {
+                    Some(field) => format!("field '{field}' not found"),
+                    None => "decrypted secret is empty".to_string(),
+                },
+            )
+        })
+    }
+}
+
+impl Default for PassResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl SecretResolver for PassResolver {
+    async fn resolve(&self, reference: &str) -> Result<Option<String>> {
+        let Some(config) = parse_typed_reference::<PassResolverConfig>(reference, "pass")? else {
+            return Ok(None);
+        };
+
+        self.decrypt(&config).await.map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// A resolver for a store whose `work/db` entry decrypts to a password
+    /// followed by fields
+    fn resolver(store: &TempDir) -> PassResolver {
+        std::fs::create_dir(store.path().join("work")).unwrap();
+        let entry = store.path().join("work/db.gpg");
+        std::fs::write(&entry, "encrypted").unwrap();
+
+        let executor = CommandExecutorFactory::test();
+        executor.add_simple_response(
+            "gpg",
+            &[
+                "--quiet".to_string(),
+                "--batch".to_string(),
+                "--decrypt".to_string(),
+                entry.display().to_string(),
+            ],
+            "s3cret\nusername: app\nurl: https://db.internal\n",
+        );
+        PassResolver::with_executor(Box::new(executor))
+    }
+
+    #[tokio::test]
+    async fn test_decrypts_password() {
+        let store = TempDir::new().unwrap();
+        let resolver = resolver(&store);
+
+        let password = format!(
+            r#"cuenv-resolver://{{"type":"pass","name":"work/db","store":"{}"}}"#,
+            store.path().display()
+        );
+        assert_eq!(
+            resolver.resolve(&password).await.unwrap(),
+            Some("s3cret".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_decrypts_field() {
+        let store = TempDir::new().unwrap();
+        let resolver = resolver(&store);
+
+        let username = format!(
+            r#"cuenv-resolver://{{"type":"pass","name":"work/db","store":"{}","field":"username"}}"#,
+            store.path().display()
+        );
+        assert_eq!(
+            resolver.resolve(&username).await.unwrap(),
+            Some("app".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_entry_outside_store_rejected() {
+        let store = TempDir::new().unwrap();
+        let resolver = resolver(&store);
+
+        let escaping = format!(
+            r#"cuenv-resolver://{{"type":"pass","name":"../db","store":"{}"}}"#,
+            store.path().display()
+        );
+        assert!(resolver.resolve(&escaping).await.is_err());
+    }
+}
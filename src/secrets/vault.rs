@@ -0,0 +1,275 @@
+//! HashiCorp Vault KV v2 secret resolver
+use super::{parse_typed_reference, ResolverResilience, SecretResolver};
+use crate::errors::{Error, Result};
+use crate::resilience::{CircuitBreakerConfig, RetryConfig};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Configuration of a `type: "vault"` resolver
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct VaultResolverConfig {
+    /// Vault server address, defaults to `VAULT_ADDR`
+    #[serde(default)]
+    pub address: Option<String>,
+    /// Mount path of the KV v2 engine
+    #[serde(default = "default_mount")]
+    pub mount: String,
+    /// Path of the secret below the mount
+    pub path: String,
+    /// Key within the secret data
+    pub field: String,
+    /// Secret version, defaults to the latest
+    #[serde(default)]
+    pub version: Option<u64>,
+    /// Enterprise namespace, defaults to `VAULT_NAMESPACE`
+    #[serde(default)]
+    pub namespace: Option<String>,
+}
+
+fn default_mount() -> String {
+    "secret".to_string()
+}
+
+/// Reads secrets from the Vault KV v2 HTTP API.
+///
+/// The token is taken from `VAULT_TOKEN` or `~/.vault-token`, like the
+/// `vault` CLI does.
+pub struct VaultResolver {
+    client: reqwest::Client,
+    token: Option<String>,
+    resilience: ResolverResilience,
+}
+
+impl VaultResolver {
+    pub fn new() -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            client,
+            token: None,
+            resilience: ResolverResilience::new(
+                RetryConfig::for_network(),
+                CircuitBreakerConfig {
+                    failure_threshold: 5,
+                    success_threshold: 2,
+                    timeout: Duration::from_secs(300),
+                    break_duration: Duration::from_secs(60),
+                    half_open_max_calls: 3,
+                },
+            ),
+        }
+    }
+
+    /// Use a fixed token instead of the one from the environment
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn with_resilience(
+        mut self,
+        retry_config: RetryConfig,
+        circuit_breaker_config: CircuitBreakerConfig,
+    ) -> Self {
+        self.resilience = ResolverResilience::new(retry_config, circuit_breaker_config);
+        self
+    }
+
+    fn token(&self) -> Result<String> {
+        if let Some(token) = &self.token {
+            return Ok(token.clone());
+        }
+        if let Ok(token) = std::env::var("VAULT_TOKEN") {
+            return Ok(token);
+        }
+
+        let token_path = dirs::home_dir()
+            .map(|home| home.join(".vault-token"))
+            .unwrap_or_else(|| PathBuf::from(".vault-token"));
+        std::fs::read_to_string(&token_path)
+            .map(|token| token.trim().to_string())
+            .map_err(|_| {
+                Error::secret_resolution(
+                    "vault",
+                    "no Vault token found, set VAULT_TOKEN or run `vault login`",
+                )
+            })
+    }
+
+    fn secret_url(config: &VaultResolverConfig) -> Result<String> {
+        let address = match &config.address {
+            Some(address) => address.clone(),
+            None => std::env::var("VAULT_ADDR").map_err(|_| {
+                Error::secret_resolution(
+                    &config.path,
+                    "no Vault address configured, set VAULT_ADDR",
+                )
+            })?,
+        };
+
+        let mut url = format!(
+            "{}/v1/{}/data/{}",
+            address.trim_end_matches('/'),
+            config.mount.trim_matches('/'),
+            config.path.trim_matches('/')
+        );
+        if let Some(version) = config.version {
+            url.push_str(&format!("?version={version}"));
+        }
+        Ok(url)
+    }
+
+    async fn read_secret(&self, config: &VaultResolverConfig) -> Result<String> {
+        let url = Self::secret_url(config)?;
+        let token = self.token()?;
+        let namespace = config
+            .namespace
+            .clone()
+            .or_else(|| std::env::var("VAULT_NAMESPACE").ok());
+
+        self.resilience
+            .call(|| async {
+                let mut request = self.client.get(&url).header("X-Vault-Token", &token);
+                if let Some(namespace) = &namespace {
+                    request = request.header("X-Vault-Namespace", namespace);
+                }
+
+                let response = request
+                    .send()
+                    .await
+                    .map_err(|e| Error::network(&url, e.to_string()))?;
+
+                let status = response.status();
+                if status.is_server_error() {
+                    return Err(Error::network(&url, format!("Vault returned {status}")));
+                }
+                if !status.is_success() {
+                    return Err(Error::secret_resolution(
+                        &config.path,
+                        format!("Vault returned {status}"),
+                    ));
+                }
+
+                let body: serde_json::Value = response
+                    .json()
+                    .await
+                    .map_err(|e| Error::network(&url, format!("invalid Vault response: {e}")))?;
+                Self::extract_field(&body, config)
+            })
+            .await
+    }
+
+    /// Pick the requested field out of a KV v2 read response
+    fn extract_field(body: &serde_json::Value, config: &VaultResolverConfig) -> Result<String> {
+        match body
+            .pointer("/data/data")
+            .and_then(|data| data.get(&config.field))
+        {
+            Some(serde_json::Value::String(value)) => Ok(value.clone()),
+            Some(value) => Ok(value.to_string()),
+            None => Err(Error::secret_resolution(
+                &config.path,
+                format!("field '{}' not found in Vault secret", config.field),
+            )),
+        }
+    }
+}
+
+impl Default for VaultResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl SecretResolver for VaultResolver {
+    async fn resolve(&self, reference: &str) -> Result<Option<String>> {
+        let Some(config) = parse_typed_reference::<VaultResolverConfig>(reference, "vault")? else {
+            return Ok(None);
+        };
+
+        self.read_secret(&config).await.map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serve a single HTTP response and return the request that was received
+    async fn serve_once(
+        status: &'static str,
+        body: &'static str,
+    ) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = vec![0; 4096];
+            let read = stream.read(&mut buffer).await.unwrap();
+            let response = format!(
+                "HTTP/1.1 {status}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&buffer[..read]).to_string()
+        });
+
+        (address, handle)
+    }
+
+    #[tokio::test]
+    async fn test_reads_kv_v2_field() {
+        let (address, request) = serve_once(
+            "200 OK",
+            r#"{"data":{"data":{"password":"hunter2","port":5432},"metadata":{"version":3}}}"#,
+        )
+        .await;
+
+        let reference = format!(
+            r#"cuenv-resolver://{{"type":"vault","address":"{address}","mount":"kv","path":"app/db","field":"password","version":3}}"#
+        );
+        let resolver = VaultResolver::new().with_token("s.test");
+        let secret = resolver.resolve(&reference).await.unwrap();
+        assert_eq!(secret.as_deref(), Some("hunter2"));
+
+        let request = request.await.unwrap().to_lowercase();
+        assert!(request.starts_with("get /v1/kv/data/app/db?version=3 "));
+        assert!(request.contains("x-vault-token: s.test"));
+    }
+
+    #[tokio::test]
+    async fn test_permission_denied_is_not_retried() {
+        let (address, _request) =
+            serve_once("403 Forbidden", r#"{"errors":["permission denied"]}"#).await;
+
+        let reference = format!(
+            r#"cuenv-resolver://{{"type":"vault","address":"{address}","path":"app/db","field":"password"}}"#
+        );
+        // The server only answers once, so a retry would fail with a network error
+        let error = VaultResolver::new()
+            .with_token("s.test")
+            .resolve(&reference)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::SecretResolution { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_ignores_other_resolver_types() {
+        let reference = r#"cuenv-resolver://{"cmd":"op","args":["read","op://vault/item"]}"#;
+        assert!(VaultResolver::new()
+            .resolve(reference)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,509 @@
+//! Security policies suggested from audit runs
+//!
+//! `cuenv run --audit --suggest-policy` turns the [`AuditReport`] of each task
+//! into a `security` block allowing what the task accessed. Paths are
+//! collapsed into as few directories as possible without widening access
+//! beyond what is needed:
+//!
+//! - files of system directories like `/usr`, and of `/tmp`, `/dev` and
+//!   `/proc`, allow the whole directory
+//! - files of the project allow the top-level entry of the project they are in
+//! - other files are merged with the files closest to them into their common
+//!   directory, never above the second level like `/etc/ssl`
+//!
+//! Written files allow their directory, as creating, renaming or removing a
+//! file needs access to the directory. Connections allow the address and port
+//! connected to, which users should replace with the host names they belong
+//! to. `--write` patches the block into the task definition.
+use crate::access_restrictions::{AccessKind, AuditReport};
+use crate::atomic_file::write_atomic_string;
+use crate::errors::{Error, Result};
+use crate::workspace::TaskRef;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Directories allowed as a whole, as programs and libraries are spread across them
+const SYSTEM_DIRS: &[&str] = &[
+    "/bin",
+    "/sbin",
+    "/lib",
+    "/lib32",
+    "/lib64",
+    "/usr",
+    "/nix/store",
+    "/opt/homebrew",
+    "/tmp",
+    "/dev",
+    "/proc",
+];
+
+/// Ports of connections left out of policies, name resolution happens
+/// outside the sandbox
+const IGNORED_PORTS: &[u16] = &[53];
+
+/// A `security` block for a task, as suggested by an audit run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuggestedPolicy {
+    pub task: String,
+    /// Directory of the project the task ran in
+    pub working_dir: PathBuf,
+    pub read_only_paths: Vec<String>,
+    pub read_write_paths: Vec<String>,
+    pub allowed_hosts: Vec<String>,
+}
+
+impl SuggestedPolicy {
+    /// Suggest the policy allowing everything `report` saw the task access
+    pub fn from_report(report: &AuditReport) -> Self {
+        let working_dir = &report.working_dir;
+        let (mut reads, mut writes) = (BTreeMap::new(), BTreeMap::new());
+        for (path, kinds) in &report.files {
+            if kinds.contains(&AccessKind::Write) {
+                let dir = path.parent().unwrap_or(path);
+                let (dir, mergeable) = anchor(dir, working_dir);
+                writes.insert(dir, mergeable);
+            } else {
+                let (path, mergeable) = anchor(path, working_dir);
+                reads.insert(path, mergeable);
+            }
+        }
+
+        let writes = minimize(merge_siblings(writes), &BTreeSet::new());
+        let reads = minimize(merge_siblings(reads), &writes);
+
+        let allowed_hosts: BTreeSet<String> = report
+            .connections
+            .iter()
+            .filter(|addr| !IGNORED_PORTS.contains(&addr.port()))
+            .map(|addr| addr.to_string())
+            .collect();
+
+        let render = |paths: BTreeSet<PathBuf>| {
+            let paths: BTreeSet<String> = paths
+                .iter()
+                .map(|path| display_path(path, working_dir))
+                .collect();
+            paths.into_iter().collect()
+        };
+        Self {
+            task: report.task.clone(),
+            working_dir: working_dir.clone(),
+            read_only_paths: render(reads),
+            read_write_paths: render(writes),
+            allowed_hosts: allowed_hosts.into_iter().collect(),
+        }
+    }
+
+    /// The policy as a CUE `security` block, without indentation
+    pub fn to_cue(&self) -> String {
+        let mut cue =
+            String::from("security: {\n\trestrictDisk:    true\n\trestrictNetwork: true\n");
+        for (field, values) in [
+            ("readOnlyPaths", &self.read_only_paths),
+            ("readWritePaths", &self.read_write_paths),
+            ("allowedHosts", &self.allowed_hosts),
+        ] {
+            if values.is_empty() {
+                let _ = writeln!(cue, "\t{field}: []");
+                continue;
+            }
+            if field == "allowedHosts" {
+                cue.push_str(
+                    "\t// Addresses seen in the audit run, replace them with host names\n",
+                );
+            }
+            let _ = writeln!(cue, "\t{field}: [");
+            for value in values {
+                let _ = writeln!(cue, "\t\t{value:?},");
+            }
+            cue.push_str("\t]\n");
+        }
+        cue.push('}');
+        cue
+    }
+
+    /// Replace or add the `security` block of the task's definition in the
+    /// CUE files of its project, returning the patched file
+    pub fn write_to_task(&self) -> Result<PathBuf> {
+        let name = TaskRef::parse(&self.task).map_or_else(|| self.task.clone(), |r| r.task);
+
+        let mut files: Vec<PathBuf> = fs::read_dir(&self.working_dir)
+            .map_err(|e| Error::file_system(self.working_dir.clone(), "read directory", e))?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "cue"))
+            .collect();
+        files.sort();
+
+        for file in files {
+            let source = fs::read_to_string(&file)
+                .map_err(|e| Error::file_system(file.clone(), "read", e))?;
+            if let Some(patched) = patch_task(&source, &name, &self.to_cue()) {
+                write_atomic_string(&file, &patched)?;
+                return Ok(file);
+            }
+        }
+
+        Err(Error::configuration(format!(
+            "Definition of task '{name}' not found in the CUE files of {}, add the suggested policy by hand",
+            self.working_dir.display()
+        )))
+    }
+}
+
+/// The path to allow for `path`, and whether it may still be merged into its parent
+fn anchor(path: &Path, working_dir: &Path) -> (PathBuf, bool) {
+    // Projects may live in a system directory like `/tmp`
+    if let Ok(relative) = path.strip_prefix(working_dir) {
+        let top = relative
+            .components()
+            .next()
+            .map_or_else(|| working_dir.to_path_buf(), |c| working_dir.join(c));
+        return (top, false);
+    }
+    if let Some(dir) = SYSTEM_DIRS.iter().find(|dir| path.starts_with(dir)) {
+        return (PathBuf::from(dir), false);
+    }
+    (path.to_path_buf(), true)
+}
+
+/// Merge entries into their closest common directory with other entries,
+/// closest first, as long as it is at least two levels deep
+fn merge_siblings(mut paths: BTreeMap<PathBuf, bool>) -> BTreeMap<PathBuf, bool> {
+    loop {
+        let mergeable: Vec<&PathBuf> = paths
+            .iter()
+            .filter(|(_, mergeable)| **mergeable)
+            .map(|(path, _)| path)
+            .collect();
+        let Some(common) = mergeable
+            .windows(2)
+            .filter_map(|pair| common_ancestor(pair[0], pair[1]))
+            .filter(|common| depth(common) >= 2)
+            .max_by_key(|common| depth(common))
+        else {
+            return paths;
+        };
+
+        paths.retain(|path, mergeable| !*mergeable || !path.starts_with(&common));
+        paths.insert(common, true);
+    }
+}
+
+fn common_ancestor(a: &Path, b: &Path) -> Option<PathBuf> {
+    a.ancestors()
+        .skip(1)
+        .find(|ancestor| b.starts_with(ancestor))
+        .map(Path::to_path_buf)
+}
+
+/// Leave out paths allowed through one of their ancestors, or through `covered`
+fn minimize(paths: BTreeMap<PathBuf, bool>, covered: &BTreeSet<PathBuf>) -> BTreeSet<PathBuf> {
+    let paths: BTreeSet<PathBuf> = paths.into_keys().collect();
+    paths
+        .iter()
+        .filter(|path| {
+            !path
+                .ancestors()
+                .skip(1)
+                .any(|ancestor| paths.contains(ancestor))
+                && !path.ancestors().any(|ancestor| covered.contains(ancestor))
+        })
+        .cloned()
+        .collect()
+}
+
+fn depth(path: &Path) -> usize {
+    path.components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .count()
+}
+
+/// Paths of the project relative to it, as `./src`, others absolute
+fn display_path(path: &Path, working_dir: &Path) -> String {
+    match path.strip_prefix(working_dir) {
+        Ok(relative) if relative.as_os_str().is_empty() => ".".to_string(),
+        Ok(relative) => format!("./{}", relative.display()),
+        Err(_) => path.display().to_string(),
+    }
+}
+
+/// `source` with `block` as the `security` field of task `name`
+fn patch_task(source: &str, name: &str, block: &str) -> Option<String> {
+    let tasks = find_field(source, 0, source.len(), "tasks")?;
+    let (task_open, task_close) = if source[tasks..].starts_with('{') {
+        let close = matching_brace(source, tasks)?;
+        let value = find_field(source, tasks + 1, close, name)?;
+        (value, matching_brace(source, value)?)
+    } else {
+        // `tasks: name: { ... }`
+        let key = [name.to_string(), format!("{name:?}")]
+            .into_iter()
+            .find(|key| source[tasks..].starts_with(key.as_str()))?;
+        let value = value_after(source, tasks + key.len())?;
+        (value, matching_brace(source, value)?)
+    };
+    if !source[task_open..].starts_with('{') {
+        return None;
+    }
+
+    let line_indent = |pos: usize| {
+        let start = source[..pos].rfind('\n').map_or(0, |i| i + 1);
+        source[start..]
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect::<String>()
+    };
+    let close_indent = line_indent(task_close);
+    let content = source[task_open + 1..task_close].trim_end().len() + task_open + 1;
+    let indent = match source[task_open + 1..task_close].find(|c: char| !c.is_whitespace()) {
+        Some(offset) if source[task_open + 1..task_open + 1 + offset].contains('\n') => {
+            line_indent(task_open + 1 + offset)
+        }
+        _ => format!("{close_indent}\t"),
+    };
+    let block = block.replace('\n', &format!("\n{indent}"));
+
+    if let Some(security) = find_field(source, task_open + 1, task_close, "security") {
+        if source[security..].starts_with('{') {
+            let key = source[..security].rfind("security")?;
+            let end = matching_brace(source, security)? + 1;
+            return Some(format!("{}{block}{}", &source[..key], &source[end..]));
+        }
+    }
+
+    let mut patched = format!("{}\n{indent}{block}", &source[..content]);
+    if !source[content..task_close].contains('\n') {
+        patched.push('\n');
+        patched.push_str(&close_indent);
+    }
+    patched.push_str(&source[content..]);
+    Some(patched)
+}
+
+/// Position of the value of field `key` of the struct between `from` and
+/// `to`, skipping nested values, strings and comments
+fn find_field(source: &str, from: usize, to: usize, key: &str) -> Option<usize> {
+    let bytes = source.as_bytes();
+    let to = to.min(bytes.len());
+    let mut depth = 0;
+    let mut i = from;
+    while i < to {
+        match bytes[i] {
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                i = source[i..].find('\n').map_or(to, |end| i + end);
+            }
+            b'{' | b'[' | b'(' => {
+                depth += 1;
+                i += 1;
+            }
+            b'}' | b']' | b')' => {
+                depth -= 1;
+                i += 1;
+            }
+            b'"' => {
+                let end = string_end(bytes, i)?;
+                if depth == 0 && &source[i + 1..end] == key {
+                    if let Some(value) = value_after(source, end + 1) {
+                        return Some(value);
+                    }
+                }
+                i = end + 1;
+            }
+            b if is_identifier(b) => {
+                let start = i;
+                while i < to && is_identifier(bytes[i]) {
+                    i += 1;
+                }
+                if depth == 0 && &source[start..i] == key {
+                    if let Some(value) = value_after(source, i) {
+                        return Some(value);
+                    }
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+fn is_identifier(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'_' | b'#' | b'$' | b'.' | b'-')
+}
+
+/// Position of the value following a `:` at `pos`, spaces aside
+fn value_after(source: &str, pos: usize) -> Option<usize> {
+    let rest = source[pos..].trim_start_matches([' ', '\t']);
+    let rest = rest.strip_prefix(':')?;
+    let value = rest.trim_start();
+    Some(source.len() - value.len())
+}
+
+/// Position of the `}` closing the `{` at `open`
+fn matching_brace(source: &str, open: usize) -> Option<usize> {
+    let bytes = source.as_bytes();
+    let mut depth = 0;
+    let mut i = open;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                i = source[i..].find('\n').map_or(bytes.len(), |end| i + end);
+                continue;
+            }
+            b'"' => i = string_end(bytes, i)?,
+            b'{' => depth += 1,
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Position of the `"` closing the string opened at `start`
+fn string_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return Some(i),
+            b'\n' => return None,
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Audit report of a cargo build in `project`
+    fn build_report(project: &Path) -> AuditReport {
+        let mut report = AuditReport::new("build");
+        report.working_dir = project.to_path_buf();
+        for (path, kind) in [
+            ("/usr/bin/cargo", AccessKind::Exec),
+            ("/usr/lib/libc.so.6", AccessKind::Read),
+            ("/etc/hostname", AccessKind::Read),
+            ("/home/dev/.cargo/registry/a/lib.rs", AccessKind::Read),
+            ("/home/dev/.cargo/registry/b/lib.rs", AccessKind::Read),
+            ("/home/dev/.cargo/config.toml", AccessKind::Read),
+            ("/proc/self/maps", AccessKind::Read),
+        ] {
+            report.files.insert(path.into(), BTreeSet::from([kind]));
+        }
+        for (path, kind) in [
+            ("src/main.rs", AccessKind::Read),
+            ("Cargo.toml", AccessKind::Read),
+            ("target/debug/app", AccessKind::Write),
+            ("target/debug/deps/app.d", AccessKind::Write),
+        ] {
+            report
+                .files
+                .insert(project.join(path), BTreeSet::from([kind]));
+        }
+        report
+            .connections
+            .insert("140.82.112.3:443".parse().unwrap());
+        report.connections.insert("127.0.0.53:53".parse().unwrap());
+        report
+    }
+
+    /// Project with a build and a test task, the latter with a security block
+    fn project() -> tempfile::TempDir {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let source = "package env\n\ntasks: {\n\tbuild: {\n\t\tcommand: \"cargo build\" // }\n\t}\n\ttest: {\n\t\tsecurity: {\n\t\t\trestrictDisk: false\n\t\t}\n\t}\n}\n";
+        fs::write(temp_dir.path().join("env.cue"), source).unwrap();
+        temp_dir
+    }
+
+    #[test]
+    fn test_suggest_paths() {
+        let project = PathBuf::from("/home/dev/project");
+        let policy = SuggestedPolicy::from_report(&build_report(&project));
+        assert_eq!(
+            policy.read_only_paths,
+            [
+                "./Cargo.toml",
+                "./src",
+                "/etc/hostname",
+                "/home/dev/.cargo",
+                "/proc",
+                "/usr"
+            ]
+        );
+        assert_eq!(policy.read_write_paths, ["./target"]);
+    }
+
+    #[test]
+    fn test_suggest_hosts_without_loopback() {
+        let project = PathBuf::from("/home/dev/project");
+        let policy = SuggestedPolicy::from_report(&build_report(&project));
+        assert_eq!(policy.allowed_hosts, ["140.82.112.3:443"]);
+    }
+
+    #[test]
+    fn test_write_policy_to_task() {
+        let project = project();
+        let policy = SuggestedPolicy::from_report(&build_report(project.path()));
+
+        let env_cue = project.path().join("env.cue");
+        assert_eq!(policy.write_to_task().unwrap(), env_cue);
+        let patched = fs::read_to_string(&env_cue).unwrap();
+        assert!(patched.contains(
+            "\t\tcommand: \"cargo build\" // }\n\t\tsecurity: {\n\t\t\trestrictDisk:    true\n"
+        ));
+        assert!(patched.contains(
+            "\t\t\t// Addresses seen in the audit run, replace them with host names\n\t\t\tallowedHosts: [\n\t\t\t\t\"140.82.112.3:443\",\n\t\t\t]\n\t\t}\n\t}\n\ttest:"
+        ));
+    }
+
+    #[test]
+    fn test_write_policy_replaces_security_block() {
+        let project = project();
+        let policy = SuggestedPolicy {
+            task: "test".to_string(),
+            ..SuggestedPolicy::from_report(&build_report(project.path()))
+        };
+
+        policy.write_to_task().unwrap();
+        let patched = fs::read_to_string(project.path().join("env.cue")).unwrap();
+        assert!(!patched.contains("restrictDisk: false"));
+        assert_eq!(patched.matches("restrictNetwork: true").count(), 1);
+    }
+
+    #[test]
+    fn test_write_policy_to_missing_task() {
+        let project = project();
+        let policy = SuggestedPolicy {
+            task: "deploy".to_string(),
+            ..SuggestedPolicy::from_report(&build_report(project.path()))
+        };
+        assert!(policy.write_to_task().is_err());
+    }
+
+    #[test]
+    fn test_temporary_files_allow_tmp() {
+        let mut report = AuditReport::new("test");
+        report.working_dir = PathBuf::from("/home/dev/project");
+        for (path, kind) in [
+            ("/tmp/x", AccessKind::Write),
+            ("/dev/null", AccessKind::Read),
+            ("/proc/self/status", AccessKind::Read),
+        ] {
+            report.files.insert(path.into(), BTreeSet::from([kind]));
+        }
+
+        let policy = SuggestedPolicy::from_report(&report);
+        assert_eq!(policy.read_write_paths, ["/tmp"]);
+        assert_eq!(policy.read_only_paths, ["/dev", "/proc"]);
+    }
+}
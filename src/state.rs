@@ -21,6 +21,15 @@ pub struct CuenvState {
     pub environment: Option<String>,
     /// The capabilities that were loaded
     pub capabilities: Vec<String>,
+    /// The directory each CUE-defined variable was loaded from
+    #[serde(default)]
+    pub sources: HashMap<String, PathBuf>,
+    /// The instance selected when loading, reused when the hook reloads
+    #[serde(default)]
+    pub instance: Option<String>,
+    /// The files of the loaded package
+    #[serde(default)]
+    pub files: Vec<PathBuf>,
 }
 
 /// Represents a snapshot of environment variables for rollback
@@ -201,41 +210,36 @@ impl StateManager {
     }
 
     /// Store the core state information
-    async fn store_state(
-        transaction: &mut StateTransaction,
-        dir: &Path,
-        file: &Path,
-        environment: Option<&str>,
-        capabilities: &[String],
-    ) -> Result<()> {
+    async fn store_state(transaction: &mut StateTransaction, state: &CuenvState) -> Result<()> {
         // Log environment state change
         if let Some(logger) = audit_logger() {
             let _ = logger
-                .log_environment_change("load", dir, environment, capabilities)
+                .log_environment_change(
+                    "load",
+                    &state.dir,
+                    state.environment.as_deref(),
+                    &state.capabilities,
+                )
                 .await;
         }
 
         // Set CUENV_DIR with leading '-' like direnv
         transaction.set_var(
             Self::env_var_name("CUENV_DIR"),
-            format!("-{}", dir.display()),
+            format!("-{}", state.dir.display()),
         );
 
         // Set CUENV_FILE
-        transaction.set_var(Self::env_var_name("CUENV_FILE"), file.display().to_string());
-
-        // Create and encode the state object
-        let state = CuenvState {
-            dir: dir.to_path_buf(),
-            file: file.to_path_buf(),
-            environment: environment.map(str::to_string),
-            capabilities: capabilities.to_vec(),
-        };
+        transaction.set_var(
+            Self::env_var_name("CUENV_FILE"),
+            state.file.display().to_string(),
+        );
 
+        // Encode the state object
         Self::encode_and_store(
             transaction,
             Self::env_var_name("CUENV_STATE"),
-            &state,
+            state,
             "Failed to encode state",
         )?;
 
@@ -276,11 +280,47 @@ impl StateManager {
         diff: &EnvDiff,
         watches: &FileTimes,
     ) -> Result<()> {
+        Self::load_with_sources(
+            dir,
+            file,
+            environment,
+            capabilities,
+            diff,
+            watches,
+            &HashMap::new(),
+        )
+        .await
+    }
+
+    /// Load state for a directory, recording which directory each variable came from
+    pub async fn load_with_sources(
+        dir: &Path,
+        file: &Path,
+        environment: Option<&str>,
+        capabilities: &[String],
+        diff: &EnvDiff,
+        watches: &FileTimes,
+        sources: &HashMap<String, PathBuf>,
+    ) -> Result<()> {
+        let state = CuenvState {
+            dir: dir.to_path_buf(),
+            file: file.to_path_buf(),
+            environment: environment.map(str::to_string),
+            capabilities: capabilities.to_vec(),
+            sources: sources.clone(),
+            instance: None,
+            files: vec![file.to_path_buf()],
+        };
+        Self::load_state(&state, diff, watches).await
+    }
+
+    /// Load a complete state, including the selected instance and package files
+    pub async fn load_state(state: &CuenvState, diff: &EnvDiff, watches: &FileTimes) -> Result<()> {
         // Create a transaction with snapshot of current state
         let mut transaction = StateTransaction::new(&Self::state_var_names())?;
 
         // Store all state components (this includes async logging)
-        Self::store_state(&mut transaction, dir, file, environment, capabilities).await?;
+        Self::store_state(&mut transaction, state).await?;
         Self::store_metadata(&mut transaction, diff, watches)?;
 
         // Now acquire the lock and commit
@@ -507,6 +547,21 @@ mod tests {
         let loaded_diff = StateManager::get_diff().unwrap().unwrap();
         assert_eq!(loaded_diff, diff);
 
+        // The selected instance and package files survive a reload
+        let package_file = dir.join("backend.cue");
+        let state = CuenvState {
+            file: package_file.clone(),
+            instance: Some("backend".to_string()),
+            files: vec![package_file.clone()],
+            ..state
+        };
+        StateManager::load_state(&state, &diff, &watches)
+            .await
+            .unwrap();
+        let state = StateManager::get_state().unwrap().unwrap();
+        assert_eq!(state.instance, Some("backend".to_string()));
+        assert_eq!(state.files, vec![package_file]);
+
         // Unload
         StateManager::unload().await.unwrap();
         assert!(!StateManager::is_loaded());
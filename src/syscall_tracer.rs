@@ -0,0 +1,563 @@
+//! In-process syscall tracer behind audit mode
+//!
+//! Audited tasks run under ptrace, stopping at every syscall. The tracer
+//! follows forks, clones and execs, and records the files each process
+//! successfully reads, writes or executes, resolving relative paths against
+//! the process's working directory or directory descriptor, and the addresses
+//! it connects to. Tracees are children of the thread tracing them only, so
+//! concurrently audited tasks never see each other's processes. Like any task,
+//! the traced command's process group is killed at its timeout or when the
+//! run is cancelled.
+use crate::access_restrictions::{AccessKind, AuditReport};
+use crate::errors::{Error, Result};
+use libc::pid_t;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::{mpsc, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Not in libc for musl, available since Linux 5.3
+const PTRACE_GET_SYSCALL_INFO: libc::c_uint = 0x420e;
+const PTRACE_SYSCALL_INFO_ENTRY: u8 = 1;
+const PTRACE_SYSCALL_INFO_EXIT: u8 = 2;
+
+/// Longest path read from a tracee
+const MAX_PATH_LEN: usize = 4096;
+const PAGE_SIZE: usize = 4096;
+
+/// How often the timeout and cancellation are checked
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// `struct ptrace_syscall_info`, with the entry arguments or exit value in `data`
+#[repr(C)]
+#[derive(Default)]
+struct SyscallInfo {
+    op: u8,
+    _pad: [u8; 3],
+    _arch: u32,
+    _instruction_pointer: u64,
+    _stack_pointer: u64,
+    data: [u64; 8],
+}
+
+/// An access a syscall makes if it succeeds
+enum Access {
+    File(PathBuf, AccessKind),
+    Connect(SocketAddr),
+}
+
+/// Run `cmd` to completion under the tracer, returning its exit code and the
+/// accesses of all its processes. Its process group is killed and an error
+/// returned once `timeout` passes or `cancel` is set.
+pub fn trace(
+    cmd: &mut Command,
+    task: &str,
+    timeout: Duration,
+    cancel: &AtomicBool,
+) -> Result<(i32, AuditReport)> {
+    let watch = Watch::default();
+    let watch = &watch;
+    let (done, finished) = mpsc::channel::<()>();
+
+    let traced = thread::scope(|scope| {
+        // A dedicated thread, as only the tracing thread may wait for its tracees
+        let tracer = scope.spawn(move || {
+            let _done = done;
+            Tracer::new(task).run(cmd, watch)
+        });
+        watch.enforce(&finished, timeout, cancel);
+        tracer.join()
+    })
+    .map_err(|_| Error::configuration("Syscall tracer panicked"))?;
+
+    match watch.stop.get() {
+        Some(Stop::Timeout) => Err(Error::timeout(format!("task {task}"), timeout)),
+        Some(Stop::Cancelled) => Err(Error::configuration("Process cancelled")),
+        None => traced,
+    }
+}
+
+/// Why the traced command was killed
+#[derive(Debug, Clone, Copy)]
+enum Stop {
+    Timeout,
+    Cancelled,
+}
+
+/// State shared between the tracer and the thread enforcing the timeout
+#[derive(Default)]
+struct Watch {
+    /// Process group of the traced command, once spawned
+    root: AtomicI32,
+    stop: OnceLock<Stop>,
+}
+
+impl Watch {
+    /// Wait until the tracer is `finished`, killing the traced command at the
+    /// timeout or when cancelled
+    fn enforce(&self, finished: &mpsc::Receiver<()>, timeout: Duration, cancel: &AtomicBool) {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let wait = deadline.saturating_duration_since(Instant::now());
+            match finished.recv_timeout(wait.min(POLL_INTERVAL)) {
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                _ => return,
+            }
+            if cancel.load(Ordering::SeqCst) {
+                return self.kill(Stop::Cancelled);
+            }
+            if Instant::now() >= deadline {
+                return self.kill(Stop::Timeout);
+            }
+        }
+    }
+
+    fn kill(&self, stop: Stop) {
+        if self.stop.set(stop).is_ok() {
+            self.kill_group();
+        }
+    }
+
+    /// Kill the process group, if the command was spawned and is to be stopped
+    fn kill_group(&self) {
+        let root = self.root.load(Ordering::SeqCst);
+        if root > 0 && self.stop.get().is_some() {
+            // Safety: killpg only sends a signal to the command's process group.
+            // Tracees stopped by the tracer are killed all the same.
+            unsafe { libc::killpg(root, libc::SIGKILL) };
+        }
+    }
+}
+
+struct Tracer {
+    report: AuditReport,
+    /// Accesses of the syscall each process is in, recorded once it succeeds
+    pending: HashMap<pid_t, Vec<Access>>,
+    /// Processes whose first stop has been seen
+    known: HashSet<pid_t>,
+    /// New processes whose first stop is still to come
+    awaiting_stop: HashSet<pid_t>,
+}
+
+impl Tracer {
+    fn new(task: &str) -> Self {
+        Self {
+            report: AuditReport::new(task),
+            pending: HashMap::new(),
+            known: HashSet::new(),
+            awaiting_stop: HashSet::new(),
+        }
+    }
+
+    fn run(mut self, cmd: &mut Command, watch: &Watch) -> Result<(i32, AuditReport)> {
+        let program = cmd.get_program().to_string_lossy().into_owned();
+        self.report.working_dir = match cmd.get_current_dir() {
+            Some(dir) => dir.to_path_buf(),
+            None => std::env::current_dir().unwrap_or_default(),
+        };
+        let traced = |e: io::Error| {
+            Error::command_execution(
+                program.clone(),
+                Vec::new(),
+                format!("Failed to trace command: {e}"),
+                None,
+            )
+        };
+
+        // Safety: PTRACE_TRACEME only makes this thread the tracer of the child
+        unsafe {
+            cmd.pre_exec(|| ptrace(libc::PTRACE_TRACEME as _, 0, 0, 0).map(drop));
+        }
+        cmd.process_group(0);
+        let child = cmd.spawn().map_err(traced)?;
+        let root = child.id() as pid_t;
+        // The command may have been stopped before it was spawned
+        watch.root.store(root, Ordering::SeqCst);
+        watch.kill_group();
+
+        // The child stops at its exec, before running the program
+        let status = wait(root).map_err(traced)?;
+        if !libc::WIFSTOPPED(status) {
+            return Err(traced(io::Error::other("command exited before tracing")));
+        }
+        let options = libc::PTRACE_O_TRACESYSGOOD
+            | libc::PTRACE_O_TRACEFORK
+            | libc::PTRACE_O_TRACEVFORK
+            | libc::PTRACE_O_TRACECLONE
+            | libc::PTRACE_O_TRACEEXEC
+            | libc::PTRACE_O_EXITKILL;
+        ptrace(libc::PTRACE_SETOPTIONS as _, root, 0, options as usize).map_err(traced)?;
+        if let Ok(exe) = std::fs::read_link(format!("/proc/{root}/exe")) {
+            self.record_file(exe, AccessKind::Exec);
+        }
+        self.known.insert(root);
+        resume(root, 0);
+
+        let mut exit_code = 1;
+        loop {
+            let mut status = 0;
+            // Safety: waits for tracees of this thread only
+            let pid = unsafe { libc::waitpid(-1, &mut status, libc::__WALL | libc::__WNOTHREAD) };
+            if pid == -1 {
+                match io::Error::last_os_error() {
+                    e if e.kind() == io::ErrorKind::Interrupted => continue,
+                    e if e.raw_os_error() == Some(libc::ECHILD) => break,
+                    e => return Err(traced(e)),
+                }
+            }
+
+            if libc::WIFEXITED(status) || libc::WIFSIGNALED(status) {
+                self.pending.remove(&pid);
+                if pid == root && libc::WIFEXITED(status) {
+                    exit_code = libc::WEXITSTATUS(status);
+                }
+                // Tracees outside the killed group die once this thread exits
+                if pid == root && watch.stop.get().is_some() {
+                    break;
+                }
+                continue;
+            }
+            if !libc::WIFSTOPPED(status) {
+                continue;
+            }
+
+            let signal = match libc::WSTOPSIG(status) {
+                sig if sig == libc::SIGTRAP | 0x80 => {
+                    self.syscall_stop(pid);
+                    0
+                }
+                libc::SIGTRAP if status >> 16 != 0 => {
+                    self.event_stop(pid, status >> 16);
+                    0
+                }
+                libc::SIGSTOP if self.awaiting_stop.remove(&pid) | self.known.insert(pid) => 0,
+                sig => sig,
+            };
+            resume(pid, signal);
+        }
+
+        Ok((exit_code, self.report))
+    }
+
+    fn event_stop(&mut self, pid: pid_t, event: libc::c_int) {
+        if !matches!(
+            event,
+            libc::PTRACE_EVENT_FORK | libc::PTRACE_EVENT_VFORK | libc::PTRACE_EVENT_CLONE
+        ) {
+            return;
+        }
+        let mut new_pid: libc::c_ulong = 0;
+        let message = &mut new_pid as *mut libc::c_ulong as usize;
+        if ptrace(libc::PTRACE_GETEVENTMSG as _, pid, 0, message).is_ok() {
+            let new_pid = new_pid as pid_t;
+            if !self.known.contains(&new_pid) {
+                self.awaiting_stop.insert(new_pid);
+            }
+        }
+    }
+
+    fn syscall_stop(&mut self, pid: pid_t) {
+        let mut info = SyscallInfo::default();
+        let size = std::mem::size_of::<SyscallInfo>();
+        let info_addr = &mut info as *mut SyscallInfo as usize;
+        if ptrace(PTRACE_GET_SYSCALL_INFO as _, pid, size, info_addr).is_err() {
+            return;
+        }
+
+        match info.op {
+            PTRACE_SYSCALL_INFO_ENTRY => {
+                let mut args = [0; 6];
+                args.copy_from_slice(&info.data[1..7]);
+                let accesses = syscall_accesses(pid, info.data[0] as libc::c_long, args);
+                self.pending.insert(pid, accesses);
+            }
+            PTRACE_SYSCALL_INFO_EXIT => {
+                let accesses = self.pending.remove(&pid).unwrap_or_default();
+                let result = info.data[0] as i64;
+                if result >= 0 || result == -i64::from(libc::EINPROGRESS) {
+                    for access in accesses {
+                        match access {
+                            Access::File(path, kind) => self.record_file(path, kind),
+                            Access::Connect(addr) => {
+                                self.report.connections.insert(addr);
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn record_file(&mut self, path: PathBuf, kind: AccessKind) {
+        self.report.files.entry(path).or_default().insert(kind);
+    }
+}
+
+/// Accesses the syscall `nr` with `args` makes, read at its entry
+fn syscall_accesses(pid: pid_t, nr: libc::c_long, args: [u64; 6]) -> Vec<Access> {
+    let file = |dirfd: u64, path: u64, kind: AccessKind| {
+        resolve_path(pid, dirfd as i32, path).map(|path| Access::File(path, kind))
+    };
+    let cwd = libc::AT_FDCWD as u64;
+
+    let accesses = match nr {
+        libc::SYS_openat => vec![file(args[0], args[1], open_kind(args[2]))],
+        libc::SYS_openat2 => {
+            // The flags are the first field of struct open_how
+            let flags = read_memory(pid, args[2], 8)
+                .and_then(|bytes| Some(u64::from_ne_bytes(bytes.try_into().ok()?)))
+                .unwrap_or(0);
+            vec![file(args[0], args[1], open_kind(flags))]
+        }
+        libc::SYS_execve => vec![file(cwd, args[0], AccessKind::Exec)],
+        libc::SYS_execveat => vec![file(args[0], args[1], AccessKind::Exec)],
+        libc::SYS_unlinkat | libc::SYS_mkdirat => vec![file(args[0], args[1], AccessKind::Write)],
+        libc::SYS_renameat | libc::SYS_renameat2 => vec![
+            file(args[0], args[1], AccessKind::Write),
+            file(args[2], args[3], AccessKind::Write),
+        ],
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_open => vec![file(cwd, args[0], open_kind(args[1]))],
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_creat | libc::SYS_unlink | libc::SYS_rmdir | libc::SYS_mkdir => {
+            vec![file(cwd, args[0], AccessKind::Write)]
+        }
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_rename => vec![
+            file(cwd, args[0], AccessKind::Write),
+            file(cwd, args[1], AccessKind::Write),
+        ],
+        libc::SYS_connect => vec![read_socket_addr(pid, args[1], args[2]).map(Access::Connect)],
+        _ => Vec::new(),
+    };
+    accesses.into_iter().flatten().collect()
+}
+
+/// Read access for read-only opens, write access otherwise
+fn open_kind(flags: u64) -> AccessKind {
+    let flags = flags as libc::c_int;
+    if flags & libc::O_ACCMODE == libc::O_RDONLY && flags & (libc::O_CREAT | libc::O_TRUNC) == 0 {
+        AccessKind::Read
+    } else {
+        AccessKind::Write
+    }
+}
+
+/// The path at `addr` in the tracee, made absolute against `dirfd` or its
+/// working directory
+fn resolve_path(pid: pid_t, dirfd: i32, addr: u64) -> Option<PathBuf> {
+    let path = PathBuf::from(OsStr::from_bytes(&read_string(pid, addr)?));
+    let base = || {
+        let link = if dirfd == libc::AT_FDCWD {
+            format!("/proc/{pid}/cwd")
+        } else {
+            format!("/proc/{pid}/fd/{dirfd}")
+        };
+        std::fs::read_link(link).ok()
+    };
+
+    let path = if path.is_absolute() {
+        path
+    } else if path.as_os_str().is_empty() {
+        base()?
+    } else {
+        base()?.join(path)
+    };
+    Some(normalize(&path))
+}
+
+/// Remove `.` and `..` components without following symlinks
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+fn read_socket_addr(pid: pid_t, addr: u64, len: u64) -> Option<SocketAddr> {
+    let bytes = read_memory(pid, addr, (len as usize).min(128))?;
+    let family = libc::c_int::from(u16::from_ne_bytes([*bytes.first()?, *bytes.get(1)?]));
+    let port = u16::from_be_bytes([*bytes.get(2)?, *bytes.get(3)?]);
+
+    match family {
+        libc::AF_INET => {
+            let ip: [u8; 4] = bytes.get(4..8)?.try_into().ok()?;
+            Some(SocketAddr::from((Ipv4Addr::from(ip), port)))
+        }
+        libc::AF_INET6 => {
+            let ip: [u8; 16] = bytes.get(8..24)?.try_into().ok()?;
+            Some(SocketAddr::from((Ipv6Addr::from(ip), port)))
+        }
+        _ => None,
+    }
+}
+
+/// A NUL-terminated string in the tracee, read a page at a time so reads stay
+/// within mapped memory
+fn read_string(pid: pid_t, mut addr: u64) -> Option<Vec<u8>> {
+    let mut string = Vec::new();
+    while string.len() < MAX_PATH_LEN {
+        let chunk = PAGE_SIZE - (addr as usize % PAGE_SIZE);
+        let bytes = read_memory(pid, addr, chunk)?;
+        if let Some(end) = bytes.iter().position(|b| *b == 0) {
+            string.extend_from_slice(&bytes[..end]);
+            return Some(string);
+        }
+        string.extend_from_slice(&bytes);
+        addr += chunk as u64;
+    }
+    None
+}
+
+fn read_memory(pid: pid_t, addr: u64, len: usize) -> Option<Vec<u8>> {
+    if addr == 0 {
+        return None;
+    }
+    let mut buffer = vec![0u8; len];
+    let local = libc::iovec {
+        iov_base: buffer.as_mut_ptr().cast(),
+        iov_len: len,
+    };
+    let remote = libc::iovec {
+        iov_base: addr as *mut libc::c_void,
+        iov_len: len,
+    };
+    // Safety: only writes to the local buffer of the given length
+    let read = unsafe { libc::process_vm_readv(pid, &local, 1, &remote, 1, 0) };
+    if read < 0 {
+        return None;
+    }
+    buffer.truncate(read as usize);
+    Some(buffer)
+}
+
+fn ptrace(request: libc::c_uint, pid: pid_t, addr: usize, data: usize) -> io::Result<libc::c_long> {
+    // Safety: the requests used here only read from or write to `data` as
+    // given by the caller
+    let result = unsafe { libc::ptrace(request as _, pid, addr, data) };
+    if result == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result)
+    }
+}
+
+fn resume(pid: pid_t, signal: libc::c_int) {
+    // A tracee can die at any point, then there is nothing left to resume
+    let _ = ptrace(libc::PTRACE_SYSCALL as _, pid, 0, signal as usize);
+}
+
+fn wait(pid: pid_t) -> io::Result<libc::c_int> {
+    let mut status = 0;
+    loop {
+        // Safety: waits for the given child of this thread
+        if unsafe { libc::waitpid(pid, &mut status, libc::__WALL) } != -1 {
+            return Ok(status);
+        }
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+    use tempfile::TempDir;
+
+    /// Trace `script` in a directory holding `src/input`
+    fn trace_script(script: &str) -> (TempDir, PathBuf, i32, AuditReport) {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = std::fs::canonicalize(temp_dir.path()).unwrap();
+        std::fs::create_dir(dir.join("src")).unwrap();
+        std::fs::write(dir.join("src/input"), "data").unwrap();
+
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(script).current_dir(&dir);
+        let cancel = AtomicBool::new(false);
+        let (exit_code, report) =
+            trace(&mut cmd, "build", Duration::from_secs(30), &cancel).unwrap();
+        (temp_dir, dir, exit_code, report)
+    }
+
+    fn kinds(report: &AuditReport, path: &Path) -> BTreeSet<AccessKind> {
+        report.files.get(path).cloned().unwrap_or_default()
+    }
+
+    #[test]
+    fn test_trace_reports_exit_code_and_working_dir() {
+        let (_temp_dir, dir, exit_code, report) = trace_script("exit 3");
+        assert_eq!(exit_code, 3);
+        assert_eq!(report.task, "build");
+        assert_eq!(report.working_dir, dir);
+    }
+
+    #[test]
+    fn test_trace_records_reads_relative_to_changed_directory() {
+        let (_temp_dir, dir, _, report) = trace_script("cd src && cat input > /dev/null");
+        assert_eq!(
+            kinds(&report, &dir.join("src/input")),
+            BTreeSet::from([AccessKind::Read])
+        );
+    }
+
+    #[test]
+    fn test_trace_records_writes_of_removed_files() {
+        let (_temp_dir, dir, _, report) = trace_script("echo data > output && rm output");
+        assert_eq!(
+            kinds(&report, &dir.join("output")),
+            BTreeSet::from([AccessKind::Write])
+        );
+    }
+
+    #[test]
+    fn test_trace_records_executed_programs() {
+        let (_temp_dir, _, _, report) = trace_script("(cat src/input > /dev/null)");
+        assert!(report
+            .files
+            .iter()
+            .any(|(path, kinds)| path.ends_with("cat") && kinds.contains(&AccessKind::Exec)));
+    }
+
+    #[test]
+    fn test_trace_kills_command_at_timeout() {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg("sleep 30 & sleep 30");
+        let cancel = AtomicBool::new(false);
+
+        let started = Instant::now();
+        let result = trace(&mut cmd, "slow", Duration::from_millis(200), &cancel);
+        assert!(matches!(result, Err(Error::Timeout { .. })));
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_trace_kills_command_when_cancelled() {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg("sleep 30");
+        let cancel = AtomicBool::new(true);
+
+        let started = Instant::now();
+        let result = trace(&mut cmd, "slow", Duration::from_secs(60), &cancel);
+        assert!(result.unwrap_err().to_string().contains("cancelled"));
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+}
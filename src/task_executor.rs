@@ -1,23 +1,203 @@
-use crate::cache::CacheManager;
+use crate::access_restrictions::{AccessRestrictions, AuditReport};
+use crate::audit::audit_logger;
+use crate::cache::{CacheKeyComponents, CacheManager};
+#[cfg(target_os = "linux")]
+use crate::cgroup::TaskCgroup;
 use crate::cleanup::ProcessGuard;
+use crate::constants::{CUENV_RESOLVER_PREFIX, TASK_TIMEOUT_EXIT_CODE};
 use crate::cue_parser::TaskConfig;
-use crate::env_manager::EnvManager;
+use crate::env_manager::{EnvManager, TaskEnv};
 use crate::errors::{Error, Result};
+use crate::remote_cache::RemoteCacheClient;
+use crate::resilience::{self, RetryConfig};
+use crate::resource_limits::{ResourceLimits, ResourceUsage, TaskResources};
+use crate::secrets::SecretManager;
 use crate::security::SecurityValidator;
-use std::collections::{HashMap, HashSet, VecDeque};
+use crate::task_graph::TaskGraph;
+use crate::task_output::TaskOutput;
+use crate::task_params;
+use crate::workspace::{self, ProjectPath, TaskRef};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::io::{self, BufRead, BufReader, IsTerminal, Read};
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::task::JoinSet;
 
 /// Represents a task execution plan with resolved dependencies
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct TaskExecutionPlan {
     /// Tasks organized by execution level (level 0 = no dependencies, etc.)
     pub levels: Vec<Vec<String>>,
-    /// Original task configurations
+    /// Task configurations, with dependencies named as in the plan
     pub tasks: HashMap<String, TaskConfig>,
+    /// Directory of every task that belongs to another project of the workspace
+    pub projects: HashMap<String, PathBuf>,
+}
+
+/// A task found by name or reference while building a plan
+struct ResolvedTask {
+    /// Name of the task in the plan, a label like `//libs/proto:generate` for
+    /// tasks of other projects
+    name: String,
+    /// Directory of the project, `None` for the current one
+    project: Option<PathBuf>,
+    config: TaskConfig,
+}
+
+/// Cache state shared by the tasks of a single execution
+struct TaskCache {
+    manager: Arc<CacheManager>,
+    /// Result digests of the tasks that completed so far, `failed:<exit_code>`
+    /// for failed ones
+    result_digests: Mutex<HashMap<String, String>>,
+}
+
+impl TaskCache {
+    /// Result digests of the dependencies of a task, `None` if a dependency
+    /// has none because its outputs could not be captured
+    fn dependency_digests(&self, task_config: &TaskConfig) -> Option<BTreeMap<String, String>> {
+        let digests = self.result_digests.lock().ok()?;
+
+        task_config
+            .dependencies
+            .iter()
+            .flatten()
+            .map(|dep| Some((dep.clone(), digests.get(dep)?.clone())))
+            .collect()
+    }
+
+    fn record_result(&self, task_name: &str, result: &crate::cache::CachedTaskResult) {
+        if let Ok(mut digests) = self.result_digests.lock() {
+            digests.insert(task_name.to_string(), result_digest(result));
+        }
+    }
+
+    /// Record a failed task, so tasks allowed to run after it are keyed on the failure
+    fn record_failure(&self, task_name: &str, exit_code: i32) {
+        if let Ok(mut digests) = self.result_digests.lock() {
+            digests.insert(task_name.to_string(), failure_digest(exit_code));
+        }
+    }
+}
+
+/// What dependents of a task are keyed on: the digest of its result, or a
+/// marker of the failure they were allowed to run after
+fn result_digest(result: &crate::cache::CachedTaskResult) -> String {
+    if result.exit_code == 0 {
+        result.digest()
+    } else {
+        failure_digest(result.exit_code)
+    }
+}
+
+fn failure_digest(exit_code: i32) -> String {
+    format!("failed:{exit_code}")
+}
+
+/// Timeout of tasks that don't set one
+const DEFAULT_TASK_TIMEOUT: Duration = Duration::from_secs(3600);
+
+/// Time a task may run before its process group is killed
+fn task_timeout(task_config: &TaskConfig) -> Duration {
+    task_config.timeout.map_or(DEFAULT_TASK_TIMEOUT, |secs| {
+        Duration::from_secs(secs.into())
+    })
+}
+
+/// Tracks which tasks of a plan are ready to run as their dependencies complete
+struct TaskScheduler {
+    /// Number of dependencies each waiting task still needs to succeed
+    waiting: HashMap<String, usize>,
+    /// Tasks depending on each task
+    dependents: HashMap<String, Vec<String>>,
+    ready: VecDeque<String>,
+}
+
+impl TaskScheduler {
+    fn new(plan: &TaskExecutionPlan) -> Self {
+        let mut waiting = HashMap::with_capacity(plan.tasks.len());
+        let mut dependents: HashMap<String, Vec<String>> = HashMap::new();
+        for (task_name, config) in &plan.tasks {
+            let dependencies: HashSet<&String> = config.dependencies.iter().flatten().collect();
+            for dependency in &dependencies {
+                dependents
+                    .entry((*dependency).clone())
+                    .or_default()
+                    .push(task_name.clone());
+            }
+            waiting.insert(task_name.clone(), dependencies.len());
+        }
+
+        // Start in plan order, so runs are reproducible
+        let ready = plan
+            .levels
+            .iter()
+            .flatten()
+            .filter(|task_name| waiting.get(*task_name) == Some(&0))
+            .cloned()
+            .collect();
+        for task_name in &ready {
+            waiting.remove(task_name);
+        }
+
+        Self {
+            waiting,
+            dependents,
+            ready,
+        }
+    }
+
+    fn next_ready(&mut self) -> Option<String> {
+        self.ready.pop_front()
+    }
+
+    /// Record a finished task, dependents of failed tasks are never run
+    fn completed(&mut self, task_name: &str, success: bool) {
+        if !success {
+            return;
+        }
+        for dependent in self.dependents.get(task_name).into_iter().flatten() {
+            if let Some(count) = self.waiting.get_mut(dependent) {
+                *count -= 1;
+                if *count == 0 {
+                    self.waiting.remove(dependent);
+                    self.ready.push_back(dependent.clone());
+                }
+            }
+        }
+    }
+
+    /// Tasks that were never started, sorted by name
+    fn not_started(&self) -> Vec<String> {
+        let mut tasks: Vec<String> = self
+            .waiting
+            .keys()
+            .chain(self.ready.iter())
+            .cloned()
+            .collect();
+        tasks.sort();
+        tasks
+    }
+}
+
+/// Settings shared by the tasks of a single execution
+#[derive(Clone)]
+struct ExecutionSettings {
+    working_dir: PathBuf,
+    args: Vec<String>,
+    /// Reports of the tasks executed in audit mode
+    audit_reports: Option<Arc<Mutex<Vec<AuditReport>>>>,
+    cancel: Arc<AtomicBool>,
+    output: Arc<TaskOutput>,
+    /// Whether tasks keep the terminal, unless their output needs masking
+    inherit_stdio: bool,
+}
+
+fn default_jobs() -> usize {
+    std::thread::available_parallelism().map_or(1, usize::from)
 }
 
 /// Main task executor that handles dependency resolution and execution
@@ -25,16 +205,39 @@ pub struct TaskExecutor {
     env_manager: EnvManager,
     working_dir: PathBuf,
     cache_manager: Arc<CacheManager>,
+    /// Set to cancel running tasks, killing their process groups
+    cancel: Arc<AtomicBool>,
+    /// Whether cached results may be used instead of executing tasks
+    use_cache: bool,
+    /// Maximum number of tasks running at once
+    jobs: usize,
+    /// Whether tasks not depending on a failed task are still executed
+    keep_going: bool,
+    /// Environments of the other projects tasks were referenced from, by directory
+    projects: Mutex<HashMap<PathBuf, Arc<EnvManager>>>,
 }
 
 impl TaskExecutor {
     /// Create a new task executor
+    ///
+    /// Task results are shared with the remote cache configured through
+    /// `CUENV_REMOTE_CACHE`, if any.
     pub async fn new(env_manager: EnvManager, working_dir: PathBuf) -> Result<Self> {
         let cache_config = crate::cache::CacheConfig::default();
+        let mut cache_manager = CacheManager::new(cache_config).await?;
+        if let Some(remote) = RemoteCacheClient::from_env()? {
+            cache_manager = cache_manager.with_remote(remote);
+        }
+
         Ok(Self {
             env_manager,
             working_dir,
-            cache_manager: Arc::new(CacheManager::new(cache_config).await?),
+            cache_manager: Arc::new(cache_manager),
+            cancel: Arc::new(AtomicBool::new(false)),
+            use_cache: true,
+            jobs: default_jobs(),
+            keep_going: false,
+            projects: Mutex::new(HashMap::new()),
         })
     }
 
@@ -49,9 +252,46 @@ impl TaskExecutor {
             env_manager,
             working_dir,
             cache_manager: Arc::new(CacheManager::new(cache_config).await?),
+            cancel: Arc::new(AtomicBool::new(false)),
+            use_cache: true,
+            jobs: default_jobs(),
+            keep_going: false,
+            projects: Mutex::new(HashMap::new()),
         })
     }
 
+    /// Always execute tasks instead of reusing cached results
+    pub fn without_cache(mut self) -> Self {
+        self.use_cache = false;
+        self
+    }
+
+    /// Run at most `jobs` tasks at once, the number of CPUs by default
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    /// Keep executing tasks that don't depend on a failed task, reporting all
+    /// failures at the end
+    pub fn with_keep_going(mut self, keep_going: bool) -> Self {
+        self.keep_going = keep_going;
+        self
+    }
+
+    /// Handle that cancels the tasks currently being executed when set.
+    ///
+    /// Running task process groups are killed and no further dependency levels
+    /// are started. Reset the flag before executing again.
+    pub fn cancellation_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.cancel)
+    }
+
+    /// Get the working directory tasks are executed from
+    pub fn working_dir(&self) -> &Path {
+        &self.working_dir
+    }
+
     /// Execute a single task by name
     pub async fn execute_task(&self, task_name: &str, args: &[String]) -> Result<i32> {
         self.execute_tasks_with_dependencies(&[task_name.to_string()], args, false)
@@ -64,117 +304,373 @@ impl TaskExecutor {
             .await
     }
 
+    /// Execute a single task by name with audit mode, returning the audit
+    /// reports of it and the dependencies executed for it
+    pub async fn audit_task(
+        &self,
+        task_name: &str,
+        args: &[String],
+    ) -> Result<(i32, Vec<AuditReport>)> {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let status = self
+            .execute_tasks(&[task_name.to_string()], args, Some(Arc::clone(&reports)))
+            .await?;
+        let reports = reports
+            .lock()
+            .map(|mut reports| std::mem::take(&mut *reports))
+            .unwrap_or_default();
+        Ok((status, reports))
+    }
+
     /// Execute multiple tasks with their dependencies
     pub async fn execute_tasks_with_dependencies(
         &self,
         task_names: &[String],
         args: &[String],
         audit_mode: bool,
+    ) -> Result<i32> {
+        let audit_reports = audit_mode.then(|| Arc::new(Mutex::new(Vec::new())));
+        self.execute_tasks(task_names, args, audit_reports).await
+    }
+
+    async fn execute_tasks(
+        &self,
+        task_names: &[String],
+        args: &[String],
+        audit_reports: Option<Arc<Mutex<Vec<AuditReport>>>>,
     ) -> Result<i32> {
         // Build execution plan
-        let plan = self.build_execution_plan(task_names)?;
-
-        // Execute tasks level by level
-        for level in &plan.levels {
-            let mut join_set = JoinSet::new();
-            let failed_tasks = Arc::new(Mutex::new(Vec::with_capacity(level.len())));
-
-            // Launch all tasks in this level concurrently
-            for task_name in level {
-                let task_config = match plan.tasks.get(task_name) {
-                    Some(config) => config.clone(),
-                    None => {
-                        return Err(Error::configuration(format!(
-                            "Task '{task_name}' not found in execution plan"
-                        )));
-                    }
+        let mut plan = self.build_execution_plan(task_names)?;
+        let args = Self::apply_task_arguments(&mut plan, task_names, args)?;
+        // Audited tasks always run, so their accesses are traced
+        let use_cache = self.use_cache && audit_reports.is_none();
+        let cache = use_cache.then(|| {
+            Arc::new(TaskCache {
+                manager: Arc::clone(&self.cache_manager),
+                result_digests: Mutex::new(HashMap::new()),
+            })
+        });
+
+        // A single task keeps the terminal, for colors, progress bars and prompts
+        let inherit_stdio =
+            plan.tasks.len() == 1 && io::stdout().is_terminal() && io::stderr().is_terminal();
+        let output = Arc::new(if inherit_stdio {
+            TaskOutput::without_progress()
+        } else {
+            TaskOutput::for_terminal()
+        });
+        let settings = ExecutionSettings {
+            working_dir: self.working_dir.clone(),
+            args,
+            audit_reports,
+            cancel: Arc::clone(&self.cancel),
+            output: Arc::clone(&output),
+            inherit_stdio,
+        };
+
+        // Keep the elapsed times of the progress view current
+        let ticker = output.is_interactive().then(|| {
+            let output = Arc::clone(&output);
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_millis(250));
+                loop {
+                    interval.tick().await;
+                    output.redraw();
+                }
+            })
+        });
+
+        let result = self.execute_plan(&plan, cache, &settings).await;
+        if let Some(ticker) = ticker {
+            ticker.abort();
+        }
+        output.finish();
+        result
+    }
+
+    /// Parse `args` against the parameters of the first requested task that
+    /// declares any, returning the arguments left to append to commands
+    fn apply_task_arguments(
+        plan: &mut TaskExecutionPlan,
+        task_names: &[String],
+        args: &[String],
+    ) -> Result<Vec<String>> {
+        let Some(task_name) = task_names.iter().find(|task_name| {
+            plan.tasks
+                .get(*task_name)
+                .and_then(|config| config.params.as_ref())
+                .is_some_and(|params| !params.is_empty())
+        }) else {
+            return Ok(args.to_vec());
+        };
+        let Some(config) = plan.tasks.get_mut(task_name) else {
+            return Ok(args.to_vec());
+        };
+
+        let parsed = task_params::parse_arguments(task_name, config, args)?;
+        *config = task_params::apply_arguments(config, &parsed.values);
+        Ok(parsed.passthrough)
+    }
+
+    /// Execute the tasks of a plan, each as soon as its own dependencies
+    /// succeeded, with at most `jobs` running at once
+    async fn execute_plan(
+        &self,
+        plan: &TaskExecutionPlan,
+        cache: Option<Arc<TaskCache>>,
+        settings: &ExecutionSettings,
+    ) -> Result<i32> {
+        let mut scheduler = TaskScheduler::new(plan);
+        let mut join_set = JoinSet::new();
+        let mut failed = Vec::new();
+        let mut timed_out = Vec::new();
+        let mut stopping = false;
+
+        loop {
+            if self.cancel.load(Ordering::SeqCst) {
+                stopping = true;
+            }
+
+            while !stopping && join_set.len() < self.jobs {
+                let Some(task_name) = scheduler.next_ready() else {
+                    break;
                 };
-                let working_dir = self.working_dir.clone();
-                let task_args = args.to_vec();
-                let failed_tasks = Arc::clone(&failed_tasks);
-                let task_name = task_name.clone();
-                let cache_manager = Arc::clone(&self.cache_manager);
+                let task_config = plan.tasks.get(&task_name).cloned().ok_or_else(|| {
+                    Error::configuration(format!("Task '{task_name}' not found in execution plan"))
+                })?;
+                let task_env = self.task_env(plan, &task_name, &task_config);
+                let cache = cache.clone();
+                let mut settings = settings.clone();
+                // Tasks of other projects run in their own directory
+                if let Some(project) = plan.projects.get(&task_name) {
+                    settings.working_dir = project.clone();
+                }
 
                 join_set.spawn(async move {
-                    match Self::execute_single_task_with_cache(
-                        &task_name,
-                        &task_config,
-                        &working_dir,
-                        &task_args,
-                        &cache_manager,
-                        audit_mode,
-                    )
-                    .await
-                    {
-                        Ok(status) => {
-                            if status != 0 {
-                                if let Ok(mut guard) = failed_tasks.lock() {
-                                    guard.push((task_name, status));
-                                } else {
-                                    log::error!("Failed to acquire lock for failed tasks tracking");
-                                }
-                            }
-                            status
-                        }
-                        Err(e) => {
-                            if let Ok(mut guard) = failed_tasks.lock() {
-                                guard.push((task_name.clone(), -1));
-                            } else {
-                                log::error!("Failed to acquire lock for failed tasks tracking");
-                            }
-                            eprintln!("Task '{task_name}' failed: {e}");
-                            -1
+                    // A task whose environment failed to load fails like a failed run
+                    let result = match task_env {
+                        Ok(task_env) => {
+                            Self::execute_single_task_with_cache(
+                                &task_name,
+                                &task_config,
+                                &task_env,
+                                &settings,
+                                cache.as_deref(),
+                            )
+                            .await
                         }
-                    }
+                        Err(e) => Err(e),
+                    };
+                    (task_name, result)
                 });
             }
 
-            // Wait for all tasks in this level to complete
-            while let Some(result) = join_set.join_next().await {
-                if let Err(e) = result {
-                    return Err(Error::configuration(format!("Task execution failed: {e}")));
+            let Some(result) = join_set.join_next().await else {
+                break;
+            };
+            let (task_name, result) =
+                result.map_err(|e| Error::configuration(format!("Task execution failed: {e}")))?;
+
+            let task_timed_out = matches!(result, Err(Error::Timeout { .. }));
+            let exit_code = match &result {
+                Ok(status) => *status,
+                Err(Error::Timeout { .. }) => TASK_TIMEOUT_EXIT_CODE,
+                Err(_) => 1,
+            };
+            let success = match result {
+                Ok(status) => status == 0,
+                // Already reported when the task was killed
+                Err(Error::Timeout { .. }) => false,
+                Err(e) => {
+                    settings
+                        .output
+                        .line(&task_name, &format!("error: {e}"), true);
+                    settings.output.finished(&task_name, false);
+                    false
+                }
+            };
+            let allowed = !success
+                && plan
+                    .tasks
+                    .get(&task_name)
+                    .and_then(|config| config.allow_failure)
+                    .unwrap_or(false);
+            if !success {
+                if let Some(cache) = &cache {
+                    cache.record_failure(&task_name, exit_code);
                 }
             }
+            if allowed {
+                settings.output.line(
+                    &task_name,
+                    "failure allowed, continuing with dependent tasks",
+                    true,
+                );
+            }
 
-            // Check if any tasks failed
-            let failed = failed_tasks
-                .lock()
-                .map_err(|e| Error::configuration(format!("Failed to acquire lock: {e}")))?;
-            if !failed.is_empty() {
-                let failed_names: Vec<&str> =
-                    failed.iter().map(|(name, _)| name.as_str()).collect();
-                return Err(Error::configuration(format!(
-                    "Tasks failed: {}",
-                    failed_names.join(", ")
-                )));
+            scheduler.completed(&task_name, success || allowed);
+            if !success && !allowed {
+                if task_timed_out {
+                    timed_out.push(task_name);
+                } else {
+                    failed.push(task_name);
+                }
+                // Without --keep-going the running tasks are finished, but no new ones started
+                stopping |= !self.keep_going;
+            }
+        }
+
+        if self.cancel.load(Ordering::SeqCst) {
+            return Err(Error::configuration("Task execution cancelled".to_string()));
+        }
+        if failed.is_empty() && timed_out.is_empty() {
+            return Ok(0);
+        }
+
+        let not_run = scheduler.not_started();
+        let not_run = if not_run.is_empty() {
+            String::new()
+        } else {
+            format!(" (not run: {})", not_run.join(", "))
+        };
+
+        // Only timeouts are reported as such, so they get their own exit code
+        if failed.is_empty() {
+            let timeout = timed_out
+                .iter()
+                .filter_map(|name| plan.tasks.get(name))
+                .map(task_timeout)
+                .max()
+                .unwrap_or(DEFAULT_TASK_TIMEOUT);
+            let operation = match timed_out.as_slice() {
+                [task_name] => format!("task {task_name}{not_run}"),
+                _ => format!("tasks {}{not_run}", timed_out.join(", ")),
+            };
+            return Err(Error::timeout(operation, timeout));
+        }
+
+        let mut message = format!("Tasks failed: {}", failed.join(", "));
+        if !timed_out.is_empty() {
+            message.push_str(&format!("; timed out: {}", timed_out.join(", ")));
+        }
+        message.push_str(&not_run);
+        Err(Error::configuration(message))
+    }
+
+    /// Dependency graph of `task_names`, or of all tasks if none are given,
+    /// with the tasks expected to be found in the cache. With `args`, they are
+    /// parsed as in an actual run, since parameter values affect cache keys.
+    pub async fn task_graph(
+        &self,
+        task_names: &[String],
+        args: Option<&[String]>,
+    ) -> Result<TaskGraph> {
+        let task_names = if task_names.is_empty() {
+            let mut all: Vec<String> = self.env_manager.get_tasks().keys().cloned().collect();
+            all.sort();
+            all
+        } else {
+            task_names.to_vec()
+        };
+
+        let mut plan = self.build_execution_plan(&task_names)?;
+        let passthrough = match args {
+            Some(args) => Self::apply_task_arguments(&mut plan, &task_names, args)?,
+            None => Vec::new(),
+        };
+        let cached = if self.use_cache {
+            Some(self.predict_cache_hits(&plan, &passthrough).await?)
+        } else {
+            None
+        };
+        Ok(TaskGraph::new(&plan, cached.as_ref()))
+    }
+
+    /// Whether each task of a plan would be found in the local or the remote
+    /// cache. A task whose dependency has to run is expected to run as well,
+    /// since the dependency's new result is part of its cache key. `args` are
+    /// the arguments appended to the commands. The remote cache is only asked
+    /// whether it has a result, so the local cache is left as it is. Tasks
+    /// depending on a result only the remote cache has are expected to run,
+    /// as their keys depend on outputs that are not downloaded.
+    async fn predict_cache_hits(
+        &self,
+        plan: &TaskExecutionPlan,
+        args: &[String],
+    ) -> Result<HashMap<String, bool>> {
+        let process_env: HashMap<String, String> = std::env::vars().collect();
+        let mut result_digests: HashMap<&str, String> = HashMap::new();
+        let mut predictions = HashMap::with_capacity(plan.tasks.len());
+
+        for task_name in plan.levels.iter().flatten() {
+            let Some(task_config) = plan.tasks.get(task_name) else {
+                continue;
+            };
+            let dependency_digests: Option<BTreeMap<String, String>> = task_config
+                .dependencies
+                .iter()
+                .flatten()
+                .map(|dep| Some((dep.clone(), result_digests.get(dep.as_str())?.clone())))
+                .collect();
+
+            let (cached, digest) = match dependency_digests {
+                Some(dependency_digests) => {
+                    let env_vars = self
+                        .task_env(plan, task_name, task_config)?
+                        .apply_to(process_env.clone());
+                    let working_dir = plan.projects.get(task_name).unwrap_or(&self.working_dir);
+                    let components = CacheKeyComponents::collect(
+                        task_name,
+                        task_config,
+                        args,
+                        &env_vars,
+                        working_dir,
+                        dependency_digests,
+                    )?;
+                    let cache_key = components.key();
+                    match self.cache_manager.peek_cached_result(&cache_key) {
+                        Some(result) => (true, Some(result_digest(&result))),
+                        None => (self.cache_manager.has_remote_result(&cache_key).await, None),
+                    }
+                }
+                None => (false, None),
+            };
+
+            if let Some(digest) = digest {
+                result_digests.insert(task_name, digest);
             }
+            predictions.insert(task_name.clone(), cached);
         }
 
-        Ok(0)
+        Ok(predictions)
     }
 
     /// Build an execution plan with dependency resolution
+    ///
+    /// Tasks may be named by reference to run them in other projects of the
+    /// workspace, like `//libs/proto:generate` or `//...:test`.
     pub fn build_execution_plan(&self, task_names: &[String]) -> Result<TaskExecutionPlan> {
-        let all_tasks = self.env_manager.get_tasks();
-
-        // Validate that all requested tasks exist
+        let mut requested = Vec::with_capacity(task_names.len());
         for task_name in task_names {
-            if !all_tasks.contains_key(task_name) {
+            let resolved = self.resolve_tasks(None, task_name)?;
+            if resolved.is_empty() {
                 return Err(Error::configuration(format!(
                     "Task '{task_name}' not found"
                 )));
             }
+            requested.extend(resolved);
         }
 
         // Build dependency graph
-        let mut task_dependencies = HashMap::with_capacity(all_tasks.len());
-        let mut visited = HashSet::with_capacity(all_tasks.len());
+        let mut plan = TaskExecutionPlan::default();
+        let mut task_dependencies = HashMap::new();
+        let mut visited = HashSet::new();
         let mut stack = HashSet::new();
 
-        for task_name in task_names {
-            Self::collect_dependencies(
-                task_name,
-                all_tasks,
+        for task in requested {
+            self.collect_dependencies(
+                task,
+                &mut plan,
                 &mut task_dependencies,
                 &mut visited,
                 &mut stack,
@@ -182,67 +678,156 @@ impl TaskExecutor {
         }
 
         // Topological sort to determine execution order
-        let levels = self.topological_sort(&task_dependencies)?;
-
-        // Build final execution plan
-        let mut plan_tasks = HashMap::with_capacity(task_dependencies.len());
-        for task_name in task_dependencies.keys() {
-            if let Some(config) = all_tasks.get(task_name) {
-                plan_tasks.insert(task_name.clone(), config.clone());
-            }
-        }
-
-        Ok(TaskExecutionPlan {
-            levels,
-            tasks: plan_tasks,
-        })
+        plan.levels = self.topological_sort(&task_dependencies)?;
+        Ok(plan)
     }
 
-    /// Recursively collect all dependencies for a task
+    /// Recursively collect all dependencies for a task, adding them to `plan`
+    /// with their dependencies resolved to plan task names
     fn collect_dependencies(
-        task_name: &str,
-        all_tasks: &HashMap<String, TaskConfig>,
+        &self,
+        mut task: ResolvedTask,
+        plan: &mut TaskExecutionPlan,
         task_dependencies: &mut HashMap<String, Vec<String>>,
         visited: &mut HashSet<String>,
         stack: &mut HashSet<String>,
     ) -> Result<()> {
+        let task_name = task.name.clone();
+
         // Check for circular dependencies
-        if stack.contains(task_name) {
+        if stack.contains(&task_name) {
             return Err(Error::configuration(format!(
                 "Circular dependency detected involving task '{task_name}'"
             )));
         }
 
-        if visited.contains(task_name) {
+        if visited.contains(&task_name) {
             return Ok(());
         }
 
-        stack.insert(task_name.to_owned());
-
-        let task_config = all_tasks
-            .get(task_name)
-            .ok_or_else(|| Error::configuration(format!("Task '{task_name}' not found")))?;
-
-        let dependencies = task_config.dependencies.clone().unwrap_or_default();
+        stack.insert(task_name.clone());
 
         // Validate and collect dependencies
-        for dep_name in &dependencies {
-            if !all_tasks.contains_key(dep_name) {
+        let mut dependencies = Vec::new();
+        for dep_name in task.config.dependencies.iter().flatten() {
+            let resolved = self.resolve_tasks(task.project.as_deref(), dep_name)?;
+            if resolved.is_empty() {
                 return Err(Error::configuration(format!(
                     "Dependency '{dep_name}' of task '{task_name}' not found"
                 )));
             }
 
-            Self::collect_dependencies(dep_name, all_tasks, task_dependencies, visited, stack)?;
+            for dependency in resolved {
+                dependencies.push(dependency.name.clone());
+                self.collect_dependencies(dependency, plan, task_dependencies, visited, stack)?;
+            }
         }
 
-        task_dependencies.insert(task_name.to_owned(), dependencies);
-        visited.insert(task_name.to_owned());
-        stack.remove(task_name);
+        if task.config.dependencies.is_some() {
+            task.config.dependencies = Some(dependencies.clone());
+        }
+        if let Some(project) = task.project {
+            plan.projects.insert(task_name.clone(), project);
+        }
+        plan.tasks.insert(task_name.clone(), task.config);
+        task_dependencies.insert(task_name.clone(), dependencies);
+        visited.insert(task_name.clone());
+        stack.remove(&task_name);
 
         Ok(())
     }
 
+    /// Tasks a name or task reference made from the project in `from`, or the
+    /// current project, points to. Empty if a named task doesn't exist.
+    fn resolve_tasks(&self, from: Option<&Path>, reference: &str) -> Result<Vec<ResolvedTask>> {
+        let Some(task_ref) = TaskRef::parse(reference) else {
+            let Some(project) = from else {
+                return Ok(self
+                    .env_manager
+                    .get_task(reference)
+                    .map(|config| ResolvedTask {
+                        name: reference.to_string(),
+                        project: None,
+                        config: config.clone(),
+                    })
+                    .into_iter()
+                    .collect());
+            };
+            return self.resolve_project_task(project, reference);
+        };
+
+        let current =
+            std::fs::canonicalize(&self.working_dir).unwrap_or_else(|_| self.working_dir.clone());
+        let mut resolved = Vec::new();
+        for dir in workspace::project_dirs(&task_ref.project, from.unwrap_or(&current))? {
+            if dir == current {
+                resolved.extend(self.resolve_tasks(None, &task_ref.task)?);
+            } else {
+                resolved.extend(self.resolve_project_task(&dir, &task_ref.task)?);
+            }
+        }
+
+        // Only `//...` may match projects without the task
+        if resolved.is_empty() && task_ref.project == ProjectPath::All {
+            return Err(Error::configuration(format!(
+                "No project in the workspace defines task '{}'",
+                task_ref.task
+            )));
+        }
+        Ok(resolved)
+    }
+
+    /// Task `task_name` of the project in `dir`, if it defines one
+    fn resolve_project_task(&self, dir: &Path, task_name: &str) -> Result<Vec<ResolvedTask>> {
+        let project = self.project_env(dir)?;
+        Ok(project
+            .get_task(task_name)
+            .map(|config| ResolvedTask {
+                name: workspace::task_label(dir, task_name),
+                project: Some(dir.to_path_buf()),
+                config: config.clone(),
+            })
+            .into_iter()
+            .collect())
+    }
+
+    /// Environment of the project in `dir`, loaded once per executor
+    fn project_env(&self, dir: &Path) -> Result<Arc<EnvManager>> {
+        let mut projects = self
+            .projects
+            .lock()
+            .map_err(|_| Error::configuration("Project environments lock poisoned"))?;
+        if let Some(project) = projects.get(dir) {
+            return Ok(Arc::clone(project));
+        }
+
+        let project = Arc::new(self.env_manager.load_project(dir)?);
+        projects.insert(dir.to_path_buf(), Arc::clone(&project));
+        Ok(project)
+    }
+
+    /// Environment of a task of the plan, from its own project. The variables
+    /// of the current project are withheld from tasks of other projects.
+    fn task_env(
+        &self,
+        plan: &TaskExecutionPlan,
+        task_name: &str,
+        task_config: &TaskConfig,
+    ) -> Result<TaskEnv> {
+        let Some(dir) = plan.projects.get(task_name) else {
+            return self.env_manager.task_env(task_config);
+        };
+
+        let mut env = self.project_env(dir)?.task_env(task_config)?;
+        for key in self.env_manager.cue_vars().keys() {
+            if !env.vars.contains_key(key) && !env.withheld.contains(key) {
+                env.withheld.push(key.clone());
+            }
+        }
+        env.withheld.sort();
+        Ok(env)
+    }
+
     /// Perform topological sort to determine execution levels
     fn topological_sort(
         &self,
@@ -314,29 +899,83 @@ impl TaskExecutor {
     }
 
     /// Execute a single task with caching support
+    ///
+    /// Without a cache the task is always executed and its result is not stored.
+    /// The same goes for tasks with a dependency whose result is unknown, as
+    /// their cache key would leave out what the dependency produced.
     async fn execute_single_task_with_cache(
         task_name: &str,
         task_config: &TaskConfig,
-        working_dir: &Path,
-        args: &[String],
-        cache_manager: &CacheManager,
-        audit_mode: bool,
+        task_env: &TaskEnv,
+        settings: &ExecutionSettings,
+        cache: Option<&TaskCache>,
     ) -> Result<i32> {
-        // Generate cache key
-        let env_vars = std::env::vars().collect();
-        let cache_key =
-            cache_manager.generate_cache_key(task_name, task_config, &env_vars, working_dir)?;
+        let cached = cache.and_then(|cache| Some((cache, cache.dependency_digests(task_config)?)));
+        let Some((cache, dependency_digests)) = cached else {
+            return Self::execute_task_with_retries(task_name, task_config, task_env, settings)
+                .await
+                .map(|(exit_code, _)| exit_code);
+        };
+        let working_dir = settings.working_dir.as_path();
+
+        let cache_manager = &cache.manager;
 
-        // Check if task result is cached
-        if let Some(cached_result) = cache_manager.get_cached_result(&cache_key) {
-            println!("✓ Task '{task_name}' found in cache, skipping execution");
-            return Ok(cached_result.exit_code);
+        // Generate cache key from the environment the task will see
+        let env_vars = task_env.apply_to(std::env::vars().collect());
+        let components = CacheKeyComponents::collect(
+            task_name,
+            task_config,
+            &settings.args,
+            &env_vars,
+            working_dir,
+            dependency_digests,
+        )?;
+        let cache_key = components.key();
+        if let Err(e) = cache_manager.record_key_components(working_dir, &components) {
+            log::warn!("Failed to record cache key of task '{task_name}': {e}");
+        }
+
+        // Outputs are resolved relative to the directory the task runs in
+        let output_patterns = task_config.outputs.clone().unwrap_or_default();
+        let output_dir = match &task_config.working_dir {
+            Some(task_wd) => working_dir.join(task_wd),
+            None => working_dir.to_path_buf(),
+        };
+
+        // Check if task result is cached, locally first
+        let cached_result = match cache_manager.get_cached_result(&cache_key) {
+            Some(result) => Some(result),
+            None => cache_manager.get_remote_result(&cache_key).await,
+        };
+        if let Some(cached_result) = cached_result {
+            match cache_manager.restore_outputs(&cached_result, &output_patterns, &output_dir) {
+                Ok(restored) => {
+                    settings.output.cached(task_name, restored);
+                    cache.record_result(task_name, &cached_result);
+                    return Ok(cached_result.exit_code);
+                }
+                Err(e) => {
+                    log::warn!("Failed to restore cached outputs of task '{task_name}': {e}");
+                }
+            }
         }
 
         // Execute the task
-        println!("→ Executing task '{task_name}'");
-        let exit_code =
-            Self::execute_single_task(task_config, working_dir, args, audit_mode).await?;
+        let (exit_code, resource_usage) =
+            Self::execute_task_with_retries(task_name, task_config, task_env, settings).await?;
+
+        // Only successful runs are cached, so only their outputs are worth storing
+        let (output_files, output_metadata) = if exit_code == 0 {
+            match cache_manager.capture_outputs(&output_patterns, &output_dir) {
+                Ok(captured) => captured,
+                Err(e) => {
+                    log::warn!("Failed to capture outputs of task '{task_name}': {e}");
+                    return Ok(exit_code);
+                }
+            }
+        } else {
+            Default::default()
+        };
 
         // Cache the result
         let cached_result = crate::cache::CachedTaskResult {
@@ -345,9 +984,18 @@ impl TaskExecutor {
             exit_code,
             stdout: None,
             stderr: None,
-            output_files: std::collections::HashMap::new(),
+            output_files,
+            output_metadata,
+            resource_usage,
         };
 
+        if exit_code == 0 {
+            cache.record_result(task_name, &cached_result);
+        }
+
+        if let Err(e) = cache_manager.store_remote_result(&cached_result).await {
+            log::warn!("Failed to upload task '{task_name}' result to the remote cache: {e}");
+        }
         if let Err(e) = cache_manager.store_result(cache_key, cached_result) {
             log::warn!("Failed to cache task '{task_name}' result: {e}");
         }
@@ -355,13 +1003,80 @@ impl TaskExecutor {
         Ok(exit_code)
     }
 
-    /// Execute a single task
+    /// Execute a task, retrying failed and timed out runs as often as its
+    /// `retries` allow. Every run is audit logged. The resource usage is that
+    /// of the successful run, if measured.
+    async fn execute_task_with_retries(
+        task_name: &str,
+        task_config: &TaskConfig,
+        task_env: &TaskEnv,
+        settings: &ExecutionSettings,
+    ) -> Result<(i32, Option<ResourceUsage>)> {
+        let retries = task_config.retries.unwrap_or(0);
+        let delay = Duration::from_secs(task_config.retry_delay.unwrap_or(1).into());
+        let config = RetryConfig::for_task(retries as usize, delay);
+        let attempts = AtomicU32::new(0);
+
+        let result = resilience::retry(&config, || async {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+            if attempt > 1 {
+                settings.output.line(
+                    task_name,
+                    &format!("retrying, attempt {attempt} of {}", retries + 1),
+                    true,
+                );
+            }
+
+            let started = Instant::now();
+            let result =
+                Self::execute_single_task(task_name, task_config, task_env, settings).await;
+            let exit_code = match &result {
+                Ok((exit_code, _)) => Some(*exit_code),
+                Err(Error::Timeout { .. }) => None,
+                Err(_) => return result,
+            };
+            if let Some(logger) = audit_logger() {
+                let duration_ms = started.elapsed().as_millis() as u64;
+                if let Err(e) = logger
+                    .log_task_execution(task_name, attempt, exit_code, duration_ms)
+                    .await
+                {
+                    log::warn!("Failed to audit log task '{task_name}': {e}");
+                }
+            }
+
+            // Failed runs are errors, so they are retried
+            match result {
+                Ok((exit_code, _)) if exit_code != 0 => Err(Error::command_execution(
+                    task_name,
+                    Vec::new(),
+                    "task failed",
+                    Some(exit_code),
+                )),
+                result => result,
+            }
+        })
+        .await;
+
+        match result {
+            Err(Error::CommandExecution {
+                exit_code: Some(exit_code),
+                ..
+            }) => Ok((exit_code, None)),
+            result => result,
+        }
+    }
+
+    /// Execute a single task, reporting its output, result and resource usage
     async fn execute_single_task(
+        task_name: &str,
         task_config: &TaskConfig,
-        working_dir: &Path,
-        args: &[String],
-        audit_mode: bool,
-    ) -> Result<i32> {
+        task_env: &TaskEnv,
+        settings: &ExecutionSettings,
+    ) -> Result<(i32, Option<ResourceUsage>)> {
+        let working_dir = settings.working_dir.as_path();
+        let args = settings.args.as_slice();
+
         // Determine what to execute
         let (shell, script_content) = match (&task_config.command, &task_config.script) {
             (Some(command), None) => {
@@ -427,6 +1142,21 @@ impl TaskExecutor {
             working_dir.to_path_buf()
         };
 
+        // Only the secrets this task can see are resolved
+        let env_vars = if task_env
+            .vars
+            .values()
+            .any(|value| value.starts_with(CUENV_RESOLVER_PREFIX))
+        {
+            let resolved = SecretManager::new()
+                .resolve_secrets(task_env.vars.clone().into())
+                .await?;
+            settings.output.add_secrets(resolved.secret_values.iter());
+            resolved.env_vars.into_inner()
+        } else {
+            task_env.vars.clone()
+        };
+
         // Configure process group for better cleanup
         let mut cmd = Command::new(&shell);
         cmd.arg("-c")
@@ -435,6 +1165,39 @@ impl TaskExecutor {
             .stdin(Stdio::inherit())
             .stdout(Stdio::inherit())
             .stderr(Stdio::inherit());
+        for key in &task_env.withheld {
+            cmd.env_remove(key);
+        }
+        cmd.envs(&env_vars);
+
+        // Nothing needs to be masked in the output of a task keeping the terminal
+        let direct = settings.inherit_stdio && !settings.output.has_secrets();
+        if direct {
+            settings.output.writes_directly(task_name);
+        }
+
+        // Tasks with resources run in a cgroup of their own, if possible
+        let resources = task_config
+            .resources
+            .as_ref()
+            .map(TaskResources::from_config)
+            .transpose()?;
+        #[cfg(target_os = "linux")]
+        let cgroup = match &resources {
+            Some(resources) => match TaskCgroup::create(task_name, resources) {
+                Ok(cgroup) => Some(cgroup),
+                Err(e) => {
+                    let mut message = format!("{e}, limiting only memory with rlimits");
+                    let unenforced = resources.cgroup_only();
+                    if !unenforced.is_empty() {
+                        message.push_str(&format!(", {} not enforced", unenforced.join(", ")));
+                    }
+                    settings.output.line(task_name, &message, true);
+                    None
+                }
+            },
+            None => None,
+        };
 
         // On Unix, create a new process group for better cleanup
         #[cfg(unix)]
@@ -442,11 +1205,30 @@ impl TaskExecutor {
             use std::os::unix::process::CommandExt;
             cmd.process_group(0);
 
+            // Join the cgroup before any restrictions apply
+            #[cfg(target_os = "linux")]
+            let in_cgroup = match &cgroup {
+                Some(cgroup) => {
+                    cgroup.add_to_command(&mut cmd);
+                    true
+                }
+                None => false,
+            };
+            #[cfg(not(target_os = "linux"))]
+            let in_cgroup = false;
+
+            // Rlimits enforce what no cgroup does
+            let limits = resources
+                .as_ref()
+                .map_or_else(ResourceLimits::default, |resources| {
+                    resources.rlimits(in_cgroup)
+                });
+
             // Apply resource limits before spawning
             unsafe {
-                cmd.pre_exec(|| {
-                    use crate::resource_limits::apply_default_limits;
-                    match apply_default_limits() {
+                cmd.pre_exec(move || {
+                    use crate::resource_limits::apply_limits;
+                    match apply_limits(&limits) {
                         Ok(()) => Ok(()),
                         Err(e) => {
                             eprintln!("Warning: Failed to apply resource limits: {e}");
@@ -457,26 +1239,106 @@ impl TaskExecutor {
             }
         }
 
+        // Audited tasks run without restrictions, whether configured or not
+        if let Some(audit_reports) = &settings.audit_reports {
+            let mut restrictions = task_config
+                .security
+                .as_ref()
+                .map(|security| {
+                    AccessRestrictions::from_security_config_with_task(security, task_config)
+                })
+                .unwrap_or_default();
+            restrictions.enable_audit_mode();
+            settings
+                .output
+                .notice(&format!("🔍 Running task '{task_name}' in audit mode"));
+
+            // The tracer spawns the command, so its output goes through pipes made here
+            let readers = if direct {
+                Vec::new()
+            } else {
+                let pipe_failed = |e: io::Error| {
+                    Error::command_execution(
+                        &shell,
+                        vec!["-c".to_string(), script_content.clone()],
+                        format!("Failed to create output pipe: {e}"),
+                        None,
+                    )
+                };
+                let (stdout, stdout_writer) = io::pipe().map_err(pipe_failed)?;
+                let (stderr, stderr_writer) = io::pipe().map_err(pipe_failed)?;
+                cmd.stdout(stdout_writer).stderr(stderr_writer);
+                vec![
+                    Self::forward_output(task_name, stdout, false, &settings.output),
+                    Self::forward_output(task_name, stderr, true, &settings.output),
+                ]
+            };
+
+            settings.output.started(task_name);
+            let timeout = task_timeout(task_config);
+            let cancel = Arc::clone(&settings.cancel);
+            let task = task_name.to_string();
+            // The tracer blocks until the task exits, off the async worker threads
+            let traced = tokio::task::spawn_blocking(move || {
+                let traced = restrictions.run_with_audit(&task, &mut cmd, timeout, &cancel);
+                // The command holds the write ends of the pipes until dropped
+                drop(cmd);
+                for reader in readers {
+                    if reader.join().is_err() {
+                        log::warn!("Task output reader panicked");
+                    }
+                }
+                // Removing the cgroup waits for its processes to be killed
+                #[cfg(target_os = "linux")]
+                drop(cgroup);
+                traced
+            })
+            .await
+            .map_err(|e| Error::configuration(format!("Task wait failed: {e}")))?;
+            let (exit_code, audit_report) = match traced {
+                Ok(traced) => traced,
+                Err(Error::Timeout { .. }) => {
+                    settings.output.timed_out(task_name);
+                    return Err(Error::timeout(format!("task {task_name}"), timeout));
+                }
+                Err(e) => return Err(e),
+            };
+            settings.output.notice(&audit_report.summary());
+            if let Ok(mut audit_reports) = audit_reports.lock() {
+                audit_reports.push(audit_report);
+            }
+            settings.output.finished(task_name, exit_code == 0);
+            return Ok((exit_code, None));
+        }
+
         // Apply security restrictions if configured
+        #[cfg(target_os = "linux")]
+        let mut network_proxy = None;
         if let Some(security) = &task_config.security {
-            use crate::access_restrictions::AccessRestrictions;
-            let mut restrictions =
+            let restrictions =
                 AccessRestrictions::from_security_config_with_task(security, task_config);
 
-            if audit_mode {
-                restrictions.enable_audit_mode();
-                println!("🔍 Running task in audit mode...");
-
-                let (exit_code, audit_report) = restrictions.run_with_audit(&mut cmd)?;
-                audit_report.print_summary();
-                return Ok(exit_code);
-            } else if restrictions.has_any_restrictions() {
+            if restrictions.has_any_restrictions() {
+                settings
+                    .output
+                    .restricted(task_name, restrictions.enforcement_level());
+                #[cfg(target_os = "linux")]
+                {
+                    network_proxy = restrictions.apply_to_command_with_proxy(&mut cmd)?;
+                }
+                #[cfg(not(target_os = "linux"))]
                 restrictions.apply_to_command(&mut cmd)?;
             }
         }
 
+        // Output is read line by line, so it can be prefixed and masked
+        if !direct {
+            cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+        }
+
         // Spawn the process with timeout
-        let child = cmd.spawn().map_err(|e| {
+        settings.output.started(task_name);
+        let mut child = cmd.spawn().map_err(|e| {
             Error::command_execution(
                 &shell,
                 vec!["-c".to_string(), script_content.clone()],
@@ -485,25 +1347,112 @@ impl TaskExecutor {
             )
         })?;
 
+        // Connections of the task go through its proxy until it exits
+        #[cfg(target_os = "linux")]
+        let _network_proxy = match network_proxy
+            .map(|proxy| proxy.start(task_name))
+            .transpose()
+        {
+            Ok(proxy) => proxy,
+            Err(e) => {
+                // Safety: killpg only sends a signal to the task's process group
+                unsafe { libc::killpg(child.id() as libc::pid_t, libc::SIGKILL) };
+                let _ = child.wait();
+                return Err(e);
+            }
+        };
+
         // Use ProcessGuard for automatic cleanup
-        let timeout = match task_config.timeout {
-            Some(timeout_secs) => Duration::from_secs(timeout_secs as u64),
-            None => Duration::from_secs(3600), // Default 1 hour timeout
+        let timeout = task_timeout(task_config);
+
+        let readers = [
+            child
+                .stdout
+                .take()
+                .map(|stdout| Self::forward_output(task_name, stdout, false, &settings.output)),
+            child
+                .stderr
+                .take()
+                .map(|stderr| Self::forward_output(task_name, stderr, true, &settings.output)),
+        ];
+
+        let mut guard =
+            ProcessGuard::new(child, timeout).with_cancellation(Arc::clone(&settings.cancel));
+
+        // Wait for completion with timeout off the async worker threads, then
+        // for the remaining output. The cgroup is removed there too, as that
+        // waits for its processes to be killed.
+        let (status, usage) = tokio::task::spawn_blocking(move || {
+            let status = guard.wait_with_timeout();
+            for reader in readers.into_iter().flatten() {
+                if reader.join().is_err() {
+                    log::warn!("Task output reader panicked");
+                }
+            }
+            #[cfg(target_os = "linux")]
+            let usage = cgroup.as_ref().map(TaskCgroup::usage);
+            #[cfg(not(target_os = "linux"))]
+            let usage = None;
+            #[cfg(target_os = "linux")]
+            drop(cgroup);
+            (status, usage)
+        })
+        .await
+        .map_err(|e| Error::configuration(format!("Task wait failed: {e}")))?;
+
+        // The whole process group was killed at the timeout
+        let status = match status {
+            Ok(status) => status,
+            Err(Error::Timeout { .. }) => {
+                settings.output.timed_out(task_name);
+                return Err(Error::timeout(format!("task {task_name}"), timeout));
+            }
+            Err(e) => {
+                return Err(Error::command_execution(
+                    &shell,
+                    vec!["-c".to_string(), script_content.clone()],
+                    e.to_string(),
+                    None,
+                ))
+            }
         };
 
-        let mut guard = ProcessGuard::new(child, timeout);
+        let exit_code = status.code().unwrap_or(1);
+        settings.output.finished(task_name, exit_code == 0);
 
-        // Wait for completion with timeout
-        let status = guard.wait_with_timeout().map_err(|e| {
-            Error::command_execution(
-                &shell,
-                vec!["-c".to_string(), script_content.clone()],
-                e.to_string(),
-                None,
-            )
-        })?;
+        if let Some(usage) = &usage {
+            settings.output.resources_used(task_name, usage);
+        }
+        Ok((exit_code, usage))
+    }
 
-        Ok(status.code().unwrap_or(1))
+    /// Report each line read from `stream` as output of `task_name`
+    fn forward_output(
+        task_name: &str,
+        stream: impl Read + Send + 'static,
+        stderr: bool,
+        output: &Arc<TaskOutput>,
+    ) -> std::thread::JoinHandle<()> {
+        let task_name = task_name.to_string();
+        let output = Arc::clone(output);
+        std::thread::spawn(move || {
+            let mut reader = BufReader::new(stream);
+            let mut line = Vec::new();
+            loop {
+                line.clear();
+                match reader.read_until(b'\n', &mut line) {
+                    Ok(0) => break,
+                    Ok(_) => {
+                        let text = String::from_utf8_lossy(&line);
+                        output.line(&task_name, text.trim_end_matches(['\n', '\r']), stderr);
+                    }
+                    Err(e) => {
+                        log::warn!("Failed to read output of task '{task_name}': {e}");
+                        break;
+                    }
+                }
+            }
+        })
     }
 
     /// List all available tasks
@@ -527,6 +1476,7 @@ impl TaskExecutor {
         println!("Cache Statistics:");
         println!("  Hits: {}", stats.hits);
         println!("  Misses: {}", stats.misses);
+        println!("  Remote hits: {}", stats.remote_hits);
         println!("  Writes: {}", stats.writes);
         println!("  Errors: {}", stats.errors);
         println!("  Lock contentions: {}", stats.lock_contentions);
@@ -783,4 +1733,307 @@ tasks: {
         assert!(plan.levels[1].contains(&"test".to_string()));
         assert_eq!(plan.levels[2], vec!["deploy"]);
     }
+
+    #[test]
+    fn test_scheduler_starts_tasks_when_their_dependencies_succeed() {
+        let task = |dependencies: &[&str]| -> TaskConfig {
+            TaskConfig {
+                dependencies: Some(dependencies.iter().map(|dep| dep.to_string()).collect()),
+                ..Default::default()
+            }
+        };
+        let plan = TaskExecutionPlan {
+            levels: vec![
+                vec!["compile".to_string(), "lint".to_string()],
+                vec!["build".to_string(), "test".to_string()],
+                vec!["deploy".to_string()],
+            ],
+            tasks: HashMap::from([
+                ("compile".to_string(), task(&[])),
+                ("lint".to_string(), task(&[])),
+                ("build".to_string(), task(&["compile"])),
+                ("test".to_string(), task(&["compile", "lint"])),
+                ("deploy".to_string(), task(&["build", "test"])),
+            ]),
+            projects: HashMap::new(),
+        };
+
+        let mut scheduler = TaskScheduler::new(&plan);
+        assert_eq!(scheduler.next_ready().as_deref(), Some("compile"));
+        assert_eq!(scheduler.next_ready().as_deref(), Some("lint"));
+        assert_eq!(scheduler.next_ready(), None);
+
+        // build doesn't wait for lint, which test still needs
+        scheduler.completed("compile", true);
+        assert_eq!(scheduler.next_ready().as_deref(), Some("build"));
+        assert_eq!(scheduler.next_ready(), None);
+
+        scheduler.completed("lint", true);
+        assert_eq!(scheduler.next_ready().as_deref(), Some("test"));
+
+        scheduler.completed("build", true);
+        scheduler.completed("test", false);
+        assert_eq!(scheduler.next_ready(), None);
+        assert_eq!(scheduler.not_started(), ["deploy"]);
+    }
+
+    fn settings(working_dir: &Path) -> ExecutionSettings {
+        ExecutionSettings {
+            working_dir: working_dir.to_path_buf(),
+            args: Vec::new(),
+            audit_reports: None,
+            cancel: Arc::new(AtomicBool::new(false)),
+            output: Arc::new(TaskOutput::new(false, false)),
+            inherit_stdio: false,
+        }
+    }
+
+    /// Execute a task of `config` in `working_dir` as often as its retries allow
+    async fn run_with_retries(config: serde_json::Value, working_dir: &Path) -> Result<i32> {
+        let config: TaskConfig = serde_json::from_value(config).unwrap();
+        TaskExecutor::execute_task_with_retries(
+            "flaky",
+            &config,
+            &TaskEnv::default(),
+            &settings(working_dir),
+        )
+        .await
+        .map(|(exit_code, _)| exit_code)
+    }
+
+    #[tokio::test]
+    async fn test_retries_until_success() {
+        let temp_dir = TempDir::new().unwrap();
+        // Fails twice before succeeding
+        let flaky =
+            "if [ -f b ]; then exit 0; elif [ -f a ]; then touch b; else touch a; fi; exit 3";
+
+        let config = serde_json::json!({"script": flaky, "retries": 2, "retryDelay": 0});
+        assert_eq!(run_with_retries(config, temp_dir.path()).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_exhausted_retries_return_exit_code() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let config = serde_json::json!({"command": "exit 3", "retries": 1, "retryDelay": 0});
+        assert_eq!(run_with_retries(config, temp_dir.path()).await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_kills_task() {
+        let temp_dir = TempDir::new().unwrap();
+        let started = Instant::now();
+
+        let config = serde_json::json!({"command": "sleep 30", "timeout": 1});
+        let result = run_with_retries(config, temp_dir.path()).await;
+        assert!(matches!(result, Err(Error::Timeout { .. })));
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn test_allowed_failure_runs_dependents() {
+        let temp_dir = TempDir::new().unwrap();
+        let executor = TaskExecutor::new_with_config(
+            EnvManager::new(),
+            temp_dir.path().to_path_buf(),
+            crate::cache::CacheConfig {
+                base_dir: temp_dir.path().join("cache"),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap()
+        .without_cache();
+        let task = |config| serde_json::from_value::<TaskConfig>(config).unwrap();
+        let plan = TaskExecutionPlan {
+            levels: vec![vec!["lint".to_string()], vec!["test".to_string()]],
+            tasks: HashMap::from([
+                (
+                    "lint".to_string(),
+                    task(serde_json::json!({"command": "exit 1", "allowFailure": true})),
+                ),
+                (
+                    "test".to_string(),
+                    task(serde_json::json!({"command": "touch tested", "dependencies": ["lint"]})),
+                ),
+            ]),
+            projects: HashMap::new(),
+        };
+
+        let exit_code = executor
+            .execute_plan(&plan, None, &settings(temp_dir.path()))
+            .await
+            .unwrap();
+        assert_eq!(exit_code, 0);
+        assert!(temp_dir.path().join("tested").exists());
+    }
+
+    async fn task_cache(base_dir: &Path) -> TaskCache {
+        let manager = CacheManager::new(crate::cache::CacheConfig {
+            base_dir: base_dir.to_path_buf(),
+            max_size: 1024 * 1024,
+            mode: crate::cache::CacheMode::ReadWrite,
+            inline_threshold: 4096,
+        })
+        .await
+        .unwrap();
+        TaskCache {
+            manager: Arc::new(manager),
+            result_digests: Mutex::new(HashMap::new()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_dependency_is_part_of_digests() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = task_cache(temp_dir.path()).await;
+        let config: TaskConfig =
+            serde_json::from_value(serde_json::json!({"dependencies": ["lint"]})).unwrap();
+
+        cache.record_failure("lint", 2);
+        assert_eq!(
+            cache.dependency_digests(&config),
+            Some(BTreeMap::from([(
+                "lint".to_string(),
+                "failed:2".to_string()
+            )]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dependent_of_uncaptured_outputs_is_not_cached() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = task_cache(&temp_dir.path().join("cache")).await;
+        let settings = settings(temp_dir.path());
+        let task = |config| serde_json::from_value::<TaskConfig>(config).unwrap();
+        // Capturing the outputs of build fails on the invalid pattern
+        let build = task(serde_json::json!({"command": "true", "outputs": ["["]}));
+        let test = task(serde_json::json!({"command": "true", "dependencies": ["build"]}));
+
+        for (task_name, config) in [("build", &build), ("test", &test)] {
+            let exit_code = TaskExecutor::execute_single_task_with_cache(
+                task_name,
+                config,
+                &TaskEnv::default(),
+                &settings,
+                Some(&cache),
+            )
+            .await
+            .unwrap();
+            assert_eq!(exit_code, 0);
+        }
+        assert_eq!(cache.dependency_digests(&test), None);
+        assert_eq!(cache.manager.get_statistics().writes, 0);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_leaves_local_cache_unchanged() {
+        use crate::cache::{CacheConfig, CacheMode, CachedTaskResult};
+        use crate::remote_cache::{RemoteCacheConfig, RemoteCacheServer};
+        use std::io::Cursor;
+        use std::net::{TcpListener, TcpStream};
+
+        let cache_config = |dir: &TempDir| CacheConfig {
+            base_dir: dir.path().to_path_buf(),
+            max_size: 1024 * 1024,
+            mode: CacheMode::ReadWrite,
+            inline_threshold: 16,
+        };
+        let server_dir = TempDir::new().unwrap();
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let server = RemoteCacheServer::new(RemoteCacheConfig {
+            address,
+            enable_action_cache: true,
+            enable_cas: true,
+            cache_config: cache_config(&server_dir),
+        })
+        .await
+        .unwrap();
+        tokio::spawn(server.serve());
+        while TcpStream::connect(address).is_err() {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let remote = || {
+            RemoteCacheClient::connect(&format!("grpc://{address}"), CacheMode::ReadWrite).unwrap()
+        };
+
+        let work_dir = TempDir::new().unwrap();
+        let local_dir = TempDir::new().unwrap();
+        let mut executor = TaskExecutor::new_with_config(
+            EnvManager::new(),
+            work_dir.path().to_path_buf(),
+            cache_config(&local_dir),
+        )
+        .await
+        .unwrap();
+        executor.cache_manager = Arc::new(
+            CacheManager::new(cache_config(&local_dir))
+                .await
+                .unwrap()
+                .with_remote(remote()),
+        );
+
+        let build: TaskConfig =
+            serde_json::from_value(serde_json::json!({"command": "make"})).unwrap();
+        let test: TaskConfig =
+            serde_json::from_value(serde_json::json!({"dependencies": ["build"]})).unwrap();
+        let cache_key = CacheKeyComponents::collect(
+            "build",
+            &build,
+            &[],
+            &executor
+                .env_manager
+                .task_env(&build)
+                .unwrap()
+                .apply_to(std::env::vars().collect()),
+            work_dir.path(),
+            BTreeMap::new(),
+        )
+        .unwrap()
+        .key();
+
+        // Another machine uploaded the result of build
+        let uploader_dir = TempDir::new().unwrap();
+        let uploader = CacheManager::new(cache_config(&uploader_dir))
+            .await
+            .unwrap()
+            .with_remote(remote());
+        let output = uploader
+            .content_store()
+            .store(Cursor::new(b"a binary larger than inlined".to_vec()))
+            .unwrap();
+        uploader
+            .store_remote_result(&CachedTaskResult {
+                cache_key: cache_key.clone(),
+                executed_at: std::time::SystemTime::now(),
+                exit_code: 0,
+                stdout: Some(b"built\n".to_vec()),
+                stderr: None,
+                output_files: HashMap::from([("bin/app".to_string(), output.clone())]),
+                output_metadata: HashMap::new(),
+                resource_usage: None,
+            })
+            .await
+            .unwrap();
+
+        let plan = TaskExecutionPlan {
+            levels: vec![vec!["build".to_string()], vec!["test".to_string()]],
+            tasks: HashMap::from([("build".to_string(), build), ("test".to_string(), test)]),
+            projects: HashMap::new(),
+        };
+        let predictions = executor.predict_cache_hits(&plan, &[]).await.unwrap();
+        assert_eq!(
+            predictions,
+            HashMap::from([("build".to_string(), true), ("test".to_string(), false)])
+        );
+
+        let cache = &executor.cache_manager;
+        assert!(cache.peek_cached_result(&cache_key).is_none());
+        assert!(!cache.content_store().contains(&output));
+        assert_eq!(cache.get_statistics().remote_hits, 0);
+    }
 }
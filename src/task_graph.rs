@@ -0,0 +1,277 @@
+//! Task dependency graphs
+//!
+//! Backs `cuenv task graph` and `cuenv run --dry-run`, which show what a task
+//! runs and which of its dependencies are expected to come from the cache.
+use crate::errors::{Error, Result};
+use crate::task_executor::TaskExecutionPlan;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::str::FromStr;
+
+/// Output format of `cuenv task graph`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+    Json,
+}
+
+impl FromStr for GraphFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "dot" | "graphviz" => Ok(Self::Dot),
+            "mermaid" => Ok(Self::Mermaid),
+            "json" => Ok(Self::Json),
+            other => Err(Error::unsupported(
+                "graph format",
+                format!("Unknown format '{other}', expected dot, mermaid or json"),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for GraphFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Dot => "dot",
+            Self::Mermaid => "mermaid",
+            Self::Json => "json",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TaskNode {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub dependencies: Vec<String>,
+    /// Position in the topological order, 0 for tasks without dependencies
+    pub level: usize,
+    /// Whether a cached result is expected, `None` if the cache is disabled
+    pub cached: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TaskGraph {
+    /// Tasks ordered by level, then name
+    pub tasks: Vec<TaskNode>,
+}
+
+impl TaskGraph {
+    /// Graph of the tasks in `plan`, with predicted cache hits if known
+    pub fn new(plan: &TaskExecutionPlan, cached: Option<&HashMap<String, bool>>) -> Self {
+        let mut tasks = Vec::with_capacity(plan.tasks.len());
+        for (level, names) in plan.levels.iter().enumerate() {
+            let mut names: Vec<&String> = names.iter().collect();
+            names.sort();
+            for name in names {
+                let config = plan.tasks.get(name);
+                tasks.push(TaskNode {
+                    name: name.clone(),
+                    description: config.and_then(|config| config.description.clone()),
+                    dependencies: config
+                        .and_then(|config| config.dependencies.clone())
+                        .unwrap_or_default(),
+                    level,
+                    cached: cached.map(|cached| cached.get(name).copied().unwrap_or(false)),
+                });
+            }
+        }
+        Self { tasks }
+    }
+
+    pub fn render(&self, format: GraphFormat) -> Result<String> {
+        match format {
+            GraphFormat::Dot => Ok(self.to_dot()),
+            GraphFormat::Mermaid => Ok(self.to_mermaid()),
+            GraphFormat::Json => {
+                let mut json = serde_json::to_string_pretty(self).map_err(|e| Error::Json {
+                    message: "Failed to serialize task graph".to_string(),
+                    source: e,
+                })?;
+                json.push('\n');
+                Ok(json)
+            }
+        }
+    }
+
+    /// The execution plan as printed by `cuenv run --dry-run`
+    pub fn render_plan(&self) -> String {
+        let width = self
+            .tasks
+            .iter()
+            .map(|task| task.name.len())
+            .max()
+            .unwrap_or_default();
+        let cached = self
+            .tasks
+            .iter()
+            .filter(|task| task.cached == Some(true))
+            .count();
+
+        let mut output = match self.tasks.len() {
+            1 => "1 task".to_string(),
+            count => format!("{count} tasks"),
+        };
+        if self.tasks.iter().any(|task| task.cached.is_some()) {
+            let _ = write!(output, ", {cached} cached");
+        }
+        output.push('\n');
+
+        let mut level = None;
+        for task in &self.tasks {
+            if level != Some(task.level) {
+                level = Some(task.level);
+                let _ = writeln!(output, "Level {}", task.level + 1);
+            }
+            let (symbol, state) = match task.cached {
+                Some(true) => ("✓", "cached"),
+                Some(false) => ("→", "runs"),
+                None => ("→", "runs, cache disabled"),
+            };
+            let _ = write!(output, "  {symbol} {:width$}  {state}", task.name);
+            if !task.dependencies.is_empty() {
+                let _ = write!(output, ", after {}", task.dependencies.join(", "));
+            }
+            output.push('\n');
+        }
+        output
+    }
+
+    fn to_dot(&self) -> String {
+        let mut output = String::from("digraph tasks {\n  rankdir=LR;\n  node [shape=box];\n");
+        for task in &self.tasks {
+            let name = dot_quote(&task.name);
+            let style = match task.cached {
+                Some(true) => ", style=filled, fillcolor=palegreen",
+                Some(false) => ", style=dashed",
+                None => "",
+            };
+            let tooltip = task
+                .description
+                .as_deref()
+                .map(|description| format!(", tooltip={}", dot_quote(description)))
+                .unwrap_or_default();
+            let _ = writeln!(output, "  {name} [label={name}{style}{tooltip}];");
+        }
+        for task in &self.tasks {
+            for dependency in &task.dependencies {
+                let _ = writeln!(
+                    output,
+                    "  {} -> {};",
+                    dot_quote(dependency),
+                    dot_quote(&task.name)
+                );
+            }
+        }
+        output.push_str("}\n");
+        output
+    }
+
+    fn to_mermaid(&self) -> String {
+        // Task names may contain characters Mermaid doesn't allow in node ids
+        let ids: HashMap<&str, String> = self
+            .tasks
+            .iter()
+            .enumerate()
+            .map(|(index, task)| (task.name.as_str(), format!("task{index}")))
+            .collect();
+
+        let mut output = String::from("graph LR\n");
+        for task in &self.tasks {
+            let class = match task.cached {
+                Some(true) => ":::cached",
+                Some(false) => ":::uncached",
+                None => "",
+            };
+            let label = task.name.replace('"', "#quot;");
+            let _ = writeln!(output, "  {}[\"{label}\"]{class}", ids[task.name.as_str()]);
+        }
+        for task in &self.tasks {
+            for dependency in &task.dependencies {
+                if let Some(dependency) = ids.get(dependency.as_str()) {
+                    let _ = writeln!(output, "  {dependency} --> {}", ids[task.name.as_str()]);
+                }
+            }
+        }
+        if self.tasks.iter().any(|task| task.cached.is_some()) {
+            output.push_str("  classDef cached fill:#c8e6c9,stroke:#2e7d32\n");
+            output.push_str("  classDef uncached stroke-dasharray:4 4\n");
+        }
+        output
+    }
+}
+
+fn dot_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cue_parser::TaskConfig;
+
+    /// Lint and compile feeding into test, with only compile cached
+    fn graph() -> TaskGraph {
+        let task = |dependencies: &[&str]| TaskConfig {
+            dependencies: Some(dependencies.iter().map(|dep| dep.to_string()).collect()),
+            ..Default::default()
+        };
+        let plan = TaskExecutionPlan {
+            levels: vec![
+                vec!["lint".to_string(), "compile".to_string()],
+                vec!["test".to_string()],
+            ],
+            tasks: HashMap::from([
+                ("compile".to_string(), task(&[])),
+                ("lint".to_string(), task(&[])),
+                ("test".to_string(), task(&["compile", "lint"])),
+            ]),
+            projects: HashMap::new(),
+        };
+        let cached = HashMap::from([("compile".to_string(), true)]);
+        TaskGraph::new(&plan, Some(&cached))
+    }
+
+    #[test]
+    fn test_render_dot() {
+        assert_eq!(
+            graph().render(GraphFormat::Dot).unwrap(),
+            "digraph tasks {\n  rankdir=LR;\n  node [shape=box];\n  \
+             \"compile\" [label=\"compile\", style=filled, fillcolor=palegreen];\n  \
+             \"lint\" [label=\"lint\", style=dashed];\n  \
+             \"test\" [label=\"test\", style=dashed];\n  \
+             \"compile\" -> \"test\";\n  \"lint\" -> \"test\";\n}\n"
+        );
+        assert_eq!("graphviz".parse::<GraphFormat>().unwrap(), GraphFormat::Dot);
+    }
+
+    #[test]
+    fn test_render_mermaid() {
+        assert!(graph()
+            .render(GraphFormat::Mermaid)
+            .unwrap()
+            .contains("  task0[\"compile\"]:::cached\n  task1[\"lint\"]:::uncached\n"));
+    }
+
+    #[test]
+    fn test_render_json() {
+        let json: serde_json::Value =
+            serde_json::from_str(&graph().render(GraphFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["tasks"][2]["level"], 1);
+    }
+
+    #[test]
+    fn test_render_plan() {
+        assert_eq!(
+            graph().render_plan(),
+            "3 tasks, 1 cached\nLevel 1\n  ✓ compile  cached\n  → lint     runs\n\
+             Level 2\n  → test     runs, after compile, lint\n"
+        );
+    }
+}
@@ -0,0 +1,430 @@
+//! Output of concurrently running tasks
+//!
+//! Task output is read line by line, masked through [`OutputFilter`] and
+//! printed with a `[task]` prefix, so the lines of tasks running in parallel
+//! stay readable. On a terminal a progress view of all tasks is kept below the
+//! output, and the last lines of every failed task are replayed once execution
+//! ends. A single task may write to the terminal itself instead.
+use crate::output_filter::OutputFilter;
+use crate::resource_limits::ResourceUsage;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{self, IsTerminal, Write};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// ANSI colors task prefixes cycle through, red is kept for failures
+const PREFIX_COLORS: &[u8] = &[36, 32, 33, 35, 34, 96, 92, 93, 95, 94];
+
+/// Lines of output kept per task for replaying failures
+const MAX_LOG_LINES: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TaskStatus {
+    Running(Instant),
+    Cached,
+    Succeeded(Duration),
+    Failed(Duration),
+    TimedOut(Duration),
+}
+
+/// Last masked output lines of a task
+#[derive(Debug, Default)]
+struct TaskLog {
+    lines: VecDeque<String>,
+    /// Lines dropped to stay within [`MAX_LOG_LINES`]
+    omitted: usize,
+}
+
+impl TaskLog {
+    fn push(&mut self, line: String) {
+        if self.lines.len() == MAX_LOG_LINES {
+            self.lines.pop_front();
+            self.omitted += 1;
+        }
+        self.lines.push_back(line);
+    }
+}
+
+#[derive(Default)]
+struct OutputState {
+    /// Tasks in the order they started
+    tasks: Vec<(String, TaskStatus)>,
+    /// Output of the tasks that didn't succeed so far
+    logs: HashMap<String, TaskLog>,
+    /// Tasks writing to the terminal themselves, whose output is already shown
+    direct: HashSet<String>,
+    /// Lines of the progress view currently on screen
+    drawn: usize,
+}
+
+impl OutputState {
+    fn status_mut(&mut self, task: &str) -> Option<&mut TaskStatus> {
+        self.tasks
+            .iter_mut()
+            .find(|(name, _)| name == task)
+            .map(|(_, status)| status)
+    }
+}
+
+/// Reports the output and progress of the tasks of a single execution
+pub struct TaskOutput {
+    secrets: Arc<RwLock<HashSet<String>>>,
+    /// Whether the progress view is drawn
+    interactive: bool,
+    color: bool,
+    state: Mutex<OutputState>,
+}
+
+impl TaskOutput {
+    pub fn new(interactive: bool, color: bool) -> Self {
+        Self {
+            secrets: Arc::new(RwLock::new(HashSet::new())),
+            interactive,
+            color,
+            state: Mutex::new(OutputState::default()),
+        }
+    }
+
+    /// Progress view and colors when stdout is a terminal, colors unless `NO_COLOR` is set
+    pub fn for_terminal() -> Self {
+        let interactive = io::stdout().is_terminal();
+        Self::new(
+            interactive,
+            interactive && std::env::var_os("NO_COLOR").is_none(),
+        )
+    }
+
+    /// Colors as [`TaskOutput::for_terminal`], without a progress view, which
+    /// tasks writing to the terminal themselves would garble
+    pub fn without_progress() -> Self {
+        Self {
+            interactive: false,
+            ..Self::for_terminal()
+        }
+    }
+
+    pub fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    /// Mask `values` in all further output
+    pub fn add_secrets<'a>(&self, values: impl IntoIterator<Item = &'a str>) {
+        let mut secrets = match self.secrets.write() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        secrets.extend(values.into_iter().map(str::to_string));
+    }
+
+    /// Whether any output needs masking
+    pub fn has_secrets(&self) -> bool {
+        match self.secrets.read() {
+            Ok(secrets) => !secrets.is_empty(),
+            Err(poisoned) => !poisoned.into_inner().is_empty(),
+        }
+    }
+
+    /// Note that `task` writes to the terminal itself, so its output isn't replayed
+    pub fn writes_directly(&self, task: &str) {
+        self.lock().direct.insert(task.to_string());
+    }
+
+    pub fn started(&self, task: &str) {
+        self.update(task, TaskStatus::Running(Instant::now()), || {
+            format!("→ Executing task '{task}'")
+        });
+    }
+
+    pub fn cached(&self, task: &str, restored: usize) {
+        self.lock().logs.remove(task);
+        self.update(task, TaskStatus::Cached, || match restored {
+            0 => format!("✓ Task '{task}' found in cache, skipping execution"),
+            _ => format!("✓ Task '{task}' found in cache, restored {restored} output file(s)"),
+        });
+    }
+
+    pub fn finished(&self, task: &str, success: bool) {
+        if success {
+            self.lock().logs.remove(task);
+        }
+        let elapsed = self.elapsed(task);
+        let status = if success {
+            TaskStatus::Succeeded(elapsed)
+        } else {
+            TaskStatus::Failed(elapsed)
+        };
+        self.update(task, status, || {
+            let elapsed = format_elapsed(elapsed);
+            if success {
+                format!("✓ Task '{task}' completed in {elapsed}")
+            } else {
+                format!("✗ Task '{task}' failed after {elapsed}")
+            }
+        });
+    }
+
+    /// Report a task killed at its timeout
+    pub fn timed_out(&self, task: &str) {
+        let elapsed = self.elapsed(task);
+        self.update(task, TaskStatus::TimedOut(elapsed), || {
+            format!(
+                "✗ Task '{task}' timed out after {}",
+                format_elapsed(elapsed)
+            )
+        });
+    }
+
+    /// Report how much of the restrictions of a task are enforced
+    pub fn restricted(&self, task: &str, enforcement: impl std::fmt::Display) {
+        self.notice(&format!(
+            "🔒 Restrictions of task '{task}' are {enforcement}"
+        ));
+    }
+
+    /// Report the peak memory and CPU time a task used
+    pub fn resources_used(&self, task: &str, usage: &ResourceUsage) {
+        self.notice(&format!("📊 Task '{task}' used {usage}"));
+    }
+
+    /// Print a masked message about the execution above the progress view
+    pub fn notice(&self, message: &str) {
+        let masked = self.mask(message);
+        let mut state = self.lock();
+        self.clear(&mut state);
+        println!("{masked}");
+        self.draw(&mut state);
+    }
+
+    /// Print a line of task output, `stderr` for lines the task wrote to stderr
+    pub fn line(&self, task: &str, line: &str, stderr: bool) {
+        let masked = self.mask(line);
+        let formatted = self.format_line(task, &masked);
+
+        let mut state = self.lock();
+        state.logs.entry(task.to_string()).or_default().push(masked);
+        self.clear(&mut state);
+        if stderr {
+            let mut err = io::stderr().lock();
+            let _ = writeln!(err, "{formatted}");
+            let _ = err.flush();
+        } else {
+            println!("{formatted}");
+        }
+        self.draw(&mut state);
+    }
+
+    /// Redraw the progress view, updating the elapsed time of running tasks
+    pub fn redraw(&self) {
+        if !self.interactive {
+            return;
+        }
+        let mut state = self.lock();
+        self.clear(&mut state);
+        self.draw(&mut state);
+    }
+
+    /// Leave the final progress view on screen and replay the logs of failed tasks
+    pub fn finish(&self) {
+        let mut state = self.lock();
+        self.clear(&mut state);
+        if self.interactive {
+            self.draw(&mut state);
+            state.drawn = 0;
+        }
+
+        for (task, status) in &state.tasks {
+            if !matches!(status, TaskStatus::Failed(_) | TaskStatus::TimedOut(_))
+                || state.direct.contains(task)
+            {
+                continue;
+            }
+            eprintln!("\n── Output of failed task '{task}' ──");
+            match state.logs.get(task) {
+                Some(log) if !log.lines.is_empty() => {
+                    if log.omitted > 0 {
+                        eprintln!("({} earlier lines omitted)", log.omitted);
+                    }
+                    for line in &log.lines {
+                        eprintln!("{line}");
+                    }
+                }
+                _ => eprintln!("(no output)"),
+            }
+        }
+    }
+
+    /// Time a running task has been running for
+    fn elapsed(&self, task: &str) -> Duration {
+        self.lock()
+            .tasks
+            .iter()
+            .find_map(|(name, status)| match status {
+                TaskStatus::Running(started) if name == task => Some(started.elapsed()),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    fn update(&self, task: &str, status: TaskStatus, message: impl FnOnce() -> String) {
+        let mut state = self.lock();
+        match state.status_mut(task) {
+            Some(current) => *current = status,
+            None => state.tasks.push((task.to_string(), status)),
+        }
+
+        if self.interactive {
+            self.clear(&mut state);
+            self.draw(&mut state);
+        } else {
+            println!("{}", message());
+        }
+    }
+
+    fn mask(&self, line: &str) -> String {
+        let mut masked = Vec::with_capacity(line.len());
+        let mut filter = OutputFilter::new(&mut masked, Arc::clone(&self.secrets));
+        if filter.write_all(line.as_bytes()).is_err() {
+            return String::new();
+        }
+        String::from_utf8_lossy(&masked).into_owned()
+    }
+
+    fn format_line(&self, task: &str, line: &str) -> String {
+        if !self.color {
+            return format!("[{task}] {line}");
+        }
+        let color =
+            PREFIX_COLORS[task.bytes().map(usize::from).sum::<usize>() % PREFIX_COLORS.len()];
+        format!("\x1b[{color}m[{task}]\x1b[0m {line}")
+    }
+
+    /// Lines of the progress view
+    fn progress(&self, state: &OutputState) -> Vec<String> {
+        let width = state
+            .tasks
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or_default();
+
+        state
+            .tasks
+            .iter()
+            .map(|(name, status)| {
+                let (symbol, color, detail) = match status {
+                    TaskStatus::Running(started) => (
+                        "●",
+                        33,
+                        format!("running {}", format_elapsed(started.elapsed())),
+                    ),
+                    TaskStatus::Cached => ("↺", 36, "cached".to_string()),
+                    TaskStatus::Succeeded(elapsed) => {
+                        ("✓", 32, format!("done in {}", format_elapsed(*elapsed)))
+                    }
+                    TaskStatus::Failed(elapsed) => (
+                        "✗",
+                        31,
+                        format!("failed after {}", format_elapsed(*elapsed)),
+                    ),
+                    TaskStatus::TimedOut(elapsed) => (
+                        "✗",
+                        31,
+                        format!("timed out after {}", format_elapsed(*elapsed)),
+                    ),
+                };
+                if self.color {
+                    format!("\x1b[{color}m{symbol}\x1b[0m {name:width$}  {detail}")
+                } else {
+                    format!("{symbol} {name:width$}  {detail}")
+                }
+            })
+            .collect()
+    }
+
+    fn clear(&self, state: &mut OutputState) {
+        if state.drawn > 0 {
+            print!("\x1b[{}A\x1b[J", state.drawn);
+            state.drawn = 0;
+        }
+    }
+
+    fn draw(&self, state: &mut OutputState) {
+        if !self.interactive {
+            return;
+        }
+        let lines = self.progress(state);
+        let mut out = io::stdout().lock();
+        for line in &lines {
+            let _ = writeln!(out, "{line}");
+        }
+        let _ = out.flush();
+        state.drawn = lines.len();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, OutputState> {
+        match self.state.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    format!("{:.1}s", elapsed.as_secs_f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lines_are_prefixed_masked_and_logged() {
+        let output = TaskOutput::new(false, false);
+        output.add_secrets(["hunter2"]);
+
+        output.started("build");
+        output.line("build", "password is hunter2", false);
+        output.finished("build", false);
+        output.cached("lint", 0);
+        output.started("e2e");
+        output.timed_out("e2e");
+
+        assert_eq!(
+            output.format_line("build", "compiling"),
+            "[build] compiling"
+        );
+        let state = output.lock();
+        assert_eq!(state.logs["build"].lines, ["password is **********"]);
+        assert!(matches!(state.tasks[0], (_, TaskStatus::Failed(_))));
+
+        let progress = output.progress(&state);
+        assert!(progress[0].starts_with("✗ build  failed after "));
+        assert_eq!(progress[1], "↺ lint   cached");
+        assert!(progress[2].starts_with("✗ e2e    timed out after "));
+    }
+
+    #[test]
+    fn test_log_of_succeeded_task_is_dropped() {
+        let output = TaskOutput::new(false, false);
+        output.started("build");
+        output.line("build", "compiling", false);
+        output.finished("build", true);
+
+        assert!(output.lock().logs.is_empty());
+    }
+
+    #[test]
+    fn test_log_keeps_last_lines() {
+        let output = TaskOutput::new(false, false);
+        output.started("test");
+        for i in 0..MAX_LOG_LINES + 5 {
+            output.line("test", &format!("line {i}"), false);
+        }
+
+        let state = output.lock();
+        let log = &state.logs["test"];
+        assert_eq!(log.lines.len(), MAX_LOG_LINES);
+        assert_eq!(log.omitted, 5);
+        assert_eq!(log.lines[0], "line 5");
+    }
+}
@@ -0,0 +1,307 @@
+//! Task parameters
+//!
+//! Tasks declare `params` in CUE, which `cuenv run <task>` parses from
+//! `--name value` arguments. Values are exposed to the task as
+//! `CUENV_PARAM_<NAME>` variables and substituted for `{{name}}` in its
+//! command or script. Both end up in the task configuration, so they are part
+//! of the cache key.
+use crate::constants::{CUENV_EXPAND_PREFIX, CUENV_RESOLVER_PREFIX};
+use crate::cue_parser::{ParamConfig, ParamType, TaskConfig};
+use crate::errors::{Error, Result};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Arguments of a task parsed against its parameters
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TaskArguments {
+    /// Value of every parameter, defaults included
+    pub values: BTreeMap<String, String>,
+    /// Positional arguments and everything after `--`, appended to the command
+    pub passthrough: Vec<String>,
+}
+
+/// Parse `args` against the parameters of `task_config`
+pub fn parse_arguments(
+    task_name: &str,
+    task_config: &TaskConfig,
+    args: &[String],
+) -> Result<TaskArguments> {
+    let params = task_config.params.clone().unwrap_or_default();
+    let mut parsed = TaskArguments::default();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            parsed.passthrough.extend(args.by_ref().cloned());
+            break;
+        }
+        let Some(flag) = arg.strip_prefix("--") else {
+            parsed.passthrough.push(arg.clone());
+            continue;
+        };
+
+        let (name, inline_value) = match flag.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (flag, None),
+        };
+        let (name, param, negated) = match params.get(name) {
+            Some(param) => (name, param, false),
+            None => match name
+                .strip_prefix("no-")
+                .and_then(|n| Some((n, params.get(n)?)))
+            {
+                Some((name, param)) if param.param_type == ParamType::Bool => (name, param, true),
+                _ => return Err(unknown_parameter(task_name, name, &params)),
+            },
+        };
+
+        let value = match (param.param_type, inline_value) {
+            (_, Some(value)) if !negated => value,
+            (ParamType::Bool, None) => (!negated).to_string(),
+            (_, None) if !negated => args.next().cloned().ok_or_else(|| {
+                Error::configuration(format!(
+                    "Parameter --{name} of task '{task_name}' requires a value"
+                ))
+            })?,
+            _ => return Err(unknown_parameter(task_name, &format!("no-{name}"), &params)),
+        };
+        parsed
+            .values
+            .insert(name.to_string(), validate(task_name, name, param, value)?);
+    }
+
+    for (name, param) in &params {
+        if parsed.values.contains_key(name) {
+            continue;
+        }
+        let Some(default) = &param.default else {
+            return Err(Error::configuration(format!(
+                "Task '{task_name}' requires parameter --{name}"
+            )));
+        };
+        parsed.values.insert(name.clone(), value_string(default));
+    }
+
+    Ok(parsed)
+}
+
+/// The task configuration with parameter values substituted into its command
+/// or script and added to its environment
+pub fn apply_arguments(task_config: &TaskConfig, values: &BTreeMap<String, String>) -> TaskConfig {
+    let mut task_config = task_config.clone();
+    if values.is_empty() {
+        return task_config;
+    }
+
+    task_config.command = task_config
+        .command
+        .map(|command| substitute(&command, values));
+    task_config.script = task_config.script.map(|script| substitute(&script, values));
+
+    let env = task_config.env.get_or_insert_with(Default::default);
+    for (name, value) in values {
+        env.insert(param_env_var(name), value.clone());
+    }
+    task_config
+}
+
+/// Whether `args` ask for the help of a task rather than passing it arguments
+pub fn wants_help(args: &[String]) -> bool {
+    args.iter()
+        .take_while(|arg| *arg != "--")
+        .any(|arg| arg == "--help" || arg == "-h")
+}
+
+/// Usage of a task and its parameters, as printed by `cuenv run <task> --help`
+pub fn help(task_name: &str, task_config: &TaskConfig) -> String {
+    let mut output = format!("Usage: cuenv run {task_name} [OPTIONS] [-- ARGS...]\n");
+    if let Some(description) = &task_config.description {
+        let _ = write!(output, "\n{description}\n");
+    }
+
+    let params = task_config.params.clone().unwrap_or_default();
+    if params.is_empty() {
+        output.push_str("\nThe task takes no parameters, arguments are appended to its command.\n");
+        return output;
+    }
+
+    let usages: Vec<(String, String)> = params
+        .iter()
+        .map(|(name, param)| {
+            let usage = match param.param_type {
+                ParamType::Bool => format!("--{name}, --no-{name}"),
+                ParamType::Int => format!("--{name} <INT>"),
+                ParamType::String => format!("--{name} <VALUE>"),
+            };
+
+            let mut details = param.description.clone().unwrap_or_default();
+            if let Some(choices) = &param.choices {
+                let choices: Vec<String> = choices.iter().map(value_string).collect();
+                let _ = write!(details, " [possible values: {}]", choices.join(", "));
+            }
+            match &param.default {
+                Some(default) => {
+                    let _ = write!(details, " [default: {}]", value_string(default));
+                }
+                None => details.push_str(" [required]"),
+            }
+            (usage, details.trim_start().to_string())
+        })
+        .collect();
+
+    let width = usages
+        .iter()
+        .map(|(usage, _)| usage.len())
+        .max()
+        .unwrap_or(0);
+    output.push_str("\nParameters:\n");
+    for (usage, details) in usages {
+        let _ = writeln!(output, "  {usage:width$}  {details}");
+    }
+    output
+}
+
+/// Name of the variable holding the value of parameter `name`
+pub fn param_env_var(name: &str) -> String {
+    format!("CUENV_PARAM_{}", name.to_uppercase().replace('-', "_"))
+}
+
+fn validate(task_name: &str, name: &str, param: &ParamConfig, value: String) -> Result<String> {
+    let invalid = |expected: &str| {
+        Error::configuration(format!(
+            "Invalid value '{value}' for parameter --{name} of task '{task_name}': expected {expected}"
+        ))
+    };
+
+    // Values are passed on as plain strings, never as references to resolve
+    if value.starts_with(CUENV_RESOLVER_PREFIX) || value.starts_with(CUENV_EXPAND_PREFIX) {
+        return Err(invalid("a plain value"));
+    }
+
+    match param.param_type {
+        ParamType::String => {}
+        ParamType::Int if value.parse::<i64>().is_err() => return Err(invalid("an integer")),
+        ParamType::Bool if value != "true" && value != "false" => {
+            return Err(invalid("true or false"))
+        }
+        _ => {}
+    }
+
+    if let Some(choices) = &param.choices {
+        let choices: Vec<String> = choices.iter().map(value_string).collect();
+        if !choices.contains(&value) {
+            return Err(invalid(&format!("one of {}", choices.join(", "))));
+        }
+    }
+    Ok(value)
+}
+
+fn unknown_parameter(task_name: &str, name: &str, params: &BTreeMap<String, ParamConfig>) -> Error {
+    let known = if params.is_empty() {
+        "it takes no parameters, pass arguments after --".to_string()
+    } else {
+        let names: Vec<String> = params.keys().map(|name| format!("--{name}")).collect();
+        format!("expected one of {}", names.join(", "))
+    };
+    Error::configuration(format!(
+        "Unknown parameter --{name} for task '{task_name}': {known}"
+    ))
+}
+
+/// Replace `{{name}}` and `{{ name }}` with the shell-quoted value of each parameter
+fn substitute(template: &str, values: &BTreeMap<String, String>) -> String {
+    let mut result = template.to_string();
+    for (name, value) in values {
+        let quoted = shell_quote(value);
+        result = result
+            .replace(&format!("{{{{{name}}}}}"), &quoted)
+            .replace(&format!("{{{{ {name} }}}}"), &quoted);
+    }
+    result
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+fn value_string(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn deploy() -> TaskConfig {
+        serde_json::from_value(serde_json::json!({
+            "command": "deploy --region {{region}} --replicas {{ replicas }}",
+            "params": {
+                "region": {"enum": ["eu-west-1", "us-east-1"], "description": "Target region"},
+                "replicas": {"type": "int", "default": 2},
+                "dry-run": {"type": "bool", "default": false},
+            },
+        }))
+        .unwrap()
+    }
+
+    fn parse(list: &[&str]) -> Result<TaskArguments> {
+        parse_arguments("deploy", &deploy(), &args(list))
+    }
+
+    #[test]
+    fn test_parse_arguments() {
+        let parsed =
+            parse(&["--region", "eu-west-1", "--dry-run", "extra", "--", "--raw"]).unwrap();
+        assert_eq!(parsed.values["region"], "eu-west-1");
+        assert_eq!(parsed.values["replicas"], "2");
+        assert_eq!(parsed.values["dry-run"], "true");
+        assert_eq!(parsed.passthrough, ["extra", "--raw"]);
+
+        assert_eq!(
+            parse(&["--region=us-east-1", "--no-dry-run"])
+                .unwrap()
+                .values["dry-run"],
+            "false"
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_arguments() {
+        assert!(parse(&[]).is_err(), "region is required");
+        assert!(parse(&["--region", "ap-south-1"]).is_err());
+        assert!(parse(&["--region=us-east-1", "--replicas", "two"]).is_err());
+        assert!(parse(&["--region=us-east-1", "--zone", "a"]).is_err());
+    }
+
+    #[test]
+    fn test_apply_arguments() {
+        let task = deploy();
+        let parsed = parse(&["--region", "eu-west-1", "--dry-run"]).unwrap();
+
+        let applied = apply_arguments(&task, &parsed.values);
+        assert_eq!(
+            applied.command.as_deref(),
+            Some("deploy --region 'eu-west-1' --replicas '2'")
+        );
+        assert_eq!(applied.env.unwrap()["CUENV_PARAM_DRY_RUN"], "true");
+    }
+
+    #[test]
+    fn test_help() {
+        assert!(wants_help(&args(&["--region", "x", "-h"])));
+        assert!(!wants_help(&args(&["--", "--help"])));
+
+        let help = help("deploy", &deploy());
+        assert!(help.contains(
+            "  --region <VALUE>         Target region [possible values: eu-west-1, us-east-1] [required]"
+        ));
+        assert!(help.contains("  --replicas <INT>         [default: 2]"));
+    }
+}
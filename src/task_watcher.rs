@@ -0,0 +1,358 @@
+//! Watch mode for tasks
+//!
+//! Re-executes a task and its dependency chain whenever files matched by the
+//! `inputs` of any task in its execution plan change.
+
+use crate::cache::expand_glob_pattern;
+use crate::errors::{Error, Result};
+use crate::task_executor::{TaskExecutionPlan, TaskExecutor};
+use globset::{Glob, GlobMatcher};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Default quiet period after the last file event before re-running
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// A single input pattern of a task, relative to the task's directory
+struct InputPattern {
+    base_dir: PathBuf,
+    pattern: String,
+    matcher: Option<GlobMatcher>,
+}
+
+impl InputPattern {
+    fn new(base_dir: PathBuf, pattern: &str) -> Result<Self> {
+        let is_glob = pattern.contains('*') || pattern.contains('?') || pattern.contains('[');
+        let matcher = if is_glob {
+            let glob = Glob::new(pattern).map_err(|e| {
+                Error::configuration(format!("Invalid glob pattern '{pattern}': {e}"))
+            })?;
+            Some(glob.compile_matcher())
+        } else {
+            None
+        };
+
+        Ok(Self {
+            base_dir,
+            pattern: pattern.to_string(),
+            matcher,
+        })
+    }
+
+    /// Whether a changed path is covered by this pattern
+    fn matches(&self, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.base_dir) else {
+            return false;
+        };
+
+        match &self.matcher {
+            Some(matcher) => matcher.is_match(relative),
+            // Plain paths cover the file itself or everything below a directory
+            None => relative.starts_with(&self.pattern),
+        }
+    }
+}
+
+/// The inputs of every task in an execution plan
+pub struct WatchInputs {
+    patterns: Vec<InputPattern>,
+}
+
+impl WatchInputs {
+    /// Collect the input patterns of all tasks in `plan`, relative to
+    /// `working_dir` or the project of tasks from other projects
+    pub fn from_plan(plan: &TaskExecutionPlan, working_dir: &Path) -> Result<Self> {
+        let mut patterns = Vec::new();
+
+        for (task_name, config) in &plan.tasks {
+            let project_dir = plan
+                .projects
+                .get(task_name)
+                .map_or(working_dir, PathBuf::as_path);
+            let base_dir = match &config.working_dir {
+                Some(task_wd) => project_dir.join(task_wd),
+                None => project_dir.to_path_buf(),
+            };
+
+            for pattern in config.inputs.iter().flatten() {
+                patterns.push(InputPattern::new(base_dir.clone(), pattern)?);
+            }
+        }
+
+        Ok(Self { patterns })
+    }
+
+    /// Whether no task in the plan declares any inputs
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Directories that need to be watched to observe every input
+    pub fn roots(&self) -> Vec<PathBuf> {
+        let mut roots: Vec<PathBuf> = Vec::new();
+        for pattern in &self.patterns {
+            if !roots.iter().any(|root| pattern.base_dir.starts_with(root)) {
+                roots.retain(|root| !root.starts_with(&pattern.base_dir));
+                roots.push(pattern.base_dir.clone());
+            }
+        }
+        roots
+    }
+
+    /// Whether a file system event touches any of the inputs
+    pub fn is_relevant(&self, event: &Event) -> bool {
+        if matches!(event.kind, EventKind::Access(_)) {
+            return false;
+        }
+
+        event
+            .paths
+            .iter()
+            .any(|path| self.patterns.iter().any(|pattern| pattern.matches(path)))
+    }
+
+    /// Hash the paths and contents of all files currently matched by the inputs
+    pub fn hash(&self) -> Result<String> {
+        let mut files = Vec::new();
+        for pattern in &self.patterns {
+            files.extend(expand_glob_pattern(&pattern.pattern, &pattern.base_dir)?);
+        }
+        files.sort();
+        files.dedup();
+
+        let mut hasher = Sha256::new();
+        for file in files {
+            // Files may disappear between expansion and reading
+            let Ok(content) = std::fs::read(&file) else {
+                continue;
+            };
+            hasher.update(file.to_string_lossy().as_bytes());
+            hasher.update((content.len() as u64).to_le_bytes());
+            hasher.update(&content);
+        }
+
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+}
+
+/// Re-runs a task whenever its inputs change
+pub struct TaskWatcher {
+    executor: Arc<TaskExecutor>,
+    task_name: String,
+    args: Vec<String>,
+    debounce: Duration,
+}
+
+impl TaskWatcher {
+    /// Create a watcher for `task_name`.
+    ///
+    /// The executor should be built with [`TaskExecutor::without_cache`] so
+    /// that re-runs are not answered from the cache.
+    pub fn new(executor: TaskExecutor, task_name: impl Into<String>, args: Vec<String>) -> Self {
+        Self {
+            executor: Arc::new(executor),
+            task_name: task_name.into(),
+            args,
+            debounce: DEFAULT_DEBOUNCE,
+        }
+    }
+
+    /// Set the quiet period that must pass after a change before re-running
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Run the task, then keep re-running it as its inputs change.
+    ///
+    /// A change while the task is running cancels it by killing its process
+    /// group before the task and its dependencies are executed again. Changes
+    /// that leave the input contents unchanged do not trigger a re-run.
+    pub async fn run(self) -> Result<()> {
+        let plan = self
+            .executor
+            .build_execution_plan(std::slice::from_ref(&self.task_name))?;
+        let inputs = WatchInputs::from_plan(&plan, self.executor.working_dir())?;
+
+        if inputs.is_empty() {
+            return Err(Error::configuration(format!(
+                "Task '{}' and its dependencies declare no inputs to watch",
+                self.task_name
+            )));
+        }
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut watcher: RecommendedWatcher =
+            notify::recommended_watcher(move |res: notify::Result<Event>| match res {
+                Ok(event) => {
+                    let _ = tx.send(event);
+                }
+                Err(e) => log::warn!("File watch error: {e}"),
+            })
+            .map_err(|e| Error::configuration(format!("Failed to create file watcher: {e}")))?;
+
+        for root in inputs.roots() {
+            watcher
+                .watch(&root, RecursiveMode::Recursive)
+                .map_err(|e| {
+                    Error::configuration(format!("Failed to watch {}: {e}", root.display()))
+                })?;
+        }
+
+        println!(
+            "👀 Watching inputs of task '{}' (press Ctrl+C to stop)",
+            self.task_name
+        );
+
+        let cancel = self.executor.cancellation_handle();
+        let mut last_hash = inputs.hash()?;
+        let mut running = Some(self.spawn_run());
+
+        loop {
+            tokio::select! {
+                result = wait_for_run(&mut running) => {
+                    running = None;
+                    self.report(result);
+                }
+                event = rx.recv() => {
+                    let Some(event) = event else {
+                        break;
+                    };
+                    if !inputs.is_relevant(&event) {
+                        continue;
+                    }
+
+                    self.debounce(&mut rx).await;
+
+                    let hash = inputs.hash()?;
+                    if hash == last_hash {
+                        log::debug!("Inputs of task '{}' unchanged, skipping re-run", self.task_name);
+                        continue;
+                    }
+                    last_hash = hash;
+
+                    if let Some(handle) = running.take() {
+                        println!("↻ Inputs changed, restarting task '{}'", self.task_name);
+                        cancel.store(true, Ordering::SeqCst);
+                        let _ = handle.await;
+                        cancel.store(false, Ordering::SeqCst);
+                    } else {
+                        println!("↻ Inputs changed, re-running task '{}'", self.task_name);
+                    }
+
+                    running = Some(self.spawn_run());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Wait until no events arrive for the debounce period
+    async fn debounce(&self, rx: &mut mpsc::UnboundedReceiver<Event>) {
+        while let Ok(Some(_)) = tokio::time::timeout(self.debounce, rx.recv()).await {}
+    }
+
+    fn spawn_run(&self) -> JoinHandle<Result<i32>> {
+        let executor = Arc::clone(&self.executor);
+        let task_name = self.task_name.clone();
+        let args = self.args.clone();
+
+        tokio::spawn(async move { executor.execute_task(&task_name, &args).await })
+    }
+
+    fn report(&self, result: std::result::Result<Result<i32>, tokio::task::JoinError>) {
+        match result {
+            Ok(Ok(0)) => println!("✓ Task '{}' completed", self.task_name),
+            Ok(Ok(status)) => eprintln!("✗ Task '{}' exited with {status}", self.task_name),
+            Ok(Err(e)) => eprintln!("✗ Task '{}' failed: {e}", self.task_name),
+            Err(e) => eprintln!("✗ Task '{}' panicked: {e}", self.task_name),
+        }
+        println!("👀 Waiting for changes...");
+    }
+}
+
+/// Await the running task, or never resolve when nothing is running
+async fn wait_for_run(
+    running: &mut Option<JoinHandle<Result<i32>>>,
+) -> std::result::Result<Result<i32>, tokio::task::JoinError> {
+    match running {
+        Some(handle) => handle.await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cue_parser::TaskConfig;
+    use std::collections::HashMap;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn task_with_inputs(inputs: &[&str], working_dir: Option<&str>) -> TaskConfig {
+        TaskConfig {
+            command: Some("true".to_string()),
+            working_dir: working_dir.map(str::to_string),
+            inputs: Some(inputs.iter().map(|s| s.to_string()).collect()),
+            ..Default::default()
+        }
+    }
+
+    fn plan(tasks: Vec<(&str, TaskConfig)>) -> TaskExecutionPlan {
+        TaskExecutionPlan {
+            levels: vec![tasks.iter().map(|(name, _)| name.to_string()).collect()],
+            tasks: tasks
+                .into_iter()
+                .map(|(name, config)| (name.to_string(), config))
+                .collect::<HashMap<_, _>>(),
+            projects: HashMap::new(),
+        }
+    }
+
+    fn modify_event(path: PathBuf) -> Event {
+        Event::new(EventKind::Modify(notify::event::ModifyKind::Any)).add_path(path)
+    }
+
+    #[test]
+    fn test_relevant_events() {
+        let root = PathBuf::from("/project");
+        let plan = plan(vec![
+            ("build", task_with_inputs(&["src/**/*.rs"], None)),
+            (
+                "codegen",
+                task_with_inputs(&["schema.proto"], Some("proto")),
+            ),
+        ]);
+        let inputs = WatchInputs::from_plan(&plan, &root).unwrap();
+
+        assert!(inputs.is_relevant(&modify_event(root.join("src/lib.rs"))));
+        assert!(inputs.is_relevant(&modify_event(root.join("proto/schema.proto"))));
+        assert!(!inputs.is_relevant(&modify_event(root.join("README.md"))));
+        assert!(!inputs.is_relevant(&modify_event(root.join("schema.proto"))));
+        assert_eq!(inputs.roots(), vec![root]);
+    }
+
+    #[test]
+    fn test_hash_tracks_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("input.txt");
+        fs::write(&file, "one").unwrap();
+
+        let plan = plan(vec![("build", task_with_inputs(&["*.txt"], None))]);
+        let inputs = WatchInputs::from_plan(&plan, temp_dir.path()).unwrap();
+
+        let first = inputs.hash().unwrap();
+        fs::write(&file, "one").unwrap();
+        assert_eq!(first, inputs.hash().unwrap());
+
+        fs::write(&file, "two").unwrap();
+        assert_ne!(first, inputs.hash().unwrap());
+    }
+}
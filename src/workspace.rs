@@ -0,0 +1,218 @@
+//! Task references across the projects of a workspace
+//!
+//! Besides the name of a task in the same project, a task can refer to a task
+//! of another project as `//libs/proto:generate`, relative to the workspace
+//! root, or `../shared:codegen`, relative to its own project. `//...:test`
+//! refers to the `test` task of every project in the workspace. The workspace
+//! root is the nearest directory containing one of the
+//! [`HIERARCHY_ROOT_MARKERS`].
+use crate::constants::HIERARCHY_ROOT_MARKERS;
+use crate::errors::{Error, Result};
+use crate::project_config::has_env_package;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Directories never searched for projects by `//...`
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target"];
+
+/// Projects a task reference points to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProjectPath {
+    /// `//path`, relative to the workspace root
+    Root(PathBuf),
+    /// `./path` or `../path`, relative to the referring project
+    Relative(PathBuf),
+    /// `//...`, every project in the workspace
+    All,
+}
+
+/// A reference to a task of another project
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskRef {
+    pub project: ProjectPath,
+    pub task: String,
+}
+
+impl TaskRef {
+    /// Parse `//path:task`, `//...:task`, `./path:task` or `../path:task`.
+    /// Anything else names a task of the same project and gives `None`.
+    pub fn parse(reference: &str) -> Option<Self> {
+        let (path, task) = reference.rsplit_once(':')?;
+        if task.is_empty() {
+            return None;
+        }
+
+        let project = if path == "//..." {
+            ProjectPath::All
+        } else if let Some(path) = path.strip_prefix("//") {
+            ProjectPath::Root(PathBuf::from(path))
+        } else if path == "." || path == ".." || path.starts_with("./") || path.starts_with("../") {
+            ProjectPath::Relative(PathBuf::from(path))
+        } else {
+            return None;
+        };
+
+        Some(Self {
+            project,
+            task: task.to_string(),
+        })
+    }
+}
+
+/// The workspace root `dir` belongs to
+pub fn find_root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|ancestor| {
+            HIERARCHY_ROOT_MARKERS
+                .iter()
+                .any(|marker| ancestor.join(marker).exists())
+        })
+        .map(Path::to_path_buf)
+}
+
+/// Directories of the projects a reference made from `from` points to
+pub fn project_dirs(project: &ProjectPath, from: &Path) -> Result<Vec<PathBuf>> {
+    let root = || {
+        find_root(from).ok_or_else(|| {
+            Error::configuration(format!(
+                "No workspace root found above {}, expected one of: {}",
+                from.display(),
+                HIERARCHY_ROOT_MARKERS.join(", ")
+            ))
+        })
+    };
+
+    let dir = match project {
+        ProjectPath::All => return Ok(discover_projects(&root()?)),
+        ProjectPath::Root(path) => root()?.join(path),
+        ProjectPath::Relative(path) => from.join(path),
+    };
+    let dir = fs::canonicalize(&dir)
+        .map_err(|e| Error::file_system(dir.clone(), "resolve project directory", e))?;
+    if !has_env_package(&dir) {
+        return Err(Error::configuration(format!(
+            "No env package found in project {}",
+            dir.display()
+        )));
+    }
+    Ok(vec![dir])
+}
+
+/// Every directory below `root` containing an env package, in path order.
+/// Hidden directories and dependency or build directories are skipped.
+pub fn discover_projects(root: &Path) -> Vec<PathBuf> {
+    let mut projects = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        if has_env_package(&dir) {
+            projects.push(fs::canonicalize(&dir).unwrap_or_else(|_| dir.clone()));
+        }
+
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+            if is_dir && !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_ref()) {
+                pending.push(entry.path());
+            }
+        }
+    }
+
+    projects.sort();
+    projects
+}
+
+/// Label of a task of the project in `dir`, `//path:task` relative to the
+/// workspace root, or with the absolute path outside of a workspace
+pub fn task_label(dir: &Path, task: &str) -> String {
+    let relative = find_root(dir).and_then(|root| {
+        let root = fs::canonicalize(&root).unwrap_or(root);
+        let path = dir.strip_prefix(&root).ok()?;
+        let parts: Vec<String> = path
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect();
+        Some(parts.join("/"))
+    });
+
+    match relative {
+        Some(path) => format!("//{path}:{task}"),
+        None => format!("{}:{task}", dir.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Workspace with two projects and two that discovery skips
+    fn workspace() -> (TempDir, PathBuf) {
+        let temp_dir = TempDir::new().unwrap();
+        let root = fs::canonicalize(temp_dir.path()).unwrap();
+        fs::create_dir(root.join(".git")).unwrap();
+        for project in ["services/api", "libs/proto", "node_modules/dep", ".cache/x"] {
+            fs::create_dir_all(root.join(project)).unwrap();
+            fs::write(root.join(project).join("env.cue"), "package env\n").unwrap();
+        }
+        (temp_dir, root)
+    }
+
+    #[test]
+    fn test_parse_task_references() {
+        assert_eq!(TaskRef::parse("build"), None);
+        assert_eq!(TaskRef::parse("docker:build"), None);
+        assert_eq!(
+            TaskRef::parse("//libs/proto:generate"),
+            Some(TaskRef {
+                project: ProjectPath::Root(PathBuf::from("libs/proto")),
+                task: "generate".to_string(),
+            })
+        );
+        assert_eq!(
+            TaskRef::parse("../shared:codegen").map(|r| r.project),
+            Some(ProjectPath::Relative(PathBuf::from("../shared")))
+        );
+        assert_eq!(
+            TaskRef::parse("//...:test").map(|r| r.project),
+            Some(ProjectPath::All)
+        );
+    }
+
+    #[test]
+    fn test_discover_projects_skips_dependencies_and_hidden_dirs() {
+        let (_temp_dir, root) = workspace();
+        assert_eq!(
+            discover_projects(&root),
+            [root.join("libs/proto"), root.join("services/api")]
+        );
+    }
+
+    #[test]
+    fn test_project_dirs() {
+        let (_temp_dir, root) = workspace();
+        let api = root.join("services/api");
+        assert_eq!(
+            project_dirs(&ProjectPath::Relative("../../libs/proto".into()), &api).unwrap(),
+            [root.join("libs/proto")]
+        );
+        // A directory without an env package is not a project
+        assert!(project_dirs(&ProjectPath::Root("services".into()), &api).is_err());
+    }
+
+    #[test]
+    fn test_task_label() {
+        let (_temp_dir, root) = workspace();
+        assert_eq!(
+            task_label(&root.join("services/api"), "build"),
+            "//services/api:build"
+        );
+    }
+}
@@ -189,26 +189,15 @@ proptest! {
         let security = SecurityConfig {
             restrict_disk,
             restrict_network,
-            read_only_paths: None,
-            read_write_paths: None,
-            deny_paths: None,
-            allowed_hosts: None,
             infer_from_inputs_outputs: Some(infer),
+            ..Default::default()
         };
 
         let task_config = TaskConfig {
             command: Some("test".to_string()),
-            description: None,
-            script: None,
-            dependencies: None,
-            working_dir: None,
-            shell: None,
             inputs: inputs.clone(),
             outputs: outputs.clone(),
-            security: None,
-            cache: None,
-            cache_key: None,
-            timeout: None,
+            ..Default::default()
         };
 
         let restrictions = AccessRestrictions::from_security_config_with_task(&security, &task_config);
@@ -104,16 +104,10 @@ fn test_concurrent_cache_writes() {
                 let task_config = TaskConfig {
                     description: Some(format!("Test task {}", i)),
                     command: Some(format!("echo task_{}", i)),
-                    script: None,
-                    dependencies: None,
-                    working_dir: None,
-                    shell: None,
                     inputs: Some(vec![format!("src/file_{}.rs", i)]),
                     outputs: Some(vec![format!("build/output_{}.o", i)]),
                     cache: Some(true),
-                    cache_key: None,
-                    timeout: None,
-                    security: None,
+                    ..Default::default()
                 };
 
                 // Create input file
@@ -168,16 +162,11 @@ fn test_concurrent_same_key_access() {
     let task_config = Arc::new(TaskConfig {
         description: Some("Shared task".to_string()),
         command: Some("echo shared".to_string()),
-        script: None,
-        dependencies: None,
-        working_dir: None,
-        shell: None,
         inputs: Some(vec!["src/*.rs".to_string()]),
         outputs: Some(vec!["build/shared.o".to_string()]),
         cache: Some(true),
         cache_key: Some("shared_key".to_string()), // Force same cache key
-        timeout: None,
-        security: None,
+        ..Default::default()
     });
 
     // Create test files
@@ -248,16 +237,8 @@ fn test_cache_invalidation_race() {
     let initial_task_config = TaskConfig {
         description: Some("Initial task".to_string()),
         command: Some("echo initial".to_string()),
-        script: None,
-        dependencies: None,
-        working_dir: None,
-        shell: None,
-        inputs: None,
-        outputs: None,
         cache: Some(true),
-        cache_key: None,
-        timeout: None,
-        security: None,
+        ..Default::default()
     };
 
     // Generate initial cache key and save
@@ -294,16 +275,8 @@ fn test_cache_invalidation_race() {
                 let task_config = TaskConfig {
                     description: Some(format!("Task for thread {}", i)),
                     command: Some(format!("echo thread{}", i)),
-                    script: None,
-                    dependencies: None,
-                    working_dir: None,
-                    shell: None,
-                    inputs: None,
-                    outputs: None,
                     cache: Some(true),
-                    cache_key: None,
-                    timeout: None,
-                    security: None,
+                    ..Default::default()
                 };
 
                 // Generate unique cache key for this thread
@@ -425,16 +398,9 @@ fn test_cache_cleanup() {
         let task_config = TaskConfig {
             description: Some(format!("Old task {}", i)),
             command: Some("echo old".to_string()),
-            script: None,
-            dependencies: None,
-            working_dir: None,
-            shell: None,
-            inputs: None,
-            outputs: None,
             cache: Some(true),
             cache_key: Some(format!("old_key_{}", i)),
-            timeout: None,
-            security: None,
+            ..Default::default()
         };
 
         let cache_key = cache_manager
@@ -482,16 +448,10 @@ fn test_cache_lock_timeout() {
     let task_config = TaskConfig {
         description: Some("Lock test".to_string()),
         command: Some("echo test".to_string()),
-        script: None,
-        dependencies: None,
-        working_dir: None,
-        shell: None,
-        inputs: None,
         outputs: Some(vec!["output.txt".to_string()]),
         cache: Some(true),
         cache_key: Some("lock_test_key".to_string()),
-        timeout: None,
-        security: None,
+        ..Default::default()
     };
 
     // Create output file
@@ -0,0 +1,92 @@
+//! Integration tests for persisted task results and output restoration
+
+use cuenv::cache::{CacheConfig, CacheManager, CacheMode, CachedTaskResult};
+use std::fs;
+use std::time::SystemTime;
+use tempfile::TempDir;
+
+fn cache_config(dir: &TempDir) -> CacheConfig {
+    CacheConfig {
+        base_dir: dir.path().join("cuenv"),
+        max_size: 1024 * 1024 * 1024,
+        mode: CacheMode::ReadWrite,
+        inline_threshold: 4096,
+    }
+}
+
+#[tokio::test]
+async fn test_outputs_restored_by_new_cache_manager() {
+    let cache_dir = TempDir::new().unwrap();
+    let work_dir = TempDir::new().unwrap();
+    let patterns = vec!["dist/*.js".to_string()];
+    let cache_key = "a".repeat(64);
+
+    fs::create_dir_all(work_dir.path().join("dist")).unwrap();
+    fs::write(work_dir.path().join("dist/app.js"), "console.log(1)").unwrap();
+
+    {
+        let cache = CacheManager::new(cache_config(&cache_dir)).await.unwrap();
+        let (output_files, output_metadata) =
+            cache.capture_outputs(&patterns, work_dir.path()).unwrap();
+        let result = CachedTaskResult {
+            cache_key: cache_key.clone(),
+            executed_at: SystemTime::now(),
+            exit_code: 0,
+            stdout: None,
+            stderr: None,
+            output_files,
+            output_metadata,
+            resource_usage: None,
+        };
+        cache.store_result(cache_key.clone(), result).unwrap();
+    }
+
+    // Replace the outputs with the leftovers of a different build
+    fs::remove_file(work_dir.path().join("dist/app.js")).unwrap();
+    fs::write(work_dir.path().join("dist/old.js"), "stale").unwrap();
+
+    let cache = CacheManager::new(cache_config(&cache_dir)).await.unwrap();
+    let result = cache
+        .get_cached_result(&cache_key)
+        .expect("result should be persisted across cache managers");
+    let restored = cache
+        .restore_outputs(&result, &patterns, work_dir.path())
+        .unwrap();
+
+    assert_eq!(restored, 1);
+    assert_eq!(
+        fs::read_to_string(work_dir.path().join("dist/app.js")).unwrap(),
+        "console.log(1)"
+    );
+    assert!(!work_dir.path().join("dist/old.js").exists());
+}
+
+#[tokio::test]
+async fn test_tampered_result_is_ignored() {
+    let cache_dir = TempDir::new().unwrap();
+    let cache_key = "b".repeat(64);
+
+    let cache = CacheManager::new(cache_config(&cache_dir)).await.unwrap();
+    let result = CachedTaskResult {
+        cache_key: cache_key.clone(),
+        executed_at: SystemTime::now(),
+        exit_code: 0,
+        stdout: None,
+        stderr: None,
+        output_files: Default::default(),
+        output_metadata: Default::default(),
+        resource_usage: None,
+    };
+    cache.store_result(cache_key.clone(), result).unwrap();
+
+    let path = cache_dir
+        .path()
+        .join("cuenv/results")
+        .join(&cache_key[..2])
+        .join(format!("{}.json", &cache_key[2..]));
+    let content = fs::read_to_string(&path).unwrap();
+    fs::write(&path, content.replace("\"stdout\":null", "\"stdout\":[65]")).unwrap();
+
+    let cache = CacheManager::new(cache_config(&cache_dir)).await.unwrap();
+    assert!(cache.get_cached_result(&cache_key).is_none());
+}
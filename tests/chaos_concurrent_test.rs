@@ -101,16 +101,9 @@ mod chaos_concurrent_tests {
                         let task_config = TaskConfig {
                             description: Some(format!("Chaos task {}_{}", thread_id, op)),
                             command: Some("echo test".to_string()),
-                            script: None,
-                            dependencies: None,
-                            working_dir: None,
-                            shell: None,
-                            inputs: None,
                             outputs: Some(vec![format!("output_{}_{}.txt", thread_id, op)]),
-                            security: None,
                             cache: Some(true),
-                            cache_key: None,
-                            timeout: None,
+                            ..Default::default()
                         };
 
                         // Try to write output file with chaos
@@ -392,16 +385,9 @@ mod chaos_concurrent_tests {
         let task_config = TaskConfig {
             description: Some("Corruption test".to_string()),
             command: Some("echo test".to_string()),
-            script: None,
-            dependencies: None,
-            working_dir: None,
-            shell: None,
-            inputs: None,
             outputs: Some(vec!["output.txt".to_string()]),
-            security: None,
             cache: Some(true),
-            cache_key: None,
-            timeout: None,
+            ..Default::default()
         };
 
         // Create output file
@@ -64,16 +64,9 @@ mod comprehensive_concurrent_tests {
                     let task_config = TaskConfig {
                         description: Some(format!("Resource test {}", i)),
                         command: Some("dd if=/dev/zero of=/dev/null bs=1M count=100".to_string()),
-                        script: None,
-                        dependencies: None,
-                        working_dir: None,
-                        shell: None,
-                        inputs: None,
-                        outputs: None,
-                        security: None,
                         cache: Some(false),
-                        cache_key: None,
                         timeout: Some(task_timeout.as_secs() as u32),
+                        ..Default::default()
                     };
 
                     // Simulate task execution
@@ -169,16 +162,9 @@ mod comprehensive_concurrent_tests {
                         let task_config = TaskConfig {
                             description: Some(format!("Reader task {}", reader_id)),
                             command: Some("echo test".to_string()),
-                            script: None,
-                            dependencies: None,
-                            working_dir: None,
-                            shell: None,
                             inputs: Some(vec!["src/*.txt".to_string()]),
-                            outputs: None,
-                            security: None,
                             cache: Some(true),
-                            cache_key: None,
-                            timeout: None,
+                            ..Default::default()
                         };
 
                         // Generate cache key
@@ -428,16 +414,10 @@ tasks: {
         let task_config = TaskConfig {
             description: Some("Timeout test".to_string()),
             command: Some("echo test".to_string()),
-            script: None,
-            dependencies: None,
-            working_dir: None,
-            shell: None,
             inputs: Some(vec!["large.bin".to_string()]),
-            outputs: None,
-            security: None,
             cache: Some(true),
-            cache_key: None,
             timeout: Some(1), // 1 second timeout
+            ..Default::default()
         };
 
         // Thread 1: Perform cache operation with large file
@@ -24,16 +24,10 @@ mod concurrent_cache_tests {
         TaskConfig {
             description: Some(format!("Test task: {}", name)),
             command: Some("echo test".to_string()),
-            script: None,
-            dependencies: None,
-            working_dir: None,
-            shell: None,
             inputs: Some(vec!["src/*".to_string()]),
             outputs: Some(vec!["build/output.txt".to_string()]),
-            security: None,
             cache: Some(cache_enabled),
-            cache_key: None,
-            timeout: None,
+            ..Default::default()
         }
     }
 
@@ -77,16 +77,10 @@ mod concurrent_scenarios {
                     let task_config = TaskConfig {
                         description: Some("Test concurrent cache task".to_string()),
                         command: Some("echo test > build/output.txt".to_string()),
-                        script: None,
-                        dependencies: None,
-                        working_dir: None,
-                        shell: None,
                         inputs: Some(vec!["src/*".to_string()]),
                         outputs: Some(vec!["build/output.txt".to_string()]),
-                        security: None,
                         cache: Some(true),
-                        cache_key: None,
-                        timeout: None,
+                        ..Default::default()
                     };
 
                     // Generate cache key
@@ -268,16 +262,10 @@ mod concurrent_scenarios {
         let task_config = TaskConfig {
             description: Some("Test error recovery task".to_string()),
             command: Some("false".to_string()), // Command that always fails
-            script: None,
-            dependencies: None,
-            working_dir: None,
-            shell: None,
             inputs: Some(vec!["src/*".to_string()]),
             outputs: Some(vec!["build/output.txt".to_string()]),
-            security: None,
             cache: Some(true),
-            cache_key: None,
-            timeout: None,
+            ..Default::default()
         };
 
         let cache_key = cache
@@ -344,16 +332,9 @@ mod concurrent_scenarios {
                     let task_config = TaskConfig {
                         description: Some(format!("Resource test task {}", i)),
                         command: Some("sleep 0.1".to_string()), // Simulate work
-                        script: None,
-                        dependencies: None,
-                        working_dir: None,
-                        shell: None,
-                        inputs: None,
-                        outputs: None,
-                        security: None,
-                        cache: Some(false), // Disable cache for this test
-                        cache_key: None,
-                        timeout: Some(5), // 5 second timeout
+                        cache: Some(false),                     // Disable cache for this test
+                        timeout: Some(5),                       // 5 second timeout
+                        ..Default::default()
                     };
 
                     // Generate unique cache key
@@ -434,16 +415,10 @@ mod concurrent_scenarios {
                     TaskConfig {
                         description: Some(format!("Compile task {}", i)),
                         command: Some(format!("cp src/file{}.txt build/compiled_{}.txt", i, i)),
-                        script: None,
-                        dependencies: None,
-                        working_dir: None,
-                        shell: None,
                         inputs: Some(vec![format!("src/file{}.txt", i)]),
                         outputs: Some(vec![format!("build/compiled_{}.txt", i)]),
-                        security: None,
                         cache: Some(true),
-                        cache_key: None,
-                        timeout: None,
+                        ..Default::default()
                     },
                 );
             }
@@ -454,20 +429,15 @@ mod concurrent_scenarios {
                 TaskConfig {
                     description: Some("Bundle compiled files".to_string()),
                     command: Some("/bin/sh -c 'cat build/compiled_0.txt build/compiled_1.txt build/compiled_2.txt > build/bundle.txt'".to_string()),
-                    script: None,
                     dependencies: Some(vec![
                         "compile_0".to_string(),
                         "compile_1".to_string(),
                         "compile_2".to_string(),
                     ]),
-                    working_dir: None,
-                    shell: None,
                     inputs: Some(vec!["build/compiled_*.txt".to_string()]),
                     outputs: Some(vec!["build/bundle.txt".to_string()]),
-                    security: None,
                     cache: Some(true),
-                    cache_key: None,
-                    timeout: None,
+                    ..Default::default()
                 },
             );
 
@@ -594,16 +564,9 @@ tasks: {
                     let task_config = TaskConfig {
                         description: Some("Cache invalidation test".to_string()),
                         command: Some("echo test".to_string()),
-                        script: None,
-                        dependencies: None,
-                        working_dir: None,
-                        shell: None,
                         inputs: Some(vec!["src/input.txt".to_string()]),
-                        outputs: None,
-                        security: None,
                         cache: Some(true),
-                        cache_key: None,
-                        timeout: None,
+                        ..Default::default()
                     };
 
                     // Generate initial cache key
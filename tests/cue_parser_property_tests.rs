@@ -146,6 +146,7 @@ proptest! {
         let options = ParseOptions {
             environment: None,
             capabilities: vec![],
+            instance: None,
         };
 
         let result = CueParser::eval_package_with_options(temp_dir.path(), "env", &options).unwrap();
@@ -199,6 +200,7 @@ proptest! {
         let options = ParseOptions {
             environment: None,
             capabilities: all_capabilities.clone(),
+            instance: None,
         };
 
         let result = CueParser::eval_package_with_options(temp_dir.path(), "env", &options).unwrap();
@@ -298,6 +300,7 @@ proptest! {
             let options = ParseOptions {
                 environment: Some(env_name.clone()),
                 capabilities: vec![],
+                instance: None,
                 };
 
             let result = CueParser::eval_package_with_options(temp_dir.path(), "env", &options).unwrap();
@@ -391,6 +394,7 @@ proptest! {
         let options = ParseOptions {
             environment: None,
             capabilities: selected_caps.clone(),
+            instance: None,
         };
 
         let result = CueParser::eval_package_with_options(temp_dir.path(), "env", &options).unwrap();
@@ -493,6 +497,7 @@ proptest! {
         let options = ParseOptions {
             environment: Some(env_name.clone()),
             capabilities: vec![],
+            instance: None,
         };
 
         let result = CueParser::eval_package_with_options(temp_dir.path(), "env", &options).unwrap();
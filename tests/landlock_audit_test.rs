@@ -1,74 +1,98 @@
 #![allow(unused)]
 #[cfg(all(test, target_os = "linux"))]
 mod landlock_audit_tests {
-    use cuenv::access_restrictions::AccessRestrictions;
+    use cuenv::access_restrictions::{AccessKind, AccessRestrictions};
+    use std::collections::BTreeSet;
     use std::fs;
+    use std::net::TcpListener;
     use std::path::Path;
     use std::process::Command;
+    use std::sync::atomic::AtomicBool;
+    use std::time::Duration;
+
+    const TIMEOUT: Duration = Duration::from_secs(60);
+    static NOT_CANCELLED: AtomicBool = AtomicBool::new(false);
+
+    /// Whether this process may trace its children, which some containers
+    /// forbid. Audit tests return early without it, other errors fail them.
+    fn ptrace_permitted() -> bool {
+        // SAFETY: The child only calls ptrace(2) and _exit(2), which are
+        // async-signal-safe, and exits without executing anything
+        unsafe {
+            match libc::fork() {
+                0 => libc::_exit(libc::ptrace(libc::PTRACE_TRACEME, 0, 0, 0) as i32),
+                -1 => false,
+                pid => {
+                    let mut status = 0;
+                    libc::waitpid(pid, &mut status, 0) == pid
+                        && libc::WIFEXITED(status)
+                        && libc::WEXITSTATUS(status) == 0
+                }
+            }
+        }
+    }
 
     #[test]
-    #[ignore] // Requires strace to be installed
     fn test_audit_mode_basic() {
+        if !ptrace_permitted() {
+            eprintln!("Skipping audit test, ptrace is not permitted");
+            return;
+        }
+
         let mut restrictions = AccessRestrictions::new(true, true);
         restrictions.enable_audit_mode();
 
         let mut cmd = Command::new("cat");
         cmd.arg("/etc/hostname");
 
-        match restrictions.run_with_audit(&mut cmd) {
-            Ok((exit_code, report)) => {
-                // The command should have succeeded
-                assert_eq!(exit_code, 0);
-
-                // Should have detected file access
-                assert!(!report.accessed_files.is_empty());
-
-                // Should include /etc/hostname in accessed files
-                let accessed_paths: Vec<&str> =
-                    report.accessed_files.iter().map(|s| s.as_str()).collect();
-
-                // May include the binary and libraries too
-                assert!(accessed_paths
-                    .iter()
-                    .any(|&p| p.contains("/etc/hostname") || p.contains("hostname")));
-            }
-            Err(e) => {
-                // If strace is not available, that's OK for CI
-                eprintln!("Audit test skipped: {}", e);
-            }
-        }
+        let (exit_code, report) = restrictions
+            .run_with_audit("basic", &mut cmd, TIMEOUT, &NOT_CANCELLED)
+            .unwrap();
+        assert_eq!(exit_code, 0);
+        assert_eq!(report.task, "basic");
+
+        // Should include /etc/hostname as read, and cat as executed
+        assert_eq!(
+            report.files.get(Path::new("/etc/hostname")),
+            Some(&BTreeSet::from([AccessKind::Read]))
+        );
+        assert!(report
+            .files
+            .values()
+            .any(|kinds| kinds.contains(&AccessKind::Exec)));
     }
 
     #[test]
-    #[ignore] // Requires strace and network utilities
     fn test_audit_mode_network() {
+        if !ptrace_permitted() {
+            eprintln!("Skipping audit test, ptrace is not permitted");
+            return;
+        }
+
         let mut restrictions = AccessRestrictions::new(false, true);
         restrictions.enable_audit_mode();
 
-        let mut cmd = Command::new("ping");
-        cmd.args(&["-c", "1", "-W", "1", "127.0.0.1"]);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
 
-        match restrictions.run_with_audit(&mut cmd) {
-            Ok((_, report)) => {
-                // Should have detected network access
-                assert!(!report.network_connections.is_empty());
+        let mut cmd = Command::new("bash");
+        cmd.arg("-c")
+            .arg(format!("echo > /dev/tcp/127.0.0.1/{}", addr.port()));
 
-                // Should include localhost connection
-                let has_localhost = report
-                    .network_connections
-                    .iter()
-                    .any(|conn| conn.contains("127.0.0.1"));
-                assert!(has_localhost);
-            }
-            Err(e) => {
-                eprintln!("Audit test skipped: {}", e);
-            }
-        }
+        let (_, report) = restrictions
+            .run_with_audit("network", &mut cmd, TIMEOUT, &NOT_CANCELLED)
+            .unwrap();
+        // Should have detected the connection to the listener
+        assert!(report.connections.contains(&addr));
     }
 
     #[test]
-    #[ignore] // Requires strace
     fn test_audit_mode_complex_command() {
+        if !ptrace_permitted() {
+            eprintln!("Skipping audit test, ptrace is not permitted");
+            return;
+        }
+
         let mut restrictions = AccessRestrictions::new(true, true);
         restrictions.enable_audit_mode();
 
@@ -77,37 +101,65 @@ mod landlock_audit_tests {
         cmd.arg("-c");
         cmd.arg("cat /etc/os-release && ls /tmp");
 
-        match restrictions.run_with_audit(&mut cmd) {
-            Ok((exit_code, report)) => {
-                assert_eq!(exit_code, 0);
+        let (exit_code, report) = restrictions
+            .run_with_audit("complex", &mut cmd, TIMEOUT, &NOT_CANCELLED)
+            .unwrap();
+        assert_eq!(exit_code, 0);
 
-                // Should have multiple file accesses
-                assert!(report.accessed_files.len() > 1);
+        // Should have multiple file accesses
+        assert!(report.files.len() > 1);
 
-                // Print summary for debugging
-                report.print_summary();
-            }
-            Err(e) => {
-                eprintln!("Audit test skipped: {}", e);
-            }
+        // Print summary for debugging
+        report.print_summary();
+    }
+
+    #[test]
+    fn test_concurrent_audits_are_separate() {
+        if !ptrace_permitted() {
+            eprintln!("Skipping audit test, ptrace is not permitted");
+            return;
         }
+
+        let run = |task: &'static str, file: &'static str| {
+            std::thread::spawn(move || {
+                let restrictions = AccessRestrictions::new(true, false);
+                let mut cmd = Command::new("sh");
+                cmd.arg("-c").arg(format!("sleep 0.2; cat {file}"));
+                restrictions.run_with_audit(task, &mut cmd, TIMEOUT, &NOT_CANCELLED)
+            })
+        };
+        let first = run("first", "/etc/hostname");
+        let second = run("second", "/etc/passwd");
+
+        let (_, first) = first.join().unwrap().unwrap();
+        let (_, second) = second.join().unwrap().unwrap();
+
+        assert!(first.files.contains_key(Path::new("/etc/hostname")));
+        assert!(!first.files.contains_key(Path::new("/etc/passwd")));
+        assert!(second.files.contains_key(Path::new("/etc/passwd")));
+        assert!(!second.files.contains_key(Path::new("/etc/hostname")));
     }
 
     #[test]
     fn test_audit_report_summary() {
         use cuenv::access_restrictions::AuditReport;
 
-        let report = AuditReport {
-            accessed_files: vec![
-                "/etc/passwd".to_string(),
-                "/usr/lib/libc.so".to_string(),
-                "/home/user/data.txt".to_string(),
-            ],
-            network_connections: vec![
-                "tcp:example.com:443".to_string(),
-                "udp:8.8.8.8:53".to_string(),
-            ],
-        };
+        let mut report = AuditReport::new("build");
+        report
+            .files
+            .insert("/etc/passwd".into(), BTreeSet::from([AccessKind::Read]));
+        report.files.insert(
+            "/usr/lib/libc.so".into(),
+            BTreeSet::from([AccessKind::Read, AccessKind::Exec]),
+        );
+        report.files.insert(
+            "/home/user/data.txt".into(),
+            BTreeSet::from([AccessKind::Write]),
+        );
+        report
+            .connections
+            .insert("93.184.216.34:443".parse().unwrap());
+        report.connections.insert("8.8.8.8:53".parse().unwrap());
 
         // Just test that print_summary doesn't panic
         report.print_summary();
@@ -117,10 +169,7 @@ mod landlock_audit_tests {
     fn test_empty_audit_report() {
         use cuenv::access_restrictions::AuditReport;
 
-        let report = AuditReport {
-            accessed_files: vec![],
-            network_connections: vec![],
-        };
+        let report = AuditReport::new("empty");
 
         // Should handle empty report gracefully
         report.print_summary();
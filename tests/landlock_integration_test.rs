@@ -12,12 +12,8 @@ mod landlock_tests {
     fn test_filesystem_restriction_blocks_access() {
         let security_config = SecurityConfig {
             restrict_disk: Some(true),
-            restrict_network: None,
             read_only_paths: Some(vec!["/usr".into(), "/bin".into()]),
-            read_write_paths: None,
-            deny_paths: None,
-            allowed_hosts: None,
-            infer_from_inputs_outputs: None,
+            ..Default::default()
         };
 
         let restrictions = AccessRestrictions::from_security_config(&security_config);
@@ -37,13 +33,9 @@ mod landlock_tests {
     #[ignore] // Requires Landlock support in kernel
     fn test_network_restriction_blocks_connections() {
         let security_config = SecurityConfig {
-            restrict_disk: None,
             restrict_network: Some(true),
-            read_only_paths: None,
-            read_write_paths: None,
-            deny_paths: None,
             allowed_hosts: None, // No allowed hosts = block all
-            infer_from_inputs_outputs: None,
+            ..Default::default()
         };
 
         let restrictions = AccessRestrictions::from_security_config(&security_config);
@@ -61,13 +53,9 @@ mod landlock_tests {
     #[ignore] // Requires Landlock support in kernel
     fn test_allowed_port_permits_connection() {
         let security_config = SecurityConfig {
-            restrict_disk: None,
             restrict_network: Some(true),
-            read_only_paths: None,
-            read_write_paths: None,
-            deny_paths: None,
             allowed_hosts: Some(vec!["443".to_string()]),
-            infer_from_inputs_outputs: None,
+            ..Default::default()
         };
 
         let restrictions = AccessRestrictions::from_security_config(&security_config);
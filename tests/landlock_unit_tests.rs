@@ -15,6 +15,7 @@ mod landlock_unit_tests {
             deny_paths: Some(vec!["/etc/shadow".into()]),
             allowed_hosts: Some(vec!["443".to_string(), "80".to_string()]),
             infer_from_inputs_outputs: Some(false),
+            ..Default::default()
         };
 
         let restrictions = AccessRestrictions::from_security_config(&security_config);
@@ -29,15 +30,7 @@ mod landlock_unit_tests {
 
     #[test]
     fn test_security_config_defaults() {
-        let security_config = SecurityConfig {
-            restrict_disk: None,
-            restrict_network: None,
-            read_only_paths: None,
-            read_write_paths: None,
-            deny_paths: None,
-            allowed_hosts: None,
-            infer_from_inputs_outputs: None,
-        };
+        let security_config = SecurityConfig::default();
 
         let restrictions = AccessRestrictions::from_security_config(&security_config);
 
@@ -52,28 +45,15 @@ mod landlock_unit_tests {
     #[test]
     fn test_infer_from_task_inputs_outputs() {
         let security_config = SecurityConfig {
-            restrict_disk: None,
-            restrict_network: None,
-            read_only_paths: None,
-            read_write_paths: None,
-            deny_paths: None,
-            allowed_hosts: None,
             infer_from_inputs_outputs: Some(true),
+            ..Default::default()
         };
 
         let task_config = TaskConfig {
             command: Some("process".to_string()),
-            description: None,
-            script: None,
-            dependencies: None,
-            working_dir: None,
-            shell: None,
             inputs: Some(vec!["/data/input.txt".to_string()]),
             outputs: Some(vec!["/data/output.txt".to_string()]),
-            security: None,
-            cache: None,
-            cache_key: None,
-            timeout: None,
+            ..Default::default()
         };
 
         let restrictions =
@@ -96,27 +76,17 @@ mod landlock_unit_tests {
     fn test_infer_with_existing_paths_merge() {
         let security_config = SecurityConfig {
             restrict_disk: Some(true),
-            restrict_network: None,
             read_only_paths: Some(vec!["/usr".into()]),
             read_write_paths: Some(vec!["/tmp".into()]),
-            deny_paths: None,
-            allowed_hosts: None,
             infer_from_inputs_outputs: Some(true),
+            ..Default::default()
         };
 
         let task_config = TaskConfig {
             command: Some("process".to_string()),
-            description: None,
-            script: None,
-            dependencies: None,
-            working_dir: None,
-            shell: None,
             inputs: Some(vec!["/data/input.txt".to_string()]),
             outputs: Some(vec!["/data/output.txt".to_string()]),
-            security: None,
-            cache: None,
-            cache_key: None,
-            timeout: None,
+            ..Default::default()
         };
 
         let restrictions =
@@ -143,13 +113,9 @@ mod landlock_unit_tests {
     #[test]
     fn test_empty_allowed_hosts_blocks_all() {
         let security_config = SecurityConfig {
-            restrict_disk: None,
             restrict_network: Some(true),
-            read_only_paths: None,
-            read_write_paths: None,
-            deny_paths: None,
             allowed_hosts: Some(vec![]), // Empty list should block all
-            infer_from_inputs_outputs: None,
+            ..Default::default()
         };
 
         let restrictions = AccessRestrictions::from_security_config(&security_config);
@@ -161,18 +127,14 @@ mod landlock_unit_tests {
     #[test]
     fn test_port_parsing_in_allowed_hosts() {
         let security_config = SecurityConfig {
-            restrict_disk: None,
             restrict_network: Some(true),
-            read_only_paths: None,
-            read_write_paths: None,
-            deny_paths: None,
             allowed_hosts: Some(vec![
                 "443".to_string(),
                 "80".to_string(),
                 "8080".to_string(),
                 "invalid-port".to_string(), // Should be handled gracefully
             ]),
-            infer_from_inputs_outputs: None,
+            ..Default::default()
         };
 
         let restrictions = AccessRestrictions::from_security_config(&security_config);
@@ -234,6 +196,7 @@ mod landlock_unit_tests {
                 "3000".to_string(), // Custom app
             ]),
             infer_from_inputs_outputs: Some(false),
+            ..Default::default()
         };
 
         let restrictions = AccessRestrictions::from_security_config(&security_config);
@@ -67,16 +67,10 @@ mod performance_concurrent_tests {
                             let task_config = TaskConfig {
                                 description: Some(format!("Perf test {}_{}", thread_id, op)),
                                 command: Some("echo test".to_string()),
-                                script: None,
-                                dependencies: None,
-                                working_dir: None,
-                                shell: None,
                                 inputs: Some(vec![format!("src_{}/input.txt", num_threads)]),
-                                outputs: None,
-                                security: None,
                                 cache: Some(true),
                                 cache_key: Some(format!("perf_{}_{}", thread_id, op)),
-                                timeout: None,
+                                ..Default::default()
                             };
 
                             let op_start = Instant::now();
@@ -169,16 +163,9 @@ mod performance_concurrent_tests {
                         let task_config = TaskConfig {
                             description: Some(format!("Stress test {}", thread_id)),
                             command: Some("echo stress".to_string()),
-                            script: None,
-                            dependencies: None,
-                            working_dir: None,
-                            shell: None,
-                            inputs: None,
-                            outputs: None,
-                            security: None,
                             cache: Some(true),
                             cache_key: Some(format!("stress_{}", thread_id)),
-                            timeout: None,
+                            ..Default::default()
                         };
 
                         let env_vars = HashMap::new();
@@ -289,16 +276,9 @@ mod performance_concurrent_tests {
                             let task_config = TaskConfig {
                                 description: Some(format!("Pattern test")),
                                 command: Some("echo test".to_string()),
-                                script: None,
-                                dependencies: None,
-                                working_dir: None,
-                                shell: None,
                                 inputs: Some(vec![format!("src/file{}.txt", file_index)]),
-                                outputs: None,
-                                security: None,
                                 cache: Some(true),
-                                cache_key: None,
-                                timeout: None,
+                                ..Default::default()
                             };
 
                             let env_vars = HashMap::new();
@@ -478,16 +458,9 @@ tasks: {{
             let task_config = TaskConfig {
                 description: Some(format!("Cleanup test {}", i)),
                 command: Some("echo test".to_string()),
-                script: None,
-                dependencies: None,
-                working_dir: None,
-                shell: None,
-                inputs: None,
-                outputs: None,
-                security: None,
                 cache: Some(true),
                 cache_key: Some(format!("cleanup_key_{}", i)),
-                timeout: None,
+                ..Default::default()
             };
 
             let env_vars = HashMap::new();
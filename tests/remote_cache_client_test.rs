@@ -0,0 +1,215 @@
+//! Integration tests for sharing task results through the remote cache server
+
+use cuenv::cache::{CacheConfig, CacheManager, CacheMode, CachedTaskResult};
+use cuenv::remote_cache::{RemoteCacheClient, RemoteCacheConfig, RemoteCacheServer};
+use cuenv::resource_limits::ResourceUsage;
+use std::fs;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::os::unix::fs::PermissionsExt;
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
+
+fn cache_config(dir: &TempDir, mode: CacheMode) -> CacheConfig {
+    CacheConfig {
+        base_dir: dir.path().join("cuenv"),
+        max_size: 1024 * 1024 * 1024,
+        mode,
+        inline_threshold: 64,
+    }
+}
+
+/// Start a remote cache server on a free local port
+async fn start_server(dir: &TempDir) -> SocketAddr {
+    let address = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let server = RemoteCacheServer::new(RemoteCacheConfig {
+        address,
+        enable_action_cache: true,
+        enable_cas: true,
+        cache_config: cache_config(dir, CacheMode::ReadWrite),
+    })
+    .await
+    .unwrap();
+    tokio::spawn(server.serve());
+
+    for _ in 0..100 {
+        if TcpStream::connect(address).is_ok() {
+            return address;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("remote cache server did not start");
+}
+
+async fn manager(
+    dir: &TempDir,
+    local: CacheMode,
+    address: SocketAddr,
+    remote: CacheMode,
+) -> CacheManager {
+    let client = RemoteCacheClient::connect(&format!("grpc://{address}"), remote).unwrap();
+    CacheManager::new(cache_config(dir, local))
+        .await
+        .unwrap()
+        .with_remote(client)
+}
+
+/// Capture the outputs below `work_dir` as a successful task result
+fn task_result(cache: &CacheManager, cache_key: &str, work_dir: &TempDir) -> CachedTaskResult {
+    let patterns = vec!["bin/*".to_string()];
+    let (output_files, output_metadata) =
+        cache.capture_outputs(&patterns, work_dir.path()).unwrap();
+
+    CachedTaskResult {
+        cache_key: cache_key.to_string(),
+        executed_at: SystemTime::now(),
+        exit_code: 0,
+        stdout: Some(b"built\n".to_vec()),
+        stderr: None,
+        output_files,
+        output_metadata,
+        resource_usage: Some(ResourceUsage {
+            peak_memory: Some(64 << 20),
+            cpu_time: Duration::from_millis(1500),
+        }),
+    }
+}
+
+#[tokio::test]
+async fn test_result_shared_through_remote_cache() {
+    let server_dir = TempDir::new().unwrap();
+    let address = start_server(&server_dir).await;
+    let cache_key = "c".repeat(64);
+
+    // CI builds the task and uploads its result
+    let ci_dir = TempDir::new().unwrap();
+    let ci_work = TempDir::new().unwrap();
+    fs::create_dir_all(ci_work.path().join("bin")).unwrap();
+    let tool = ci_work.path().join("bin/tool");
+    fs::write(
+        &tool,
+        "#!/bin/sh\necho a tool that is too large to inline\n",
+    )
+    .unwrap();
+    fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let ci = manager(&ci_dir, CacheMode::ReadWrite, address, CacheMode::ReadWrite).await;
+    let result = task_result(&ci, &cache_key, &ci_work);
+    ci.store_remote_result(&result).await.unwrap();
+
+    // A laptop with a cold local cache restores it
+    let laptop_dir = TempDir::new().unwrap();
+    let laptop_work = TempDir::new().unwrap();
+    let laptop = manager(&laptop_dir, CacheMode::ReadWrite, address, CacheMode::Read).await;
+
+    assert!(laptop.get_cached_result(&cache_key).is_none());
+    let fetched = laptop
+        .get_remote_result(&cache_key)
+        .await
+        .expect("result should be served by the remote cache");
+    assert_eq!(fetched.stdout.as_deref(), Some(&b"built\n"[..]));
+    assert_eq!(fetched.resource_usage, result.resource_usage);
+
+    let restored = laptop
+        .restore_outputs(&fetched, &["bin/*".to_string()], laptop_work.path())
+        .unwrap();
+    assert_eq!(restored, 1);
+    let restored_tool = laptop_work.path().join("bin/tool");
+    assert_eq!(fs::read(&restored_tool).unwrap(), fs::read(&tool).unwrap());
+    assert_ne!(
+        fs::metadata(&restored_tool).unwrap().permissions().mode() & 0o111,
+        0
+    );
+
+    // The hit is kept in the writable local tier
+    assert!(laptop.get_cached_result(&cache_key).is_some());
+    assert_eq!(laptop.get_statistics().remote_hits, 1);
+}
+
+#[tokio::test]
+async fn test_modes_apply_per_tier() {
+    let server_dir = TempDir::new().unwrap();
+    let address = start_server(&server_dir).await;
+    let work = TempDir::new().unwrap();
+    fs::create_dir_all(work.path().join("bin")).unwrap();
+    fs::write(work.path().join("bin/tool"), "some build output of a task").unwrap();
+
+    // A read-only remote tier is never written to
+    let read_only_key = "d".repeat(64);
+    let reader_dir = TempDir::new().unwrap();
+    let reader = manager(&reader_dir, CacheMode::ReadWrite, address, CacheMode::Read).await;
+    let result = task_result(&reader, &read_only_key, &work);
+    reader.store_remote_result(&result).await.unwrap();
+    assert!(reader.get_remote_result(&read_only_key).await.is_none());
+
+    // A write-only remote tier is never read from
+    let shared_key = "e".repeat(64);
+    let writer_dir = TempDir::new().unwrap();
+    let writer = manager(&writer_dir, CacheMode::ReadWrite, address, CacheMode::Write).await;
+    let result = task_result(&writer, &shared_key, &work);
+    writer.store_remote_result(&result).await.unwrap();
+    assert!(writer.get_remote_result(&shared_key).await.is_none());
+
+    // Remote hits are not kept when the local tier is read-only
+    let local_read_dir = TempDir::new().unwrap();
+    let local_read = manager(&local_read_dir, CacheMode::Read, address, CacheMode::Read).await;
+    assert!(local_read.get_remote_result(&shared_key).await.is_some());
+    assert!(local_read.get_cached_result(&shared_key).is_none());
+}
+
+#[tokio::test]
+async fn test_unreachable_remote_is_a_miss() {
+    let address = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let dir = TempDir::new().unwrap();
+    let cache = manager(&dir, CacheMode::ReadWrite, address, CacheMode::ReadWrite).await;
+
+    assert!(cache.get_remote_result(&"f".repeat(64)).await.is_none());
+    assert_eq!(cache.get_statistics().errors, 1);
+}
+
+#[tokio::test]
+async fn test_outputs_above_batch_limit_use_byte_stream() {
+    let server_dir = TempDir::new().unwrap();
+    let address = start_server(&server_dir).await;
+    let cache_key = "0".repeat(64);
+
+    let work = TempDir::new().unwrap();
+    fs::create_dir_all(work.path().join("bin")).unwrap();
+    let data: Vec<u8> = (0..5 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+    fs::write(work.path().join("bin/large"), &data).unwrap();
+
+    let uploader_dir = TempDir::new().unwrap();
+    let uploader = manager(
+        &uploader_dir,
+        CacheMode::ReadWrite,
+        address,
+        CacheMode::Write,
+    )
+    .await;
+    let result = task_result(&uploader, &cache_key, &work);
+    uploader.store_remote_result(&result).await.unwrap();
+
+    let downloader_dir = TempDir::new().unwrap();
+    let downloader = manager(
+        &downloader_dir,
+        CacheMode::ReadWrite,
+        address,
+        CacheMode::Read,
+    )
+    .await;
+    let fetched = downloader.get_remote_result(&cache_key).await.unwrap();
+
+    let restore_dir = TempDir::new().unwrap();
+    downloader
+        .restore_outputs(&fetched, &["bin/*".to_string()], restore_dir.path())
+        .unwrap();
+    assert_eq!(
+        fs::read(restore_dir.path().join("bin/large")).unwrap(),
+        data
+    );
+}
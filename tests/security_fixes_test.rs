@@ -68,16 +68,8 @@ async fn test_action_cache_signing_integration() {
     let task_config = TaskConfig {
         description: Some("Test task".to_string()),
         command: Some("echo hello".to_string()),
-        script: None,
-        dependencies: None,
-        working_dir: None,
-        shell: None,
-        inputs: None,
-        outputs: None,
         cache: Some(true),
-        cache_key: None,
-        timeout: None,
-        security: None,
+        ..Default::default()
     };
 
     let digest = cache
@@ -93,8 +85,11 @@ async fn test_action_cache_signing_integration() {
                 stdout_hash: Some("hello\n".to_string()),
                 stderr_hash: None,
                 output_files: HashMap::new(),
+                output_metadata: HashMap::new(),
+                output_directories: HashMap::new(),
                 executed_at: SystemTime::now(),
                 duration_ms: 10,
+                execution_metadata: Vec::new(),
             })
         })
         .await